@@ -0,0 +1,170 @@
+/// Channel and group addressing, as on the hardware remote.
+///
+/// The remote reaches lights by RF channel and group, which are set on each
+/// light. Cabled lights can be given the channel and group they're set to, so
+/// `Target::Channel` covers every connected light on a channel, and a
+/// broadcast sets a whole channel at once: over RF through the 2.4GHz dongle
+/// (see `rf`) and over USB to the cabled lights on it. Addresses are persisted
+/// under `light_addresses` in the settings store, keyed by device id.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::errors::LightError;
+use crate::groups::{DeviceError, FanOutReport, GroupManager, Target};
+use crate::protocol::{RF_CHANNELS, RF_GROUPS};
+use crate::rf::RfDongle;
+use crate::serial::SerialManager;
+use crate::STORE_FILE;
+
+const ADDRESSES_KEY: &str = "light_addresses";
+/// How the dongle is listed in broadcast reports.
+pub const RF_DEVICE: &str = "rf";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Address {
+    /// RF channel, 1-16.
+    pub channel: u8,
+    /// Group 1-4, or `None` when the light isn't in a group.
+    pub group: Option<u8>,
+}
+
+impl Address {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=RF_CHANNELS).contains(&self.channel) {
+            return Err(format!("RF channel must be between 1 and {RF_CHANNELS}"));
+        }
+        if self.group.is_some_and(|g| !(1..=RF_GROUPS).contains(&g)) {
+            return Err(format!("RF group must be between 1 and {RF_GROUPS}"));
+        }
+        Ok(())
+    }
+}
+
+pub struct AddressBook {
+    addresses: Mutex<BTreeMap<String, Address>>,
+}
+
+impl AddressBook {
+    pub fn new() -> Self {
+        Self {
+            addresses: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, Address> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(ADDRESSES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.addresses.lock().unwrap() = saved;
+    }
+
+    /// Addresses by device id. Devices not listed aren't on any channel.
+    pub fn list(&self) -> BTreeMap<String, Address> {
+        self.addresses.lock().unwrap().clone()
+    }
+
+    /// Ids of the devices set to `channel`, connected or not.
+    pub fn on_channel(&self, channel: u8) -> Vec<String> {
+        self.addresses
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, a)| a.channel == channel)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Set a device's address; `None` takes it off its channel.
+    pub fn set(&self, app: &AppHandle, id: &str, address: Option<Address>) -> Result<(), String> {
+        let mut all = self.addresses.lock().unwrap();
+        match address {
+            Some(address) => {
+                address.validate()?;
+                all.insert(id.to_string(), address);
+            }
+            None => {
+                all.remove(id);
+            }
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            ADDRESSES_KEY,
+            serde_json::to_value(&*all).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())
+    }
+}
+
+/// Set every light on `channel`: all its groups over RF if the dongle is
+/// connected, reported as `RF_DEVICE`, and the connected cabled lights set to
+/// it. Fails only if nothing succeeded.
+pub fn broadcast(
+    app: &AppHandle,
+    channel: u8,
+    brightness: u8,
+    kelvin: u32,
+) -> Result<FanOutReport, String> {
+    let mut results = Vec::new();
+    let rf = app.state::<RfDongle>();
+    if rf.is_connected() {
+        let result = rf.send_cct(app, channel, None, brightness, kelvin);
+        results.push((RF_DEVICE.to_string(), result.map_err(LightError::from)));
+    }
+    let ids = app
+        .state::<GroupManager>()
+        .resolve(app, &Target::Channel(channel))?;
+    let serial = app.state::<SerialManager>();
+    for id in ids {
+        let result = serial.set_cct_to(&id, brightness, kelvin);
+        results.push((id, result));
+    }
+
+    let mut report = FanOutReport::default();
+    for (device, result) in results {
+        match result {
+            Ok(()) => report.succeeded.push(device),
+            Err(error) => report.failed.push(DeviceError::new(device, error)),
+        }
+    }
+    if report.succeeded.is_empty() {
+        if report.failed.is_empty() {
+            return Err(format!("No lights on channel {channel}"));
+        }
+        let errors: Vec<String> = report.failed.iter().map(|f| f.error.clone()).collect();
+        return Err(errors.join("; "));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let address = Address {
+            channel: 16,
+            group: Some(4),
+        };
+        assert!(address.validate().is_ok());
+        assert!(Address {
+            channel: 0,
+            group: None,
+        }
+        .validate()
+        .is_err());
+        assert!(Address {
+            group: Some(5),
+            ..address
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,229 @@
+/// Ambient light sensor integration.
+///
+/// On Macs with an ambient light sensor, an optional loop reads the room's
+/// illuminance and drives brightness inversely: the darker the room, the
+/// brighter the key light. Lux is mapped on a log scale between a "dark" and a
+/// "bright" room reading onto a level range, smoothed, and only applied once it
+/// moves by more than the hysteresis so the light doesn't creep. The sensor is
+/// read through `ioreg`, which exposes it as `CurrentLux` on current hardware.
+/// Configuration is persisted under `ambient_light` in the settings store.
+use std::process::Command;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::groups::{self, Target};
+use crate::sessionlog::{self, Source};
+use crate::{notify, STORE_FILE};
+
+const AMBIENT_KEY: &str = "ambient_light";
+/// Weight of each new reading in the moving average.
+const SMOOTHING: f64 = 0.3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AmbientConfig {
+    pub enabled: bool,
+    /// Room lux at (or below) which the light is at `max_level`.
+    pub dark_lux: f64,
+    /// Room lux at (or above) which the light is at `min_level`.
+    pub bright_lux: f64,
+    pub min_level: u8,
+    pub max_level: u8,
+    /// Minimum level change before the light is updated.
+    pub hysteresis: u8,
+    pub interval_ms: u64,
+    /// Lights to drive; all connected lights by default.
+    pub target: Option<Target>,
+}
+
+impl Default for AmbientConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dark_lux: 10.0,
+            bright_lux: 500.0,
+            min_level: 20,
+            max_level: 100,
+            hysteresis: 5,
+            interval_ms: 2000,
+            target: None,
+        }
+    }
+}
+
+impl AmbientConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.dark_lux > 0.0 && self.bright_lux > self.dark_lux) {
+            return Err("Bright lux must be greater than dark lux, both positive".into());
+        }
+        if self.min_level > self.max_level || self.max_level > 100 {
+            return Err("Ambient level range must be within 0-100".into());
+        }
+        if self.interval_ms < 250 {
+            return Err("Ambient interval must be at least 250 ms".into());
+        }
+        Ok(())
+    }
+
+    /// Level for a room reading: inverse, linear in log lux.
+    fn level_for(&self, lux: f64) -> u8 {
+        let t = ((lux.max(f64::MIN_POSITIVE).ln() - self.dark_lux.ln())
+            / (self.bright_lux.ln() - self.dark_lux.ln()))
+        .clamp(0.0, 1.0);
+        let (lo, hi) = (self.min_level as f64, self.max_level as f64);
+        (hi - (hi - lo) * t).round() as u8
+    }
+}
+
+/// Reported after every sensor read, as "ambient-light".
+#[derive(Debug, Clone, Serialize)]
+pub struct AmbientReading {
+    pub lux: f64,
+    pub level: u8,
+}
+
+pub struct AmbientLight {
+    config: Mutex<AmbientConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl AmbientLight {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(AmbientConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: AmbientConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(AMBIENT_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> AmbientConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: AmbientConfig) -> Result<(), String> {
+        config.validate()?;
+        if config.enabled {
+            // Fail early on machines without a sensor
+            read_lux()?;
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            AMBIENT_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: AmbientConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn run(app: AppHandle, config: AmbientConfig, current: Arc<AtomicU64>, gen: u64) {
+    sessionlog::set_source(Source::Automation);
+    let mut smoothed: Option<f64> = None;
+    let mut applied: Option<u8> = None;
+    let mut failing = false;
+    while current.load(Ordering::SeqCst) == gen {
+        match read_lux() {
+            Ok(lux) => {
+                failing = false;
+                let lux = smoothed.map_or(lux, |s| s + (lux - s) * SMOOTHING);
+                smoothed = Some(lux);
+                let level = config.level_for(lux);
+                let moved = applied.is_none_or(|a| a.abs_diff(level) >= config.hysteresis);
+                if moved {
+                    let dither = app.state::<Ditherer>();
+                    let result = groups::fan_out(&app, config.target.as_ref(), |serial, id| {
+                        let status = serial
+                            .status_of(id)
+                            .ok_or("No status received from light yet")?;
+                        dither.set_level(&app, id, level, status.kelvin)
+                    });
+                    if result.is_ok() {
+                        applied = Some(level);
+                    }
+                }
+                let _ = app.emit("ambient-light", AmbientReading { lux, level });
+            }
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Can't read the ambient light sensor", &e);
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(config.interval_ms));
+    }
+}
+
+/// Current room illuminance from the built-in sensor.
+fn read_lux() -> Result<f64, String> {
+    if !cfg!(target_os = "macos") {
+        return Err("Ambient light sensing is only supported on macOS".into());
+    }
+    let output = Command::new("ioreg")
+        .args(["-r", "-l", "-k", "CurrentLux"])
+        .output()
+        .map_err(|e| format!("Failed to run ioreg: {e}"))?;
+    parse_lux(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| "No ambient light sensor found".into())
+}
+
+/// First `"CurrentLux" = <n>` value in ioreg output.
+fn parse_lux(ioreg: &str) -> Option<f64> {
+    ioreg.lines().find_map(|line| {
+        let (_, value) = line.split_once("\"CurrentLux\" = ")?;
+        value.trim().parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_is_inverse_log() {
+        let config = AmbientConfig::default();
+        assert_eq!(config.level_for(1.0), 100);
+        assert_eq!(config.level_for(10_000.0), 20);
+        // Geometric midpoint of 10 and 500 lux
+        assert_eq!(config.level_for((10.0f64 * 500.0).sqrt()), 60);
+    }
+
+    #[test]
+    fn test_parse_lux() {
+        let out = "  |   \"IOClass\" = \"Sensor\"\n  |   \"CurrentLux\" = 231\n";
+        assert_eq!(parse_lux(out), Some(231.0));
+        assert_eq!(parse_lux("nothing here"), None);
+    }
+}
@@ -0,0 +1,274 @@
+/// Arbitration between the sources that change the lights.
+///
+/// The panel, presets, automations and network integrations (see
+/// `sessionlog`) can all write to the same light. Each write claims the light
+/// for its source, and for `hold_secs` afterwards writes from lower-priority
+/// sources are refused, so a schedule or camera follower doesn't undo a manual
+/// change seconds later. Sources of equal or higher priority take over at
+/// once, and a claim lapses when its hold runs out. Priorities are an ordered
+/// list, highest first; a hold of 0, the default, turns arbitration off, as
+/// otherwise a touch of a slider would hold off presets as well.
+///
+/// With a manual hold set, any manual change also latches a hold across all
+/// lights: automations are refused everywhere until it runs out or is
+/// cleared, and each manual change restarts it. Status events carry the time
+/// left. The source of each light's last write is kept, so writes that follow
+/// from it (a linked light's) can be made as the same source. Checked in the
+/// write path next to the control lock; the configuration is persisted under
+/// `arbitration` in the settings store.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::errors::{ErrorCode, LightError};
+use crate::sessionlog::Source;
+use crate::STORE_FILE;
+
+const ARBITRATION_KEY: &str = "arbitration";
+/// Longest allowed hold, one day.
+const MAX_HOLD_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArbitrationConfig {
+    /// Sources from highest to lowest priority. Unlisted sources rank last.
+    pub priority: Vec<Source>,
+    /// How long a write keeps lower-priority sources off the light.
+    pub hold_secs: u64,
+    /// How long a manual change holds off every automation; 0 for no hold.
+    pub manual_hold_secs: u64,
+}
+
+impl Default for ArbitrationConfig {
+    fn default() -> Self {
+        Self {
+            priority: vec![
+                Source::Manual,
+                Source::Preset,
+                Source::External,
+                Source::Automation,
+            ],
+            hold_secs: 0,
+            manual_hold_secs: 0,
+        }
+    }
+}
+
+impl ArbitrationConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.hold_secs > MAX_HOLD_SECS || self.manual_hold_secs > MAX_HOLD_SECS {
+            return Err("Hold can be at most a day".into());
+        }
+        for (i, source) in self.priority.iter().enumerate() {
+            if self.priority[..i].contains(source) {
+                return Err("Each source can only be listed once".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Lower is higher priority.
+    fn rank(&self, source: Source) -> usize {
+        self.priority
+            .iter()
+            .position(|&s| s == source)
+            .unwrap_or(self.priority.len())
+    }
+
+    /// Whether `source` may write over `claim` at `now`.
+    fn admits(&self, claim: Option<&Claim>, source: Source, now: Instant) -> bool {
+        match claim {
+            Some(claim) if now < claim.until => self.rank(source) <= self.rank(claim.source),
+            _ => true,
+        }
+    }
+}
+
+/// The source that last wrote a light, and until when it holds it.
+#[derive(Debug, Clone, Copy)]
+struct Claim {
+    source: Source,
+    until: Instant,
+}
+
+pub struct Arbiter {
+    config: Mutex<ArbitrationConfig>,
+    /// Claims by device id.
+    claims: Mutex<BTreeMap<String, Claim>>,
+    /// End of the manual hold, if one was latched.
+    hold: Mutex<Option<Instant>>,
+    /// Source of the last write admitted to each light, by device id.
+    last: Mutex<BTreeMap<String, Source>>,
+}
+
+impl Arbiter {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(ArbitrationConfig::default()),
+            claims: Mutex::new(BTreeMap::new()),
+            hold: Mutex::new(None),
+            last: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: ArbitrationConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(ARBITRATION_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved;
+    }
+
+    pub fn get(&self) -> ArbitrationConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Save new priorities. Current claims keep their hold.
+    pub fn set(&self, app: &AppHandle, config: ArbitrationConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            ARBITRATION_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// Time left of the manual hold, if one is on.
+    pub fn hold_remaining(&self) -> Option<Duration> {
+        let until = (*self.hold.lock().unwrap())?;
+        let left = until.saturating_duration_since(Instant::now());
+        (!left.is_zero()).then_some(left)
+    }
+
+    /// Source of the last write admitted to light `id`, which the changes it
+    /// reports since most likely came from.
+    pub fn last_source(&self, id: &str) -> Option<Source> {
+        self.last.lock().unwrap().get(id).copied()
+    }
+
+    /// End the manual hold, letting automations back in.
+    pub fn clear_hold(&self) {
+        *self.hold.lock().unwrap() = None;
+    }
+
+    /// Claim light `id` for a write from `source`, or fail if a
+    /// higher-priority source holds it or automations are on hold. Manual
+    /// writes (re)start the manual hold.
+    pub fn claim(&self, id: &str, source: Source) -> Result<(), LightError> {
+        let config = self.config.lock().unwrap();
+        let now = Instant::now();
+        {
+            let mut hold = self.hold.lock().unwrap();
+            if source == Source::Manual && config.manual_hold_secs > 0 {
+                *hold = Some(now + Duration::from_secs(config.manual_hold_secs));
+            } else if let Some(until) =
+                hold.filter(|&until| source == Source::Automation && now < until)
+            {
+                let left = until.saturating_duration_since(now).as_secs() + 1;
+                return Err(LightError::new(
+                    ErrorCode::Refused,
+                    format!("Automations are on hold for another {left}s"),
+                ));
+            }
+        }
+        if config.hold_secs > 0 {
+            let mut claims = self.claims.lock().unwrap();
+            let current = claims.get(id);
+            if !config.admits(current, source, now) {
+                let claim = current.unwrap();
+                let left = claim.until.saturating_duration_since(now).as_secs() + 1;
+                return Err(LightError::new(
+                    ErrorCode::Refused,
+                    format!(
+                        "Held by {} control for another {left}s",
+                        claim.source.as_str()
+                    ),
+                ));
+            }
+            let until = now + Duration::from_secs(config.hold_secs);
+            claims.insert(id.to_string(), Claim { source, until });
+        }
+        self.last.lock().unwrap().insert(id.to_string(), source);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admits_by_priority_while_held() {
+        let config = ArbitrationConfig::default();
+        let now = Instant::now();
+        let claim = Claim {
+            source: Source::Manual,
+            until: now + Duration::from_secs(60),
+        };
+        assert!(config.admits(None, Source::Automation, now));
+        assert!(config.admits(Some(&claim), Source::Manual, now));
+        assert!(!config.admits(Some(&claim), Source::Automation, now));
+        assert!(!config.admits(Some(&claim), Source::Preset, now));
+        assert!(config.admits(Some(&claim), Source::Automation, claim.until));
+
+        let claim = Claim {
+            source: Source::Automation,
+            ..claim
+        };
+        assert!(config.admits(Some(&claim), Source::External, now));
+    }
+
+    #[test]
+    fn test_manual_hold_blocks_automations() {
+        let arbiter = Arbiter::new();
+        *arbiter.config.lock().unwrap() = ArbitrationConfig {
+            hold_secs: 0,
+            manual_hold_secs: 60,
+            ..ArbitrationConfig::default()
+        };
+        assert!(arbiter.claim("a", Source::Automation).is_ok());
+        assert!(arbiter.claim("a", Source::Manual).is_ok());
+        assert!(arbiter.hold_remaining().is_some());
+        assert!(arbiter.claim("b", Source::Automation).is_err());
+        assert!(arbiter.claim("b", Source::External).is_ok());
+        arbiter.clear_hold();
+        assert!(arbiter.claim("b", Source::Automation).is_ok());
+    }
+
+    #[test]
+    fn test_manual_write_to_all_lights_reaches_followers() {
+        let arbiter = Arbiter::new();
+        *arbiter.config.lock().unwrap() = ArbitrationConfig {
+            hold_secs: 60,
+            manual_hold_secs: 60,
+            ..ArbitrationConfig::default()
+        };
+        // A manual write to every light claims the leader and its follower
+        assert!(arbiter.claim("leader", Source::Manual).is_ok());
+        assert!(arbiter.claim("follower", Source::Manual).is_ok());
+        assert!(arbiter.claim("follower", Source::Automation).is_err());
+        // The link writes the follower as the leader's source
+        let source = arbiter.last_source("leader").unwrap();
+        assert!(arbiter.claim("follower", source).is_ok());
+        assert_eq!(arbiter.last_source("follower"), Some(Source::Manual));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ArbitrationConfig::default().validate().is_ok());
+        let twice = ArbitrationConfig {
+            priority: vec![Source::Manual, Source::Manual],
+            ..ArbitrationConfig::default()
+        };
+        assert!(twice.validate().is_err());
+    }
+}
@@ -0,0 +1,248 @@
+/// Webcam-driven auto-brightness.
+///
+/// An optional feedback loop grabs a frame from a capture device at a fixed
+/// interval, meters its exposure, and trims the lights' brightness to hold a
+/// target level as ambient light drifts during the day. Frames are grabbed as
+/// a small grayscale image (see `capture`); metering is center-weighted, where
+/// a presenter's face usually sits. Adjustments are proportional, bounded per
+/// step, and skipped inside a deadband so the light doesn't hunt.
+/// Configuration is persisted under `auto_exposure` in the settings store.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::groups::{self, Target};
+use crate::sessionlog::{self, Source};
+use crate::{capture, errors, notify, STORE_FILE};
+
+const AUTO_EXPOSURE_KEY: &str = "auto_exposure";
+const FRAME_W: usize = 64;
+const FRAME_H: usize = 48;
+/// Hardware brightness change per unit of luma error.
+const GAIN: f64 = 0.1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoExposureConfig {
+    pub enabled: bool,
+    /// Capture device: an index ("0"), or a platform device name/path.
+    pub capture_device: String,
+    /// Desired metered luma, 0-255.
+    pub target_luma: u8,
+    /// No adjustment while the metered luma is within this of the target.
+    pub deadband: u8,
+    pub interval_ms: u64,
+    /// Largest brightness change per adjustment, in hardware steps.
+    pub max_step: u8,
+    pub min_brightness: u8,
+    pub max_brightness: u8,
+    /// Lights to adjust; all connected lights by default.
+    pub target: Option<Target>,
+}
+
+impl Default for AutoExposureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capture_device: "0".into(),
+            target_luma: 118,
+            deadband: 8,
+            interval_ms: 2000,
+            max_step: 3,
+            min_brightness: 5,
+            max_brightness: 100,
+            target: None,
+        }
+    }
+}
+
+impl AutoExposureConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.interval_ms < 500 {
+            return Err("Auto-exposure interval must be at least 500 ms".into());
+        }
+        if !(1..=20).contains(&self.max_step) {
+            return Err("Auto-exposure step must be 1-20".into());
+        }
+        if self.min_brightness > self.max_brightness || self.max_brightness > 100 {
+            return Err("Auto-exposure brightness range must be within 0-100".into());
+        }
+        Ok(())
+    }
+
+    /// Brightness change for a metered luma, or 0 inside the deadband.
+    fn step(&self, luma: f64) -> i32 {
+        let error = self.target_luma as f64 - luma;
+        if error.abs() <= self.deadband as f64 {
+            return 0;
+        }
+        let max = self.max_step as f64;
+        let step = (error * GAIN).clamp(-max, max).round() as i32;
+        // Always move at least one step once outside the deadband
+        if step == 0 {
+            error.signum() as i32
+        } else {
+            step
+        }
+    }
+}
+
+/// Reported after every metering, as "auto-exposure".
+#[derive(Debug, Clone, Serialize)]
+pub struct ExposureReading {
+    pub luma: f64,
+    pub step: i32,
+}
+
+pub struct AutoExposure {
+    config: Mutex<AutoExposureConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl AutoExposure {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(AutoExposureConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: AutoExposureConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(AUTO_EXPOSURE_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> AutoExposureConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: AutoExposureConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            AUTO_EXPOSURE_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: AutoExposureConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn run(app: AppHandle, config: AutoExposureConfig, current: Arc<AtomicU64>, gen: u64) {
+    sessionlog::set_source(Source::Automation);
+    let mut failing = false;
+    while current.load(Ordering::SeqCst) == gen {
+        match capture_luma(&config.capture_device) {
+            Ok(luma) => {
+                failing = false;
+                let step = config.step(luma);
+                if step != 0 {
+                    let result = groups::fan_out(&app, config.target.as_ref(), |serial, id| {
+                        let status = serial
+                            .status_of(id)
+                            .ok_or("No status received from light yet")?;
+                        // Leave lights that are off alone
+                        if status.brightness == 0 {
+                            return Ok(());
+                        }
+                        let bri = (status.brightness as i32 + step)
+                            .clamp(config.min_brightness as i32, config.max_brightness as i32)
+                            as u8;
+                        serial.set_cct_to(id, bri, status.kelvin)
+                    });
+                    errors::check_fan_out(&app, "auto_exposure", result);
+                }
+                let _ = app.emit("auto-exposure", ExposureReading { luma, step });
+            }
+            Err(e) => {
+                // Notify once per failure streak
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Auto-exposure can't read the camera", &e);
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(config.interval_ms));
+    }
+}
+
+/// Grab one small grayscale frame and meter it.
+fn capture_luma(device: &str) -> Result<f64, String> {
+    let frame = capture::grab(
+        &capture::Source::Camera(device),
+        FRAME_W,
+        FRAME_H,
+        capture::PixelFormat::Gray,
+    )?;
+    Ok(center_weighted(&frame, FRAME_W, FRAME_H))
+}
+
+/// Mean luma with the central third of the frame weighted 4x.
+fn center_weighted(pixels: &[u8], w: usize, h: usize) -> f64 {
+    let (mut sum, mut weights) = (0.0, 0.0);
+    for (i, &p) in pixels.iter().enumerate() {
+        let (x, y) = (i % w, i / w);
+        let center = (w / 3..w - w / 3).contains(&x) && (h / 3..h - h / 3).contains(&y);
+        let weight = if center { 4.0 } else { 1.0 };
+        sum += p as f64 * weight;
+        weights += weight;
+    }
+    if weights == 0.0 {
+        0.0
+    } else {
+        sum / weights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_center_weighted_favors_center() {
+        let (w, h) = (6, 6);
+        let mut pixels = vec![0u8; w * h];
+        for y in 2..4 {
+            for x in 2..4 {
+                pixels[y * w + x] = 200;
+            }
+        }
+        // 4 center pixels at weight 4 out of a total weight of 32 + 16
+        assert_eq!(center_weighted(&pixels, w, h), 200.0 * 16.0 / 48.0);
+    }
+
+    #[test]
+    fn test_step_respects_deadband_and_limit() {
+        let config = AutoExposureConfig::default();
+        assert_eq!(config.step(120.0), 0);
+        assert_eq!(config.step(100.0), 2);
+        assert_eq!(config.step(0.0), 3);
+        assert_eq!(config.step(250.0), -3);
+    }
+}
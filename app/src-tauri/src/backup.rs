@@ -0,0 +1,123 @@
+/// Full backup and restore.
+///
+/// An export (see `bundle`) carries the items worth sharing between setups;
+/// a backup is everything the app has stored, for moving to a new machine:
+/// settings, known devices with their names, models and overrides, presets,
+/// scenes, schedules (timelines, calendar, Pomodoro), shortcut, DMX and
+/// address mappings, and the rest of the settings store. The archive is a
+/// JSON file recording its own format version and the store's schema
+/// version (see `migrations`). Restoring checks both, brings an older store
+/// up to the current layout, replaces the whole store and restarts the app,
+/// so every part starts again from the restored state.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::migrations::{self, Values};
+use crate::STORE_FILE;
+
+/// Format version written to archives.
+const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    /// Schema version of `store`.
+    pub schema_version: u32,
+    /// When it was made, in Unix seconds.
+    pub created: u64,
+    /// Every value in the settings store, by key.
+    pub store: Values,
+}
+
+impl Archive {
+    fn validate(&self) -> Result<(), String> {
+        if self.version > ARCHIVE_VERSION {
+            return Err(format!(
+                "Backup is from a newer version (format {}), this app reads up to {}",
+                self.version, ARCHIVE_VERSION
+            ));
+        }
+        if self.schema_version > migrations::CURRENT {
+            return Err(format!(
+                "Backup holds settings from a newer version (schema {}), this app reads up to {}",
+                self.schema_version,
+                migrations::CURRENT
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Write everything stored to a backup archive at `path`.
+pub fn backup_all(app: &AppHandle, path: &str) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    let mut values: Values = store.entries().into_iter().collect();
+    let schema_version = values
+        .remove(migrations::VERSION_KEY)
+        .and_then(|v| v.as_u64())
+        .map_or(migrations::CURRENT, |v| v as u32);
+    let archive = Archive {
+        version: ARCHIVE_VERSION,
+        schema_version,
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        store: values,
+    };
+    let json = serde_json::to_string_pretty(&archive).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+/// Replace everything stored with the backup archive at `path`, then restart
+/// the app. Nothing is changed if the archive can't be read or is from a
+/// newer version.
+pub fn restore_all(app: &AppHandle, path: &str) -> Result<(), String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let archive: Archive =
+        serde_json::from_str(&json).map_err(|e| format!("Not a valid backup file: {e}"))?;
+    archive.validate()?;
+
+    let mut values = archive.store;
+    migrations::migrate(&mut values, archive.schema_version);
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.clear();
+    for (key, value) in values {
+        store.set(key, value);
+    }
+    store.set(migrations::VERSION_KEY, json!(migrations::CURRENT));
+    store.save().map_err(|e| e.to_string())?;
+    app.restart()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_newer_archives() {
+        let archive = Archive {
+            version: ARCHIVE_VERSION,
+            schema_version: 0,
+            created: 0,
+            store: Values::new(),
+        };
+        assert!(archive.validate().is_ok());
+        let newer_format = Archive {
+            version: ARCHIVE_VERSION + 1,
+            ..archive.clone()
+        };
+        assert!(newer_format
+            .validate()
+            .unwrap_err()
+            .contains("newer version"));
+        let newer_schema = Archive {
+            schema_version: migrations::CURRENT + 1,
+            ..archive
+        };
+        assert!(newer_schema.validate().unwrap_err().contains("schema"));
+    }
+}
@@ -0,0 +1,112 @@
+/// Setting several lights to different states at once.
+///
+/// Applying one light after another, each write waiting on the previous
+/// light's checks, makes a key/fill/back change visibly ripple across the
+/// set. A batch lists each light with its own state; all of them are checked
+/// first and their commands then queued together (see
+/// `SerialManager::set_cct_many`), and each light's outcome is reported.
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::curves::CurveManager;
+use crate::groups::{DeviceError, FanOutReport};
+use crate::serial::SerialManager;
+
+/// A state to set a light to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LightState {
+    /// Slider level 0-100 (perceptual, before the dimming curve).
+    pub brightness: u8,
+    pub kelvin: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchEntry {
+    pub device: String,
+    pub state: LightState,
+}
+
+fn validate(batch: &[BatchEntry]) -> Result<(), String> {
+    if batch.is_empty() {
+        return Err("Nothing to set".into());
+    }
+    let mut seen = BTreeSet::new();
+    for entry in batch {
+        if entry.state.brightness > 100 {
+            return Err(format!("{}: brightness must be 0-100", entry.device));
+        }
+        if !seen.insert(entry.device.as_str()) {
+            return Err(format!("{} is listed more than once", entry.device));
+        }
+    }
+    Ok(())
+}
+
+/// Every connected light that has reported its state, as it is now.
+pub fn capture(app: &AppHandle) -> Vec<BatchEntry> {
+    let serial = app.state::<SerialManager>();
+    serial
+        .ids()
+        .into_iter()
+        .filter_map(|id| {
+            let status = serial.status_of(&id)?;
+            Some(BatchEntry {
+                device: id,
+                state: LightState {
+                    brightness: status.level,
+                    kelvin: status.kelvin,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Set every light in `batch` to its state together. Fails only if no light
+/// was set; the others' failures are listed in the report.
+pub fn apply(app: &AppHandle, batch: &[BatchEntry]) -> Result<FanOutReport, String> {
+    validate(batch)?;
+    let curves = app.state::<CurveManager>();
+    let settings: Vec<(String, u8, u32)> = batch
+        .iter()
+        .map(|e| {
+            let hw = curves.to_hw(&e.device, e.state.brightness);
+            (e.device.clone(), hw, e.state.kelvin)
+        })
+        .collect();
+    let results = app.state::<SerialManager>().set_cct_many(&settings);
+
+    let mut report = FanOutReport::default();
+    for ((device, _, _), result) in settings.into_iter().zip(results) {
+        match result {
+            Ok(()) => report.succeeded.push(device),
+            Err(error) => report.failed.push(DeviceError::new(device, error)),
+        }
+    }
+    if report.succeeded.is_empty() {
+        let errors: Vec<String> = report.failed.iter().map(|f| f.error.clone()).collect();
+        return Err(errors.join("; "));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let entry = |device: &str, brightness| BatchEntry {
+            device: device.into(),
+            state: LightState {
+                brightness,
+                kelvin: 5600,
+            },
+        };
+        assert!(validate(&[entry("a", 50), entry("b", 100)]).is_ok());
+        assert!(validate(&[]).is_err());
+        assert!(validate(&[entry("a", 101)]).is_err());
+        assert!(validate(&[entry("a", 50), entry("a", 60)]).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+/// Configuration import/export.
+///
+/// Presets, groups and automations (timelines, macros and scripts) can be
+/// exported to a JSON file and imported on another machine. An import is
+/// validated in full before anything is changed. `Merge` adds the imported
+/// items and replaces existing ones with the same name; `Replace` also removes
+/// items the file doesn't contain. The panel reloads its presets on
+/// "presets-changed".
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::groups::{Group, GroupManager};
+use crate::macros::{Macro, MacroRecorder};
+use crate::presets::{self, Preset};
+use crate::scripting::{Script, ScriptHost};
+use crate::timeline::{Timeline, TimelineEngine};
+use crate::STORE_FILE;
+
+/// Format version written to exported files.
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bundle {
+    pub version: u32,
+    pub presets: Vec<Preset>,
+    pub groups: Vec<Group>,
+    pub timelines: Vec<Timeline>,
+    pub macros: Vec<Macro>,
+    pub scripts: Vec<Script>,
+}
+
+impl Bundle {
+    fn validate(&self) -> Result<(), String> {
+        if self.version > BUNDLE_VERSION {
+            return Err(format!(
+                "File is from a newer version (format {}), this app reads up to {}",
+                self.version, BUNDLE_VERSION
+            ));
+        }
+        self.presets.iter().try_for_each(Preset::validate)?;
+        if self.groups.iter().any(|g| g.name.trim().is_empty()) {
+            return Err("Group name cannot be empty".into());
+        }
+        self.timelines.iter().try_for_each(Timeline::validate)?;
+        self.macros.iter().try_for_each(Macro::validate)?;
+        self.scripts.iter().try_for_each(Script::validate)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+/// Number of items of each kind imported.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub presets: usize,
+    pub groups: usize,
+    pub timelines: usize,
+    pub macros: usize,
+    pub scripts: usize,
+}
+
+/// Everything exportable, as currently saved.
+pub fn collect(app: &AppHandle) -> Bundle {
+    Bundle {
+        version: BUNDLE_VERSION,
+        presets: presets::load(app),
+        groups: app.state::<GroupManager>().list(),
+        timelines: app.state::<TimelineEngine>().list(),
+        macros: app.state::<MacroRecorder>().list(),
+        scripts: app.state::<ScriptHost>().list(),
+    }
+}
+
+pub fn export(app: &AppHandle, path: &str) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&collect(app)).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+pub fn import(app: &AppHandle, path: &str, mode: ImportMode) -> Result<ImportReport, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let bundle: Bundle =
+        serde_json::from_str(&json).map_err(|e| format!("Not a valid export file: {e}"))?;
+    bundle.validate()?;
+    apply(app, bundle, mode)
+}
+
+fn apply(app: &AppHandle, bundle: Bundle, mode: ImportMode) -> Result<ImportReport, String> {
+    let replace = matches!(mode, ImportMode::Replace);
+    let report = ImportReport {
+        presets: bundle.presets.len(),
+        groups: bundle.groups.len(),
+        timelines: bundle.timelines.len(),
+        macros: bundle.macros.len(),
+        scripts: bundle.scripts.len(),
+    };
+
+    let presets = if replace {
+        bundle.presets
+    } else {
+        merge_by_name(presets::load(app), bundle.presets, |p| &p.name)
+    };
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        presets::PRESETS_KEY,
+        serde_json::to_value(&presets).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    let _ = app.emit("presets-changed", ());
+
+    let groups = app.state::<GroupManager>();
+    if replace {
+        for old in stale(groups.list(), &bundle.groups, |g| &g.name) {
+            groups.delete(app, &old)?;
+        }
+    }
+    for group in bundle.groups {
+        groups.save(app, &group.name, group.members)?;
+    }
+
+    let timelines = app.state::<TimelineEngine>();
+    if replace {
+        for old in stale(timelines.list(), &bundle.timelines, |t| &t.name) {
+            timelines.delete(app, &old)?;
+        }
+    }
+    for timeline in bundle.timelines {
+        timelines.save(app, timeline)?;
+    }
+
+    let macros = app.state::<MacroRecorder>();
+    if replace {
+        for old in stale(macros.list(), &bundle.macros, |m| &m.name) {
+            macros.delete(app, &old)?;
+        }
+    }
+    for mac in bundle.macros {
+        macros.save(app, mac)?;
+    }
+
+    let scripts = app.state::<ScriptHost>();
+    if replace {
+        for old in stale(scripts.list(), &bundle.scripts, |s| &s.name) {
+            scripts.delete(app, &old)?;
+        }
+    }
+    for script in bundle.scripts {
+        scripts.save(app, script)?;
+    }
+    Ok(report)
+}
+
+/// `existing` with same-named items replaced in place and new ones appended.
+fn merge_by_name<T>(
+    mut existing: Vec<T>,
+    incoming: Vec<T>,
+    name: impl Fn(&T) -> &String,
+) -> Vec<T> {
+    for item in incoming {
+        match existing.iter_mut().find(|e| name(e) == name(&item)) {
+            Some(slot) => *slot = item,
+            None => existing.push(item),
+        }
+    }
+    existing
+}
+
+/// Names in `existing` that `incoming` doesn't contain.
+fn stale<T>(existing: Vec<T>, incoming: &[T], name: impl Fn(&T) -> &String) -> Vec<String> {
+    existing
+        .iter()
+        .filter(|e| !incoming.iter().any(|i| name(i) == name(e)))
+        .map(|e| name(e).clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str, brightness: u8) -> Preset {
+        Preset {
+            name: name.into(),
+            brightness,
+            kelvin: 5600,
+        }
+    }
+
+    #[test]
+    fn test_merge_by_name() {
+        let merged = merge_by_name(
+            vec![preset("Key", 50), preset("Fill", 20)],
+            vec![preset("Fill", 30), preset("Rim", 80)],
+            |p| &p.name,
+        );
+        let summary: Vec<(&str, u8)> = merged
+            .iter()
+            .map(|p| (p.name.as_str(), p.brightness))
+            .collect();
+        assert_eq!(summary, [("Key", 50), ("Fill", 30), ("Rim", 80)]);
+    }
+
+    #[test]
+    fn test_stale_names() {
+        let old = vec![preset("Key", 50), preset("Fill", 20)];
+        assert_eq!(stale(old, &[preset("Fill", 30)], |p| &p.name), ["Key"]);
+    }
+
+    #[test]
+    fn test_rejects_newer_format_and_bad_presets() {
+        let newer = Bundle {
+            version: BUNDLE_VERSION + 1,
+            ..Bundle::default()
+        };
+        assert!(newer.validate().is_err());
+        let bad = Bundle {
+            version: BUNDLE_VERSION,
+            presets: vec![preset("Key", 101)],
+            ..Bundle::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+}
@@ -0,0 +1,320 @@
+/// Calendar-based automation.
+///
+/// An optional loop polls the system calendar and, a configurable number of
+/// minutes before an event whose title matches a filter, applies an "on-air"
+/// preset; once no matching event is in progress the lights are restored to
+/// how they were. Events are read from Calendar.app (and so every account it
+/// syncs, backed by EventKit) through `osascript`; other platforms aren't
+/// supported yet. Configuration is persisted under `calendar` in the settings
+/// store.
+use std::process::Command;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::groups::{self, Target};
+use crate::history::{self, Snapshot};
+use crate::sessionlog::{self, Source};
+use crate::{notify, presets, STORE_FILE};
+
+const CALENDAR_KEY: &str = "calendar";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalendarConfig {
+    pub enabled: bool,
+    /// Case-insensitive substring an event title must contain; empty matches
+    /// every event.
+    pub title_filter: String,
+    /// Apply the preset this many minutes before the event starts.
+    pub lead_minutes: u32,
+    /// Index of the preset to apply (panel order).
+    pub preset: usize,
+    /// Put the lights back afterwards.
+    pub restore: bool,
+    pub poll_seconds: u64,
+    /// Lights to drive; all connected lights by default.
+    pub target: Option<Target>,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title_filter: String::new(),
+            lead_minutes: 2,
+            preset: 0,
+            restore: true,
+            poll_seconds: 60,
+            target: None,
+        }
+    }
+}
+
+impl CalendarConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.lead_minutes > 120 {
+            return Err("Calendar lead time must be at most 120 minutes".into());
+        }
+        if !(15..=3600).contains(&self.poll_seconds) {
+            return Err("Calendar poll interval must be 15-3600 seconds".into());
+        }
+        Ok(())
+    }
+
+    /// The first matching event that is in progress, or starts within the
+    /// lead time, at `now` (ms since the epoch).
+    fn active<'a>(&self, events: &'a [Event], now: u64) -> Option<&'a Event> {
+        let lead = self.lead_minutes as u64 * 60_000;
+        let filter = self.title_filter.to_lowercase();
+        events.iter().find(|e| {
+            e.start_ms.saturating_sub(lead) <= now
+                && now < e.end_ms
+                && e.title.to_lowercase().contains(&filter)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    pub title: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// Reported as "calendar-automation" when the on-air state changes.
+#[derive(Debug, Clone, Serialize)]
+pub struct OnAir {
+    pub event: Option<Event>,
+}
+
+pub struct CalendarAutomation {
+    config: Mutex<CalendarConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl CalendarAutomation {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(CalendarConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start polling if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: CalendarConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(CALENDAR_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> CalendarConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: CalendarConfig) -> Result<(), String> {
+        config.validate()?;
+        if config.enabled {
+            // Surface platform and permission problems while the user is here
+            read_events(0)?;
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            CALENDAR_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: CalendarConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+fn run(app: AppHandle, config: CalendarConfig, current: Arc<AtomicU64>, gen: u64) {
+    sessionlog::set_source(Source::Automation);
+    // State to restore and the event that caused it, while on air
+    let mut on_air: Option<(Snapshot, Event)> = None;
+    let mut failing = false;
+    let poll = Duration::from_secs(config.poll_seconds);
+    // Look far enough ahead that an event's lead time can't fall between polls
+    let horizon = config.lead_minutes + (config.poll_seconds / 60) as u32 + 1;
+
+    while current.load(Ordering::SeqCst) == gen {
+        match read_events(horizon) {
+            Ok(events) => {
+                failing = false;
+                let active = config.active(&events, now_ms()).cloned();
+                match (&on_air, active) {
+                    (None, Some(event)) => {
+                        let saved = history::snapshot(&app);
+                        match apply(&app, &config) {
+                            Ok(()) => {
+                                let _ = app.emit(
+                                    "calendar-automation",
+                                    OnAir {
+                                        event: Some(event.clone()),
+                                    },
+                                );
+                                on_air = Some((saved, event));
+                            }
+                            Err(e) => notify::error(&app, "Calendar automation failed", &e),
+                        }
+                    }
+                    (Some((saved, _)), None) => {
+                        if config.restore {
+                            history::restore(&app, saved);
+                        }
+                        on_air = None;
+                        let _ = app.emit("calendar-automation", OnAir { event: None });
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Can't read the calendar", &e);
+                }
+            }
+        }
+        // Sleep in short steps so reconfiguring takes effect promptly
+        let mut slept = Duration::ZERO;
+        while slept < poll && current.load(Ordering::SeqCst) == gen {
+            std::thread::sleep(Duration::from_secs(1));
+            slept += Duration::from_secs(1);
+        }
+    }
+}
+
+fn apply(app: &AppHandle, config: &CalendarConfig) -> Result<(), String> {
+    let preset = presets::get(app, config.preset)?;
+    let dither = app.state::<Ditherer>();
+    groups::fan_out(app, config.target.as_ref(), |_, id| {
+        dither.set_level(app, id, preset.brightness, preset.kelvin)
+    })?;
+    Ok(())
+}
+
+/// JXA script listing events that overlap now..now + argv[0] minutes, one per
+/// line as "title<TAB>start ms<TAB>end ms".
+const EVENTS_SCRIPT: &str = r#"
+function run(argv) {
+    const now = new Date();
+    const until = new Date(now.getTime() + Number(argv[0]) * 60000);
+    const lines = [];
+    for (const cal of Application("Calendar").calendars()) {
+        const events = cal.events.whose({
+            startDate: { _lessThan: until },
+            endDate: { _greaterThan: now },
+        })();
+        for (const e of events) {
+            const title = (e.summary() || "").replace(/[\t\n]/g, " ");
+            lines.push([title, e.startDate().getTime(), e.endDate().getTime()].join("\t"));
+        }
+    }
+    return lines.join("\n");
+}
+"#;
+
+/// Events overlapping the next `minutes`.
+fn read_events(minutes: u32) -> Result<Vec<Event>, String> {
+    if !cfg!(target_os = "macos") {
+        return Err("Calendar automation is only supported on macOS".into());
+    }
+    let output = Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", EVENTS_SCRIPT])
+        .arg(minutes.to_string())
+        .output()
+        .map_err(|e| format!("Failed to run osascript: {e}"))?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Calendar access failed: {}", err.trim()));
+    }
+    Ok(parse_events(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_events(out: &str) -> Vec<Event> {
+    out.lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, '\t');
+            let end_ms = fields.next()?.trim().parse().ok()?;
+            let start_ms = fields.next()?.trim().parse().ok()?;
+            let title = fields.next()?.to_string();
+            Some(Event {
+                title,
+                start_ms,
+                end_ms,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_events() {
+        let events = parse_events("Standup\t1000\t2000\nbad line\nLive: Q&A\t3000\t4000\n");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].title, "Live: Q&A");
+        assert_eq!((events[1].start_ms, events[1].end_ms), (3000, 4000));
+    }
+
+    #[test]
+    fn test_active_applies_lead_and_filter() {
+        let config = CalendarConfig {
+            title_filter: "live".into(),
+            lead_minutes: 2,
+            ..CalendarConfig::default()
+        };
+        let start = 10 * 60_000;
+        let events = [
+            Event {
+                title: "Standup".into(),
+                start_ms: 0,
+                end_ms: 3_600_000,
+            },
+            Event {
+                title: "LIVE stream".into(),
+                start_ms: start,
+                end_ms: start + 60_000,
+            },
+        ];
+        assert_eq!(config.active(&events, start - 3 * 60_000), None);
+        assert_eq!(
+            config.active(&events, start - 60_000).map(|e| e.start_ms),
+            Some(start)
+        );
+        assert_eq!(config.active(&events, start + 60_000), None);
+    }
+}
@@ -0,0 +1,206 @@
+/// Lux calibration.
+///
+/// Users measure illuminance with a light meter at a few brightness levels and
+/// distances and store the readings per device. Readings are normalized to 1 m
+/// with the inverse-square law and interpolated piecewise-linearly over
+/// hardware brightness, which lets the backend estimate illuminance at the
+/// subject distance and set a light by target lux. Tables are persisted under
+/// `calibration` in the settings store, keyed by device id.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::STORE_FILE;
+
+const CALIBRATION_KEY: &str = "calibration";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationPoint {
+    /// Hardware brightness 1-100.
+    pub brightness: u8,
+    pub distance_m: f64,
+    pub lux: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationTable {
+    /// Light-to-subject distance used for estimates and lux targets.
+    pub subject_distance_m: f64,
+    pub points: Vec<CalibrationPoint>,
+}
+
+impl CalibrationTable {
+    fn validate(&self) -> Result<(), String> {
+        if !positive(self.subject_distance_m) {
+            return Err("Subject distance must be positive".into());
+        }
+        if self.points.is_empty() {
+            return Err("Calibration needs at least one reading".into());
+        }
+        for p in &self.points {
+            if !(1..=100).contains(&p.brightness) {
+                return Err("Calibration brightness must be 1-100".into());
+            }
+            if !positive(p.distance_m) || !(p.lux.is_finite() && p.lux >= 0.0) {
+                return Err("Calibration distance must be positive and lux non-negative".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// (brightness, lux at 1 m) knots sorted by brightness, anchored at 0.
+    /// Duplicate brightness readings are averaged.
+    fn curve(&self) -> Vec<(f64, f64)> {
+        let mut sums: BTreeMap<u8, (f64, u32)> = BTreeMap::new();
+        for p in &self.points {
+            let e = sums.entry(p.brightness).or_default();
+            e.0 += p.lux * p.distance_m * p.distance_m;
+            e.1 += 1;
+        }
+        std::iter::once((0.0, 0.0))
+            .chain(
+                sums.into_iter()
+                    .map(|(b, (sum, n))| (b as f64, sum / n as f64)),
+            )
+            .collect()
+    }
+
+    /// Estimated lux at the subject for a hardware brightness. Extrapolates
+    /// linearly past the highest reading.
+    pub fn estimate(&self, brightness: u8) -> f64 {
+        let knots = self.curve();
+        let b = brightness as f64;
+        let seg = knots
+            .windows(2)
+            .find(|w| b <= w[1].0)
+            .unwrap_or_else(|| &knots[knots.len().saturating_sub(2)..]);
+        let lux_1m = match seg {
+            [(b0, l0), (b1, l1)] => l0 + (l1 - l0) * (b - b0) / (b1 - b0),
+            _ => 0.0,
+        };
+        lux_1m.max(0.0) / (self.subject_distance_m * self.subject_distance_m)
+    }
+
+    /// Lowest hardware brightness whose estimate reaches `lux`, capped at 100.
+    pub fn brightness_for(&self, lux: f64) -> u8 {
+        (0..=100u8)
+            .find(|&b| self.estimate(b) >= lux)
+            .unwrap_or(100)
+    }
+}
+
+fn positive(x: f64) -> bool {
+    x.is_finite() && x > 0.0
+}
+
+pub struct Calibration {
+    tables: Mutex<BTreeMap<String, CalibrationTable>>,
+}
+
+impl Calibration {
+    pub fn new() -> Self {
+        Self {
+            tables: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, CalibrationTable> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(CALIBRATION_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.tables.lock().unwrap() = saved;
+    }
+
+    pub fn get(&self, id: &str) -> Option<CalibrationTable> {
+        self.tables.lock().unwrap().get(id).cloned()
+    }
+
+    /// Store a device's table; `None` removes it.
+    pub fn set(
+        &self,
+        app: &AppHandle,
+        id: &str,
+        table: Option<CalibrationTable>,
+    ) -> Result<(), String> {
+        let mut tables = self.tables.lock().unwrap();
+        match table {
+            Some(table) => {
+                table.validate()?;
+                tables.insert(id.to_string(), table);
+            }
+            None => {
+                tables.remove(id);
+            }
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            CALIBRATION_KEY,
+            serde_json::to_value(&*tables).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())
+    }
+
+    /// Estimated lux at the subject, if the device is calibrated.
+    pub fn estimate(&self, id: &str, brightness: u8) -> Option<f64> {
+        self.tables
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|t| t.estimate(brightness))
+    }
+
+    /// Hardware brightness for a target lux on a calibrated device.
+    pub fn brightness_for(&self, id: &str, lux: f64) -> Result<u8, String> {
+        self.tables
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|t| t.brightness_for(lux))
+            .ok_or_else(|| format!("{id} has no lux calibration"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> CalibrationTable {
+        CalibrationTable {
+            subject_distance_m: 1.0,
+            points: vec![
+                CalibrationPoint {
+                    brightness: 50,
+                    distance_m: 2.0,
+                    lux: 100.0,
+                },
+                CalibrationPoint {
+                    brightness: 100,
+                    distance_m: 1.0,
+                    lux: 800.0,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_estimate_normalizes_distance() {
+        let t = table();
+        // 100 lux at 2 m is 400 lux at 1 m
+        assert_eq!(t.estimate(50), 400.0);
+        assert_eq!(t.estimate(25), 200.0);
+        assert_eq!(t.estimate(75), 600.0);
+    }
+
+    #[test]
+    fn test_brightness_for_inverts_estimate() {
+        let t = table();
+        assert_eq!(t.brightness_for(600.0), 75);
+        assert_eq!(t.brightness_for(10_000.0), 100);
+    }
+}
@@ -1,8 +1,71 @@
 /// Tauri commands exposed to the frontend.
-use tauri::State;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
+use tauri::{Manager, State};
+
+use crate::addressing::{self, Address, AddressBook};
+use crate::ambient::{AmbientConfig, AmbientLight};
+use crate::arbitration::{Arbiter, ArbitrationConfig};
+use crate::autoexposure::{AutoExposure, AutoExposureConfig};
+use crate::backup;
+use crate::batch::{self, BatchEntry};
+use crate::bundle::{self, ImportMode, ImportReport};
+use crate::calendar::{CalendarAutomation, CalendarConfig};
+use crate::calibration::{Calibration, CalibrationTable};
+use crate::compare::{AbCompare, CompareStatus, Slot};
+use crate::config::{Settings, SettingsManager};
+use crate::curves::{CurveManager, DimmingCurve};
+use crate::devices::{self, BluetoothAliases, DeviceInfo, DeviceNames, Preference, PreferredDevice};
+use crate::dmx::{DmxConfig, DmxOutput};
+use crate::effects::{Effect, EffectEngine, EffectParams, EffectStatus, StrobeParams};
+use crate::energy::{EnergyConfig, EnergyMeter, EnergyTotals};
+use crate::fade::{self, FadeEngine};
+use crate::frontmost::{AppRules, AppRulesConfig};
+use crate::groups::{self, FanOutReport, Group, GroupManager, Target};
+use crate::health::{self, HealthReport};
+use crate::history::{History, HistoryStatus};
+use crate::hue::{self, HueBridge, HueConfig};
+use crate::idle::{IdleConfig, IdleDimmer};
+use crate::latency::{self, LatencyReport};
+use crate::limits::BrightnessLimits;
+use crate::links::{Link, LinkManager};
+use crate::lock::{ControlLock, LockStatus};
+use crate::macros::{Macro, MacroRecorder};
+use crate::matching::{ColorMatching, Offset};
+use crate::models::{Capabilities, DeviceModels, Model, RangeOverride};
+use crate::mqtt::{MqttBridge, MqttConfig};
+use crate::nightshift::{NightShiftConfig, NightShiftFollow};
+use crate::packets::{CapturedFrame, PacketCapture};
+use crate::plugins::{Manifest, PluginHost};
+use crate::pomodoro::{Pomodoro, PomodoroConfig, PomodoroStatus};
+use crate::portconfig::{PortConfig, SerialParams};
+use crate::presets::{self, Preset};
+use crate::presetsync::{PresetSync, SyncConfig};
+use crate::profiles::{ProfileManager, Profiles};
 use crate::protocol;
-use crate::serial::SerialManager;
+use crate::proximity::{self, BleDevice, ProximityConfig, ProximityPresence};
+use crate::registry::{DeviceRegistry, KnownDevice};
+use crate::rf::{RfConfig, RfDongle};
+use crate::scenes::{Scene, SceneEntry, SceneManager};
+use crate::screensync::{ScreenSync, ScreenSyncConfig};
+use crate::scripting::{Script, ScriptHost};
+use crate::scroll::ScrollAdjuster;
+use crate::serial::{LightStatus, PortResult, SerialManager, WriteStats};
+use crate::sessionlog::{self, ExportFormat, SessionLog, Source};
+use crate::shortcuts::{Binding, ShortcutAction, ShortcutManager};
+use crate::snapshots::{Snapshot, Snapshots};
+use crate::steps::{self, KelvinStepReport};
+use crate::supervisor::Supervisor;
+use crate::sweep::{ExposureSweep, SweepConfig};
+use crate::thermal::{ThermalConfig, ThermalProtection};
+use crate::timeline::{PlaybackStatus, Timeline, TimelineEngine};
+use crate::toggle::{PowerToggle, ToggleReport};
+use crate::tray;
+use crate::usage::{DeviceUsage, UsageTracker};
+use crate::webhooks::{self, Webhook, Webhooks};
+use crate::wemo::{WemoConfig, WemoEmulation};
+use crate::whitebalance::{self, WhiteBalance};
 
 #[tauri::command]
 pub fn quit_app(app: tauri::AppHandle) {
@@ -10,23 +73,278 @@ pub fn quit_app(app: tauri::AppHandle) {
 }
 
 #[tauri::command]
-pub fn list_ports() -> Vec<String> {
-    serialport::available_ports()
-        .unwrap_or_default()
+pub fn list_ports(app: tauri::AppHandle) -> Vec<String> {
+    devices::scan(&app)
         .into_iter()
-        .filter(|p| p.port_name.contains("usbserial"))
-        .map(|p| p.port_name)
+        .map(|(_, port, _)| port)
         .collect()
 }
 
 #[tauri::command]
-pub fn connect(path: String, app: tauri::AppHandle, state: State<'_, SerialManager>) -> Result<(), String> {
-    state.connect(&path, app)
+pub fn list_devices(
+    app: tauri::AppHandle,
+    names: State<'_, DeviceNames>,
+    models: State<'_, DeviceModels>,
+    registry: State<'_, DeviceRegistry>,
+    serial: State<'_, SerialManager>,
+) -> Vec<DeviceInfo> {
+    let connected = serial.ids();
+    let mut known = registry.list();
+    let mut found: Vec<DeviceInfo> = devices::lights(&app)
+        .into_iter()
+        .map(|light| {
+            let seen = known.remove(&light.id);
+            DeviceInfo {
+                name: names.name(&light.id, &light.ports[0]),
+                connected: connected.contains(&light.id),
+                format: models.detected(&light.id),
+                port: light.ports[0].clone(),
+                present: true,
+                last_seen: seen.as_ref().map(|k| k.last_seen),
+                last_state: seen.and_then(|k| k.last_state),
+                id: light.id,
+                ports: light.ports,
+                serial_number: light.serial_number,
+            }
+        })
+        .collect();
+    // Lights connected before whose ports aren't attached now
+    for (id, k) in known {
+        let Some(port) = k.ports.first().cloned() else {
+            continue;
+        };
+        found.push(DeviceInfo {
+            name: names.name(&id, &port),
+            connected: connected.contains(&id),
+            format: models.detected(&id),
+            serial_number: (id != port).then(|| id.clone()),
+            port,
+            ports: k.ports,
+            present: false,
+            last_seen: Some(k.last_seen),
+            last_state: k.last_state,
+            id,
+        });
+    }
+    found
+}
+
+/// Every light connected so far, including those not attached now.
+#[tauri::command]
+pub fn list_known_devices(state: State<'_, DeviceRegistry>) -> BTreeMap<String, KnownDevice> {
+    state.list()
+}
+
+/// Remove a light that isn't connected from the device list.
+#[tauri::command]
+pub fn forget_device(
+    device: String,
+    app: tauri::AppHandle,
+    registry: State<'_, DeviceRegistry>,
+    serial: State<'_, SerialManager>,
+) -> Result<(), String> {
+    if serial.ids().contains(&device) {
+        return Err(format!("{device} is connected"));
+    }
+    registry.forget(&app, &device)
+}
+
+/// Serial number of the light each Bluetooth port reaches, by port.
+#[tauri::command]
+pub fn list_bluetooth_aliases(state: State<'_, BluetoothAliases>) -> BTreeMap<String, String> {
+    state.list()
+}
+
+/// Note which light (by USB serial number) a Bluetooth port reaches, so it's
+/// listed once, or forget it with `None`.
+#[tauri::command]
+pub fn set_bluetooth_alias(
+    port: String,
+    device: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, BluetoothAliases>,
+) -> Result<(), String> {
+    state.set(&app, &port, device)
+}
+
+/// The port auto-connect would use: the pinned device, else the last one
+/// connected, else (unless limited to the preferred device) the first found.
+/// None when auto-connect is off.
+#[tauri::command]
+pub fn preferred_port(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsManager>,
+    state: State<'_, PreferredDevice>,
+) -> Option<String> {
+    state.port(&app, settings.get().auto_connect)
+}
+
+/// Pin a device to prefer when auto-connecting.
+#[tauri::command]
+pub fn pin_device(
+    id: String,
+    app: tauri::AppHandle,
+    state: State<'_, PreferredDevice>,
+) -> Result<Preference, String> {
+    state.pin(&app, Some(id))
+}
+
+#[tauri::command]
+pub fn unpin_device(app: tauri::AppHandle, state: State<'_, PreferredDevice>) -> Result<Preference, String> {
+    state.pin(&app, None)
+}
+
+/// Serial parameters by device id; devices not listed use 115200-8N1.
+#[tauri::command]
+pub fn list_serial_params(state: State<'_, PortConfig>) -> BTreeMap<String, SerialParams> {
+    state.list()
+}
+
+/// Set or reset (with `None`) a device's serial parameters, used from the
+/// next connect.
+#[tauri::command]
+pub fn set_serial_params(
+    device: String,
+    params: Option<SerialParams>,
+    app: tauri::AppHandle,
+    state: State<'_, PortConfig>,
+) -> Result<(), String> {
+    state.set(&app, &device, params)
+}
+
+#[tauri::command]
+pub fn rename_device(
+    id: String,
+    name: String,
+    app: tauri::AppHandle,
+    names: State<'_, DeviceNames>,
+) -> Result<(), String> {
+    names.rename(&app, &id, &name)?;
+    tray::refresh(&app);
+    Ok(())
 }
 
+/// What one device, or the primary device, supports, from its model.
 #[tauri::command]
-pub fn disconnect(state: State<'_, SerialManager>) {
-    state.disconnect();
+pub fn get_capabilities(
+    device: Option<String>,
+    models: State<'_, DeviceModels>,
+    serial: State<'_, SerialManager>,
+) -> Result<Capabilities, String> {
+    let id = match device {
+        Some(id) => id,
+        None => serial.device().ok_or("Port not open")?.0,
+    };
+    Ok(models.capabilities(&id))
+}
+
+/// Set which model a device is, returning its capabilities.
+#[tauri::command]
+pub fn set_device_model(
+    device: String,
+    model: Model,
+    app: tauri::AppHandle,
+    models: State<'_, DeviceModels>,
+) -> Result<Capabilities, String> {
+    models.set(&app, &device, model)
+}
+
+/// Give a device its own temperature range and step count, or with no
+/// range go back to its model's, returning its capabilities.
+#[tauri::command]
+pub fn set_device_kelvin_range(
+    device: String,
+    range: Option<RangeOverride>,
+    app: tauri::AppHandle,
+    models: State<'_, DeviceModels>,
+) -> Result<Capabilities, String> {
+    models.set_range(&app, &device, range)
+}
+
+/// Connect to the light on a port and keep it connected (see `supervisor`).
+#[tauri::command]
+pub fn connect(
+    path: String,
+    app: tauri::AppHandle,
+    state: State<'_, SerialManager>,
+    supervisor: State<'_, Supervisor>,
+) -> Result<(), String> {
+    let id = state.connect(&path, app)?;
+    supervisor.want(&id);
+    Ok(())
+}
+
+/// Connect to every Neewer light found, reporting the outcome per port.
+#[tauri::command]
+pub fn connect_all(
+    app: tauri::AppHandle,
+    state: State<'_, SerialManager>,
+    supervisor: State<'_, Supervisor>,
+) -> BTreeMap<String, PortResult> {
+    let results = state.connect_all(&app);
+    for id in results.values().filter_map(|result| result.device.as_deref()) {
+        supervisor.want(id);
+    }
+    results
+}
+
+/// Disconnect one device, or every device if none is given.
+#[tauri::command]
+pub fn disconnect(
+    device: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, SerialManager>,
+    supervisor: State<'_, Supervisor>,
+) {
+    supervisor.release(device.as_deref());
+    match device {
+        Some(id) => state.disconnect_device(&id),
+        None => state.disconnect(),
+    }
+    tray::refresh(&app);
+}
+
+/// Ask one light, or the primary light, for its current status.
+#[tauri::command]
+pub fn query_status(device: Option<String>, state: State<'_, SerialManager>) -> Result<LightStatus, String> {
+    let id = match device {
+        Some(id) => id,
+        None => state.device().ok_or("Port not open")?.0,
+    };
+    state.query(&id).map_err(String::from)
+}
+
+/// Write pipeline counters of every connected light, by device id.
+#[tauri::command]
+pub fn write_stats(state: State<'_, SerialManager>) -> BTreeMap<String, WriteStats> {
+    state.write_stats()
+}
+
+/// Connection state, status age, queue depth and error counters of every
+/// light, and which integrations are enabled, for a status dashboard.
+#[tauri::command]
+pub fn health(app: tauri::AppHandle) -> HealthReport {
+    health::report(&app)
+}
+
+/// Time `samples` status queries to one light, or the primary light, and
+/// report the round trips.
+#[tauri::command]
+pub async fn measure_latency(
+    samples: u32,
+    device: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<LatencyReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let serial = app.state::<SerialManager>();
+        let id = match device {
+            Some(id) => id,
+            None => serial.device().ok_or("Port not open")?.0,
+        };
+        latency::measure(&serial, &id, samples)
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
@@ -34,8 +352,1204 @@ pub fn is_connected(state: State<'_, SerialManager>) -> bool {
     state.is_connected()
 }
 
+/// Set brightness (slider level 0-100, mapped through each light's dimming
+/// curve) and temperature, given as exactly one of `kelvin` or `mired`,
+/// fading from each light's current state over `transition_ms` if given.
+#[tauri::command]
+pub fn set_light(
+    brightness: u8,
+    kelvin: Option<u32>,
+    mired: Option<u32>,
+    target: Option<Target>,
+    transition_ms: Option<u64>,
+    app: tauri::AppHandle,
+) -> Result<FanOutReport, String> {
+    let kelvin = match (kelvin, mired) {
+        (Some(k), None) => k,
+        (None, Some(m)) => protocol::mired_to_kelvin(m),
+        _ => return Err("Pass exactly one of kelvin or mired".into()),
+    };
+    let transition_ms = transition_ms.unwrap_or(0);
+    if transition_ms > fade::MAX_TRANSITION_MS {
+        return Err("Transition must be at most an hour".into());
+    }
+    app.state::<History>().checkpoint(&app);
+    fade::transition(
+        &app,
+        target.as_ref(),
+        Some(brightness),
+        Some(kelvin),
+        Duration::from_millis(transition_ms),
+    )
+}
+
+/// Set several lights, each to its own state, together.
+#[tauri::command]
+pub fn set_lights(batch: Vec<BatchEntry>, app: tauri::AppHandle) -> Result<FanOutReport, String> {
+    app.state::<History>().checkpoint(&app);
+    batch::apply(&app, &batch)
+}
+
+#[tauri::command]
+pub fn set_power(on: bool, target: Option<Target>, app: tauri::AppHandle) -> Result<FanOutReport, String> {
+    app.state::<History>().checkpoint(&app);
+    groups::fan_out(&app, target.as_ref(), |serial, id| serial.set_power_to(id, on)).map_err(String::from)
+}
+
+/// Set the lights to an HSI colour: hue in degrees, saturation and brightness
+/// 0-100. Lights whose model has no HSI colour are listed as failed with an
+/// "unsupported" error.
+#[tauri::command]
+pub fn set_color(
+    hue: u16,
+    saturation: u8,
+    brightness: u8,
+    target: Option<Target>,
+    app: tauri::AppHandle,
+) -> Result<FanOutReport, String> {
+    app.state::<History>().checkpoint(&app);
+    groups::fan_out(&app, target.as_ref(), |serial, id| serial.set_hsi_to(id, hue, saturation, brightness))
+        .map_err(String::from)
+}
+
+/// Switch the lights off if any is on, remembering each one's state, or back
+/// on to what was remembered, fading over `fade_ms` if given.
+#[tauri::command]
+pub fn toggle(
+    fade_ms: Option<u64>,
+    target: Option<Target>,
+    app: tauri::AppHandle,
+    state: State<'_, PowerToggle>,
+) -> Result<ToggleReport, String> {
+    let fade_ms = fade_ms.unwrap_or(0);
+    if fade_ms > fade::MAX_TRANSITION_MS {
+        return Err("Fade must be at most an hour".into());
+    }
+    app.state::<History>().checkpoint(&app);
+    state.toggle(&app, target.as_ref(), Duration::from_millis(fade_ms))
+}
+
+/// Raise the brightness of each light by `step` levels (10 by default) from
+/// where it is.
+#[tauri::command]
+pub fn brightness_up(step: Option<u8>, target: Option<Target>, app: tauri::AppHandle) -> Result<FanOutReport, String> {
+    app.state::<History>().checkpoint(&app);
+    let step = step.unwrap_or(steps::DEFAULT_BRIGHTNESS_STEP);
+    steps::brightness(&app, target.as_ref(), step as i32).map_err(String::from)
+}
+
+/// Lower the brightness of each light by `step` levels (10 by default).
+#[tauri::command]
+pub fn brightness_down(step: Option<u8>, target: Option<Target>, app: tauri::AppHandle) -> Result<FanOutReport, String> {
+    app.state::<History>().checkpoint(&app);
+    let step = step.unwrap_or(steps::DEFAULT_BRIGHTNESS_STEP);
+    steps::brightness(&app, target.as_ref(), -(step as i32)).map_err(String::from)
+}
+
+/// Make each light cooler by `step` Kelvin (200 by default), to the nearest
+/// temperature it can show.
+#[tauri::command]
+pub fn kelvin_up(step: Option<u32>, target: Option<Target>, app: tauri::AppHandle) -> Result<KelvinStepReport, String> {
+    let step = steps::kelvin_step(step)?;
+    app.state::<History>().checkpoint(&app);
+    steps::kelvin(&app, target.as_ref(), step).map_err(String::from)
+}
+
+/// Make each light warmer by `step` Kelvin (200 by default).
+#[tauri::command]
+pub fn kelvin_down(step: Option<u32>, target: Option<Target>, app: tauri::AppHandle) -> Result<KelvinStepReport, String> {
+    let step = steps::kelvin_step(step)?;
+    app.state::<History>().checkpoint(&app);
+    steps::kelvin(&app, target.as_ref(), -step).map_err(String::from)
+}
+
+/// Apply the saved preset at `index` (panel order).
+#[tauri::command]
+pub fn apply_preset(
+    index: usize,
+    target: Option<Target>,
+    app: tauri::AppHandle,
+) -> Result<FanOutReport, String> {
+    presets::apply(&app, index, target.as_ref())
+}
+
+#[tauri::command]
+pub fn list_profiles(state: State<'_, ProfileManager>) -> Profiles {
+    state.list()
+}
+
+/// Save the current presets and settings as a named profile and make it active.
+#[tauri::command]
+pub fn save_profile(
+    name: String,
+    app: tauri::AppHandle,
+    state: State<'_, ProfileManager>,
+) -> Result<Profiles, String> {
+    state.save(&app, &name)
+}
+
+#[tauri::command]
+pub fn delete_profile(
+    name: String,
+    app: tauri::AppHandle,
+    state: State<'_, ProfileManager>,
+) -> Result<Profiles, String> {
+    state.delete(&app, &name)
+}
+
+/// Swap in a profile's presets and settings.
+#[tauri::command]
+pub fn switch_profile(
+    name: String,
+    app: tauri::AppHandle,
+    state: State<'_, ProfileManager>,
+) -> Result<Profiles, String> {
+    state.switch(&app, &name)
+}
+
+/// Write presets, groups and automations to a JSON file at `path`.
+#[tauri::command]
+pub fn export_config(path: String, app: tauri::AppHandle) -> Result<(), String> {
+    bundle::export(&app, &path)
+}
+
+/// Import an exported file, merging with or replacing the current items.
+#[tauri::command]
+pub fn import_config(path: String, mode: ImportMode, app: tauri::AppHandle) -> Result<ImportReport, String> {
+    bundle::import(&app, &path, mode)
+}
+
+/// Write everything the app has stored to a backup file at `path`.
+#[tauri::command]
+pub fn backup_all(path: String, app: tauri::AppHandle) -> Result<(), String> {
+    backup::backup_all(&app, &path)
+}
+
+/// Replace everything stored with a backup file and restart the app.
+#[tauri::command]
+pub fn restore_all(path: String, app: tauri::AppHandle) -> Result<(), String> {
+    backup::restore_all(&app, &path)
+}
+
+#[tauri::command]
+pub fn get_preset_sync(state: State<'_, PresetSync>) -> SyncConfig {
+    state.get()
+}
+
+/// Save the preset sync folder, (re)starting or stopping the sync loop.
+#[tauri::command]
+pub fn set_preset_sync(
+    config: SyncConfig,
+    app: tauri::AppHandle,
+    state: State<'_, PresetSync>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+/// Set calibrated lights to a target illuminance at their subject distance,
+/// keeping their temperature.
+#[tauri::command]
+pub fn set_light_lux(lux: f64, target: Option<Target>, app: tauri::AppHandle) -> Result<FanOutReport, String> {
+    if !(lux.is_finite() && lux >= 0.0) {
+        return Err("Target lux must be non-negative".into());
+    }
+    app.state::<History>().checkpoint(&app);
+    let calibration = app.state::<Calibration>();
+    groups::fan_out(&app, target.as_ref(), |serial, id| {
+        let bri = calibration.brightness_for(id, lux)?;
+        let kelvin = serial
+            .status_of(id)
+            .map_or(protocol::DEFAULT_TEMP_K, |s| s.kelvin);
+        serial.set_cct_to(id, bri, kelvin)
+    })
+    .map_err(String::from)
+}
+
+#[tauri::command]
+pub fn get_calibration(device: String, state: State<'_, Calibration>) -> Option<CalibrationTable> {
+    state.get(&device)
+}
+
+/// Store a device's lux calibration, or remove it with `None`.
+#[tauri::command]
+pub fn set_calibration(
+    device: String,
+    table: Option<CalibrationTable>,
+    app: tauri::AppHandle,
+    state: State<'_, Calibration>,
+) -> Result<(), String> {
+    state.set(&app, &device, table)
+}
+
+#[tauri::command]
+pub fn get_auto_exposure(state: State<'_, AutoExposure>) -> AutoExposureConfig {
+    state.get()
+}
+
+/// Save the webcam auto-brightness settings, (re)starting or stopping the loop.
+#[tauri::command]
+pub fn set_auto_exposure(
+    config: AutoExposureConfig,
+    app: tauri::AppHandle,
+    state: State<'_, AutoExposure>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_ambient_light(state: State<'_, AmbientLight>) -> AmbientConfig {
+    state.get()
+}
+
+/// Save the ambient light sensor mapping, (re)starting or stopping the loop.
+#[tauri::command]
+pub fn set_ambient_light(
+    config: AmbientConfig,
+    app: tauri::AppHandle,
+    state: State<'_, AmbientLight>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_screen_sync(state: State<'_, ScreenSync>) -> ScreenSyncConfig {
+    state.get()
+}
+
+/// Save the screen sync settings, (re)starting or stopping the bias light loop.
+#[tauri::command]
+pub fn set_screen_sync(
+    config: ScreenSyncConfig,
+    app: tauri::AppHandle,
+    state: State<'_, ScreenSync>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_calendar_automation(state: State<'_, CalendarAutomation>) -> CalendarConfig {
+    state.get()
+}
+
+/// Save the calendar automation, (re)starting or stopping the polling loop.
+#[tauri::command]
+pub fn set_calendar_automation(
+    config: CalendarConfig,
+    app: tauri::AppHandle,
+    state: State<'_, CalendarAutomation>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_night_shift(state: State<'_, NightShiftFollow>) -> NightShiftConfig {
+    state.get()
+}
+
+/// Save the Night Shift follow settings, (re)starting or stopping the loop.
+#[tauri::command]
+pub fn set_night_shift(
+    config: NightShiftConfig,
+    app: tauri::AppHandle,
+    state: State<'_, NightShiftFollow>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_idle_dim(state: State<'_, IdleDimmer>) -> IdleConfig {
+    state.get()
+}
+
+/// Save the idle auto-dim settings, (re)starting or stopping the loop.
+#[tauri::command]
+pub fn set_idle_dim(
+    config: IdleConfig,
+    app: tauri::AppHandle,
+    state: State<'_, IdleDimmer>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_thermal_protection(state: State<'_, ThermalProtection>) -> ThermalConfig {
+    state.get()
+}
+
+/// Save the thermal-protection settings, (re)starting or stopping the loop.
+#[tauri::command]
+pub fn set_thermal_protection(
+    config: ThermalConfig,
+    app: tauri::AppHandle,
+    state: State<'_, ThermalProtection>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+/// Stop thermal protection dimming lights for `minutes`; 0 ends a snooze.
+#[tauri::command]
+pub fn snooze_thermal_protection(minutes: u32, state: State<'_, ThermalProtection>) {
+    state.snooze(minutes);
+}
+
+/// Step the lights through a range of levels, emitting "exposure-sweep" at
+/// each step.
+#[tauri::command]
+pub fn start_exposure_sweep(
+    config: SweepConfig,
+    app: tauri::AppHandle,
+    state: State<'_, ExposureSweep>,
+) -> Result<(), String> {
+    state.start(&app, config)
+}
+
+/// Stop the exposure sweep and put the lights back where they were.
+#[tauri::command]
+pub fn stop_exposure_sweep(app: tauri::AppHandle, state: State<'_, ExposureSweep>) {
+    state.stop(&app);
+}
+
+/// End the exposure sweep at `level` (where it is by default) and save that
+/// level as a preset.
+#[tauri::command]
+pub fn save_exposure_sweep(
+    name: String,
+    level: Option<u8>,
+    app: tauri::AppHandle,
+    state: State<'_, ExposureSweep>,
+) -> Result<Preset, String> {
+    state.save(&app, &name, level)
+}
+
+#[tauri::command]
+pub fn get_proximity(state: State<'_, ProximityPresence>) -> ProximityConfig {
+    state.get()
+}
+
+/// Save the Bluetooth proximity settings, (re)starting or stopping the loop.
+#[tauri::command]
+pub fn set_proximity(
+    config: ProximityConfig,
+    app: tauri::AppHandle,
+    state: State<'_, ProximityPresence>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+/// Listen for Bluetooth LE devices for a few seconds, to pick one to follow.
+#[tauri::command]
+pub async fn nearby_bluetooth_devices() -> Result<Vec<BleDevice>, String> {
+    proximity::nearby().await
+}
+
+#[tauri::command]
+pub fn get_app_rules(state: State<'_, AppRules>) -> AppRulesConfig {
+    state.get()
+}
+
+/// Save the frontmost-app rules, (re)starting or stopping the watcher.
+#[tauri::command]
+pub fn set_app_rules(
+    config: AppRulesConfig,
+    app: tauri::AppHandle,
+    state: State<'_, AppRules>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn list_webhooks(state: State<'_, Webhooks>) -> Vec<Webhook> {
+    state.list()
+}
+
+#[tauri::command]
+pub fn set_webhooks(
+    hooks: Vec<Webhook>,
+    app: tauri::AppHandle,
+    state: State<'_, Webhooks>,
+) -> Result<(), String> {
+    state.set(&app, hooks)
+}
+
+/// Cumulative on-time per device id, split by brightness band.
+#[tauri::command]
+pub fn usage_stats(state: State<'_, UsageTracker>) -> BTreeMap<String, DeviceUsage> {
+    state.stats()
+}
+
+/// Clear the usage totals of one device, or of all devices.
+#[tauri::command]
+pub fn reset_usage(
+    device: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, UsageTracker>,
+) -> Result<(), String> {
+    state.reset(&app, device.as_deref())
+}
+
+#[tauri::command]
+pub fn get_energy_config(state: State<'_, EnergyMeter>) -> EnergyConfig {
+    state.get()
+}
+
+/// Save the wattage curves and daily summary setting.
+#[tauri::command]
+pub fn set_energy_config(
+    config: EnergyConfig,
+    app: tauri::AppHandle,
+    state: State<'_, EnergyMeter>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+/// Estimated energy use in Wh per device id, in total and for today.
+#[tauri::command]
+pub fn energy_stats(state: State<'_, EnergyMeter>) -> EnergyTotals {
+    state.totals()
+}
+
+/// Clear the energy estimates of one device, or of all devices.
+#[tauri::command]
+pub fn reset_energy(
+    device: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, EnergyMeter>,
+) -> Result<(), String> {
+    state.reset(&app, device.as_deref())
+}
+
+#[tauri::command]
+pub fn get_mqtt(state: State<'_, MqttBridge>) -> MqttConfig {
+    state.get()
+}
+
+/// Save the MQTT settings, reconnecting or disconnecting as needed.
+#[tauri::command]
+pub fn set_mqtt(
+    config: MqttConfig,
+    app: tauri::AppHandle,
+    state: State<'_, MqttBridge>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_hue(state: State<'_, HueBridge>) -> HueConfig {
+    state.get()
+}
+
+/// Save the Hue bridge emulation settings, restarting or stopping it.
+#[tauri::command]
+pub fn set_hue(
+    config: HueConfig,
+    app: tauri::AppHandle,
+    state: State<'_, HueBridge>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+/// Accept Hue app pairing for a while, like the bridge's link button.
+/// Returns how long pairing stays open, in seconds.
+#[tauri::command]
+pub fn pair_hue(state: State<'_, HueBridge>) -> u64 {
+    state.pair();
+    hue::PAIRING_WINDOW.as_secs()
+}
+
+/// Forget every paired Hue app.
+#[tauri::command]
+pub fn unpair_hue(app: tauri::AppHandle, state: State<'_, HueBridge>) -> Result<(), String> {
+    state.unpair_all(&app)
+}
+
+#[tauri::command]
+pub fn get_wemo(state: State<'_, WemoEmulation>) -> WemoConfig {
+    state.get()
+}
+
+/// Save the Wemo emulation settings, restarting or stopping it.
+#[tauri::command]
+pub fn set_wemo(
+    config: WemoConfig,
+    app: tauri::AppHandle,
+    state: State<'_, WemoEmulation>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_dmx(state: State<'_, DmxOutput>) -> DmxConfig {
+    state.get()
+}
+
+/// Save the DMX output settings, opening or closing the adapter as needed.
+#[tauri::command]
+pub fn set_dmx(
+    config: DmxConfig,
+    app: tauri::AppHandle,
+    state: State<'_, DmxOutput>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+#[tauri::command]
+pub fn get_rf_dongle(state: State<'_, RfDongle>) -> RfConfig {
+    state.get()
+}
+
+/// Save the 2.4GHz dongle settings, opening its port if it changed.
+#[tauri::command]
+pub fn set_rf_dongle(
+    config: RfConfig,
+    app: tauri::AppHandle,
+    state: State<'_, RfDongle>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+/// Select the RF channel the 2.4GHz dongle sends on, as with the remote's
+/// channel buttons.
+#[tauri::command]
+pub fn set_target_channel(
+    channel: u8,
+    app: tauri::AppHandle,
+    state: State<'_, RfDongle>,
+) -> Result<(), String> {
+    state.set_channel(&app, channel)
+}
+
+/// Set every light on an RF channel at once (the dongle's channel if `None`):
+/// over RF through the dongle, and over USB to cabled lights on the channel.
+#[tauri::command]
+pub fn broadcast_channel(
+    brightness: u8,
+    kelvin: u32,
+    channel: Option<u8>,
+    app: tauri::AppHandle,
+) -> Result<FanOutReport, String> {
+    let channel = channel.unwrap_or_else(|| app.state::<RfDongle>().get().channel);
+    app.state::<History>().checkpoint(&app);
+    addressing::broadcast(&app, channel, brightness, kelvin)
+}
+
+/// RF channel and group of cabled lights, by device id.
+#[tauri::command]
+pub fn list_light_addresses(state: State<'_, AddressBook>) -> BTreeMap<String, Address> {
+    state.list()
+}
+
+/// Record the channel and group a cabled light is set to, or take it off its
+/// channel with `None`.
+#[tauri::command]
+pub fn set_light_address(
+    device: String,
+    address: Option<Address>,
+    app: tauri::AppHandle,
+    state: State<'_, AddressBook>,
+) -> Result<(), String> {
+    state.set(&app, &device, address)
+}
+
+/// Set lights over RF through the 2.4GHz dongle: one group (1-4) on the
+/// selected channel, or every group for `None`.
+#[tauri::command]
+pub fn rf_set_cct(
+    brightness: u8,
+    kelvin: u32,
+    group: Option<u8>,
+    app: tauri::AppHandle,
+    state: State<'_, RfDongle>,
+) -> Result<(), String> {
+    state.set_cct(&app, group, brightness, kelvin)
+}
+
+/// Send a test event to `url` and report whether it was accepted.
+#[tauri::command]
+pub async fn test_webhook(url: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || webhooks::test(&url))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn list_white_balance() -> Vec<WhiteBalance> {
+    whitebalance::list()
+}
+
+/// Set the temperature to a named white balance / gel setpoint, keeping each
+/// light's brightness.
+#[tauri::command]
+pub fn apply_white_balance(
+    name: String,
+    target: Option<Target>,
+    app: tauri::AppHandle,
+) -> Result<FanOutReport, String> {
+    let wb = whitebalance::get(&name)?;
+    app.state::<History>().checkpoint(&app);
+    groups::fan_out(&app, target.as_ref(), |serial, id| {
+        let status = serial
+            .status_of(id)
+            .ok_or("No status received from light yet")?;
+        serial.set_cct_to(id, status.brightness, wb.kelvin)
+    })
+    .map_err(String::from)
+}
+
+/// Fade smoothly from preset `preset_a` to preset `preset_b` over `seconds`.
+#[tauri::command]
+pub fn crossfade(
+    preset_a: usize,
+    preset_b: usize,
+    seconds: f64,
+    target: Option<Target>,
+    app: tauri::AppHandle,
+    fade: State<'_, FadeEngine>,
+) -> Result<(), String> {
+    if !(0.0..=3600.0).contains(&seconds) {
+        return Err("Crossfade duration must be between 0 and 3600 seconds".into());
+    }
+    let a = presets::get(&app, preset_a)?;
+    let b = presets::get(&app, preset_b)?;
+    app.state::<History>().checkpoint(&app);
+    fade.start(
+        &app,
+        target,
+        (a.brightness, a.kelvin),
+        (b.brightness, b.kelvin),
+        Duration::from_secs_f64(seconds),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub fn stop_fade(fade: State<'_, FadeEngine>) {
+    fade.cancel();
+}
+
+/// Run a software effect (candle, fire, tv, lightning, drift) until stopped.
+#[tauri::command]
+pub fn start_effect(
+    effect: Effect,
+    params: Option<EffectParams>,
+    target: Option<Target>,
+    app: tauri::AppHandle,
+    state: State<'_, EffectEngine>,
+) -> Result<(), String> {
+    app.state::<ControlLock>().check(Source::Manual)?;
+    state.start(&app, effect, params.unwrap_or_default(), target)
+}
+
+/// Strobe the lights. Rate and duty cycle are capped for safety; the settings
+/// actually used are returned.
+#[tauri::command]
+pub fn start_strobe(
+    params: StrobeParams,
+    target: Option<Target>,
+    app: tauri::AppHandle,
+    state: State<'_, EffectEngine>,
+) -> Result<StrobeParams, String> {
+    app.state::<ControlLock>().check(Source::Manual)?;
+    state.strobe(&app, params, target)
+}
+
+/// Stop the running effect or strobe at once and restore the lights.
+#[tauri::command]
+pub fn stop_effect(app: tauri::AppHandle, state: State<'_, EffectEngine>) {
+    state.stop(&app);
+}
+
+#[tauri::command]
+pub fn effect_status(state: State<'_, EffectEngine>) -> EffectStatus {
+    state.status()
+}
+
+#[tauri::command]
+pub fn get_pomodoro(state: State<'_, Pomodoro>) -> PomodoroConfig {
+    state.get()
+}
+
+/// Save the timer settings; they apply from the next start.
+#[tauri::command]
+pub fn set_pomodoro(
+    config: PomodoroConfig,
+    app: tauri::AppHandle,
+    state: State<'_, Pomodoro>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+/// Start a timer session with a work phase.
+#[tauri::command]
+pub fn start_pomodoro(app: tauri::AppHandle, state: State<'_, Pomodoro>) -> PomodoroStatus {
+    state.start(&app)
+}
+
+#[tauri::command]
+pub fn stop_pomodoro(app: tauri::AppHandle, state: State<'_, Pomodoro>) {
+    state.stop(&app);
+}
+
+#[tauri::command]
+pub fn pomodoro_status(state: State<'_, Pomodoro>) -> PomodoroStatus {
+    state.status()
+}
+
+/// The session's light changes as CSV or JSON text.
+#[tauri::command]
+pub fn export_history(format: ExportFormat, state: State<'_, SessionLog>) -> Result<String, String> {
+    state.export(format)
+}
+
+/// Checksum-valid frames the app didn't recognize, oldest first.
+#[tauri::command]
+pub fn unknown_packets(state: State<'_, PacketCapture>) -> Vec<CapturedFrame> {
+    state.list()
+}
+
+/// Frame `payload` under `tag` and write it to one light, or the primary
+/// light, as is. For mapping the protocol of other models.
+#[tauri::command]
+pub fn send_packet(
+    tag: u8,
+    payload: Vec<u8>,
+    device: Option<String>,
+    lock: State<'_, ControlLock>,
+    state: State<'_, SerialManager>,
+) -> Result<(), String> {
+    lock.check(sessionlog::current_source())?;
+    let packet = protocol::Packet::new(tag).payload(&payload).build()?;
+    let id = match device {
+        Some(id) => id,
+        None => state.device().ok_or("Port not open")?.0,
+    };
+    state.write_to(&id, &packet).map_err(String::from)
+}
+
+#[tauri::command]
+pub fn clear_unknown_packets(state: State<'_, PacketCapture>) {
+    state.clear();
+}
+
+#[tauri::command]
+pub fn lock_status(state: State<'_, ControlLock>) -> LockStatus {
+    state.status()
+}
+
+/// Lock manual control, optionally behind a PIN. `allowed` lists the sources
+/// that may still change the lights (automations and external APIs by
+/// default).
+#[tauri::command]
+pub fn lock_controls(
+    pin: Option<String>,
+    allowed: Option<Vec<Source>>,
+    app: tauri::AppHandle,
+    state: State<'_, ControlLock>,
+) -> Result<LockStatus, String> {
+    state.lock(&app, pin, allowed)
+}
+
+#[tauri::command]
+pub fn unlock_controls(
+    pin: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, ControlLock>,
+) -> Result<LockStatus, String> {
+    state.unlock(&app, pin)
+}
+
+#[tauri::command]
+pub fn get_arbitration(state: State<'_, Arbiter>) -> ArbitrationConfig {
+    state.get()
+}
+
+/// Save the source priorities and how long a write holds a light.
+#[tauri::command]
+pub fn set_arbitration(
+    config: ArbitrationConfig,
+    app: tauri::AppHandle,
+    state: State<'_, Arbiter>,
+) -> Result<(), String> {
+    state.set(&app, config)
+}
+
+/// Seconds left of the manual hold on automations, if one is on.
+#[tauri::command]
+pub fn hold_status(state: State<'_, Arbiter>) -> Option<u64> {
+    state.hold_remaining().map(|left| left.as_secs() + 1)
+}
+
+/// End the manual hold so automations can change the lights again.
+#[tauri::command]
+pub fn clear_hold(state: State<'_, Arbiter>) {
+    state.clear_hold();
+}
+
+/// Restore the light states from before the last change.
+#[tauri::command]
+pub fn undo(app: tauri::AppHandle, state: State<'_, History>) -> Result<HistoryStatus, String> {
+    state.undo(&app)
+}
+
+#[tauri::command]
+pub fn redo(app: tauri::AppHandle, state: State<'_, History>) -> Result<HistoryStatus, String> {
+    state.redo(&app)
+}
+
+#[tauri::command]
+pub fn history_status(state: State<'_, History>) -> HistoryStatus {
+    state.status()
+}
+
+/// Remember the lights' current state as setup "a" or "b".
+#[tauri::command]
+pub fn mark_ab(slot: Slot, app: tauri::AppHandle, state: State<'_, AbCompare>) -> Result<CompareStatus, String> {
+    state.mark(&app, slot)
+}
+
+/// Switch to the other marked setup.
+#[tauri::command]
+pub fn toggle_ab(app: tauri::AppHandle, state: State<'_, AbCompare>) -> Result<CompareStatus, String> {
+    state.toggle(&app)
+}
+
+#[tauri::command]
+pub fn clear_ab(app: tauri::AppHandle, state: State<'_, AbCompare>) {
+    state.clear(&app);
+}
+
+#[tauri::command]
+pub fn ab_status(state: State<'_, AbCompare>) -> CompareStatus {
+    state.status()
+}
+
+#[tauri::command]
+pub fn list_timelines(state: State<'_, TimelineEngine>) -> Vec<Timeline> {
+    state.list()
+}
+
+/// Create a timeline, or replace one with the same name.
+#[tauri::command]
+pub fn save_timeline(
+    timeline: Timeline,
+    app: tauri::AppHandle,
+    state: State<'_, TimelineEngine>,
+) -> Result<(), String> {
+    state.save(&app, timeline)
+}
+
+#[tauri::command]
+pub fn delete_timeline(
+    name: String,
+    app: tauri::AppHandle,
+    state: State<'_, TimelineEngine>,
+) -> Result<(), String> {
+    state.delete(&app, &name)
+}
+
+#[tauri::command]
+pub fn play_timeline(
+    name: String,
+    target: Option<Target>,
+    app: tauri::AppHandle,
+    state: State<'_, TimelineEngine>,
+) -> Result<(), String> {
+    app.state::<ControlLock>().check(Source::Manual)?;
+    state.play(&app, &name, target)
+}
+
+#[tauri::command]
+pub fn pause_timeline(app: tauri::AppHandle, state: State<'_, TimelineEngine>) -> Result<(), String> {
+    state.pause(&app)
+}
+
+#[tauri::command]
+pub fn resume_timeline(app: tauri::AppHandle, state: State<'_, TimelineEngine>) -> Result<(), String> {
+    state.resume(&app)
+}
+
+#[tauri::command]
+pub fn stop_timeline(app: tauri::AppHandle, state: State<'_, TimelineEngine>) {
+    state.stop(&app);
+}
+
+#[tauri::command]
+pub fn timeline_status(state: State<'_, TimelineEngine>) -> PlaybackStatus {
+    state.status()
+}
+
+#[tauri::command]
+pub fn list_scripts(state: State<'_, ScriptHost>) -> Vec<Script> {
+    state.list()
+}
+
+/// Create or replace a script; enabled scripts are (re)started immediately.
+#[tauri::command]
+pub fn save_script(
+    script: Script,
+    app: tauri::AppHandle,
+    state: State<'_, ScriptHost>,
+) -> Result<(), String> {
+    state.save(&app, script)
+}
+
+#[tauri::command]
+pub fn delete_script(
+    name: String,
+    app: tauri::AppHandle,
+    state: State<'_, ScriptHost>,
+) -> Result<(), String> {
+    state.delete(&app, &name)
+}
+
+#[tauri::command]
+pub fn list_macros(state: State<'_, MacroRecorder>) -> Vec<Macro> {
+    state.list()
+}
+
+#[tauri::command]
+pub fn start_macro_recording(app: tauri::AppHandle, state: State<'_, MacroRecorder>) -> Result<(), String> {
+    state.start_recording(&app)
+}
+
+/// Stop recording and save the captured changes as `name`.
+#[tauri::command]
+pub fn stop_macro_recording(
+    name: String,
+    app: tauri::AppHandle,
+    state: State<'_, MacroRecorder>,
+) -> Result<Macro, String> {
+    state.stop_recording(&app, &name)
+}
+
+#[tauri::command]
+pub fn cancel_macro_recording(app: tauri::AppHandle, state: State<'_, MacroRecorder>) {
+    state.cancel_recording(&app);
+}
+
+#[tauri::command]
+pub fn play_macro(name: String, app: tauri::AppHandle, state: State<'_, MacroRecorder>) -> Result<(), String> {
+    app.state::<ControlLock>().check(Source::Manual)?;
+    state.play(&app, &name)
+}
+
+#[tauri::command]
+pub fn stop_macro(state: State<'_, MacroRecorder>) {
+    state.stop();
+}
+
+#[tauri::command]
+pub fn delete_macro(
+    name: String,
+    app: tauri::AppHandle,
+    state: State<'_, MacroRecorder>,
+) -> Result<(), String> {
+    state.delete(&app, &name)
+}
+
+#[tauri::command]
+pub fn list_plugins(state: State<'_, PluginHost>) -> Vec<Manifest> {
+    state.list()
+}
+
+/// Run a plugin action directly, e.g. from a panel button.
+#[tauri::command]
+pub fn invoke_plugin_action(
+    plugin: String,
+    action: String,
+    args: Option<serde_json::Value>,
+    state: State<'_, PluginHost>,
+) -> Result<serde_json::Value, String> {
+    state.invoke(&plugin, &action, &args.unwrap_or(serde_json::Value::Null))
+}
+
+/// Configured dimming curves by device id; other devices use the default.
+#[tauri::command]
+pub fn list_dimming_curves(state: State<'_, CurveManager>) -> BTreeMap<String, DimmingCurve> {
+    state.list()
+}
+
+/// Set a device's dimming curve, or reset it to the default with `None`.
+#[tauri::command]
+pub fn set_dimming_curve(
+    device: String,
+    curve: Option<DimmingCurve>,
+    app: tauri::AppHandle,
+    state: State<'_, CurveManager>,
+) -> Result<(), String> {
+    state.set(&app, &device, curve)
+}
+
+/// Configured brightness caps by device id; other devices are uncapped.
+#[tauri::command]
+pub fn list_brightness_caps(state: State<'_, BrightnessLimits>) -> BTreeMap<String, u8> {
+    state.list()
+}
+
+/// Cap a device's hardware brightness, or remove its cap with `None`.
+#[tauri::command]
+pub fn set_brightness_cap(
+    device: String,
+    cap: Option<u8>,
+    app: tauri::AppHandle,
+    state: State<'_, BrightnessLimits>,
+) -> Result<(), String> {
+    state.set(&app, &device, cap)
+}
+
+/// Configured colour-matching offsets by device id; other devices have none.
+#[tauri::command]
+pub fn list_color_offsets(state: State<'_, ColorMatching>) -> BTreeMap<String, Offset> {
+    state.list()
+}
+
+/// Set a device's colour-matching offset, or remove it with `None`.
+#[tauri::command]
+pub fn set_color_offset(
+    device: String,
+    offset: Option<Offset>,
+    app: tauri::AppHandle,
+    state: State<'_, ColorMatching>,
+) -> Result<(), String> {
+    state.set(&app, &device, offset)
+}
+
+#[tauri::command]
+pub fn list_groups(state: State<'_, GroupManager>) -> Vec<Group> {
+    state.list()
+}
+
+/// Create a group, or replace an existing group's members.
+#[tauri::command]
+pub fn save_group(
+    name: String,
+    members: Vec<String>,
+    app: tauri::AppHandle,
+    state: State<'_, GroupManager>,
+) -> Result<(), String> {
+    state.save(&app, &name, members)
+}
+
+#[tauri::command]
+pub fn delete_group(name: String, app: tauri::AppHandle, state: State<'_, GroupManager>) -> Result<(), String> {
+    state.delete(&app, &name)
+}
+
+#[tauri::command]
+pub fn list_scenes(state: State<'_, SceneManager>) -> Vec<Scene> {
+    state.list()
+}
+
+/// Create a scene, or replace an existing one. Without `entries`, captures
+/// every connected light as it is now.
+#[tauri::command]
+pub fn save_scene(
+    name: String,
+    entries: Option<Vec<SceneEntry>>,
+    app: tauri::AppHandle,
+    state: State<'_, SceneManager>,
+) -> Result<(), String> {
+    state.save(&app, &name, entries)
+}
+
+#[tauri::command]
+pub fn delete_scene(name: String, app: tauri::AppHandle, state: State<'_, SceneManager>) -> Result<(), String> {
+    state.delete(&app, &name)
+}
+
+/// Set each light in a scene to its state, together.
+#[tauri::command]
+pub fn apply_scene(name: String, app: tauri::AppHandle, state: State<'_, SceneManager>) -> Result<FanOutReport, String> {
+    app.state::<History>().checkpoint(&app);
+    sessionlog::with_source(Source::Preset, || state.apply(&app, &name))
+}
+
+/// Saved snapshots, newest first.
+#[tauri::command]
+pub fn list_snapshots(state: State<'_, Snapshots>) -> Vec<Snapshot> {
+    state.list()
+}
+
+/// Record every connected light's current state.
+#[tauri::command]
+pub fn snapshot(app: tauri::AppHandle, state: State<'_, Snapshots>) -> Result<Snapshot, String> {
+    state.take(&app)
+}
+
+#[tauri::command]
+pub fn restore_snapshot(id: u64, app: tauri::AppHandle, state: State<'_, Snapshots>) -> Result<FanOutReport, String> {
+    app.state::<History>().checkpoint(&app);
+    state.restore(&app, id)
+}
+
+#[tauri::command]
+pub fn delete_snapshot(id: u64, app: tauri::AppHandle, state: State<'_, Snapshots>) -> Result<(), String> {
+    state.delete(&app, id)
+}
+
+#[tauri::command]
+pub fn list_links(state: State<'_, LinkManager>) -> Vec<Link> {
+    state.list()
+}
+
+/// Make `link.follower` track `link.master` with the given offsets.
+#[tauri::command]
+pub fn link_device(link: Link, app: tauri::AppHandle, state: State<'_, LinkManager>) -> Result<(), String> {
+    state.link(&app, link)
+}
+
+#[tauri::command]
+pub fn unlink_device(
+    follower: String,
+    app: tauri::AppHandle,
+    state: State<'_, LinkManager>,
+) -> Result<(), String> {
+    state.unlink(&app, &follower)
+}
+
+#[tauri::command]
+pub fn scroll_light(
+    delta: f64,
+    kelvin: bool,
+    app: tauri::AppHandle,
+    state: State<'_, ScrollAdjuster>,
+) -> Result<(), String> {
+    state.scroll(delta, kelvin, &app)
+}
+
+#[tauri::command]
+pub fn list_shortcuts(state: State<'_, ShortcutManager>) -> Vec<Binding> {
+    state.list()
+}
+
+#[tauri::command]
+pub fn bind_shortcut(
+    accelerator: String,
+    action: ShortcutAction,
+    app: tauri::AppHandle,
+    state: State<'_, ShortcutManager>,
+) -> Result<(), String> {
+    state.bind(&app, &accelerator, action)
+}
+
+#[tauri::command]
+pub fn unbind_shortcut(
+    accelerator: String,
+    app: tauri::AppHandle,
+    state: State<'_, ShortcutManager>,
+) -> Result<(), String> {
+    state.unbind(&app, &accelerator)
+}
+
+#[tauri::command]
+pub fn get_settings(state: State<'_, SettingsManager>) -> Settings {
+    state.get()
+}
+
 #[tauri::command]
-pub fn set_light(brightness: u8, kelvin: u32, state: State<'_, SerialManager>) -> Result<(), String> {
-    let cmd = protocol::cct_command(brightness, kelvin);
-    state.write(&cmd)
+pub fn update_settings(
+    settings: Settings,
+    app: tauri::AppHandle,
+    state: State<'_, SettingsManager>,
+) -> Result<(), String> {
+    state.update(&app, settings)
 }
@@ -0,0 +1,103 @@
+/// A/B comparison of two lighting setups.
+///
+/// The user marks the current state of all lights as "A" or "B", then toggles
+/// between the two to compare them on camera. Marks live only for the session.
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::history::{self, Snapshot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Slot {
+    A,
+    B,
+}
+
+/// Emitted as "ab-changed" and returned by the commands.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareStatus {
+    pub has_a: bool,
+    pub has_b: bool,
+    /// Slot last applied by `toggle`, if any.
+    pub showing: Option<Slot>,
+}
+
+#[derive(Default)]
+struct Marks {
+    a: Option<Snapshot>,
+    b: Option<Snapshot>,
+    showing: Option<Slot>,
+}
+
+impl Marks {
+    fn status(&self) -> CompareStatus {
+        CompareStatus {
+            has_a: self.a.is_some(),
+            has_b: self.b.is_some(),
+            showing: self.showing,
+        }
+    }
+}
+
+pub struct AbCompare {
+    marks: Mutex<Marks>,
+}
+
+impl AbCompare {
+    pub fn new() -> Self {
+        Self {
+            marks: Mutex::new(Marks::default()),
+        }
+    }
+
+    /// Store the lights' current state in `slot`.
+    pub fn mark(&self, app: &AppHandle, slot: Slot) -> Result<CompareStatus, String> {
+        let snapshot = history::snapshot(app);
+        if snapshot.is_empty() {
+            return Err("No light status received yet".into());
+        }
+        let status = {
+            let mut marks = self.marks.lock().unwrap();
+            match slot {
+                Slot::A => marks.a = Some(snapshot),
+                Slot::B => marks.b = Some(snapshot),
+            }
+            marks.showing = Some(slot);
+            marks.status()
+        };
+        let _ = app.emit("ab-changed", &status);
+        Ok(status)
+    }
+
+    /// Apply the other slot: B after A (or when nothing was shown yet), A
+    /// after B.
+    pub fn toggle(&self, app: &AppHandle) -> Result<CompareStatus, String> {
+        let status = {
+            let mut marks = self.marks.lock().unwrap();
+            let (Some(a), Some(b)) = (&marks.a, &marks.b) else {
+                return Err("Mark both A and B first".into());
+            };
+            let next = match marks.showing {
+                Some(Slot::B) => Slot::A,
+                _ => Slot::B,
+            };
+            history::restore(app, if next == Slot::A { a } else { b });
+            marks.showing = Some(next);
+            marks.status()
+        };
+        let _ = app.emit("ab-changed", &status);
+        Ok(status)
+    }
+
+    pub fn clear(&self, app: &AppHandle) {
+        *self.marks.lock().unwrap() = Marks::default();
+        let _ = app.emit("ab-changed", self.status());
+    }
+
+    pub fn status(&self) -> CompareStatus {
+        self.marks.lock().unwrap().status()
+    }
+}
@@ -0,0 +1,215 @@
+/// Backend settings.
+///
+/// Settings live under the `config` key of the settings store. Missing fields
+/// fall back to their defaults, so older stores load unchanged; changes of
+/// layout are migrated before loading (see `migrations`). Every update is
+/// saved, broadcast to the frontend as a "settings-changed" event, and handed
+/// to the subsystems that need to react immediately.
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::curves::CurveManager;
+use crate::focus::FocusAction;
+use crate::shortcuts::ShortcutManager;
+use crate::{dither, errors, groups, protocol, STORE_FILE};
+
+const CONFIG_KEY: &str = "config";
+
+/// Which lights to connect to on launch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoConnect {
+    Off,
+    /// The preferred device if attached, otherwise the first matching port.
+    First,
+    /// Only the preferred device (pinned, else last connected).
+    Preferred,
+    /// Every matching port.
+    All,
+}
+
+/// How to reach a light that's attached more than one way (see `devices`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    /// A USB-serial adapter, or any other serial port.
+    Usb,
+    /// A paired Bluetooth control box.
+    Bluetooth,
+}
+
+/// What to send to the light after auto-connecting on launch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupBehavior {
+    /// Leave the light exactly as it is.
+    LeaveAsIs,
+    /// Restore the last brightness/temperature the panel saved.
+    RestoreLast,
+    /// Turn the light off.
+    Off,
+}
+
+/// What to do with the lights when the app quits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuitBehavior {
+    /// Leave the lights exactly as they are.
+    LeaveAsIs,
+    /// Turn the lights off.
+    Off,
+    /// Fade the lights off over `quit_fade_secs`.
+    FadeOff,
+}
+
+/// Longest fade-off on quit, in seconds.
+pub const MAX_QUIT_FADE_SECS: u32 = 30;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Which lights to connect to on launch.
+    pub auto_connect: AutoConnect,
+    /// Only accept a port once a light answers a status query.
+    pub identify_on_connect: bool,
+    /// Transport to use for lights attached more than one way.
+    pub preferred_transport: Transport,
+    pub startup: StartupBehavior,
+    pub quit: QuitBehavior,
+    pub quit_fade_secs: u32,
+    /// Minimum spacing between coalesced writes (scroll, etc.), in ms.
+    pub write_interval_ms: u64,
+    /// Show system notifications for background errors.
+    pub notifications: bool,
+    /// Register global shortcuts.
+    pub shortcuts: bool,
+    /// Show brightness and temperature changes from shortcuts on screen.
+    pub hud: bool,
+    /// Dither low brightness levels between adjacent hardware steps.
+    pub dithering: bool,
+    /// Dither write rate, in Hz.
+    pub dither_hz: u32,
+    /// Light actions for macOS Focus modes, keyed by mode name.
+    pub focus_actions: BTreeMap<String, FocusAction>,
+    /// Restore the lights when a mapped Focus mode ends.
+    pub focus_restore: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            auto_connect: AutoConnect::First,
+            identify_on_connect: true,
+            preferred_transport: Transport::Usb,
+            startup: StartupBehavior::RestoreLast,
+            quit: QuitBehavior::LeaveAsIs,
+            quit_fade_secs: 3,
+            write_interval_ms: 30,
+            notifications: true,
+            shortcuts: true,
+            hud: true,
+            dithering: false,
+            dither_hz: 30,
+            focus_actions: BTreeMap::new(),
+            focus_restore: true,
+        }
+    }
+}
+
+pub struct SettingsManager {
+    settings: RwLock<Settings>,
+}
+
+impl SettingsManager {
+    pub fn new() -> Self {
+        Self {
+            settings: RwLock::new(Settings::default()),
+        }
+    }
+
+    /// Load persisted settings, keeping defaults if none are stored.
+    pub fn load(&self, app: &AppHandle) {
+        let stored = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(CONFIG_KEY))
+            .and_then(|v| serde_json::from_value(v).ok());
+        if let Some(settings) = stored {
+            *self.settings.write().unwrap() = settings;
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Replace the settings, persist them, and notify subsystems.
+    pub fn update(&self, app: &AppHandle, settings: Settings) -> Result<(), String> {
+        if settings.write_interval_ms == 0 {
+            return Err("write_interval_ms must be at least 1".into());
+        }
+        if !(dither::MIN_HZ..=dither::MAX_HZ).contains(&settings.dither_hz) {
+            return Err(format!(
+                "dither_hz must be between {} and {}",
+                dither::MIN_HZ,
+                dither::MAX_HZ
+            ));
+        }
+        if settings.quit_fade_secs > MAX_QUIT_FADE_SECS {
+            return Err(format!(
+                "quit_fade_secs must be at most {MAX_QUIT_FADE_SECS}"
+            ));
+        }
+
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            CONFIG_KEY,
+            serde_json::to_value(&settings).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+
+        let old = std::mem::replace(&mut *self.settings.write().unwrap(), settings.clone());
+        on_change(app, &old, &settings);
+        let _ = app.emit("settings-changed", &settings);
+        Ok(())
+    }
+}
+
+/// Send the configured startup state to a freshly connected light.
+pub fn apply_startup(app: &AppHandle) {
+    let target = match app.state::<SettingsManager>().get().startup {
+        StartupBehavior::LeaveAsIs => return,
+        StartupBehavior::Off => (0, last_saved(app).map_or(protocol::DEFAULT_TEMP_K, |s| s.1)),
+        StartupBehavior::RestoreLast => match last_saved(app) {
+            Some(state) => state,
+            None => return,
+        },
+    };
+    let curves = app.state::<CurveManager>();
+    let result = groups::fan_out(app, None, |serial, id| {
+        serial.set_cct_to(id, curves.to_hw(id, target.0), target.1)
+    });
+    errors::check_fan_out(app, "startup", result);
+}
+
+/// Last (slider level, kelvin) the panel saved, if any.
+fn last_saved(app: &AppHandle) -> Option<(u8, u32)> {
+    let store = app.store(STORE_FILE).ok()?;
+    let slider = store.get("brightness")?.as_u64()?.min(100) as u8;
+    let kelvin = store.get("kelvin")?.as_u64()? as u32;
+    let on = store.get("isOn").and_then(|v| v.as_bool()).unwrap_or(true);
+    let bri = if on { slider } else { 0 };
+    Some((bri, kelvin))
+}
+
+/// Apply changes that subsystems can't pick up lazily on next use.
+fn on_change(app: &AppHandle, old: &Settings, new: &Settings) {
+    if old.shortcuts != new.shortcuts {
+        app.state::<ShortcutManager>()
+            .set_active(app, new.shortcuts);
+    }
+}
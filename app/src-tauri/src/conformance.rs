@@ -0,0 +1,227 @@
+/// Protocol conformance against captured sessions.
+///
+/// Every `.cap` file in the `captures` directory is a byte stream recorded
+/// between a real light and its host (the official app, a serial sniffer,
+/// the light's own controls). Each line is one write to the light (`>` or
+/// `TX`) or one read from it (`<` or `RX`) as hex bytes, in any of the usual
+/// dump spellings ("3a 02", "3A02", "0x3A, 0x02"); `#` starts a comment.
+/// For each direction the suite checks that `FrameParser` takes the stream
+/// apart into frames with nothing dropped or left over, that the frames put
+/// back together give the same bytes, and that the builders in `protocol`
+/// produce exactly the commands and status frames that were captured. New
+/// captures only need to be dropped into the directory.
+use std::path::Path;
+
+use crate::protocol::{self, Frame, FrameParser, StatusFrame};
+
+const CAPTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/captures");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// Host to light.
+    ToLight,
+    /// Light to host.
+    FromLight,
+}
+
+/// One write or read of a captured session.
+#[derive(Debug, Clone, PartialEq)]
+struct Chunk {
+    direction: Direction,
+    bytes: Vec<u8>,
+    /// Line in the capture file.
+    line: usize,
+}
+
+/// Parse a capture file.
+fn import(text: &str) -> Result<Vec<Chunk>, String> {
+    let mut chunks = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (direction, rest) = if let Some(rest) = line.strip_prefix('>') {
+            (Direction::ToLight, rest)
+        } else if let Some(rest) = line.strip_prefix('<') {
+            (Direction::FromLight, rest)
+        } else if let Some(rest) = line.strip_prefix("TX") {
+            (Direction::ToLight, rest)
+        } else if let Some(rest) = line.strip_prefix("RX") {
+            (Direction::FromLight, rest)
+        } else {
+            return Err(format!("line {line_no}: expected >, <, TX or RX"));
+        };
+        let bytes =
+            hex(rest.trim_start_matches(':')).map_err(|e| format!("line {line_no}: {e}"))?;
+        chunks.push(Chunk {
+            direction,
+            bytes,
+            line: line_no,
+        });
+    }
+    Ok(chunks)
+}
+
+/// Hex bytes, optionally `0x`-prefixed and separated by spaces or commas.
+fn hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text
+        .split([' ', ',', '\t'])
+        .map(|b| b.trim_start_matches("0x").trim_start_matches("0X"))
+        .collect();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return Err(format!("{:?} isn't whole hex bytes", text.trim()));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("{:?} isn't hex", &digits[i..i + 2]))
+        })
+        .collect()
+}
+
+/// A frame as it goes on the wire, checksum computed here rather than by
+/// `protocol`.
+fn encode(frame: &Frame) -> Vec<u8> {
+    let mut bytes = vec![protocol::HEADER, frame.tag, frame.payload.len() as u8];
+    bytes.extend_from_slice(&frame.payload);
+    let sum: u16 = bytes.iter().map(|&b| b as u16).sum();
+    bytes.extend_from_slice(&sum.to_be_bytes());
+    bytes
+}
+
+/// What `protocol` builds for a frame it has a builder for.
+fn rebuild(direction: Direction, frame: &Frame) -> Option<Vec<u8>> {
+    let cct = |bri: u8, temp: u8| {
+        (bri <= 100 && temp as u32 <= protocol::TEMP_STEPS).then(|| {
+            protocol::cct_command(
+                &protocol::DEFAULT_RANGE,
+                bri,
+                protocol::byte_to_kelvin(&protocol::DEFAULT_RANGE, temp),
+            )
+        })
+    };
+    match (direction, frame.tag, frame.payload.as_slice()) {
+        (Direction::ToLight, protocol::TAG_CCT, []) => Some(protocol::status_query()),
+        (Direction::ToLight, protocol::TAG_CCT, [0x01, bri, temp]) => cct(*bri, *temp),
+        (Direction::ToLight, protocol::TAG_RF, [channel, group, 0x01, bri, temp])
+            if *bri <= 100 && *temp as u32 <= protocol::TEMP_STEPS =>
+        {
+            Some(protocol::rf_cct_command(
+                *channel,
+                *group,
+                *bri,
+                protocol::byte_to_kelvin(&protocol::DEFAULT_RANGE, *temp),
+            ))
+        }
+        // Lights report CCT status in the command's format
+        (Direction::FromLight, _, _) => match frame.status(false)? {
+            StatusFrame::Cct {
+                brightness,
+                temp_byte,
+                gm: None,
+            } => cct(brightness, temp_byte),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Check one capture, returning a description of each mismatch.
+fn check(chunks: &[Chunk]) -> Vec<String> {
+    let mut failures = Vec::new();
+    for direction in [Direction::ToLight, Direction::FromLight] {
+        let mut parser = FrameParser::new();
+        let mut stream = Vec::new();
+        let mut framed = Vec::new();
+        for chunk in chunks.iter().filter(|c| c.direction == direction) {
+            stream.extend_from_slice(&chunk.bytes);
+            for frame in parser.push(&chunk.bytes) {
+                let bytes = encode(&frame);
+                if let Some(built) = rebuild(direction, &frame) {
+                    if built != bytes {
+                        failures.push(format!(
+                            "line {}: built {built:02x?}, captured {bytes:02x?}",
+                            chunk.line
+                        ));
+                    }
+                }
+                framed.extend(bytes);
+            }
+        }
+        if parser.errors() > 0 {
+            failures.push(format!("{direction:?}: {} frames dropped", parser.errors()));
+        }
+        if framed != stream {
+            failures.push(format!(
+                "{direction:?}: {} bytes framed of {}",
+                framed.len(),
+                stream.len()
+            ));
+        }
+    }
+    failures
+}
+
+fn captures() -> Vec<(String, String)> {
+    let mut files: Vec<_> = std::fs::read_dir(Path::new(CAPTURE_DIR))
+        .expect("captures directory")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cap"))
+        .collect();
+    files.sort();
+    files
+        .into_iter()
+        .map(|path| {
+            let text = std::fs::read_to_string(&path).unwrap();
+            (path.display().to_string(), text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captures_conform() {
+        let captures = captures();
+        assert!(!captures.is_empty());
+        for (path, text) in captures {
+            let chunks = import(&text).unwrap_or_else(|e| panic!("{path}: {e}"));
+            let failures = check(&chunks);
+            assert!(failures.is_empty(), "{path}:\n{}", failures.join("\n"));
+        }
+    }
+
+    #[test]
+    fn test_import() {
+        let text = "# session\n> 3a 02 00 00 3c  # query\nRX: 0x3A,0x02,0x03\n<3a020301\n";
+        let chunks = import(text).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].direction, Direction::ToLight);
+        assert_eq!(chunks[0].bytes, protocol::status_query());
+        assert_eq!(chunks[1].direction, Direction::FromLight);
+        assert_eq!(chunks[1].bytes, [0x3A, 0x02, 0x03]);
+        assert_eq!((chunks[2].bytes.len(), chunks[2].line), (4, 4));
+
+        assert!(import("3a 02").is_err());
+        assert!(import("> 3a 0").is_err());
+        assert!(import("> zz").is_err());
+    }
+
+    #[test]
+    fn test_check_reports_mismatches() {
+        let mut cct = protocol::cct_command(&protocol::DEFAULT_RANGE, 50, 4950);
+        let chunk = |bytes: &[u8]| Chunk {
+            direction: Direction::ToLight,
+            bytes: bytes.to_vec(),
+            line: 1,
+        };
+        assert!(check(&[chunk(&cct)]).is_empty());
+        cct[7] ^= 1;
+        assert_eq!(check(&[chunk(&cct)]).len(), 2);
+    }
+}
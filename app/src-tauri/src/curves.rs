@@ -0,0 +1,181 @@
+/// Dimming curves.
+///
+/// Perceived brightness is non-linear, so the percentage the user picks (the
+/// "level") is mapped to the protocol brightness byte through a curve chosen
+/// per device. Lights without a configured curve use gamma 2.0, matching the
+/// panel's original slider mapping. Curves are persisted under
+/// `dimming_curves` in the settings store, keyed by device id.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::presets::BRI_GAMMA;
+use crate::STORE_FILE;
+
+const CURVES_KEY: &str = "dimming_curves";
+/// Base of the logarithmic curve; higher is more aggressive at the low end.
+const LOG_BASE: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DimmingCurve {
+    Linear,
+    /// Exponential output, so equal level steps look like equal brightness
+    /// steps.
+    Logarithmic,
+    Gamma {
+        exponent: f64,
+    },
+}
+
+impl Default for DimmingCurve {
+    fn default() -> Self {
+        DimmingCurve::Gamma {
+            exponent: BRI_GAMMA,
+        }
+    }
+}
+
+impl DimmingCurve {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            DimmingCurve::Gamma { exponent } if !(0.2..=5.0).contains(exponent) => {
+                Err("Gamma exponent must be between 0.2 and 5.0".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Map a level 0-100 to hardware brightness 0-100. Any non-zero level
+    /// stays lit.
+    pub fn to_hw(self, level: u8) -> u8 {
+        (self.to_hw_fine(level).round() as u8).min(100)
+    }
+
+    /// Unrounded hardware brightness for a level, for dithering. Non-zero
+    /// levels map to at least 1.0.
+    pub fn to_hw_fine(self, level: u8) -> f64 {
+        if level == 0 {
+            return 0.0;
+        }
+        let x = level.min(100) as f64 / 100.0;
+        let y = match self {
+            DimmingCurve::Linear => x,
+            DimmingCurve::Logarithmic => (LOG_BASE.powf(x) - 1.0) / (LOG_BASE - 1.0),
+            DimmingCurve::Gamma { exponent } => x.powf(exponent),
+        };
+        (y * 100.0).clamp(1.0, 100.0)
+    }
+
+    /// Map hardware brightness back to the nearest level.
+    pub fn to_level(self, hw: u8) -> u8 {
+        if hw == 0 {
+            return 0;
+        }
+        let y = hw.min(100) as f64 / 100.0;
+        let x = match self {
+            DimmingCurve::Linear => y,
+            DimmingCurve::Logarithmic => (y * (LOG_BASE - 1.0) + 1.0).log(LOG_BASE),
+            DimmingCurve::Gamma { exponent } => y.powf(1.0 / exponent),
+        };
+        ((x * 100.0).round() as u8).clamp(1, 100)
+    }
+}
+
+pub struct CurveManager {
+    curves: Mutex<BTreeMap<String, DimmingCurve>>,
+}
+
+impl CurveManager {
+    pub fn new() -> Self {
+        Self {
+            curves: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, DimmingCurve> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(CURVES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.curves.lock().unwrap() = saved;
+    }
+
+    /// Configured curves by device id. Devices not listed use the default.
+    pub fn list(&self) -> BTreeMap<String, DimmingCurve> {
+        self.curves.lock().unwrap().clone()
+    }
+
+    pub fn curve(&self, id: &str) -> DimmingCurve {
+        self.curves
+            .lock()
+            .unwrap()
+            .get(id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set a device's curve; `None` resets it to the default.
+    pub fn set(
+        &self,
+        app: &AppHandle,
+        id: &str,
+        curve: Option<DimmingCurve>,
+    ) -> Result<(), String> {
+        let mut curves = self.curves.lock().unwrap();
+        match curve {
+            Some(curve) => {
+                curve.validate()?;
+                curves.insert(id.to_string(), curve);
+            }
+            None => {
+                curves.remove(id);
+            }
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            CURVES_KEY,
+            serde_json::to_value(&*curves).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())
+    }
+
+    pub fn to_hw(&self, id: &str, level: u8) -> u8 {
+        self.curve(id).to_hw(level)
+    }
+
+    pub fn to_level(&self, id: &str, hw: u8) -> u8 {
+        self.curve(id).to_level(hw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curve_endpoints() {
+        for curve in [
+            DimmingCurve::Linear,
+            DimmingCurve::Logarithmic,
+            DimmingCurve::Gamma { exponent: 2.2 },
+        ] {
+            assert_eq!(curve.to_hw(0), 0);
+            assert_eq!(curve.to_hw(1), 1);
+            assert_eq!(curve.to_hw(100), 100);
+            assert_eq!(curve.to_level(100), 100);
+        }
+    }
+
+    #[test]
+    fn test_default_matches_slider_gamma() {
+        let curve = DimmingCurve::default();
+        assert_eq!(curve.to_hw(50), 25);
+        assert_eq!(curve.to_level(25), 50);
+    }
+}
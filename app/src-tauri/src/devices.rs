@@ -0,0 +1,447 @@
+/// Device discovery and user-assigned names.
+///
+/// Devices are identified by their USB serial number when the adapter reports
+/// one, otherwise by port path. Friendly names are persisted under
+/// `device_names` in the settings store, keyed by that identifier.
+///
+/// A light can be reachable both through a USB adapter and as a paired
+/// Bluetooth control box. Bluetooth ports report no serial number, so the
+/// user says which light one reaches (persisted under `bluetooth_aliases`,
+/// keyed by port) and it takes that light's identifier. The light is then
+/// one device with several ports: connecting uses the preferred transport's
+/// port (see `config::Transport`), and when that connection is lost the
+/// others are tried before the light is reported disconnected.
+///
+/// Auto-connect prefers a pinned device, then the device last connected to,
+/// and only then the first matching port, so machines with several USB-serial
+/// adapters reconnect to the right one. It can also be limited to the
+/// preferred device, connect to every port, or be turned off (see
+/// `config::AutoConnect`); the lights it picks are handed to the connection
+/// supervisor (see `supervisor`), which keeps them connected. The preference
+/// is persisted under `preferred_device`.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::batch::LightState;
+use crate::config::{AutoConnect, SettingsManager, Transport};
+use crate::protocol::Format;
+use crate::{spp, STORE_FILE};
+
+const NAMES_KEY: &str = "device_names";
+const PREFERRED_KEY: &str = "preferred_device";
+const ALIASES_KEY: &str = "bluetooth_aliases";
+
+/// A light as seen during a scan.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceInfo {
+    /// Stable identifier (USB serial number, or port path as a fallback).
+    pub id: String,
+    /// The port connecting uses.
+    pub port: String,
+    /// Every port the light was found on, `port` first.
+    pub ports: Vec<String>,
+    pub name: String,
+    pub serial_number: Option<String>,
+    pub connected: bool,
+    /// Command format the light answered in when last connected.
+    pub format: Option<Format>,
+    /// Whether any of its ports is attached; lights only known from the
+    /// registry (see `registry`) are listed too.
+    pub present: bool,
+    /// When it was last connected or disconnected, in Unix seconds.
+    pub last_seen: Option<u64>,
+    /// The state it last reported.
+    pub last_state: Option<LightState>,
+}
+
+/// A light found by `lights`, with every port it's reachable on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub id: String,
+    /// Preferred transport first.
+    pub ports: Vec<String>,
+    pub serial_number: Option<String>,
+}
+
+/// Enumerate matching USB serial ports, and the serial ports of paired
+/// Bluetooth control boxes, as (id, port path, serial number). Bluetooth
+/// ports with an alias take the serial number of the light they reach.
+pub fn scan(app: &AppHandle) -> Vec<(String, String, Option<String>)> {
+    let aliases = app.state::<BluetoothAliases>();
+    serialport::available_ports()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|p| p.port_name.contains("usbserial") || spp::is_neewer_port(&p.port_name))
+        .map(|p| {
+            let serial_number = match p.port_type {
+                serialport::SerialPortType::UsbPort(usb) => usb.serial_number,
+                _ => aliases.get(&p.port_name),
+            };
+            let id = serial_number.clone().unwrap_or_else(|| p.port_name.clone());
+            (id, p.port_name, serial_number)
+        })
+        .collect()
+}
+
+/// Every light `scan` finds, once each, in the order first found.
+pub fn lights(app: &AppHandle) -> Vec<Found> {
+    let preferred = app.state::<SettingsManager>().get().preferred_transport;
+    merge(scan(app), preferred)
+}
+
+/// Group scanned ports by light, ordering each light's ports by transport.
+fn merge(scanned: Vec<(String, String, Option<String>)>, preferred: Transport) -> Vec<Found> {
+    let mut found: Vec<Found> = Vec::new();
+    for (id, port, serial_number) in scanned {
+        match found.iter_mut().find(|f| f.id == id) {
+            Some(light) => light.ports.push(port),
+            None => found.push(Found {
+                id,
+                ports: vec![port],
+                serial_number,
+            }),
+        }
+    }
+    for light in &mut found {
+        light
+            .ports
+            .sort_by_key(|port| transport_of(port) != preferred);
+    }
+    found
+}
+
+/// How `port` reaches its light.
+pub fn transport_of(port: &str) -> Transport {
+    if port.starts_with(spp::SCHEME) || spp::is_neewer_port(port) {
+        Transport::Bluetooth
+    } else {
+        Transport::Usb
+    }
+}
+
+/// Identifier for the device currently at `port`, falling back to its alias
+/// and then the path.
+pub fn id_for_port(app: &AppHandle, port: &str) -> String {
+    scan(app)
+        .into_iter()
+        .find(|(_, p, _)| p == port)
+        .map(|(id, _, _)| id)
+        .or_else(|| app.state::<BluetoothAliases>().get(port))
+        .unwrap_or_else(|| port.to_string())
+}
+
+/// Ports other than `except` that reach light `id`, preferred first.
+pub fn other_ports(app: &AppHandle, id: &str, except: &str) -> Vec<String> {
+    lights(app)
+        .into_iter()
+        .find(|light| light.id == id)
+        .map(|light| light.ports.into_iter().filter(|p| p != except).collect())
+        .unwrap_or_default()
+}
+
+/// Bluetooth ports known to reach the same light as a USB adapter, with that
+/// light's serial number.
+pub struct BluetoothAliases {
+    aliases: Mutex<BTreeMap<String, String>>,
+}
+
+impl BluetoothAliases {
+    pub fn new() -> Self {
+        Self {
+            aliases: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, String> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(ALIASES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.aliases.lock().unwrap() = saved;
+    }
+
+    /// Aliases, keyed by port.
+    pub fn list(&self) -> BTreeMap<String, String> {
+        self.aliases.lock().unwrap().clone()
+    }
+
+    /// Serial number of the light `port` reaches, if known.
+    pub fn get(&self, port: &str) -> Option<String> {
+        self.aliases.lock().unwrap().get(port).cloned()
+    }
+
+    /// Note that Bluetooth `port` reaches the light with `serial_number`, or
+    /// forget it with `None`.
+    pub fn set(
+        &self,
+        app: &AppHandle,
+        port: &str,
+        serial_number: Option<String>,
+    ) -> Result<(), String> {
+        if transport_of(port) != Transport::Bluetooth {
+            return Err(format!("{port} is not a Bluetooth port"));
+        }
+        let mut aliases = self.aliases.lock().unwrap();
+        match serial_number.map(|s| s.trim().to_string()) {
+            Some(s) if s.is_empty() => return Err("Serial number is required".into()),
+            Some(s) => aliases.insert(port.to_string(), s),
+            None => aliases.remove(port),
+        };
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            ALIASES_KEY,
+            serde_json::to_value(&*aliases).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())
+    }
+}
+
+pub struct DeviceNames {
+    names: Mutex<HashMap<String, String>>,
+}
+
+impl DeviceNames {
+    pub fn new() -> Self {
+        Self {
+            names: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: HashMap<String, String> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(NAMES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.names.lock().unwrap() = saved;
+    }
+
+    /// The user's name for a device, or a default derived from its port.
+    pub fn name(&self, id: &str, port: &str) -> String {
+        if let Some(name) = self.names.lock().unwrap().get(id) {
+            return name.clone();
+        }
+        let short = port.rsplit('/').next().unwrap_or(port);
+        format!("Neewer ({short})")
+    }
+
+    /// Assign a name to a device; an empty name restores the default.
+    pub fn rename(&self, app: &AppHandle, id: &str, name: &str) -> Result<(), String> {
+        let mut names = self.names.lock().unwrap();
+        let name = name.trim();
+        if name.is_empty() {
+            names.remove(id);
+        } else {
+            names.insert(id.to_string(), name.to_string());
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            NAMES_KEY,
+            serde_json::to_value(&*names).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preference {
+    /// Device pinned by the user; preferred whenever it's present.
+    pub pinned: Option<String>,
+    /// Device last connected to.
+    pub last: Option<String>,
+}
+
+impl Preference {
+    /// Devices to keep connected in `mode`, among the ids of the lights
+    /// found. With `Preferred` that's the pinned (else last) device even
+    /// while it's unplugged, so it connects once attached.
+    fn targets(&self, mode: AutoConnect, found: &[String]) -> Vec<String> {
+        let attached = |id: &Option<String>| id.clone().filter(|id| found.contains(id));
+        let preferred = attached(&self.pinned).or_else(|| attached(&self.last));
+        let target = match mode {
+            AutoConnect::Off => None,
+            AutoConnect::All => return found.to_vec(),
+            AutoConnect::First => preferred.or_else(|| found.first().cloned()),
+            AutoConnect::Preferred => preferred
+                .or_else(|| self.pinned.clone())
+                .or_else(|| self.last.clone()),
+        };
+        target.into_iter().collect()
+    }
+
+    /// Port to auto-connect to among scanned (id, port) pairs.
+    fn choose(&self, found: &[(String, String)]) -> Option<String> {
+        self.preferred(found)
+            .or_else(|| found.first().map(|(_, port)| port.clone()))
+    }
+
+    /// Port of the pinned device, else of the last one connected, if attached.
+    fn preferred(&self, found: &[(String, String)]) -> Option<String> {
+        let find = |id: &Option<String>| {
+            let id = id.as_deref()?;
+            found.iter().find(|(i, _)| i == id)
+        };
+        find(&self.pinned)
+            .or_else(|| find(&self.last))
+            .map(|(_, port)| port.clone())
+    }
+}
+
+pub struct PreferredDevice {
+    preference: Mutex<Preference>,
+}
+
+impl PreferredDevice {
+    pub fn new() -> Self {
+        Self {
+            preference: Mutex::new(Preference::default()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: Preference = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(PREFERRED_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.preference.lock().unwrap() = saved;
+    }
+
+    pub fn get(&self) -> Preference {
+        self.preference.lock().unwrap().clone()
+    }
+
+    /// The port auto-connect should use in `mode`, if any light is attached.
+    /// With `All` that's the one it would choose first.
+    pub fn port(&self, app: &AppHandle, mode: AutoConnect) -> Option<String> {
+        let found: Vec<(String, String)> = lights(app)
+            .into_iter()
+            .map(|light| (light.id, light.ports[0].clone()))
+            .collect();
+        match mode {
+            AutoConnect::Off => None,
+            AutoConnect::Preferred => self.get().preferred(&found),
+            AutoConnect::First | AutoConnect::All => self.get().choose(&found),
+        }
+    }
+
+    /// Note a successful connection to `id`.
+    pub fn remember(&self, app: &AppHandle, id: &str) -> Result<(), String> {
+        let mut preference = self.preference.lock().unwrap();
+        if preference.last.as_deref() == Some(id) {
+            return Ok(());
+        }
+        preference.last = Some(id.to_string());
+        persist(app, &preference)
+    }
+
+    /// Pin a device to always be preferred, or clear the pin with `None`.
+    pub fn pin(&self, app: &AppHandle, id: Option<String>) -> Result<Preference, String> {
+        if id.as_ref().is_some_and(|id| id.trim().is_empty()) {
+            return Err("Device id is required".into());
+        }
+        let mut preference = self.preference.lock().unwrap();
+        preference.pinned = id;
+        persist(app, &preference)?;
+        Ok(preference.clone())
+    }
+}
+
+/// Ids of the lights to keep connected from launch, as configured (see
+/// `supervisor`).
+pub fn auto_connect_targets(app: &AppHandle) -> Vec<String> {
+    let mode = app.state::<SettingsManager>().get().auto_connect;
+    let found: Vec<String> = lights(app).into_iter().map(|light| light.id).collect();
+    app.state::<PreferredDevice>().get().targets(mode, &found)
+}
+
+fn persist(app: &AppHandle, preference: &Preference) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        PREFERRED_KEY,
+        serde_json::to_value(preference).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_prefers_pinned_then_last() {
+        let found = vec![
+            ("A".to_string(), "/dev/a".to_string()),
+            ("B".to_string(), "/dev/b".to_string()),
+            ("C".to_string(), "/dev/c".to_string()),
+        ];
+        let mut preference = Preference::default();
+        assert_eq!(preference.choose(&found).as_deref(), Some("/dev/a"));
+        preference.last = Some("B".into());
+        assert_eq!(preference.choose(&found).as_deref(), Some("/dev/b"));
+        preference.pinned = Some("C".into());
+        assert_eq!(preference.choose(&found).as_deref(), Some("/dev/c"));
+        // A pinned device that isn't attached falls through
+        preference.pinned = Some("Z".into());
+        assert_eq!(preference.choose(&found).as_deref(), Some("/dev/b"));
+        assert_eq!(preference.choose(&[]), None);
+    }
+
+    #[test]
+    fn test_preferred_needs_known_device() {
+        let found = vec![("A".to_string(), "/dev/a".to_string())];
+        let mut preference = Preference::default();
+        assert_eq!(preference.preferred(&found), None);
+        preference.last = Some("A".into());
+        assert_eq!(preference.preferred(&found).as_deref(), Some("/dev/a"));
+        preference.last = Some("B".into());
+        assert_eq!(preference.preferred(&found), None);
+    }
+
+    #[test]
+    fn test_targets_by_mode() {
+        let found = vec!["A".to_string(), "B".to_string()];
+        let preference = Preference {
+            pinned: Some("Z".into()),
+            last: Some("B".into()),
+        };
+        assert!(preference.targets(AutoConnect::Off, &found).is_empty());
+        assert_eq!(preference.targets(AutoConnect::All, &found), ["A", "B"]);
+        assert_eq!(preference.targets(AutoConnect::First, &found), ["B"]);
+        assert_eq!(
+            preference.targets(AutoConnect::First, &[]),
+            Vec::<String>::new()
+        );
+        // The unplugged pinned device is waited for
+        assert_eq!(preference.targets(AutoConnect::Preferred, &[]), ["Z"]);
+    }
+
+    #[test]
+    fn test_merge_groups_ports_of_one_light() {
+        let port = |id: &str, path: &str| (id.to_string(), path.to_string(), Some(id.to_string()));
+        let scanned = vec![
+            port("A1", "/dev/cu.NEEWER-CB60"),
+            port("B2", "/dev/cu.usbserial-2"),
+            port("A1", "/dev/cu.usbserial-1"),
+        ];
+        let found = merge(scanned.clone(), Transport::Usb);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].id, "A1");
+        assert_eq!(
+            found[0].ports,
+            ["/dev/cu.usbserial-1", "/dev/cu.NEEWER-CB60"]
+        );
+        let found = merge(scanned, Transport::Bluetooth);
+        assert_eq!(found[0].ports[0], "/dev/cu.NEEWER-CB60");
+        assert_eq!(transport_of("bt://AA:BB:CC:DD:EE:FF"), Transport::Bluetooth);
+    }
+}
@@ -0,0 +1,154 @@
+/// Temporal dithering for finer low-end dimming.
+///
+/// At low levels one hardware brightness step is a visible jump. With
+/// dithering enabled, a fractional target such as 3.4 (from the dimming curve)
+/// is synthesized by a per-light write worker that alternates between bytes 3
+/// and 4, error-diffused so the average matches the target, at the configured
+/// rate.
+///
+/// Safety limits: dithering only runs between hardware 1 and [`MAX_HW`] (never
+/// through 0, which would strobe the light), the rate is clamped to
+/// [`MIN_HZ`]..=[`MAX_HZ`], and any other write to the light stops it.
+use std::collections::BTreeMap;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+
+use crate::arbitration::Arbiter;
+use crate::config::SettingsManager;
+use crate::curves::CurveManager;
+use crate::errors::LightError;
+use crate::limits::BrightnessLimits;
+use crate::lock::ControlLock;
+use crate::matching::ColorMatching;
+use crate::models;
+use crate::serial::SerialManager;
+use crate::sessionlog::{self, SessionLog};
+
+/// Highest hardware brightness dithered; above this 1% steps aren't visible.
+pub const MAX_HW: f64 = 20.0;
+pub const MIN_HZ: u32 = 10;
+pub const MAX_HZ: u32 = 60;
+/// Fractions closer than this to a whole byte are written directly.
+const EPS: f64 = 0.05;
+
+struct Worker {
+    /// (fractional hardware brightness, kelvin)
+    target: Arc<Mutex<(f64, u32)>>,
+    stop: Arc<AtomicBool>,
+}
+
+pub struct Ditherer {
+    workers: Mutex<BTreeMap<String, Worker>>,
+}
+
+impl Ditherer {
+    pub fn new() -> Self {
+        Self {
+            workers: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Set a light to a slider level, dithering when enabled and useful.
+    pub fn set_level(
+        &self,
+        app: &AppHandle,
+        id: &str,
+        level: u8,
+        kelvin: u32,
+    ) -> Result<(), LightError> {
+        let serial = app.state::<SerialManager>();
+        let hw = app.state::<CurveManager>().curve(id).to_hw_fine(level);
+        let settings = app.state::<SettingsManager>().get();
+        let useful = (1.0..MAX_HW).contains(&hw) && (EPS..1.0 - EPS).contains(&hw.fract());
+        let capped = app
+            .state::<BrightnessLimits>()
+            .cap(id)
+            .is_some_and(|cap| hw > cap as f64);
+        if !settings.dithering || !useful || capped {
+            // set_cct_to stops any running worker and applies the cap
+            return serial.set_cct_to(id, hw.round() as u8, kelvin);
+        }
+
+        // The worker writes directly, so check the lock and arbitration and
+        // log the target here
+        let source = sessionlog::current_source();
+        app.state::<ControlLock>().check(source)?;
+        app.state::<Arbiter>().claim(id, source)?;
+        app.state::<SessionLog>()
+            .record(id, hw.round() as u8, kelvin);
+        let mut workers = self.workers.lock().unwrap();
+        if let Some(worker) = workers.get(id).filter(|w| !w.stop.load(Ordering::Relaxed)) {
+            *worker.target.lock().unwrap() = (hw, kelvin);
+            return Ok(());
+        }
+        let worker = Worker {
+            target: Arc::new(Mutex::new((hw, kelvin))),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+        let (target, stop) = (worker.target.clone(), worker.stop.clone());
+        workers.insert(id.to_string(), worker);
+        drop(workers);
+
+        let hz = settings.dither_hz.clamp(MIN_HZ, MAX_HZ);
+        let (app, id) = (app.clone(), id.to_string());
+        std::thread::spawn(move || run(app, id, hz, target, stop));
+        Ok(())
+    }
+
+    /// Whether a worker is currently dithering `id`.
+    pub fn is_active(&self, id: &str) -> bool {
+        self.workers
+            .lock()
+            .unwrap()
+            .get(id)
+            .is_some_and(|w| !w.stop.load(Ordering::Relaxed))
+    }
+
+    pub fn stop(&self, id: &str) {
+        if let Some(worker) = self.workers.lock().unwrap().remove(id) {
+            worker.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stop_all(&self) {
+        for (_, worker) in std::mem::take(&mut *self.workers.lock().unwrap()) {
+            worker.stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Write worker: error-diffuses the fractional target over whole bytes.
+fn run(app: AppHandle, id: String, hz: u32, target: Arc<Mutex<(f64, u32)>>, stop: Arc<AtomicBool>) {
+    let period = Duration::from_secs_f64(1.0 / hz as f64);
+    let mut error = 0.0;
+    let mut last: Option<(u8, u32)> = None;
+    while !stop.load(Ordering::Relaxed) {
+        let (hw, kelvin) = *target.lock().unwrap();
+        error += hw.fract();
+        let bri = if error >= 1.0 {
+            error -= 1.0;
+            hw.ceil()
+        } else {
+            hw.floor()
+        } as u8;
+
+        if last != Some((bri, kelvin)) {
+            last = Some((bri, kelvin));
+            let range = models::range(&app, &id);
+            let (bri, kelvin) =
+                app.state::<ColorMatching>()
+                    .apply(&app, &id, bri, range.clamp(kelvin));
+            let cmd = models::format(&app, &id).cct_command(&range, bri, kelvin);
+            if app.state::<SerialManager>().write_to(&id, &cmd).is_err() {
+                stop.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+        std::thread::sleep(period);
+    }
+}
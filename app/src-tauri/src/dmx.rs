@@ -0,0 +1,236 @@
+/// DMX output through an Enttec-compatible USB-DMX adapter.
+///
+/// Mirrors the lights' state onto a DMX universe so conventional fixtures can
+/// follow them: each mapping puts one light's (or the primary light's)
+/// brightness on a dimmer channel and, optionally, its temperature on a
+/// second channel, both scaled to 0-255 (2900K-7000K for temperature). The
+/// universe is sent whole, as a DMX USB Pro "output only" message, whenever a
+/// mapped light reports a change; the adapter keeps repeating the last
+/// universe on the line. Configuration is persisted under `dmx` in the
+/// settings store.
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::portconfig::SerialParams;
+use crate::protocol::{TEMP_MAX_K, TEMP_MIN_K};
+use crate::serial::{LightStatus, SerialManager};
+use crate::transport::{self, Link};
+use crate::{notify, STORE_FILE};
+
+const DMX_KEY: &str = "dmx";
+pub const UNIVERSE_SIZE: usize = 512;
+/// DMX USB Pro message framing and the "output only send DMX" label.
+const PRO_START: u8 = 0x7E;
+const PRO_END: u8 = 0xE7;
+const LABEL_SEND_DMX: u8 = 6;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DmxMapping {
+    /// Device id, or `None` for the primary light.
+    pub device: Option<String>,
+    /// DMX channel for brightness, 1-512.
+    pub dimmer: u16,
+    /// DMX channel for colour temperature, 1-512.
+    pub temperature: Option<u16>,
+}
+
+impl DmxMapping {
+    fn applies_to(&self, status: &LightStatus, primary: Option<&str>) -> bool {
+        match &self.device {
+            Some(id) => *id == status.device,
+            None => primary == Some(status.device.as_str()),
+        }
+    }
+
+    /// Write a light's state into its channels.
+    fn apply(&self, status: &LightStatus, universe: &mut [u8; UNIVERSE_SIZE]) {
+        universe[self.dimmer as usize - 1] = scale(status.brightness as u32, 0, 100);
+        if let Some(channel) = self.temperature {
+            universe[channel as usize - 1] = scale(status.kelvin, TEMP_MIN_K, TEMP_MAX_K);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DmxConfig {
+    pub enabled: bool,
+    /// Port of the USB-DMX adapter.
+    pub port: String,
+    pub mappings: Vec<DmxMapping>,
+}
+
+impl DmxConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.enabled && self.port.trim().is_empty() {
+            return Err("Choose the port of the DMX adapter".into());
+        }
+        let channels = self
+            .mappings
+            .iter()
+            .flat_map(|m| std::iter::once(m.dimmer).chain(m.temperature));
+        for channel in channels {
+            if !(1..=UNIVERSE_SIZE as u16).contains(&channel) {
+                return Err(format!(
+                    "DMX channels must be between 1 and {UNIVERSE_SIZE}"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scale `value` in `min..=max` to a DMX level.
+fn scale(value: u32, min: u32, max: u32) -> u8 {
+    let v = value.clamp(min, max) - min;
+    ((v * 255 + (max - min) / 2) / (max - min)) as u8
+}
+
+/// A DMX USB Pro message sending `universe` with a zero start code.
+fn pro_packet(universe: &[u8; UNIVERSE_SIZE]) -> Vec<u8> {
+    let len = (UNIVERSE_SIZE + 1) as u16;
+    let mut pkt = vec![PRO_START, LABEL_SEND_DMX];
+    pkt.extend_from_slice(&len.to_le_bytes());
+    pkt.push(0x00);
+    pkt.extend_from_slice(universe);
+    pkt.push(PRO_END);
+    pkt
+}
+
+pub struct DmxOutput {
+    config: Mutex<DmxConfig>,
+    port: Mutex<Option<Box<dyn Link>>>,
+    universe: Mutex<[u8; UNIVERSE_SIZE]>,
+}
+
+impl DmxOutput {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(DmxConfig::default()),
+            port: Mutex::new(None),
+            universe: Mutex::new([0; UNIVERSE_SIZE]),
+        }
+    }
+
+    /// Load the saved configuration and open the adapter if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: DmxConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(DMX_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        if let Err(e) = self.reopen(&saved) {
+            notify::error(app, "DMX adapter unavailable", &e);
+        }
+    }
+
+    pub fn get(&self) -> DmxConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: DmxConfig) -> Result<(), String> {
+        config.validate()?;
+        self.reopen(&config)?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            DMX_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config;
+        *self.universe.lock().unwrap() = [0; UNIVERSE_SIZE];
+        Ok(())
+    }
+
+    fn reopen(&self, config: &DmxConfig) -> Result<(), String> {
+        // Close first so the same port can be opened again
+        *self.port.lock().unwrap() = None;
+        if config.enabled {
+            let params = SerialParams {
+                baud_rate: 57600,
+                ..SerialParams::default()
+            };
+            *self.port.lock().unwrap() = Some(transport::open(&config.port, &params)?);
+        }
+        Ok(())
+    }
+
+    /// Mirror a light's new status onto its mapped channels.
+    pub fn on_status(&self, app: &AppHandle, status: &LightStatus) {
+        let mut port = self.port.lock().unwrap();
+        let Some(link) = port.as_mut() else {
+            return;
+        };
+        let primary = app.state::<SerialManager>().device().map(|(id, _)| id);
+        let primary = primary.as_deref();
+        let packet = {
+            let config = self.config.lock().unwrap();
+            let mut universe = self.universe.lock().unwrap();
+            let before = *universe;
+            for mapping in config
+                .mappings
+                .iter()
+                .filter(|m| m.applies_to(status, primary))
+            {
+                mapping.apply(status, &mut universe);
+            }
+            if *universe == before {
+                return;
+            }
+            pro_packet(&universe)
+        };
+        if let Err(e) = link.write_all(&packet).and_then(|_| link.flush()) {
+            // Stop until reconfigured rather than failing on every change
+            *port = None;
+            notify::error(app, "DMX output stopped", &format!("Write failed: {e}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(scale(0, 0, 100), 0);
+        assert_eq!(scale(50, 0, 100), 128);
+        assert_eq!(scale(100, 0, 100), 255);
+        assert_eq!(scale(2000, TEMP_MIN_K, TEMP_MAX_K), 0);
+        assert_eq!(scale(TEMP_MAX_K, TEMP_MIN_K, TEMP_MAX_K), 255);
+    }
+
+    #[test]
+    fn test_pro_packet() {
+        let mut universe = [0; UNIVERSE_SIZE];
+        universe[0] = 0xFF;
+        let pkt = pro_packet(&universe);
+        assert_eq!(pkt.len(), 5 + UNIVERSE_SIZE + 1);
+        // Label, length 513 little-endian, start code, channel 1
+        assert_eq!(
+            pkt[..6],
+            [PRO_START, LABEL_SEND_DMX, 0x01, 0x02, 0x00, 0xFF]
+        );
+        assert_eq!(pkt.last(), Some(&PRO_END));
+    }
+
+    #[test]
+    fn test_validate_channels() {
+        let config = |dimmer, temperature| DmxConfig {
+            mappings: vec![DmxMapping {
+                device: None,
+                dimmer,
+                temperature,
+            }],
+            ..DmxConfig::default()
+        };
+        assert!(config(1, Some(512)).validate().is_ok());
+        assert!(config(0, None).validate().is_err());
+        assert!(config(1, Some(513)).validate().is_err());
+    }
+}
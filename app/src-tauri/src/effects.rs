@@ -0,0 +1,620 @@
+/// Software lighting effects.
+///
+/// The lights have no built-in effects over USB, so candle flicker, fire, TV
+/// glow and lightning are generated here and streamed as ordinary brightness
+/// and temperature writes from a background thread. Each effect modulates the
+/// lights' level around a base (their current level unless one is given), with
+/// `intensity` setting the depth and `speed` scaling its timing. Writes happen
+/// at a fixed tick and are skipped when the quantized output doesn't change.
+/// The lights' state from before the effect is restored when it's stopped.
+///
+/// Drift is the exception: rather than a recognisable effect it adds slow,
+/// barely visible wander to each light's own level and temperature, within
+/// `drift_level` and `drift_kelvin`, so a static light reads less clinically
+/// on camera. Its path is smooth value noise, new random points several
+/// seconds apart joined with zero slope, shared by every light so matched
+/// lights stay matched.
+///
+/// A strobe runs the same way, with its rate and duty cycle capped for safety.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::curves::CurveManager;
+use crate::fade::FadeEngine;
+use crate::groups::{self, Target};
+use crate::history::{self, Snapshot};
+use crate::serial::SerialManager;
+use crate::sessionlog::{self, Source};
+use crate::{errors, models, protocol};
+
+/// Time between effect frames.
+const TICK: Duration = Duration::from_millis(40);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    Candle,
+    Fire,
+    Tv,
+    Lightning,
+    Drift,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EffectParams {
+    /// Modulation depth, 0.0-1.0.
+    pub intensity: f64,
+    /// Timing multiplier, 0.1-5.0.
+    pub speed: f64,
+    /// Base level 1-100; each light's current level if unset.
+    pub level: Option<u8>,
+    /// Drift: furthest the level wanders from its base, as a fraction of it.
+    pub drift_level: f64,
+    /// Drift: furthest the temperature wanders from each light's own, in K.
+    pub drift_kelvin: u32,
+}
+
+/// Largest drift bounds accepted; beyond these it stops being subtle.
+pub const MAX_DRIFT_LEVEL: f64 = 0.25;
+pub const MAX_DRIFT_KELVIN: u32 = 500;
+
+impl Default for EffectParams {
+    fn default() -> Self {
+        Self {
+            intensity: 0.5,
+            speed: 1.0,
+            level: None,
+            drift_level: 0.05,
+            drift_kelvin: 100,
+        }
+    }
+}
+
+impl EffectParams {
+    fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.intensity) {
+            return Err("Effect intensity must be between 0 and 1".into());
+        }
+        if !(0.1..=5.0).contains(&self.speed) {
+            return Err("Effect speed must be between 0.1 and 5".into());
+        }
+        if self.level.is_some_and(|l| !(1..=100).contains(&l)) {
+            return Err("Effect level must be 1-100".into());
+        }
+        if !(0.0..=MAX_DRIFT_LEVEL).contains(&self.drift_level) {
+            return Err(format!(
+                "Drift level must be between 0 and {MAX_DRIFT_LEVEL}"
+            ));
+        }
+        if self.drift_kelvin > MAX_DRIFT_KELVIN {
+            return Err(format!(
+                "Drift temperature must be at most {MAX_DRIFT_KELVIN}K"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Strobe settings. Rates above `MAX_STROBE_HZ` are capped rather than
+/// rejected, as is the duty cycle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StrobeParams {
+    /// Flashes per second.
+    pub hz: f64,
+    /// Fraction of each period the light is on.
+    pub duty: f64,
+    /// Flash level, 1-100.
+    pub level: u8,
+    pub kelvin: u32,
+    /// Stop automatically after this long; capped at `MAX_STROBE_TIME`.
+    pub seconds: Option<f64>,
+}
+
+impl Default for StrobeParams {
+    fn default() -> Self {
+        Self {
+            hz: 2.0,
+            duty: 0.3,
+            level: 100,
+            kelvin: 5600,
+            seconds: None,
+        }
+    }
+}
+
+/// Hard limits for the strobe. Flashing faster than three times a second
+/// risks triggering photosensitive seizures.
+pub const MAX_STROBE_HZ: f64 = 3.0;
+const DUTY_RANGE: (f64, f64) = (0.1, 0.9);
+const MAX_STROBE_TIME: Duration = Duration::from_secs(300);
+
+impl StrobeParams {
+    /// Validate and apply the safety caps.
+    fn capped(mut self) -> Result<Self, String> {
+        if !(self.hz.is_finite() && self.hz > 0.0) || !self.duty.is_finite() {
+            return Err("Strobe rate must be positive".into());
+        }
+        if !(1..=100).contains(&self.level) {
+            return Err("Strobe level must be 1-100".into());
+        }
+        self.hz = self.hz.min(MAX_STROBE_HZ);
+        self.duty = self.duty.clamp(DUTY_RANGE.0, DUTY_RANGE.1);
+        let max = MAX_STROBE_TIME.as_secs_f64();
+        self.seconds = Some(self.seconds.map_or(max, |s| s.clamp(0.0, max)));
+        Ok(self)
+    }
+}
+
+/// Reported as "effect-changed" when an effect or strobe starts or stops.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EffectStatus {
+    pub effect: Option<Effect>,
+    /// Settings of the running strobe, after caps.
+    pub strobe: Option<StrobeParams>,
+}
+
+pub struct EffectEngine {
+    /// Bumped by every start/stop; an effect thread exits when it changes.
+    generation: Arc<AtomicU64>,
+    running: Mutex<EffectStatus>,
+    /// Light states from before the first of a run of effects.
+    saved: Mutex<Option<Snapshot>>,
+}
+
+impl EffectEngine {
+    pub fn new() -> Self {
+        Self {
+            generation: Arc::new(AtomicU64::new(0)),
+            running: Mutex::new(EffectStatus::default()),
+            saved: Mutex::new(None),
+        }
+    }
+
+    /// Start `effect` on `target`, replacing any effect or fade in progress.
+    pub fn start(
+        &self,
+        app: &AppHandle,
+        effect: Effect,
+        params: EffectParams,
+        target: Option<Target>,
+    ) -> Result<(), String> {
+        params.validate()?;
+        let serial = app.state::<SerialManager>();
+        // (id, base level, own kelvin)
+        let bases: Vec<(String, u8, u32)> = resolve(app, target.as_ref())?
+            .into_iter()
+            .filter_map(|id| {
+                let status = serial.status_of(&id);
+                let level = params.level.or(status.as_ref().map(|s| s.level))?;
+                let kelvin = status.map_or(protocol::DEFAULT_TEMP_K, |s| s.kelvin);
+                Some((id, level.max(1), kelvin))
+            })
+            .collect();
+        if bases.is_empty() {
+            return Err("No status received from light yet".into());
+        }
+
+        let gen = self.begin(
+            app,
+            EffectStatus {
+                effect: Some(effect),
+                strobe: None,
+            },
+        );
+        let current = self.generation.clone();
+        let app = app.clone();
+        let mut generator = Generator::new(effect, &params, seed());
+        std::thread::spawn(move || {
+            sessionlog::set_source(Source::Automation);
+            let started = Instant::now();
+            let mut last: Vec<Option<(u8, u8)>> = vec![None; bases.len()];
+            while current.load(Ordering::SeqCst) == gen {
+                let t = started.elapsed().as_secs_f64() * params.speed;
+                let (factor, tone) = generator.sample(t);
+                let curves = app.state::<CurveManager>();
+                let serial = app.state::<SerialManager>();
+                for ((id, base, own), last) in bases.iter().zip(last.iter_mut()) {
+                    let level = (*base as f64 * factor).round().clamp(0.0, 100.0) as u8;
+                    let kelvin = tone.kelvin(*own);
+                    let hw = curves.to_hw(id, level);
+                    let range = models::range(&app, id);
+                    let wire = (hw, protocol::kelvin_to_byte(&range, kelvin));
+                    if *last != Some(wire) && serial.set_cct_to(id, hw, kelvin).is_ok() {
+                        *last = Some(wire);
+                    }
+                }
+                std::thread::sleep(TICK);
+            }
+        });
+        Ok(())
+    }
+
+    /// Strobe `target` until stopped or the time limit runs out. Returns the
+    /// settings actually used, after the safety caps.
+    pub fn strobe(
+        &self,
+        app: &AppHandle,
+        params: StrobeParams,
+        target: Option<Target>,
+    ) -> Result<StrobeParams, String> {
+        let params = params.capped()?;
+        let ids = resolve(app, target.as_ref())?;
+        if ids.is_empty() {
+            return Err("Port not open".into());
+        }
+
+        let gen = self.begin(
+            app,
+            EffectStatus {
+                effect: None,
+                strobe: Some(params.clone()),
+            },
+        );
+        let current = self.generation.clone();
+        let app = app.clone();
+        let used = params.clone();
+        std::thread::spawn(move || {
+            sessionlog::set_source(Source::Automation);
+            let period = 1.0 / params.hz;
+            let on = Duration::from_secs_f64(period * params.duty);
+            let off = Duration::from_secs_f64(period * (1.0 - params.duty));
+            let until = Instant::now() + Duration::from_secs_f64(params.seconds.unwrap_or(0.0));
+            let hw: Vec<u8> = {
+                let curves = app.state::<CurveManager>();
+                ids.iter()
+                    .map(|id| curves.to_hw(id, params.level))
+                    .collect()
+            };
+            let live = || current.load(Ordering::SeqCst) == gen;
+            while live() && Instant::now() < until {
+                let serial = app.state::<SerialManager>();
+                for (id, &bri) in ids.iter().zip(&hw) {
+                    let result = serial.set_cct_to(id, bri, params.kelvin);
+                    errors::check(&app, "strobe", Some(id), result);
+                }
+                std::thread::sleep(on);
+                // After a stop the restore puts the lights back instead
+                if !live() {
+                    return;
+                }
+                for id in &ids {
+                    let result = serial.set_cct_to(id, 0, params.kelvin);
+                    errors::check(&app, "strobe", Some(id), result);
+                }
+                std::thread::sleep(off);
+            }
+            if live() {
+                app.state::<EffectEngine>().stop(&app);
+            }
+        });
+        Ok(used)
+    }
+
+    /// Take over the lights: stop fades and other effects, remember the state
+    /// to restore, and announce the change. Returns the new generation.
+    fn begin(&self, app: &AppHandle, status: EffectStatus) -> u64 {
+        app.state::<FadeEngine>().cancel();
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        // Switching effects keeps the state from before the first one
+        self.saved
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| history::snapshot(app));
+        *self.running.lock().unwrap() = status.clone();
+        let _ = app.emit("effect-changed", status);
+        gen
+    }
+
+    /// Stop the running effect or strobe immediately and put the lights back
+    /// how they were.
+    pub fn stop(&self, app: &AppHandle) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        let was = std::mem::take(&mut *self.running.lock().unwrap());
+        if let Some(saved) = self.saved.lock().unwrap().take() {
+            // Let a write in flight land before restoring over it
+            std::thread::sleep(TICK);
+            history::restore(app, &saved);
+        }
+        if was.effect.is_some() || was.strobe.is_some() {
+            let _ = app.emit("effect-changed", EffectStatus::default());
+        }
+    }
+
+    pub fn status(&self) -> EffectStatus {
+        self.running.lock().unwrap().clone()
+    }
+}
+
+fn resolve(app: &AppHandle, target: Option<&Target>) -> Result<Vec<String>, String> {
+    app.state::<groups::GroupManager>()
+        .resolve(app, target.unwrap_or(&Target::All))
+}
+
+fn seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0x9e37_79b9_7f4a_7c15, |d| d.as_nanos() as u64)
+}
+
+/// Small xorshift PRNG; effects only need cheap, decorrelated noise.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    /// Uniform in 0.0..1.0.
+    fn unit(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (hi - lo) * self.unit()
+    }
+}
+
+/// Temperature of an effect frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tone {
+    /// Every light at this temperature.
+    Kelvin(u32),
+    /// Each light this far from its own temperature.
+    Shift(f64),
+}
+
+impl Tone {
+    fn kelvin(self, own: u32) -> u32 {
+        match self {
+            Tone::Kelvin(kelvin) => kelvin,
+            Tone::Shift(shift) => (own as f64 + shift)
+                .round()
+                .clamp(protocol::TEMP_MIN_K as f64, protocol::TEMP_MAX_K as f64)
+                as u32,
+        }
+    }
+}
+
+/// Smooth value noise in -1.0..=1.0: random points a few effect seconds
+/// apart, joined by smoothstep so the slope is zero at each one. Starts at 0.
+struct Noise {
+    from: f64,
+    to: f64,
+    /// Effect time the current segment started, and its length.
+    start: f64,
+    len: f64,
+}
+
+impl Noise {
+    fn new() -> Self {
+        Self {
+            from: 0.0,
+            to: 0.0,
+            start: 0.0,
+            len: 0.0,
+        }
+    }
+
+    fn sample(&mut self, rng: &mut Rng, t: f64) -> f64 {
+        while t >= self.start + self.len {
+            self.start += self.len;
+            self.len = rng.range(3.0, 8.0);
+            self.from = self.to;
+            self.to = rng.range(-1.0, 1.0);
+        }
+        let u = (t - self.start) / self.len;
+        self.from + (self.to - self.from) * u * u * (3.0 - 2.0 * u)
+    }
+}
+
+/// Produces effect frames as (level factor, temperature) over effect time.
+/// The factor is 0.0-1.0 except for drift, which wanders either side of 1.0.
+struct Generator {
+    effect: Effect,
+    intensity: f64,
+    /// Drift bounds, as a level fraction and in Kelvin.
+    drift: (f64, f64),
+    /// Drift paths for level and temperature.
+    noise: (Noise, Noise),
+    rng: Rng,
+    /// Effect time of the last sample.
+    t: f64,
+    /// Eased value and the value it's heading for.
+    value: f64,
+    goal: f64,
+    /// Effect time at which the next event (new goal, cut, strike) happens.
+    next_at: f64,
+    /// Remaining lightning flashes as (start, end) in effect time.
+    flashes: Vec<(f64, f64)>,
+    kelvin: u32,
+}
+
+impl Generator {
+    fn new(effect: Effect, params: &EffectParams, seed: u64) -> Self {
+        Self {
+            effect,
+            intensity: params.intensity,
+            drift: (params.drift_level, params.drift_kelvin as f64),
+            noise: (Noise::new(), Noise::new()),
+            rng: Rng::new(seed),
+            t: 0.0,
+            value: 1.0,
+            goal: 1.0,
+            next_at: 0.0,
+            flashes: Vec::new(),
+            kelvin: protocol::TEMP_MIN_K,
+        }
+    }
+
+    /// Move `value` toward `goal` with a time constant of `tau` effect seconds.
+    fn ease(&mut self, dt: f64, tau: f64) {
+        self.value += (self.goal - self.value) * (1.0 - (-dt / tau).exp());
+    }
+
+    fn sample(&mut self, t: f64) -> (f64, Tone) {
+        let dt = (t - self.t).max(0.0);
+        self.t = t;
+        let depth = self.intensity;
+        match self.effect {
+            Effect::Candle => {
+                if t >= self.next_at {
+                    self.next_at = t + self.rng.range(0.08, 0.35);
+                    // Mostly gentle wavering with the occasional gutter
+                    let dip = if self.rng.unit() < 0.05 { 0.6 } else { 0.25 };
+                    self.goal = 1.0 - dip * self.rng.unit();
+                }
+                self.ease(dt, 0.08);
+                (
+                    1.0 - depth * (1.0 - self.value),
+                    Tone::Kelvin(protocol::TEMP_MIN_K),
+                )
+            }
+            Effect::Fire => {
+                if t >= self.next_at {
+                    self.next_at = t + self.rng.range(0.04, 0.15);
+                    self.goal = self.rng.range(0.3, 1.0);
+                }
+                self.ease(dt, 0.05);
+                let factor = 1.0 - depth * (1.0 - self.value);
+                // Flare-ups burn a little whiter
+                let kelvin = protocol::TEMP_MIN_K + (self.value * 500.0).round() as u32;
+                (factor, Tone::Kelvin(kelvin))
+            }
+            Effect::Tv => {
+                if t >= self.next_at {
+                    // Scene cut
+                    self.next_at = t + self.rng.range(0.6, 4.0);
+                    self.goal = self.rng.range(0.2, 1.0);
+                    self.kelvin = self.rng.range(4500.0, 7000.0).round() as u32;
+                }
+                self.ease(dt, 0.06);
+                let flicker = self.rng.range(-0.04, 0.04);
+                let factor = 1.0 - depth * (1.0 - (self.value + flicker).clamp(0.0, 1.0));
+                (factor, Tone::Kelvin(self.kelvin))
+            }
+            Effect::Lightning => {
+                if t >= self.next_at {
+                    self.next_at = t + self.rng.range(2.0, 10.0);
+                    // A strike is a burst of one to three flashes
+                    let mut at = t;
+                    for _ in 0..1 + (self.rng.unit() * 3.0) as usize {
+                        let len = self.rng.range(0.04, 0.12);
+                        self.flashes.push((at, at + len));
+                        at += len + self.rng.range(0.05, 0.2);
+                    }
+                }
+                self.flashes.retain(|&(_, end)| end > t);
+                let flashing = self.flashes.iter().any(|&(start, _)| start <= t);
+                if flashing {
+                    (1.0, Tone::Kelvin(protocol::TEMP_MAX_K))
+                } else {
+                    // Dark, cool ambience between strikes
+                    (1.0 - depth, Tone::Kelvin(5600))
+                }
+            }
+            Effect::Drift => {
+                let level = self.noise.0.sample(&mut self.rng, t);
+                let kelvin = self.noise.1.sample(&mut self.rng, t);
+                (
+                    1.0 + self.drift.0 * level,
+                    Tone::Shift(self.drift.1 * kelvin),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(effect: Effect, intensity: f64, seconds: f64) -> Vec<(f64, Tone)> {
+        let params = EffectParams {
+            intensity,
+            ..EffectParams::default()
+        };
+        let mut g = Generator::new(effect, &params, 42);
+        let steps = (seconds / 0.04) as usize;
+        (0..steps).map(|i| g.sample(i as f64 * 0.04)).collect()
+    }
+
+    #[test]
+    fn test_effects_stay_in_range() {
+        for effect in [Effect::Candle, Effect::Fire, Effect::Tv, Effect::Lightning] {
+            for (factor, tone) in run(effect, 1.0, 60.0) {
+                assert!((0.0..=1.0).contains(&factor), "{effect:?} {factor}");
+                let Tone::Kelvin(kelvin) = tone else {
+                    panic!("{effect:?} shifted the temperature");
+                };
+                assert!((protocol::TEMP_MIN_K..=protocol::TEMP_MAX_K).contains(&kelvin));
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_intensity_is_steady() {
+        for effect in [Effect::Candle, Effect::Fire, Effect::Tv] {
+            assert!(run(effect, 0.0, 10.0).iter().all(|&(f, _)| f == 1.0));
+        }
+    }
+
+    #[test]
+    fn test_drift_is_bounded_and_smooth() {
+        let frames = run(Effect::Drift, 0.5, 120.0);
+        let mut prev = (1.0, 0.0);
+        for (factor, tone) in frames {
+            let Tone::Shift(shift) = tone else {
+                panic!("drift set an absolute temperature");
+            };
+            assert!((0.95..=1.05).contains(&factor), "{factor}");
+            assert!(shift.abs() <= 100.0, "{shift}");
+            // No visible jumps between frames
+            assert!((factor - prev.0).abs() < 0.002);
+            assert!((shift - prev.1).abs() < 5.0);
+            prev = (factor, shift);
+        }
+        assert_eq!(
+            Tone::Shift(-500.0).kelvin(protocol::TEMP_MIN_K),
+            protocol::TEMP_MIN_K
+        );
+    }
+
+    #[test]
+    fn test_strobe_caps() {
+        let params = StrobeParams {
+            hz: 20.0,
+            duty: 1.0,
+            seconds: Some(1e6),
+            ..StrobeParams::default()
+        }
+        .capped()
+        .unwrap();
+        assert_eq!(params.hz, MAX_STROBE_HZ);
+        assert_eq!(params.duty, 0.9);
+        assert_eq!(params.seconds, Some(300.0));
+        assert!(StrobeParams {
+            hz: f64::NAN,
+            ..StrobeParams::default()
+        }
+        .capped()
+        .is_err());
+    }
+
+    #[test]
+    fn test_lightning_strikes() {
+        let frames = run(Effect::Lightning, 0.8, 30.0);
+        assert!(frames.iter().any(|&(f, _)| f == 1.0));
+        assert!(frames.iter().filter(|&&(f, _)| f < 1.0).count() > frames.len() / 2);
+    }
+}
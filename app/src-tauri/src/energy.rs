@@ -0,0 +1,332 @@
+/// Energy usage estimation.
+///
+/// Each light can be given a wattage curve: its measured (or rated) power draw
+/// at a few hardware brightness levels, plus its standby draw while off. Power
+/// is interpolated piecewise-linearly over brightness and integrated over the
+/// time the light spends at each brightness while connected, giving a running
+/// estimate in watt-hours. Lights without a curve aren't estimated. When the
+/// local date changes, an optional "energy-daily" event summarizes the day
+/// just ended. Curves are persisted under `energy` and totals under
+/// `energy_totals` in the settings store, keyed by device id.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::serial::LightStatus;
+use crate::{errors, STORE_FILE};
+
+const ENERGY_KEY: &str = "energy";
+const TOTALS_KEY: &str = "energy_totals";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WattPoint {
+    /// Hardware brightness 1-100.
+    pub brightness: u8,
+    pub watts: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WattageCurve {
+    /// Draw while connected but off.
+    #[serde(default)]
+    pub standby_watts: f64,
+    pub points: Vec<WattPoint>,
+}
+
+impl WattageCurve {
+    fn validate(&self) -> Result<(), String> {
+        if self.points.is_empty() {
+            return Err("A wattage curve needs at least one point".into());
+        }
+        let valid = |w: f64| w.is_finite() && w >= 0.0;
+        if !valid(self.standby_watts) || !self.points.iter().all(|p| valid(p.watts)) {
+            return Err("Wattage must be a non-negative number".into());
+        }
+        if self
+            .points
+            .iter()
+            .any(|p| !(1..=100).contains(&p.brightness))
+        {
+            return Err("Wattage curve brightness must be 1-100".into());
+        }
+        Ok(())
+    }
+
+    /// Estimated draw at a hardware brightness. Flat past the highest point.
+    pub fn watts(&self, brightness: u8) -> f64 {
+        if brightness == 0 {
+            return self.standby_watts;
+        }
+        let mut knots: Vec<(f64, f64)> = self
+            .points
+            .iter()
+            .map(|p| (p.brightness as f64, p.watts))
+            .collect();
+        knots.sort_by(|a, b| a.0.total_cmp(&b.0));
+        knots.insert(0, (0.0, self.standby_watts));
+        let b = brightness.min(100) as f64;
+        match knots.windows(2).find(|w| b <= w[1].0) {
+            Some(w) if w[1].0 > w[0].0 => {
+                w[0].1 + (w[1].1 - w[0].1) * (b - w[0].0) / (w[1].0 - w[0].0)
+            }
+            Some(w) => w[1].1,
+            None => knots[knots.len() - 1].1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyConfig {
+    /// Wattage curves by device id.
+    pub curves: BTreeMap<String, WattageCurve>,
+    /// Emit "energy-daily" when the date changes.
+    pub daily_summary: bool,
+}
+
+impl EnergyConfig {
+    fn validate(&self) -> Result<(), String> {
+        self.curves.values().try_for_each(WattageCurve::validate)
+    }
+}
+
+/// Accumulated estimates, by device id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyTotals {
+    pub total_wh: BTreeMap<String, f64>,
+    /// Local date (YYYY-MM-DD) that `today_wh` covers.
+    pub day: String,
+    pub today_wh: BTreeMap<String, f64>,
+}
+
+impl EnergyTotals {
+    fn add(&mut self, device: &str, wh: f64) {
+        *self.total_wh.entry(device.to_string()).or_default() += wh;
+        *self.today_wh.entry(device.to_string()).or_default() += wh;
+    }
+
+    /// Start a new day, returning the summary of the previous one if it had
+    /// any usage.
+    fn roll_over(&mut self, today: &str) -> Option<DailySummary> {
+        if self.day == today {
+            return None;
+        }
+        let day = std::mem::replace(&mut self.day, today.to_string());
+        let devices = std::mem::take(&mut self.today_wh);
+        (!day.is_empty() && !devices.is_empty()).then(|| DailySummary {
+            date: day,
+            total_wh: devices.values().sum(),
+            devices,
+        })
+    }
+}
+
+/// Reported as "energy-daily" for the day that just ended.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailySummary {
+    pub date: String,
+    pub total_wh: f64,
+    pub devices: BTreeMap<String, f64>,
+}
+
+pub struct EnergyMeter {
+    config: Mutex<EnergyConfig>,
+    totals: Mutex<EnergyTotals>,
+    /// Hardware brightness of each connected light and when it was last
+    /// accounted for.
+    live: Mutex<HashMap<String, (u8, Instant)>>,
+}
+
+impl EnergyMeter {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(EnergyConfig::default()),
+            totals: Mutex::new(EnergyTotals::default()),
+            live: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Load curves and totals and start folding in running time.
+    pub fn load(&self, app: &AppHandle) {
+        let store = app.store(STORE_FILE).ok();
+        let read = |key: &str| store.as_ref().and_then(|s| s.get(key));
+        *self.config.lock().unwrap() = read(ENERGY_KEY)
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.totals.lock().unwrap() = read(TOTALS_KEY)
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let app = app.clone();
+        std::thread::spawn(move || loop {
+            let meter = app.state::<EnergyMeter>();
+            meter.flush();
+            let summary = meter.totals.lock().unwrap().roll_over(&today());
+            if let Some(summary) = summary {
+                if meter.get().daily_summary {
+                    let _ = app.emit("energy-daily", summary);
+                }
+            }
+            errors::check_save(&app, "save_energy", None, meter.save(&app));
+            std::thread::sleep(FLUSH_INTERVAL);
+        });
+    }
+
+    pub fn get(&self) -> EnergyConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: EnergyConfig) -> Result<(), String> {
+        config.validate()?;
+        // Time so far is charged at the old curves
+        self.flush();
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            ENERGY_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// Estimates up to now.
+    pub fn totals(&self) -> EnergyTotals {
+        self.flush();
+        self.totals.lock().unwrap().clone()
+    }
+
+    /// Clear the estimates of one light, or of all lights.
+    pub fn reset(&self, app: &AppHandle, device: Option<&str>) -> Result<(), String> {
+        self.flush();
+        {
+            let mut totals = self.totals.lock().unwrap();
+            match device {
+                Some(id) => {
+                    totals.total_wh.remove(id);
+                    totals.today_wh.remove(id);
+                }
+                None => {
+                    totals.total_wh.clear();
+                    totals.today_wh.clear();
+                }
+            }
+        }
+        self.save(app)
+    }
+
+    /// Charge the time at the previous brightness and start timing the new
+    /// one.
+    pub fn on_status(&self, status: &LightStatus) {
+        self.advance(&status.device, Some(status.brightness));
+    }
+
+    /// Stop timing a light that went away.
+    pub fn on_disconnect(&self, device: &str) {
+        self.advance(device, None);
+    }
+
+    fn advance(&self, device: &str, brightness: Option<u8>) {
+        let now = Instant::now();
+        let mut live = self.live.lock().unwrap();
+        let previous = match brightness {
+            Some(b) => live.insert(device.to_string(), (b, now)),
+            None => live.remove(device),
+        };
+        if let Some((b, since)) = previous {
+            self.charge(device, b, now - since);
+        }
+    }
+
+    /// Charge the running time of every connected light.
+    fn flush(&self) {
+        let now = Instant::now();
+        let mut live = self.live.lock().unwrap();
+        for (device, (b, since)) in live.iter_mut() {
+            self.charge(device, *b, now - *since);
+            *since = now;
+        }
+    }
+
+    fn charge(&self, device: &str, brightness: u8, elapsed: Duration) {
+        let Some(watts) = self
+            .config
+            .lock()
+            .unwrap()
+            .curves
+            .get(device)
+            .map(|c| c.watts(brightness))
+        else {
+            return;
+        };
+        let wh = watts * elapsed.as_secs_f64() / 3600.0;
+        self.totals.lock().unwrap().add(device, wh);
+    }
+
+    fn save(&self, app: &AppHandle) -> Result<(), String> {
+        let value =
+            serde_json::to_value(&*self.totals.lock().unwrap()).map_err(|e| e.to_string())?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(TOTALS_KEY, value);
+        store.save().map_err(|e| e.to_string())
+    }
+}
+
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve() -> WattageCurve {
+        WattageCurve {
+            standby_watts: 0.5,
+            points: vec![
+                WattPoint {
+                    brightness: 100,
+                    watts: 10.5,
+                },
+                WattPoint {
+                    brightness: 50,
+                    watts: 4.5,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_watts_interpolates() {
+        let curve = curve();
+        assert_eq!(curve.watts(0), 0.5);
+        assert_eq!(curve.watts(25), 2.5);
+        assert_eq!(curve.watts(50), 4.5);
+        assert_eq!(curve.watts(75), 7.5);
+        assert_eq!(curve.watts(100), 10.5);
+    }
+
+    #[test]
+    fn test_roll_over_summarizes_previous_day() {
+        let mut totals = EnergyTotals {
+            day: "2026-01-01".into(),
+            ..EnergyTotals::default()
+        };
+        totals.add("a", 2.0);
+        totals.add("b", 1.5);
+        assert_eq!(totals.roll_over("2026-01-01"), None);
+        let summary = totals.roll_over("2026-01-02").unwrap();
+        assert_eq!(summary.date, "2026-01-01");
+        assert_eq!(summary.total_wh, 3.5);
+        assert!(totals.today_wh.is_empty());
+        assert_eq!(totals.total_wh["a"], 2.0);
+        // Nothing to report after an idle day
+        assert_eq!(totals.roll_over("2026-01-03"), None);
+    }
+}
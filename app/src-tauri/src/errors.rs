@@ -0,0 +1,256 @@
+/// Structured errors from background work.
+///
+/// Automations, followers, connection housekeeping and periodic saves run
+/// off the command path, so when one of their operations fails there's no
+/// caller to hand the error to. They report it here instead: it's published
+/// on the event bus (see `events`) and reaches the frontend as
+/// "device-error", with a machine-readable [`ErrorCode`], the operation that
+/// failed, the device it concerned and, for failures on the port itself, the
+/// I/O error kind. The serial layer and its checks fail with a
+/// [`LightError`], which carries its code from where the failure happened;
+/// other errors are plain messages and report as `Other`.
+///
+/// A light that isn't connected is already reported through "device-state",
+/// so `NotConnected` failures aren't repeated here, and the same failure
+/// from the same operation and device is reported at most once every
+/// `REPEAT_AFTER`, so a fade or a link refused by the control lock doesn't
+/// flood the frontend.
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::events::{Event, EventBus};
+use crate::groups::FanOutReport;
+
+const REPEAT_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The light isn't connected.
+    NotConnected,
+    /// The light's model can't carry out the command (see `models`).
+    Unsupported,
+    /// The controls are locked, or another source holds the light.
+    Refused,
+    /// The light didn't answer.
+    Timeout,
+    /// Reading from or writing to the port failed.
+    Io,
+    /// Saving to the settings store failed.
+    Storage,
+    Other,
+}
+
+/// A failed background operation, as sent with "device-error".
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorEvent {
+    pub code: ErrorCode,
+    /// What was being done, e.g. "fade" or "write".
+    pub operation: String,
+    pub device: Option<String>,
+    /// Kind of the I/O error (e.g. "broken_pipe"), for `Io` failures.
+    pub io_kind: Option<String>,
+    pub message: String,
+}
+
+/// A failure in the serial layer or its checks, with its code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl LightError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Light `id` isn't connected.
+    pub fn not_connected(id: &str) -> Self {
+        Self::new(ErrorCode::NotConnected, format!("{id} is not connected"))
+    }
+
+    /// Several failures as one, with their code if they all share it.
+    pub fn join(errors: Vec<LightError>) -> Self {
+        let code = match errors.first() {
+            Some(first) if errors.iter().all(|e| e.code == first.code) => first.code,
+            _ => ErrorCode::Other,
+        };
+        let messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
+        Self::new(code, messages.join("; "))
+    }
+}
+
+impl fmt::Display for LightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Commands and other callers only pass the message on.
+impl From<LightError> for String {
+    fn from(error: LightError) -> Self {
+        error.message
+    }
+}
+
+impl From<String> for LightError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Other, message)
+    }
+}
+
+impl From<&str> for LightError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorCode::Other, message)
+    }
+}
+
+/// `kind` as a snake_case name, e.g. "broken_pipe".
+fn kind_name(kind: io::ErrorKind) -> String {
+    let mut name = String::new();
+    for (i, c) in format!("{kind:?}").char_indices() {
+        if c.is_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+pub struct ErrorReporter {
+    /// When each (code, operation, device) was last reported.
+    last: Mutex<HashMap<(ErrorCode, String, Option<String>), Instant>>,
+}
+
+impl ErrorReporter {
+    pub fn new() -> Self {
+        Self {
+            last: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn publish(&self, app: &AppHandle, error: ErrorEvent) {
+        if error.code == ErrorCode::NotConnected {
+            return;
+        }
+        let key = (error.code, error.operation.clone(), error.device.clone());
+        let now = Instant::now();
+        {
+            let mut last = self.last.lock().unwrap();
+            if last
+                .get(&key)
+                .is_some_and(|at| now.duration_since(*at) < REPEAT_AFTER)
+            {
+                return;
+            }
+            last.insert(key, now);
+        }
+        app.state::<EventBus>().publish(Event::DeviceError(error));
+    }
+}
+
+/// Report `result`'s error, if any, from `operation` on `device`.
+pub fn check<T, E: Into<LightError>>(
+    app: &AppHandle,
+    operation: &str,
+    device: Option<&str>,
+    result: Result<T, E>,
+) {
+    if let Err(error) = result {
+        let LightError { code, message } = error.into();
+        app.state::<ErrorReporter>().publish(
+            app,
+            ErrorEvent {
+                code,
+                operation: operation.to_string(),
+                device: device.map(str::to_string),
+                io_kind: None,
+                message,
+            },
+        );
+    }
+}
+
+/// Report `result`'s error, if any, from saving for `operation`.
+pub fn check_save(
+    app: &AppHandle,
+    operation: &str,
+    device: Option<&str>,
+    result: Result<(), String>,
+) {
+    if let Err(message) = result {
+        app.state::<ErrorReporter>().publish(
+            app,
+            ErrorEvent {
+                code: ErrorCode::Storage,
+                operation: operation.to_string(),
+                device: device.map(str::to_string),
+                io_kind: None,
+                message,
+            },
+        );
+    }
+}
+
+/// Report each light a fan-out (see `groups::fan_out`) failed on.
+pub fn check_fan_out(app: &AppHandle, operation: &str, result: Result<FanOutReport, LightError>) {
+    match result {
+        Ok(report) => {
+            for failed in report.failed {
+                let error = LightError::new(failed.code, failed.error);
+                check::<(), _>(app, operation, Some(&failed.device), Err(error));
+            }
+        }
+        Err(error) => check::<(), _>(app, operation, None, Err(error)),
+    }
+}
+
+/// Report an I/O error on `device`'s port.
+pub fn report_io(app: &AppHandle, operation: &str, device: &str, error: &io::Error) {
+    app.state::<ErrorReporter>().publish(
+        app,
+        ErrorEvent {
+            code: ErrorCode::Io,
+            operation: operation.to_string(),
+            device: Some(device.to_string()),
+            io_kind: Some(kind_name(error.kind())),
+            message: error.to_string(),
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_joined_errors_keep_a_shared_code() {
+        let refused = || LightError::new(ErrorCode::Refused, "Controls are locked");
+        let joined = LightError::join(vec![refused(), refused()]);
+        assert_eq!(joined.code, ErrorCode::Refused);
+        assert_eq!(joined.message, "Controls are locked; Controls are locked");
+        let mixed = LightError::join(vec![refused(), LightError::not_connected("A1")]);
+        assert_eq!(mixed.code, ErrorCode::Other);
+        // Plain messages carry no code
+        assert_eq!(
+            LightError::from("Brightness cap must be 1-100").code,
+            ErrorCode::Other
+        );
+    }
+
+    #[test]
+    fn test_kind_name() {
+        assert_eq!(kind_name(io::ErrorKind::BrokenPipe), "broken_pipe");
+        assert_eq!(kind_name(io::ErrorKind::Other), "other");
+    }
+}
@@ -0,0 +1,140 @@
+/// Internal event bus.
+///
+/// The serial layer publishes what happens to the lights as typed [`Event`]s
+/// rather than emitting them to the frontend and calling every follower
+/// itself. Each subscriber gets every event, in order, on its own thread, so
+/// a slow subscriber (an MQTT broker, a DMX adapter) never holds up the
+/// connections or the others. `init` subscribes the frontend emitter, which
+/// forwards events as "light-status", "device-state", "parse-error",
+/// "write-backlog", "device-error" and "brightness-clamped", the automations
+/// that follow the lights (links, scripts, the macro recorder, the tray), the
+/// network outputs (MQTT, DMX) and the health monitor (see `health`). Usage
+/// and energy metering stay with the connections, since they also count the
+/// dithering echoes that aren't published.
+use std::sync::{mpsc, Mutex};
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::dmx::DmxOutput;
+use crate::errors::ErrorEvent;
+use crate::health::HealthMonitor;
+use crate::limits::Clamped;
+use crate::links::LinkManager;
+use crate::macros::MacroRecorder;
+use crate::mqtt::MqttBridge;
+use crate::scripting::ScriptHost;
+use crate::serial::{DeviceStateChange, LightStatus, ParseErrors, WriteBacklog};
+use crate::sessionlog::{self, Source};
+use crate::tray;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A light reported its state.
+    Status(LightStatus),
+    /// A light's connection changed.
+    DeviceState(DeviceStateChange),
+    /// Frames from a light were dropped.
+    ParseErrors(ParseErrors),
+    /// Writes to a light are queueing up.
+    WriteBacklog(WriteBacklog),
+    /// Background work failed (see `errors`).
+    DeviceError(ErrorEvent),
+    /// A write was held to a light's brightness cap (see `limits`).
+    BrightnessClamped(Clamped),
+}
+
+pub struct EventBus {
+    subscribers: Mutex<Vec<mpsc::Sender<Event>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Call `handler` with every later event, on a thread of its own.
+    pub fn subscribe(&self, handler: impl Fn(&Event) + Send + 'static) {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        std::thread::spawn(move || rx.iter().for_each(|event| handler(&event)));
+    }
+
+    /// Send `event` to every subscriber.
+    pub fn publish(&self, event: Event) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+/// Subscribe the frontend, automations and network outputs. Called before
+/// anything connects.
+pub fn init(app: &AppHandle) {
+    let bus = app.state::<EventBus>();
+
+    let handle = app.clone();
+    bus.subscribe(move |event| {
+        let _ = match event {
+            Event::Status(status) => handle.emit("light-status", status),
+            Event::DeviceState(change) => handle.emit("device-state", change),
+            Event::ParseErrors(errors) => handle.emit("parse-error", errors),
+            Event::WriteBacklog(backlog) => handle.emit("write-backlog", backlog),
+            Event::DeviceError(error) => handle.emit("device-error", error),
+            Event::BrightnessClamped(clamped) => handle.emit("brightness-clamped", clamped),
+        };
+    });
+
+    let handle = app.clone();
+    bus.subscribe(move |event| {
+        // Followers driven from here are automation, bar linked lights
+        sessionlog::set_source(Source::Automation);
+        if let Event::Status(status) = event {
+            handle.state::<LinkManager>().on_status(&handle, status);
+            handle.state::<ScriptHost>().on_status(status);
+            handle.state::<MacroRecorder>().on_status(status);
+            tray::refresh(&handle);
+        }
+    });
+
+    let handle = app.clone();
+    bus.subscribe(move |event| {
+        if let Event::Status(status) = event {
+            handle.state::<MqttBridge>().on_status(status);
+            handle.state::<DmxOutput>().on_status(&handle, status);
+        }
+    });
+
+    let handle = app.clone();
+    bus.subscribe(move |event| handle.state::<HealthMonitor>().on_event(event));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_reaches_every_subscriber_in_order() {
+        let bus = EventBus::new();
+        let (tx, rx) = mpsc::channel();
+        for n in 0..2 {
+            let tx = tx.clone();
+            bus.subscribe(move |event| {
+                if let Event::ParseErrors(errors) = event {
+                    tx.send((n, errors.count)).unwrap();
+                }
+            });
+        }
+        for count in 1..=2 {
+            bus.publish(Event::ParseErrors(ParseErrors {
+                device: "a".into(),
+                count,
+            }));
+        }
+        let mut received: Vec<_> = rx.iter().take(4).collect();
+        received.sort_by_key(|&(n, _)| n);
+        assert_eq!(received, [(0, 1), (0, 2), (1, 1), (1, 2)]);
+    }
+}
@@ -0,0 +1,184 @@
+/// Fade engine: smooth transitions between two light states.
+///
+/// A fade runs on its own thread, writing interpolated states at a fixed tick
+/// and skipping ticks where the quantized output doesn't change. Brightness is
+/// interpolated as a slider level and mapped through each light's dimming
+/// curve on write. Starting a new
+/// fade (or calling `cancel`) stops the running one at its current state.
+/// `transition` sets lights with an optional fade from where each one is, for
+/// `set_light` and the network integrations.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Manager};
+
+use crate::curves::CurveManager;
+use crate::dither::Ditherer;
+use crate::errors::LightError;
+use crate::groups::{self, FanOutReport, Target};
+use crate::serial::SerialManager;
+use crate::sessionlog;
+use crate::{errors, models, protocol};
+
+/// Time between interpolated writes.
+const TICK: Duration = Duration::from_millis(40);
+/// Longest transition accepted from callers, an hour.
+pub const MAX_TRANSITION_MS: u64 = 60 * 60 * 1000;
+
+/// Interpolate between two (brightness, kelvin) states at `t` in 0.0..=1.0.
+pub fn lerp(from: (u8, u32), to: (u8, u32), t: f64) -> (u8, u32) {
+    let t = t.clamp(0.0, 1.0);
+    let bri = from.0 as f64 + (to.0 as f64 - from.0 as f64) * t;
+    let k = from.1 as f64 + (to.1 as f64 - from.1 as f64) * t;
+    (bri.round() as u8, k.round() as u32)
+}
+
+pub struct FadeEngine {
+    /// Bumped by every start/cancel; a fade thread exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl FadeEngine {
+    pub fn new() -> Self {
+        Self {
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Fade `target` from `from` to `to` over `duration`, replacing any fade
+    /// in progress. Returns immediately.
+    pub fn start(
+        &self,
+        app: &AppHandle,
+        target: Option<Target>,
+        from: (u8, u32),
+        to: (u8, u32),
+        duration: Duration,
+    ) {
+        let last: RefCell<BTreeMap<String, (u8, u8)>> = RefCell::new(BTreeMap::new());
+        self.run(app, duration, move |app, t| {
+            let (bri, k) = lerp(from, to, t);
+            let curves = app.state::<CurveManager>();
+            let result = groups::fan_out(app, target.as_ref(), |serial, id| {
+                // Only write when the light would actually change
+                let wire = (bri, protocol::kelvin_to_byte(&models::range(app, id), k));
+                if last.borrow().get(id) == Some(&wire) {
+                    return Ok(());
+                }
+                last.borrow_mut().insert(id.to_string(), wire);
+                serial.set_cct_to(id, curves.to_hw(id, bri), k)
+            });
+            errors::check_fan_out(app, "fade", result);
+        });
+    }
+
+    /// Fade each light in `fades`, by device id, from its own `from` to its
+    /// own `to` over `duration`, replacing any fade in progress. Returns
+    /// immediately.
+    pub fn start_each(
+        &self,
+        app: &AppHandle,
+        fades: BTreeMap<String, ((u8, u32), (u8, u32))>,
+        duration: Duration,
+    ) {
+        let mut last: BTreeMap<String, (u8, u8)> = BTreeMap::new();
+        self.run(app, duration, move |app, t| {
+            let serial = app.state::<SerialManager>();
+            let curves = app.state::<CurveManager>();
+            for (id, &(from, to)) in &fades {
+                let (bri, k) = lerp(from, to, t);
+                let wire = (bri, protocol::kelvin_to_byte(&models::range(app, id), k));
+                if last.get(id) != Some(&wire) {
+                    last.insert(id.clone(), wire);
+                    let result = serial.set_cct_to(id, curves.to_hw(id, bri), k);
+                    errors::check(app, "fade", Some(id), result);
+                }
+            }
+        });
+    }
+
+    /// Call `step` with the fade's progress, 0.0 to 1.0, every tick on a
+    /// thread of its own until it reaches 1.0 or another fade starts.
+    fn run(
+        &self,
+        app: &AppHandle,
+        duration: Duration,
+        mut step: impl FnMut(&AppHandle, f64) + Send + 'static,
+    ) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let current = self.generation.clone();
+        let app = app.clone();
+        // Steps are attributed to whatever started the fade
+        let source = sessionlog::current_source();
+
+        std::thread::spawn(move || {
+            sessionlog::set_source(source);
+            let started = Instant::now();
+            loop {
+                if current.load(Ordering::SeqCst) != gen {
+                    return;
+                }
+                let t = if duration.is_zero() {
+                    1.0
+                } else {
+                    started.elapsed().as_secs_f64() / duration.as_secs_f64()
+                };
+                step(&app, t.min(1.0));
+                if t >= 1.0 {
+                    return;
+                }
+                std::thread::sleep(TICK);
+            }
+        });
+    }
+
+    /// Stop any running fade where it is.
+    pub fn cancel(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Set every light in `target` to `level` and `kelvin`, fading from where
+/// each light is over `duration` unless it's zero. A value left `None` keeps
+/// each light's own. Lights that haven't reported a state yet are set at
+/// once. Any fade in progress stops.
+pub fn transition(
+    app: &AppHandle,
+    target: Option<&Target>,
+    level: Option<u8>,
+    kelvin: Option<u32>,
+    duration: Duration,
+) -> Result<FanOutReport, String> {
+    let engine = app.state::<FadeEngine>();
+    engine.cancel();
+    let dither = app.state::<Ditherer>();
+    let fades = RefCell::new(BTreeMap::new());
+    let report = groups::fan_out(app, target, |serial, id| -> Result<(), LightError> {
+        let status = serial.status_of(id);
+        let to = match (level, kelvin, &status) {
+            (Some(level), Some(kelvin), _) => (level, kelvin),
+            (_, _, Some(s)) => (level.unwrap_or(s.level), kelvin.unwrap_or(s.kelvin)),
+            _ => return Err("No status received from light yet".into()),
+        };
+        let to = (to.0.min(100), to.1);
+        match status.filter(|_| !duration.is_zero()) {
+            Some(s) => {
+                fades
+                    .borrow_mut()
+                    .insert(id.to_string(), ((s.level, s.kelvin), to));
+                Ok(())
+            }
+            None => dither.set_level(app, id, to.0, to.1),
+        }
+    })?;
+    let fades = fades.into_inner();
+    if !fades.is_empty() {
+        engine.start_each(app, fades, duration);
+    }
+    Ok(report)
+}
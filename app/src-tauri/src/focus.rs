@@ -0,0 +1,185 @@
+/// macOS Focus / Do Not Disturb coupling.
+///
+/// A watcher polls the active Focus mode and, when it changes to one that has
+/// an action in the settings (`focus_actions`, keyed by mode name such as
+/// "Work" or "Sleep"), applies that action to every light. When the mode ends
+/// the lights can be restored (`focus_restore`). macOS has no public API for
+/// the Focus state, so it's read from the Do Not Disturb database files, which
+/// requires Full Disk Access.
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::config::SettingsManager;
+use crate::dither::Ditherer;
+use crate::history::{self, Snapshot};
+use crate::sessionlog::{self, Source};
+use crate::{groups, notify, presets};
+
+const POLL: Duration = Duration::from_secs(3);
+
+/// What to do when a Focus mode turns on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FocusAction {
+    ApplyPreset { index: usize },
+    Set { level: u8, kelvin: u32 },
+    Off,
+}
+
+impl FocusAction {
+    fn apply(&self, app: &AppHandle) -> Result<(), String> {
+        let (level, kelvin) = match *self {
+            FocusAction::Off => {
+                groups::fan_out(app, None, |serial, id| serial.set_power_to(id, false))?;
+                return Ok(());
+            }
+            FocusAction::ApplyPreset { index } => {
+                let preset = presets::get(app, index)?;
+                (preset.brightness, preset.kelvin)
+            }
+            FocusAction::Set { level, kelvin } => (level.min(100), kelvin),
+        };
+        let dither = app.state::<Ditherer>();
+        groups::fan_out(app, None, |_, id| dither.set_level(app, id, level, kelvin))?;
+        Ok(())
+    }
+}
+
+/// Reported as "focus-changed" with the new mode name, or `None` when Focus
+/// turns off.
+#[derive(Debug, Clone, Serialize)]
+pub struct FocusChange {
+    pub mode: Option<String>,
+}
+
+/// Start watching the Focus state. Does nothing on other platforms.
+pub fn init(app: &AppHandle) {
+    if !cfg!(target_os = "macos") {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || watch(app));
+}
+
+fn watch(app: AppHandle) {
+    sessionlog::set_source(Source::Automation);
+    let mut last: Option<String> = None;
+    // State from before the current mode's action, to restore when it ends
+    let mut saved: Option<Snapshot> = None;
+    let mut failing = false;
+    loop {
+        std::thread::sleep(POLL);
+        let settings = app.state::<SettingsManager>().get();
+        if settings.focus_actions.is_empty() {
+            continue;
+        }
+        let mode = match current_mode() {
+            Ok(mode) => {
+                failing = false;
+                mode
+            }
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Can't read the Focus state", &e);
+                }
+                continue;
+            }
+        };
+        if mode == last {
+            continue;
+        }
+        last = mode.clone();
+        let _ = app.emit("focus-changed", FocusChange { mode: mode.clone() });
+
+        match mode.and_then(|m| settings.focus_actions.get(&m).cloned()) {
+            Some(action) => {
+                // Switching between mapped modes keeps the original state
+                if saved.is_none() {
+                    saved = Some(history::snapshot(&app));
+                }
+                if let Err(e) = action.apply(&app) {
+                    notify::error(&app, "Focus action failed", &e);
+                }
+            }
+            None => {
+                if let Some(snapshot) = saved.take() {
+                    if settings.focus_restore {
+                        history::restore(&app, &snapshot);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn dnd_file(name: &str) -> Result<String, String> {
+    let home = std::env::var("HOME").map_err(|e| e.to_string())?;
+    let path: PathBuf = [&home, "Library", "DoNotDisturb", "DB", name]
+        .iter()
+        .collect();
+    std::fs::read_to_string(&path).map_err(|e| {
+        format!(
+            "Can't read {} (grant Full Disk Access): {e}",
+            path.display()
+        )
+    })
+}
+
+/// Name of the active Focus mode, if any.
+fn current_mode() -> Result<Option<String>, String> {
+    let assertions: Value =
+        serde_json::from_str(&dnd_file("Assertions.json")?).map_err(|e| e.to_string())?;
+    let Some(id) = active_mode_id(&assertions) else {
+        return Ok(None);
+    };
+    let modes: Value =
+        serde_json::from_str(&dnd_file("ModeConfigurations.json")?).map_err(|e| e.to_string())?;
+    // Fall back to the identifier for modes without a configuration entry
+    Ok(Some(mode_name(&modes, &id).unwrap_or(id)))
+}
+
+/// Mode identifier of the first active Focus assertion.
+fn active_mode_id(assertions: &Value) -> Option<String> {
+    assertions["data"]
+        .as_array()?
+        .iter()
+        .filter_map(|d| d["storeAssertionRecords"].as_array())
+        .flatten()
+        .find_map(|r| {
+            r["assertionDetails"]["assertionDetailsModeIdentifier"]
+                .as_str()
+                .map(String::from)
+        })
+}
+
+fn mode_name(modes: &Value, id: &str) -> Option<String> {
+    modes["data"].as_array()?.iter().find_map(|d| {
+        d["modeConfigurations"][id]["mode"]["name"]
+            .as_str()
+            .map(String::from)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_active_mode_name() {
+        let assertions = json!({"data": [{"storeAssertionRecords": [
+            {"assertionDetails": {"assertionDetailsModeIdentifier": "com.apple.focus.work"}}
+        ]}]});
+        let modes = json!({"data": [{"modeConfigurations": {
+            "com.apple.focus.work": {"mode": {"name": "Work"}}
+        }}]});
+        let id = active_mode_id(&assertions).unwrap();
+        assert_eq!(mode_name(&modes, &id).as_deref(), Some("Work"));
+        assert_eq!(active_mode_id(&json!({"data": [{}]})), None);
+    }
+}
@@ -0,0 +1,343 @@
+/// Frontmost-application rules.
+///
+/// An optional loop watches which application is frontmost and, when it
+/// becomes one with a rule (matched case-insensitively against the app's
+/// name, e.g. "OBS" or "zoom.us"), applies that rule's preset to the rule's
+/// lights. When the frontmost app changes to one without a rule the lights
+/// can be put back as they were (`restore`); moving between apps with rules
+/// keeps the state from before the first. This app's own panel coming to the
+/// front is ignored, so adjusting the lights doesn't revert them. The
+/// frontmost app comes from `lsappinfo` on macOS, `GetForegroundWindow` on
+/// Windows and `xdotool` on Linux. Configuration is persisted under
+/// `app_rules` in the settings store.
+#[cfg(not(target_os = "windows"))]
+use std::process::Command;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::groups::{self, Target};
+use crate::history::{self, Snapshot};
+use crate::sessionlog::{self, Source};
+use crate::{notify, presets, STORE_FILE};
+
+const APP_RULES_KEY: &str = "app_rules";
+const POLL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppRule {
+    /// Application name, or part of it.
+    pub app: String,
+    /// Preset index, in panel order.
+    pub preset: usize,
+    /// Lights to set; all connected lights by default.
+    #[serde(default)]
+    pub target: Option<Target>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppRulesConfig {
+    pub enabled: bool,
+    /// The first rule matching the frontmost app applies.
+    pub rules: Vec<AppRule>,
+    /// Put the lights back when an app without a rule comes to the front.
+    pub restore: bool,
+}
+
+impl Default for AppRulesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rules: Vec::new(),
+            restore: true,
+        }
+    }
+}
+
+impl AppRulesConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.rules.iter().any(|r| r.app.trim().is_empty()) {
+            return Err("Each rule needs an application name".into());
+        }
+        Ok(())
+    }
+
+    /// Index of the rule for the app called `name`, if any.
+    fn rule_for(&self, name: &str) -> Option<usize> {
+        let name = name.to_lowercase();
+        self.rules
+            .iter()
+            .position(|r| name.contains(&r.app.trim().to_lowercase()))
+    }
+}
+
+/// Reported as "frontmost-app" when the frontmost app changes.
+#[derive(Debug, Clone, Serialize)]
+pub struct FrontmostChange {
+    pub app: String,
+    /// Index of the rule applied, if any.
+    pub rule: Option<usize>,
+}
+
+pub struct AppRules {
+    config: Mutex<AppRulesConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl AppRules {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(AppRulesConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: AppRulesConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(APP_RULES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> AppRulesConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: AppRulesConfig) -> Result<(), String> {
+        config.validate()?;
+        if config.enabled {
+            frontmost_app()?;
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            APP_RULES_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: AppRulesConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn run(app: AppHandle, config: AppRulesConfig, current: Arc<AtomicU64>, gen: u64) {
+    sessionlog::set_source(Source::Automation);
+    let own = app.package_info().name.to_lowercase();
+    let mut last: Option<String> = None;
+    // State from before the first rule applied, to restore afterwards
+    let mut saved: Option<Snapshot> = None;
+    let mut failing = false;
+
+    while current.load(Ordering::SeqCst) == gen {
+        std::thread::sleep(POLL);
+        let name = match frontmost_app() {
+            Ok(name) => {
+                failing = false;
+                name
+            }
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Can't read the frontmost app", &e);
+                }
+                continue;
+            }
+        };
+        if last.as_deref() == Some(name.as_str()) || name.to_lowercase() == own {
+            continue;
+        }
+        last = Some(name.clone());
+
+        let rule = config.rule_for(&name);
+        let _ = app.emit(
+            "frontmost-app",
+            FrontmostChange {
+                app: name.clone(),
+                rule,
+            },
+        );
+        match rule.map(|i| &config.rules[i]) {
+            Some(rule) => {
+                if saved.is_none() {
+                    saved = Some(history::snapshot(&app));
+                }
+                if let Err(e) = apply(&app, rule) {
+                    notify::error(&app, &format!("Rule for {name} failed"), &e);
+                }
+            }
+            None => {
+                if let Some(snapshot) = saved.take() {
+                    if config.restore {
+                        history::restore(&app, &snapshot);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn apply(app: &AppHandle, rule: &AppRule) -> Result<(), String> {
+    let preset = presets::get(app, rule.preset)?;
+    let dither = app.state::<Ditherer>();
+    groups::fan_out(app, rule.target.as_ref(), |_, id| {
+        dither.set_level(app, id, preset.brightness, preset.kelvin)
+    })?;
+    Ok(())
+}
+
+/// Name of the frontmost application.
+#[cfg(target_os = "macos")]
+fn frontmost_app() -> Result<String, String> {
+    let run = |args: &[&str]| {
+        Command::new("lsappinfo")
+            .args(args)
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .map_err(|e| format!("Failed to run lsappinfo: {e}"))
+    };
+    let asn = run(&["front"])?;
+    if asn.is_empty() {
+        return Err("No frontmost application".into());
+    }
+    parse_lsappinfo_name(&run(&["info", "-only", "name", &asn])?)
+        .ok_or_else(|| "Unexpected lsappinfo output".into())
+}
+
+#[cfg(target_os = "windows")]
+fn frontmost_app() -> Result<String, String> {
+    use std::ffi::c_void;
+
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetForegroundWindow() -> *mut c_void;
+        fn GetWindowThreadProcessId(window: *mut c_void, pid: *mut u32) -> u32;
+    }
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut c_void;
+        fn QueryFullProcessImageNameW(
+            process: *mut c_void,
+            flags: u32,
+            name: *mut u16,
+            size: *mut u32,
+        ) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    let mut buf = [0u16; 1024];
+    let mut len = buf.len() as u32;
+    // SAFETY: every pointer passed refers to a live local of the right type
+    // and size, and the process handle is closed before returning.
+    let ok = unsafe {
+        let window = GetForegroundWindow();
+        if window.is_null() {
+            return Err("No frontmost window".into());
+        }
+        let mut pid = 0;
+        GetWindowThreadProcessId(window, &mut pid);
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return Err("Can't open the frontmost process".into());
+        }
+        let ok = QueryFullProcessImageNameW(process, 0, buf.as_mut_ptr(), &mut len) != 0;
+        CloseHandle(process);
+        ok
+    };
+    if !ok {
+        return Err("Can't read the frontmost process name".into());
+    }
+    let path = String::from_utf16_lossy(&buf[..len as usize]);
+    Ok(app_name(&path))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn frontmost_app() -> Result<String, String> {
+    let output = Command::new("xdotool")
+        .args(["getactivewindow", "getwindowpid"])
+        .output()
+        .map_err(|e| format!("Failed to run xdotool: {e}"))?;
+    let pid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if pid.is_empty() {
+        return Err("No frontmost window".into());
+    }
+    std::fs::read_to_string(format!("/proc/{pid}/comm"))
+        .map(|comm| comm.trim().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// `"LSDisplayName"="<name>"` from `lsappinfo info` output.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_lsappinfo_name(output: &str) -> Option<String> {
+    let (_, value) = output.split_once('=')?;
+    let name = value.trim().trim_matches('"');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// File name of an executable without its extension.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn app_name(path: &str) -> String {
+    let file = path.rsplit(['\\', '/']).next().unwrap_or(path);
+    file.strip_suffix(".exe")
+        .or_else(|| file.strip_suffix(".EXE"))
+        .unwrap_or(file)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lsappinfo_name() {
+        assert_eq!(
+            parse_lsappinfo_name("\"LSDisplayName\"=\"zoom.us\"\n").as_deref(),
+            Some("zoom.us")
+        );
+        assert_eq!(parse_lsappinfo_name(""), None);
+        assert_eq!(
+            app_name(r"C:\Program Files\obs-studio\bin\64bit\obs64.exe"),
+            "obs64"
+        );
+    }
+
+    #[test]
+    fn test_first_matching_rule_applies() {
+        let rule = |app: &str, preset| AppRule {
+            app: app.into(),
+            preset,
+            target: None,
+        };
+        let config = AppRulesConfig {
+            rules: vec![rule("obs", 0), rule("Zoom", 1), rule("zoom.us", 2)],
+            ..AppRulesConfig::default()
+        };
+        assert_eq!(config.rule_for("OBS"), Some(0));
+        assert_eq!(config.rule_for("zoom.us"), Some(1));
+        assert!(config.rule_for("Safari").is_none());
+    }
+}
@@ -0,0 +1,183 @@
+/// Named groups of devices and command targets.
+///
+/// Commands that change lights take an optional `Target`: every connected
+/// light (the default), a single device, a named group, or the connected
+/// lights set to an RF channel (see `addressing`). Group commands fan
+/// out to each member and report per-device failures instead of stopping at
+/// the first one. Groups are persisted under `groups` in the settings store.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::addressing::AddressBook;
+use crate::errors::{ErrorCode, LightError};
+use crate::serial::SerialManager;
+use crate::STORE_FILE;
+
+const GROUPS_KEY: &str = "groups";
+
+/// Which lights a command applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    All,
+    Device(String),
+    Group(String),
+    /// RF channel, 1-16.
+    Channel(u8),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub name: String,
+    /// Member device ids.
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceError {
+    pub device: String,
+    pub code: ErrorCode,
+    pub error: String,
+}
+
+impl DeviceError {
+    pub fn new(device: String, error: impl Into<LightError>) -> Self {
+        let LightError { code, message } = error.into();
+        Self {
+            device,
+            code,
+            error: message,
+        }
+    }
+}
+
+/// Outcome of a command applied to several devices.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FanOutReport {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<DeviceError>,
+}
+
+pub struct GroupManager {
+    groups: Mutex<BTreeMap<String, Vec<String>>>,
+}
+
+impl GroupManager {
+    pub fn new() -> Self {
+        Self {
+            groups: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, Vec<String>> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(GROUPS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.groups.lock().unwrap() = saved;
+    }
+
+    pub fn list(&self) -> Vec<Group> {
+        self.groups
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, members)| Group {
+                name: name.clone(),
+                members: members.clone(),
+            })
+            .collect()
+    }
+
+    /// Create a group, or replace the members of an existing one.
+    pub fn save(&self, app: &AppHandle, name: &str, members: Vec<String>) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Group name cannot be empty".into());
+        }
+        let mut groups = self.groups.lock().unwrap();
+        groups.insert(name.to_string(), members);
+        persist(app, &groups)
+    }
+
+    pub fn delete(&self, app: &AppHandle, name: &str) -> Result<(), String> {
+        let mut groups = self.groups.lock().unwrap();
+        if groups.remove(name).is_none() {
+            return Err(format!("No group named {name}"));
+        }
+        persist(app, &groups)
+    }
+
+    /// Device ids a target refers to.
+    pub fn resolve(&self, app: &AppHandle, target: &Target) -> Result<Vec<String>, String> {
+        match target {
+            Target::All => Ok(app.state::<SerialManager>().ids()),
+            Target::Device(id) => Ok(vec![id.clone()]),
+            Target::Group(name) => self
+                .groups
+                .lock()
+                .unwrap()
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("No group named {name}")),
+            Target::Channel(channel) => {
+                let connected = app.state::<SerialManager>().ids();
+                Ok(app
+                    .state::<AddressBook>()
+                    .on_channel(*channel)
+                    .into_iter()
+                    .filter(|id| connected.contains(id))
+                    .collect())
+            }
+        }
+    }
+}
+
+fn persist(app: &AppHandle, groups: &BTreeMap<String, Vec<String>>) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        GROUPS_KEY,
+        serde_json::to_value(groups).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Run `op` for every device in `target` (all lights if `None`). Fails only
+/// if no device succeeded; partial failures are listed in the report.
+pub fn fan_out<E: Into<LightError>>(
+    app: &AppHandle,
+    target: Option<&Target>,
+    op: impl Fn(&SerialManager, &str) -> Result<(), E>,
+) -> Result<FanOutReport, LightError> {
+    let ids = app
+        .state::<GroupManager>()
+        .resolve(app, target.unwrap_or(&Target::All))?;
+    if ids.is_empty() {
+        return Err(LightError::new(ErrorCode::NotConnected, "Port not open"));
+    }
+
+    let serial = app.state::<SerialManager>();
+    let mut report = FanOutReport::default();
+    for id in ids {
+        match op(serial.inner(), &id) {
+            Ok(()) => report.succeeded.push(id),
+            Err(error) => report.failed.push(DeviceError::new(id, error)),
+        }
+    }
+
+    if report.succeeded.is_empty() {
+        let errors = report
+            .failed
+            .into_iter()
+            .map(|f| LightError::new(f.code, f.error))
+            .collect();
+        return Err(LightError::join(errors));
+    }
+    Ok(report)
+}
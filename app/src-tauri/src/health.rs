@@ -0,0 +1,212 @@
+/// Health report for a status dashboard.
+///
+/// `report` gathers in one call what the frontend would otherwise piece
+/// together from several commands and events: for every light that's
+/// connected, kept connected by the supervisor (see `supervisor`) or has
+/// had a connection this session, its connection state, how long ago it
+/// last reported its status, how many writes are queued for it and its
+/// error counters; and which integrations are enabled. Connection states and
+/// error counts are followed from the event bus (see `events`), so they're
+/// counted from launch and cover errors reported as "device-error".
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::ambient::AmbientLight;
+use crate::autoexposure::AutoExposure;
+use crate::calendar::CalendarAutomation;
+use crate::devices::DeviceNames;
+use crate::dmx::DmxOutput;
+use crate::errors::ErrorCode;
+use crate::events::Event;
+use crate::frontmost::AppRules;
+use crate::hue::HueBridge;
+use crate::idle::IdleDimmer;
+use crate::mqtt::MqttBridge;
+use crate::nightshift::NightShiftFollow;
+use crate::presetsync::PresetSync;
+use crate::proximity::ProximityPresence;
+use crate::rf::RfDongle;
+use crate::screensync::ScreenSync;
+use crate::serial::{ConnectionState, SerialManager};
+use crate::supervisor::Supervisor;
+use crate::thermal::ThermalProtection;
+use crate::webhooks::Webhooks;
+use crate::wemo::WemoEmulation;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceHealth {
+    pub device: String,
+    pub name: String,
+    pub state: ConnectionState,
+    /// Why it's in `state`, for errors.
+    pub reason: Option<String>,
+    /// Whether the supervisor keeps it connected.
+    pub supervised: bool,
+    /// Time since its last status report, in ms.
+    pub status_age_ms: Option<u64>,
+    /// Writes waiting to be sent.
+    pub queued: usize,
+    /// Failed writes on the current connection.
+    pub write_failures: u64,
+    /// Frames dropped on the current connection.
+    pub parse_errors: u64,
+    /// Background errors reported since launch, by code.
+    pub errors: BTreeMap<ErrorCode, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub devices: Vec<DeviceHealth>,
+    /// Whether each integration is enabled, by name.
+    pub integrations: BTreeMap<&'static str, bool>,
+}
+
+/// What the event bus has said about one light.
+#[derive(Debug, Clone, Default)]
+struct Seen {
+    state: Option<(ConnectionState, Option<String>)>,
+    parse_errors: u64,
+    errors: BTreeMap<ErrorCode, u64>,
+}
+
+pub struct HealthMonitor {
+    seen: Mutex<HashMap<String, Seen>>,
+}
+
+impl HealthMonitor {
+    pub fn new() -> Self {
+        Self {
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Follow connection changes and errors.
+    pub fn on_event(&self, event: &Event) {
+        let mut seen = self.seen.lock().unwrap();
+        match event {
+            Event::DeviceState(change) => {
+                let device = seen.entry(change.device_id.clone()).or_default();
+                device.state = Some((change.state, change.reason.clone()));
+                if change.state == ConnectionState::Connected {
+                    device.parse_errors = 0;
+                }
+            }
+            Event::ParseErrors(errors) => {
+                seen.entry(errors.device.clone()).or_default().parse_errors = errors.count;
+            }
+            Event::DeviceError(error) => {
+                if let Some(id) = &error.device {
+                    *seen
+                        .entry(id.clone())
+                        .or_default()
+                        .errors
+                        .entry(error.code)
+                        .or_default() += 1;
+                }
+            }
+            Event::Status(_) | Event::WriteBacklog(_) | Event::BrightnessClamped(_) => {}
+        }
+    }
+}
+
+/// The health of every light and which integrations are enabled.
+pub fn report(app: &AppHandle) -> HealthReport {
+    let serial = app.state::<SerialManager>();
+    let stats = serial.write_stats();
+    let ports: HashMap<String, String> = serial.devices().into_iter().collect();
+    let supervised = app.state::<Supervisor>().desired();
+    let seen = app.state::<HealthMonitor>().seen.lock().unwrap().clone();
+    let names = app.state::<DeviceNames>();
+
+    let ids: BTreeSet<&String> = stats.keys().chain(&supervised).chain(seen.keys()).collect();
+    let devices = ids
+        .into_iter()
+        .map(|id| {
+            let seen = seen.get(id).cloned().unwrap_or_default();
+            let stats = stats.get(id).cloned().unwrap_or_default();
+            let (state, reason) = match (ports.contains_key(id), seen.state) {
+                (true, _) => (ConnectionState::Connected, None),
+                (false, Some((ConnectionState::Connected, _)) | None) => {
+                    (ConnectionState::Disconnected, None)
+                }
+                (false, Some(state)) => state,
+            };
+            DeviceHealth {
+                device: id.clone(),
+                name: names.name(id, ports.get(id).unwrap_or(id)),
+                state,
+                reason,
+                supervised: supervised.contains(id),
+                status_age_ms: serial.status_age(id).map(|age| age.as_millis() as u64),
+                queued: stats.queued,
+                write_failures: stats.failed,
+                parse_errors: seen.parse_errors,
+                errors: seen.errors,
+            }
+        })
+        .collect();
+
+    HealthReport {
+        devices,
+        integrations: integrations(app),
+    }
+}
+
+fn integrations(app: &AppHandle) -> BTreeMap<&'static str, bool> {
+    BTreeMap::from([
+        ("mqtt", app.state::<MqttBridge>().get().enabled),
+        ("dmx", app.state::<DmxOutput>().get().enabled),
+        ("hue", app.state::<HueBridge>().get().enabled),
+        ("wemo", app.state::<WemoEmulation>().get().enabled),
+        ("rf_dongle", app.state::<RfDongle>().get().port.is_some()),
+        ("ambient", app.state::<AmbientLight>().get().enabled),
+        ("screen_sync", app.state::<ScreenSync>().get().enabled),
+        ("calendar", app.state::<CalendarAutomation>().get().enabled),
+        ("idle", app.state::<IdleDimmer>().get().enabled),
+        ("thermal", app.state::<ThermalProtection>().get().enabled),
+        ("proximity", app.state::<ProximityPresence>().get().enabled),
+        ("app_rules", app.state::<AppRules>().get().enabled),
+        ("auto_exposure", app.state::<AutoExposure>().get().enabled),
+        ("night_shift", app.state::<NightShiftFollow>().get().enabled),
+        ("preset_sync", app.state::<PresetSync>().get().enabled),
+        (
+            "webhooks",
+            app.state::<Webhooks>()
+                .list()
+                .iter()
+                .any(|hook| hook.enabled),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::{DeviceStateChange, ParseErrors};
+
+    #[test]
+    fn test_monitor_counts_per_connection() {
+        let monitor = HealthMonitor::new();
+        let state = |state| {
+            Event::DeviceState(DeviceStateChange {
+                device_id: "A".into(),
+                state,
+                reason: None,
+            })
+        };
+        monitor.on_event(&state(ConnectionState::Connected));
+        monitor.on_event(&Event::ParseErrors(ParseErrors {
+            device: "A".into(),
+            count: 3,
+        }));
+        assert_eq!(monitor.seen.lock().unwrap()["A"].parse_errors, 3);
+        monitor.on_event(&state(ConnectionState::Error));
+        monitor.on_event(&state(ConnectionState::Connected));
+        let seen = monitor.seen.lock().unwrap()["A"].clone();
+        assert_eq!(seen.parse_errors, 0);
+        assert_eq!(seen.state, Some((ConnectionState::Connected, None)));
+    }
+}
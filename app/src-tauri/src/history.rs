@@ -0,0 +1,171 @@
+/// Undo/redo for applied light states.
+///
+/// Before a command changes the lights it records a checkpoint: the state of
+/// every connected light. `undo` restores the previous checkpoint and `redo`
+/// reapplies the undone one. Checkpoints taken in quick succession (a slider
+/// drag) collapse into one, and the history is bounded. Changes emit
+/// "history-changed" with the current depths.
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::errors;
+use crate::serial::SerialManager;
+
+/// Maximum undo steps kept.
+const MAX_DEPTH: usize = 50;
+/// Checkpoints closer together than this are merged into the earlier one.
+const COALESCE: Duration = Duration::from_millis(750);
+
+/// (brightness, kelvin) per device id.
+pub type Snapshot = BTreeMap<String, (u8, u32)>;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HistoryStatus {
+    pub undo_depth: usize,
+    pub redo_depth: usize,
+}
+
+#[derive(Default)]
+struct Stacks {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_checkpoint: Option<Instant>,
+}
+
+impl Stacks {
+    fn push(&mut self, snapshot: Snapshot, now: Instant) {
+        self.redo.clear();
+        let merge = self
+            .last_checkpoint
+            .is_some_and(|t| now.duration_since(t) < COALESCE);
+        self.last_checkpoint = Some(now);
+        if merge || self.undo.back() == Some(&snapshot) {
+            return;
+        }
+        self.undo.push_back(snapshot);
+        if self.undo.len() > MAX_DEPTH {
+            self.undo.pop_front();
+        }
+    }
+
+    fn status(&self) -> HistoryStatus {
+        HistoryStatus {
+            undo_depth: self.undo.len(),
+            redo_depth: self.redo.len(),
+        }
+    }
+}
+
+pub struct History {
+    stacks: Mutex<Stacks>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            stacks: Mutex::new(Stacks::default()),
+        }
+    }
+
+    /// Record the current light states before applying a change.
+    pub fn checkpoint(&self, app: &AppHandle) {
+        let snapshot = snapshot(app);
+        if snapshot.is_empty() {
+            return;
+        }
+        let status = {
+            let mut stacks = self.stacks.lock().unwrap();
+            stacks.push(snapshot, Instant::now());
+            stacks.status()
+        };
+        let _ = app.emit("history-changed", status);
+    }
+
+    pub fn undo(&self, app: &AppHandle) -> Result<HistoryStatus, String> {
+        let status = {
+            let mut stacks = self.stacks.lock().unwrap();
+            let previous = stacks.undo.pop_back().ok_or("Nothing to undo")?;
+            stacks.redo.push(snapshot(app));
+            // The next change starts a new checkpoint
+            stacks.last_checkpoint = None;
+            restore(app, &previous);
+            stacks.status()
+        };
+        let _ = app.emit("history-changed", status);
+        Ok(status)
+    }
+
+    pub fn redo(&self, app: &AppHandle) -> Result<HistoryStatus, String> {
+        let status = {
+            let mut stacks = self.stacks.lock().unwrap();
+            let next = stacks.redo.pop().ok_or("Nothing to redo")?;
+            stacks.undo.push_back(snapshot(app));
+            stacks.last_checkpoint = None;
+            restore(app, &next);
+            stacks.status()
+        };
+        let _ = app.emit("history-changed", status);
+        Ok(status)
+    }
+
+    pub fn status(&self) -> HistoryStatus {
+        self.stacks.lock().unwrap().status()
+    }
+}
+
+/// Current (brightness, kelvin) of every connected light that has reported.
+pub fn snapshot(app: &AppHandle) -> Snapshot {
+    let serial = app.state::<SerialManager>();
+    serial
+        .ids()
+        .into_iter()
+        .filter_map(|id| {
+            serial
+                .status_of(&id)
+                .map(|s| (id, (s.brightness, s.kelvin)))
+        })
+        .collect()
+}
+
+/// Reapply a snapshot. Lights that have since disconnected are skipped.
+pub fn restore(app: &AppHandle, snapshot: &Snapshot) {
+    let serial = app.state::<SerialManager>();
+    for (id, (bri, k)) in snapshot {
+        errors::check(app, "restore", Some(id), serial.set_cct_to(id, *bri, *k));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(bri: u8) -> Snapshot {
+        BTreeMap::from([("a".to_string(), (bri, 4950))])
+    }
+
+    #[test]
+    fn test_push_coalesces_and_bounds() {
+        let mut stacks = Stacks::default();
+        let t0 = Instant::now();
+        stacks.push(snap(10), t0);
+        stacks.push(snap(20), t0 + Duration::from_millis(100));
+        assert_eq!(stacks.undo.len(), 1);
+
+        for i in 0..(MAX_DEPTH as u64 + 10) {
+            stacks.push(snap(i as u8), t0 + COALESCE * (i as u32 + 2));
+        }
+        assert_eq!(stacks.undo.len(), MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_push_clears_redo() {
+        let mut stacks = Stacks::default();
+        stacks.redo.push(snap(50));
+        stacks.push(snap(10), Instant::now());
+        assert!(stacks.redo.is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+/// On-screen display for shortcut changes.
+///
+/// Global shortcuts change the lights while the panel is hidden, so the new
+/// brightness or temperature is shown in a small always-on-top window near
+/// the bottom of the primary screen, like the system volume display, which
+/// hides itself a moment after the last change. The window is created hidden
+/// at launch (`public/hud.html`) and filled in by evaluating `showHud` in it.
+/// It can be turned off with the `hud` setting.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{
+    AppHandle, Manager, PhysicalPosition, WebviewUrl, WebviewWindow, WebviewWindowBuilder,
+};
+
+use crate::config::SettingsManager;
+use crate::protocol::KelvinRange;
+
+const HUD_LABEL: &str = "hud";
+/// Window size, in logical pixels.
+const SIZE: (f64, f64) = (200.0, 130.0);
+/// How long the HUD stays up after the last change.
+const HIDE_AFTER: Duration = Duration::from_millis(1200);
+
+/// What the HUD shows.
+#[derive(Debug, Clone, Serialize)]
+struct Content {
+    label: &'static str,
+    value: String,
+    /// Position of the value in its range, 0.0-1.0, for the bar.
+    fraction: f64,
+}
+
+pub struct Hud {
+    /// Bumped by every show; a hide timer only hides its own show.
+    generation: Arc<AtomicU64>,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Self {
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Create the hidden HUD window.
+    pub fn init(&self, app: &AppHandle) -> tauri::Result<()> {
+        WebviewWindowBuilder::new(app, HUD_LABEL, WebviewUrl::App("hud.html".into()))
+            .title("")
+            .inner_size(SIZE.0, SIZE.1)
+            .decorations(false)
+            .transparent(true)
+            .shadow(false)
+            .resizable(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .focused(false)
+            .visible(false)
+            .build()
+            .map(|_| ())
+    }
+
+    /// Show a brightness as a slider level 0-100, as the panel shows it.
+    pub fn brightness(&self, app: &AppHandle, level: u8) {
+        self.show(
+            app,
+            Content {
+                label: "Brightness",
+                value: format!("{level}%"),
+                fraction: level as f64 / 100.0,
+            },
+        );
+    }
+
+    /// Show a temperature within the light's `range`.
+    pub fn kelvin(&self, app: &AppHandle, kelvin: u32, range: &KelvinRange) {
+        let span = range.max.saturating_sub(range.min).max(1) as f64;
+        self.show(
+            app,
+            Content {
+                label: "Temperature",
+                value: format!("{kelvin}K"),
+                fraction: (kelvin.saturating_sub(range.min) as f64 / span).min(1.0),
+            },
+        );
+    }
+
+    fn show(&self, app: &AppHandle, content: Content) {
+        if !app.state::<SettingsManager>().get().hud {
+            return;
+        }
+        let Some(win) = app.get_webview_window(HUD_LABEL) else {
+            return;
+        };
+        let Ok(json) = serde_json::to_string(&content) else {
+            return;
+        };
+        let _ = win.eval(&format!("window.showHud({json})"));
+        place(app, &win);
+        let _ = win.show();
+
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let current = self.generation.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(HIDE_AFTER);
+            if current.load(Ordering::SeqCst) == gen {
+                let _ = win.hide();
+            }
+        });
+    }
+}
+
+/// Centre the window horizontally, a sixth of the way up the primary screen.
+fn place(app: &AppHandle, win: &WebviewWindow) {
+    let Ok(Some(monitor)) = app.primary_monitor() else {
+        return;
+    };
+    let (origin, size) = (monitor.position(), monitor.size());
+    let scale = monitor.scale_factor();
+    let (w, h) = (SIZE.0 * scale, SIZE.1 * scale);
+    let x = origin.x as f64 + (size.width as f64 - w) / 2.0;
+    let y = origin.y as f64 + size.height as f64 * 5.0 / 6.0 - h;
+    let _ = win.set_position(PhysicalPosition::new(x.round() as i32, y.round() as i32));
+}
@@ -0,0 +1,513 @@
+/// Philips Hue bridge emulation.
+///
+/// When enabled, the app answers as a Hue bridge so Hue-compatible apps and
+/// sync tools can find and control the connected lights as color temperature
+/// bulbs. It responds to SSDP discovery and serves the parts of the v1 REST
+/// API those clients use (see `upnp`):
+///
+/// - `POST /api`: pairing, accepted for 30 s after `pair` (the link button)
+/// - `GET /api/<user>`, `/api/<user>/config`: bridge configuration
+/// - `GET /api/<user>/lights`, `/api/<user>/lights/<n>`: connected lights
+/// - `PUT /api/<user>/lights/<n>/state`: `on`, `bri` and `ct`
+///
+/// `bri` (1-254) maps to the slider level and `ct` is in mireds. Lights keep
+/// the number they were first listed under. Clients that only discover
+/// bridges over mDNS need the bridge's address entered by hand. Settings are
+/// persisted under `hue`, and the bridge identity, paired users and light
+/// numbers under `hue_bridge`, in the settings store.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::devices::DeviceNames;
+use crate::groups::Target;
+use crate::mqtt::{self, SetCommand};
+use crate::serial::SerialManager;
+use crate::upnp::{self, Request, Ssdp};
+use crate::{errors, notify, protocol, STORE_FILE};
+
+const HUE_KEY: &str = "hue";
+const BRIDGE_KEY: &str = "hue_bridge";
+const SSDP_SERVICE: &str = "hue";
+/// How long pairing stays open after `pair`.
+pub const PAIRING_WINDOW: Duration = Duration::from_secs(30);
+/// Mired range of the lights (7000K-2900K).
+const CT_MIN: u32 = 143;
+const CT_MAX: u32 = 345;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HueConfig {
+    pub enabled: bool,
+    /// HTTP port; Hue apps expect 80.
+    pub port: u16,
+}
+
+impl Default for HueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 80,
+        }
+    }
+}
+
+/// Persisted identity and pairings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Bridge {
+    /// 12 hex digits, used like the bridge's MAC address.
+    serial: String,
+    /// Usernames handed out when pairing.
+    users: Vec<String>,
+    /// Device ids by light number, less one.
+    lights: Vec<String>,
+}
+
+impl Bridge {
+    /// The 16-digit bridge id Hue clients show.
+    fn id(&self) -> String {
+        let (a, b) = self.serial.split_at(6);
+        format!("{a}FFFE{b}").to_uppercase()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Route<'a> {
+    Description,
+    Pair,
+    Config(&'a str),
+    FullState(&'a str),
+    Lights(&'a str),
+    Light(&'a str, &'a str),
+    LightState(&'a str, &'a str),
+    NotFound,
+}
+
+fn route<'a>(method: &str, path: &'a str) -> Route<'a> {
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, parts.as_slice()) {
+        ("GET", ["description.xml"]) => Route::Description,
+        ("POST", ["api"]) => Route::Pair,
+        ("GET", ["api", user]) => Route::FullState(user),
+        ("GET", ["api", user, "config"]) => Route::Config(user),
+        ("GET", ["api", user, "lights"]) => Route::Lights(user),
+        ("GET", ["api", user, "lights", n]) => Route::Light(user, n),
+        ("PUT", ["api", user, "lights", n, "state"]) => Route::LightState(user, n),
+        _ => Route::NotFound,
+    }
+}
+
+/// Hue brightness (1-254) to a slider level.
+fn bri_to_level(bri: u8) -> u8 {
+    ((bri.max(1) as u32 * 100 + 127) / 254).max(1) as u8
+}
+
+/// A slider level to Hue brightness.
+fn level_to_bri(level: u8) -> u8 {
+    ((level.min(100) as u32 * 254 + 50) / 100).max(1) as u8
+}
+
+fn hue_error(kind: u32, address: &str, description: &str) -> Value {
+    json!([{ "error": { "type": kind, "address": address, "description": description } }])
+}
+
+fn random_hex(len: usize) -> String {
+    let mut out = String::new();
+    while out.len() < len {
+        let mut hasher = RandomState::new().build_hasher();
+        // Every RandomState is seeded differently
+        hasher.write_usize(out.len());
+        out.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    out.truncate(len);
+    out
+}
+
+pub struct HueBridge {
+    config: Mutex<HueConfig>,
+    bridge: Mutex<Bridge>,
+    /// Pairing is accepted until then.
+    link_until: Mutex<Option<Instant>>,
+    /// Bumped on every reconfigure; the server threads exit when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl HueBridge {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(HueConfig::default()),
+            bridge: Mutex::new(Bridge::default()),
+            link_until: Mutex::new(None),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the bridge if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let store = app.store(STORE_FILE).ok();
+        let saved: HueConfig = store
+            .as_ref()
+            .and_then(|store| store.get(HUE_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        let mut bridge: Bridge = store
+            .as_ref()
+            .and_then(|store| store.get(BRIDGE_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        if bridge.serial.len() != 12 {
+            bridge.serial = random_hex(12);
+            errors::check_save(app, "save_hue_bridge", None, save_bridge(app, &bridge));
+        }
+        *self.bridge.lock().unwrap() = bridge;
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> HueConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: HueConfig) -> Result<(), String> {
+        if config.port == 0 {
+            return Err("Choose a port for the Hue bridge".into());
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            HUE_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    /// Accept pairing requests for the next `PAIRING_WINDOW`, like pressing
+    /// the link button.
+    pub fn pair(&self) {
+        *self.link_until.lock().unwrap() = Some(Instant::now() + PAIRING_WINDOW);
+    }
+
+    /// Forget every paired app.
+    pub fn unpair_all(&self, app: &AppHandle) -> Result<(), String> {
+        let mut bridge = self.bridge.lock().unwrap();
+        bridge.users.clear();
+        save_bridge(app, &bridge)
+    }
+
+    fn link_open(&self) -> bool {
+        self.link_until
+            .lock()
+            .unwrap()
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    fn restart(&self, app: &AppHandle, config: HueConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let ssdp = app.state::<Ssdp>();
+        ssdp.unregister(SSDP_SERVICE);
+        if !config.enabled {
+            return;
+        }
+        let listener = match upnp::listen(config.port) {
+            Ok(listener) => listener,
+            Err(e) => {
+                notify::error(
+                    app,
+                    "Hue bridge unavailable",
+                    &format!("Can't listen on port {}: {e}", config.port),
+                );
+                return;
+            }
+        };
+        let current = self.generation.clone();
+        let handle = app.clone();
+        std::thread::spawn(move || {
+            upnp::serve(listener, current, gen, |request| {
+                handle.state::<HueBridge>().handle(&handle, request)
+            })
+        });
+        let handle = app.clone();
+        ssdp.register(
+            app,
+            SSDP_SERVICE,
+            Box::new(move |st, ip| search_response(&handle, config.port, st, ip)),
+        );
+    }
+
+    fn authorized(&self, user: &str) -> bool {
+        self.bridge.lock().unwrap().users.iter().any(|u| u == user)
+    }
+
+    /// The light number of a device, assigning the next one if it's new.
+    fn number(&self, app: &AppHandle, id: &str) -> usize {
+        let mut bridge = self.bridge.lock().unwrap();
+        if let Some(i) = bridge.lights.iter().position(|l| l == id) {
+            return i + 1;
+        }
+        bridge.lights.push(id.to_string());
+        errors::check_save(app, "save_hue_bridge", None, save_bridge(app, &bridge));
+        bridge.lights.len()
+    }
+
+    fn device(&self, number: &str) -> Option<String> {
+        let n: usize = number.parse().ok()?;
+        self.bridge
+            .lock()
+            .unwrap()
+            .lights
+            .get(n.checked_sub(1)?)
+            .cloned()
+    }
+
+    fn handle(&self, app: &AppHandle, request: &Request) -> (&'static str, String) {
+        let reply = |value: Value| ("application/json", value.to_string());
+        let unauthorized = || reply(hue_error(1, "/", "unauthorized user"));
+        match route(&request.method, &request.path) {
+            Route::Description => ("text/xml", self.description()),
+            Route::Pair => reply(self.create_user(app, &request.body)),
+            Route::Config(user) | Route::FullState(user) if !self.authorized(user) => {
+                unauthorized()
+            }
+            Route::Lights(user) | Route::Light(user, _) | Route::LightState(user, _)
+                if !self.authorized(user) =>
+            {
+                unauthorized()
+            }
+            Route::Config(_) => reply(self.bridge_config()),
+            Route::FullState(_) => reply(json!({
+                "lights": self.lights(app),
+                "config": self.bridge_config(),
+                "groups": {},
+                "scenes": {},
+                "schedules": {},
+            })),
+            Route::Lights(_) => reply(self.lights(app)),
+            Route::Light(_, n) => {
+                let light = self.device(n).and_then(|id| self.light(app, &id));
+                reply(light.unwrap_or_else(|| {
+                    hue_error(3, &format!("/lights/{n}"), "resource not available")
+                }))
+            }
+            Route::LightState(_, n) => reply(self.set_state(app, n, &request.body)),
+            Route::NotFound => reply(hue_error(4, &request.path, "method not available")),
+        }
+    }
+
+    fn create_user(&self, app: &AppHandle, body: &[u8]) -> Value {
+        let valid =
+            serde_json::from_slice::<Value>(body).is_ok_and(|v| v.get("devicetype").is_some());
+        if !valid {
+            return hue_error(5, "/", "invalid/missing parameters in body");
+        }
+        if !self.link_open() {
+            return hue_error(101, "", "link button not pressed");
+        }
+        let user = random_hex(40);
+        let mut bridge = self.bridge.lock().unwrap();
+        bridge.users.push(user.clone());
+        errors::check_save(app, "save_hue_bridge", None, save_bridge(app, &bridge));
+        json!([{ "success": { "username": user } }])
+    }
+
+    fn bridge_config(&self) -> Value {
+        let bridge = self.bridge.lock().unwrap();
+        let mac: Vec<String> = bridge
+            .serial
+            .as_bytes()
+            .chunks(2)
+            .map(|c| String::from_utf8_lossy(c).into_owned())
+            .collect();
+        json!({
+            "name": "Neewer Control",
+            "bridgeid": bridge.id(),
+            "mac": mac.join(":"),
+            "modelid": "BSB002",
+            "apiversion": "1.24.0",
+            "swversion": "1941132080",
+            "ipaddress": upnp::local_ip().map(|ip| ip.to_string()),
+            "linkbutton": self.link_open(),
+        })
+    }
+
+    fn lights(&self, app: &AppHandle) -> Value {
+        let lights: serde_json::Map<String, Value> = app
+            .state::<SerialManager>()
+            .ids()
+            .iter()
+            .filter_map(|id| {
+                let light = self.light(app, id)?;
+                Some((self.number(app, id).to_string(), light))
+            })
+            .collect();
+        Value::Object(lights)
+    }
+
+    /// A connected light as a Hue color temperature bulb.
+    fn light(&self, app: &AppHandle, id: &str) -> Option<Value> {
+        let serial = app.state::<SerialManager>();
+        let (_, port) = serial.devices().into_iter().find(|(d, _)| d == id)?;
+        let status = serial.status_of(id);
+        let (on, bri, ct) = match &status {
+            Some(s) => (s.brightness > 0, level_to_bri(s.level), s.mired),
+            None => (
+                false,
+                1,
+                protocol::kelvin_to_mired(protocol::DEFAULT_TEMP_K),
+            ),
+        };
+        let serial_hex = self.bridge.lock().unwrap().serial.clone();
+        Some(json!({
+            "state": {
+                "on": on,
+                "bri": bri,
+                "ct": ct.clamp(CT_MIN, CT_MAX),
+                "alert": "none",
+                "colormode": "ct",
+                "mode": "homeautomation",
+                "reachable": true,
+            },
+            "type": "Color temperature light",
+            "name": app.state::<DeviceNames>().name(id, &port),
+            "modelid": "LTW001",
+            "manufacturername": "Signify Netherlands B.V.",
+            "productname": "Hue white ambiance",
+            "uniqueid": format!("{serial_hex}-{:02x}", self.number(app, id)),
+            "swversion": "1.50.2_r30933",
+            "capabilities": { "control": { "ct": { "min": CT_MIN, "max": CT_MAX } } },
+        }))
+    }
+
+    fn set_state(&self, app: &AppHandle, n: &str, body: &[u8]) -> Value {
+        let address = format!("/lights/{n}/state");
+        let Some(id) = self.device(n) else {
+            return hue_error(3, &format!("/lights/{n}"), "resource not available");
+        };
+        let Ok(state) = serde_json::from_slice::<serde_json::Map<String, Value>>(body) else {
+            return hue_error(2, &address, "body contains invalid json");
+        };
+        let command = SetCommand {
+            on: state.get("on").and_then(Value::as_bool),
+            level: state
+                .get("bri")
+                .and_then(Value::as_u64)
+                .map(|b| bri_to_level(b.min(254) as u8)),
+            kelvin: state
+                .get("ct")
+                .and_then(Value::as_u64)
+                .map(|ct| protocol::mired_to_kelvin(ct as u32)),
+            preset: None,
+            // In tenths of a second
+            transition_ms: state
+                .get("transitiontime")
+                .and_then(Value::as_u64)
+                .map(|t| t * 100),
+        };
+        if let Err(e) = mqtt::execute(app, &Target::Device(id), &command) {
+            return hue_error(201, &address, &e);
+        }
+        let results: Vec<Value> = state
+            .iter()
+            .filter(|(key, _)| matches!(key.as_str(), "on" | "bri" | "ct"))
+            .map(|(key, value)| json!({ "success": { format!("{address}/{key}"): value } }))
+            .collect();
+        Value::Array(results)
+    }
+
+    fn description(&self) -> String {
+        let bridge = self.bridge.lock().unwrap();
+        let ip = upnp::local_ip().map_or("127.0.0.1".to_string(), |ip| ip.to_string());
+        let port = self.get().port;
+        let serial = &bridge.serial;
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8" ?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+<specVersion><major>1</major><minor>0</minor></specVersion>
+<URLBase>http://{ip}:{port}/</URLBase>
+<device>
+<deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType>
+<friendlyName>Neewer Control ({ip})</friendlyName>
+<manufacturer>Signify</manufacturer>
+<manufacturerURL>http://www.philips-hue.com</manufacturerURL>
+<modelDescription>Philips hue Personal Wireless Lighting</modelDescription>
+<modelName>Philips hue bridge 2015</modelName>
+<modelNumber>BSB002</modelNumber>
+<serialNumber>{serial}</serialNumber>
+<UDN>uuid:2f402f80-da50-11e1-9b23-{serial}</UDN>
+<presentationURL>index.html</presentationURL>
+</device>
+</root>
+"#
+        )
+    }
+}
+
+/// The answer to an SSDP search, if the bridge is what's searched for.
+fn search_response(app: &AppHandle, port: u16, st: &str, ip: IpAddr) -> Vec<String> {
+    if !matches!(
+        st,
+        "ssdp:all" | "upnp:rootdevice" | "urn:schemas-upnp-org:device:basic:1"
+    ) {
+        return Vec::new();
+    }
+    let hue = app.state::<HueBridge>();
+    let bridge = hue.bridge.lock().unwrap();
+    let (serial, id) = (&bridge.serial, bridge.id());
+    vec![format!(
+        "HTTP/1.1 200 OK\r\n\
+         CACHE-CONTROL: max-age=100\r\n\
+         EXT:\r\n\
+         LOCATION: http://{ip}:{port}/description.xml\r\n\
+         SERVER: Linux/3.14.0 UPnP/1.0 IpBridge/1.24.0\r\n\
+         hue-bridgeid: {id}\r\n\
+         ST: {st}\r\n\
+         USN: uuid:2f402f80-da50-11e1-9b23-{serial}::{st}\r\n\r\n"
+    )]
+}
+
+fn save_bridge(app: &AppHandle, bridge: &Bridge) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        BRIDGE_KEY,
+        serde_json::to_value(bridge).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route("GET", "/description.xml"), Route::Description);
+        assert_eq!(route("POST", "/api"), Route::Pair);
+        assert_eq!(route("GET", "/api/abc/lights"), Route::Lights("abc"));
+        assert_eq!(
+            route("PUT", "/api/abc/lights/2/state"),
+            Route::LightState("abc", "2")
+        );
+        assert_eq!(route("DELETE", "/api/abc/lights/2"), Route::NotFound);
+    }
+
+    #[test]
+    fn test_brightness_mapping() {
+        assert_eq!(bri_to_level(254), 100);
+        assert_eq!(bri_to_level(1), 1);
+        assert_eq!(level_to_bri(100), 254);
+        assert_eq!(level_to_bri(0), 1);
+        assert_eq!(bri_to_level(level_to_bri(40)), 40);
+    }
+}
@@ -0,0 +1,309 @@
+/// Idle auto-dim.
+///
+/// An optional loop watches for user inactivity (no keyboard or mouse input)
+/// and, after a configurable number of minutes, dims the lights or turns them
+/// off, restoring them on the next input. Dimming is skipped while any of the
+/// configured apps is running, so a recording or stream isn't darkened while
+/// the presenter sits still. Input idle time comes from IOKit (`ioreg`) on
+/// macOS, `GetLastInputInfo` on Windows and `xprintidle` on Linux.
+/// Configuration is persisted under `idle_dim` in the settings store.
+use std::process::Command;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::errors::LightError;
+use crate::groups::{self, Target};
+use crate::history::{self, Snapshot};
+use crate::sessionlog::{self, Source};
+use crate::{notify, STORE_FILE};
+
+const IDLE_DIM_KEY: &str = "idle_dim";
+/// Poll interval while active, and while dimmed (to restore promptly).
+const POLL: Duration = Duration::from_secs(5);
+const POLL_DIMMED: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IdleAction {
+    /// Dim to a level, keeping the temperature.
+    Dim {
+        level: u8,
+    },
+    Off,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleConfig {
+    pub enabled: bool,
+    pub idle_minutes: u32,
+    pub action: IdleAction,
+    /// Restore the lights on the next input.
+    pub restore: bool,
+    /// Don't dim while a process whose name contains one of these
+    /// (case-insensitive) is running.
+    pub exclude_apps: Vec<String>,
+    /// Lights to dim; all connected lights by default.
+    pub target: Option<Target>,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_minutes: 10,
+            action: IdleAction::Dim { level: 10 },
+            restore: true,
+            exclude_apps: vec!["obs".into(), "zoom.us".into(), "Ecamm Live".into()],
+            target: None,
+        }
+    }
+}
+
+impl IdleConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=480).contains(&self.idle_minutes) {
+            return Err("Idle time must be 1-480 minutes".into());
+        }
+        if matches!(self.action, IdleAction::Dim { level } if level > 100) {
+            return Err("Idle dim level must be 0-100".into());
+        }
+        Ok(())
+    }
+}
+
+/// Reported as "idle-dim" when the lights are dimmed or restored.
+#[derive(Debug, Clone, Serialize)]
+pub struct IdleState {
+    pub dimmed: bool,
+}
+
+pub struct IdleDimmer {
+    config: Mutex<IdleConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl IdleDimmer {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(IdleConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: IdleConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(IDLE_DIM_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> IdleConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: IdleConfig) -> Result<(), String> {
+        config.validate()?;
+        if config.enabled {
+            idle_time()?;
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            IDLE_DIM_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: IdleConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn run(app: AppHandle, config: IdleConfig, current: Arc<AtomicU64>, gen: u64) {
+    sessionlog::set_source(Source::Automation);
+    let threshold = Duration::from_secs(config.idle_minutes as u64 * 60);
+    // State to restore while dimmed
+    let mut dimmed: Option<Snapshot> = None;
+    let mut failing = false;
+
+    while current.load(Ordering::SeqCst) == gen {
+        std::thread::sleep(if dimmed.is_some() { POLL_DIMMED } else { POLL });
+        let idle = match idle_time() {
+            Ok(idle) => {
+                failing = false;
+                idle
+            }
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Can't read the input idle time", &e);
+                }
+                continue;
+            }
+        };
+
+        if dimmed.is_none() && idle >= threshold && !excluded(&config.exclude_apps) {
+            let saved = history::snapshot(&app);
+            if apply(&app, &config).is_ok() {
+                dimmed = Some(saved);
+                let _ = app.emit("idle-dim", IdleState { dimmed: true });
+            }
+        } else if idle < threshold {
+            if let Some(saved) = dimmed.take() {
+                if config.restore {
+                    history::restore(&app, &saved);
+                }
+                let _ = app.emit("idle-dim", IdleState { dimmed: false });
+            }
+        }
+    }
+}
+
+fn apply(app: &AppHandle, config: &IdleConfig) -> Result<(), LightError> {
+    let target = config.target.as_ref();
+    match config.action {
+        IdleAction::Off => {
+            groups::fan_out(app, target, |serial, id| serial.set_power_to(id, false))
+        }
+        IdleAction::Dim { level } => {
+            let dither = app.state::<Ditherer>();
+            groups::fan_out(app, target, |serial, id| {
+                let status = serial
+                    .status_of(id)
+                    .ok_or("No status received from light yet")?;
+                // Never brighten a light that's already below the dim level
+                dither.set_level(app, id, level.min(status.level), status.kelvin)
+            })
+        }
+    }
+    .map(|_| ())
+}
+
+/// Whether any running process matches one of `apps`.
+fn excluded(apps: &[String]) -> bool {
+    if apps.is_empty() {
+        return false;
+    }
+    let output = if cfg!(target_os = "windows") {
+        Command::new("tasklist")
+            .args(["/fo", "csv", "/nh"])
+            .output()
+    } else {
+        Command::new("ps").args(["-axo", "comm="]).output()
+    };
+    // If the process list can't be read, err on the side of not dimming
+    let Ok(output) = output else {
+        return true;
+    };
+    matches_any(&String::from_utf8_lossy(&output.stdout), apps)
+}
+
+fn matches_any(processes: &str, apps: &[String]) -> bool {
+    let processes = processes.to_lowercase();
+    apps.iter()
+        .filter(|a| !a.trim().is_empty())
+        .any(|a| processes.contains(&a.to_lowercase()))
+}
+
+/// Time since the last keyboard or mouse input.
+#[cfg(target_os = "macos")]
+fn idle_time() -> Result<Duration, String> {
+    let output = Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4", "-r", "-k", "HIDIdleTime"])
+        .output()
+        .map_err(|e| format!("Failed to run ioreg: {e}"))?;
+    parse_hid_idle(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| "No HIDIdleTime in ioreg output".into())
+}
+
+#[cfg(target_os = "windows")]
+fn idle_time() -> Result<Duration, String> {
+    #[repr(C)]
+    struct LastInputInfo {
+        size: u32,
+        time: u32,
+    }
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+    }
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    let mut info = LastInputInfo {
+        size: std::mem::size_of::<LastInputInfo>() as u32,
+        time: 0,
+    };
+    // SAFETY: `info` is a correctly sized LASTINPUTINFO owned by this frame.
+    let (ok, now) = unsafe { (GetLastInputInfo(&mut info) != 0, GetTickCount()) };
+    if !ok {
+        return Err("GetLastInputInfo failed".into());
+    }
+    Ok(Duration::from_millis(now.wrapping_sub(info.time) as u64))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn idle_time() -> Result<Duration, String> {
+    let output = Command::new("xprintidle")
+        .output()
+        .map_err(|e| format!("Failed to run xprintidle: {e}"))?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| "Unexpected xprintidle output".into())
+}
+
+/// `"HIDIdleTime" = <ns>` from ioreg output.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_hid_idle(ioreg: &str) -> Option<Duration> {
+    ioreg.lines().find_map(|line| {
+        let (_, value) = line.split_once("\"HIDIdleTime\" = ")?;
+        value.trim().parse().ok().map(Duration::from_nanos)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hid_idle() {
+        let out = "    | |   \"HIDIdleTime\" = 2500000000\n";
+        assert_eq!(parse_hid_idle(out), Some(Duration::from_millis(2500)));
+        assert_eq!(parse_hid_idle(""), None);
+    }
+
+    #[test]
+    fn test_matches_any_is_case_insensitive() {
+        let ps = "/Applications/OBS.app/Contents/MacOS/OBS\n/usr/sbin/cfprefsd\n";
+        assert!(matches_any(ps, &["obs".into()]));
+        assert!(!matches_any(ps, &["zoom.us".into(), " ".into()]));
+    }
+}
@@ -0,0 +1,95 @@
+/// Round-trip latency measurement.
+///
+/// Sends status queries to a light one after another and times each until
+/// its answer arrives, so USB hubs, cables and transports (serial, network,
+/// Bluetooth) can be compared on the same light. A round trip includes the
+/// wait for the connection's read in progress (see `portconfig`), as every
+/// command to the light does. Queries that go unanswered are counted as lost
+/// rather than timed.
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::serial::SerialManager;
+
+pub const MAX_SAMPLES: u32 = 200;
+/// Pause between queries, so answers aren't mistaken for the next one's.
+const GAP: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyReport {
+    pub device: String,
+    /// Queries answered.
+    pub samples: u32,
+    pub lost: u32,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// Summarize round trips in ms; all zero if there are none.
+fn summarize(device: &str, mut round_trips: Vec<f64>, lost: u32) -> LatencyReport {
+    round_trips.sort_by(f64::total_cmp);
+    let n = round_trips.len();
+    let (min_ms, avg_ms, p95_ms) = if n == 0 {
+        (0.0, 0.0, 0.0)
+    } else {
+        // Nearest-rank percentile
+        let rank = (n * 95).div_ceil(100).max(1);
+        (
+            round_trips[0],
+            round_trips.iter().sum::<f64>() / n as f64,
+            round_trips[rank - 1],
+        )
+    };
+    LatencyReport {
+        device: device.to_string(),
+        samples: n as u32,
+        lost,
+        min_ms,
+        avg_ms,
+        p95_ms,
+    }
+}
+
+/// Time `samples` status queries to light `id`.
+pub fn measure(serial: &SerialManager, id: &str, samples: u32) -> Result<LatencyReport, String> {
+    if !(1..=MAX_SAMPLES).contains(&samples) {
+        return Err(format!("Samples must be between 1 and {MAX_SAMPLES}"));
+    }
+    if !serial.ids().iter().any(|i| i == id) {
+        return Err(format!("{id} is not connected"));
+    }
+    let mut round_trips = Vec::new();
+    let mut lost = 0;
+    for i in 0..samples {
+        if i > 0 {
+            std::thread::sleep(GAP);
+        }
+        let start = Instant::now();
+        match serial.query(id) {
+            Ok(_) => round_trips.push(start.elapsed().as_secs_f64() * 1000.0),
+            Err(_) => lost += 1,
+        }
+    }
+    Ok(summarize(id, round_trips, lost))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let round_trips = (1..=20).map(f64::from).collect();
+        let report = summarize("a", round_trips, 2);
+        assert_eq!(report.samples, 20);
+        assert_eq!(report.lost, 2);
+        assert_eq!(report.min_ms, 1.0);
+        assert_eq!(report.avg_ms, 10.5);
+        assert_eq!(report.p95_ms, 19.0);
+
+        let empty = summarize("a", Vec::new(), 3);
+        assert_eq!((empty.samples, empty.p95_ms), (0, 0.0));
+    }
+}
@@ -0,0 +1,142 @@
+/// Legacy Neewer command format.
+///
+/// Older USB and BLE firmware speaks the format the community decoded over
+/// BLE (see RESEARCH.md) rather than the PL81-Pro's:
+///
+/// Command format: [0x78] [tag] [payload_len] [payload...] [checksum]
+/// Checksum: low byte of the sum of all preceding bytes.
+///
+/// Temperatures go in hundreds of Kelvin (0x20 = 3200K) and power is 0x01
+/// for on, 0x02 for off. These lights don't echo commands or report their
+/// CCT state, only whether they're on. Frames are read with a `FrameParser`
+/// for `Format::Legacy`; which format a light speaks comes from its model's
+/// profile (see `models`).
+use crate::protocol::{self, Frame, KelvinRange, StatusFrame};
+
+/// First byte of every frame.
+pub const HEADER: u8 = 0x78;
+/// Tags of commands.
+pub const TAG_POWER: u8 = 0x81;
+pub const TAG_POWER_QUERY: u8 = 0x84;
+pub const TAG_HSI: u8 = 0x86;
+pub const TAG_CCT: u8 = 0x87;
+/// Tag of the power state lights report.
+pub const TAG_POWER_STATUS: u8 = 0x02;
+const POWER_ON: u8 = 0x01;
+const POWER_OFF: u8 = 0x02;
+
+/// 8-bit checksum of all bytes.
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, &b| sum.wrapping_add(b))
+}
+
+fn frame(tag: u8, payload: &[u8]) -> Vec<u8> {
+    let mut pkt = vec![HEADER, tag, payload.len() as u8];
+    pkt.extend_from_slice(payload);
+    pkt.push(checksum(&pkt));
+    pkt
+}
+
+/// `kelvin` at the nearest of the light's hardware steps, in hundreds of
+/// Kelvin.
+fn temp_byte(range: &KelvinRange, kelvin: u32) -> u8 {
+    let k = protocol::byte_to_kelvin(range, protocol::kelvin_to_byte(range, kelvin));
+    ((k + 50) / 100).min(u8::MAX as u32) as u8
+}
+
+/// Kelvin a CCT temperature byte stands for, within `range`.
+pub fn kelvin(range: &KelvinRange, temp_byte: u8) -> u32 {
+    range.clamp(temp_byte as u32 * 100)
+}
+
+/// Build a CCT command for a light with temperature range `range`:
+/// brightness 0-100, temperature in Kelvin.
+pub fn cct_command(range: &KelvinRange, brightness: u8, kelvin: u32) -> Vec<u8> {
+    frame(TAG_CCT, &[brightness.min(100), temp_byte(range, kelvin)])
+}
+
+/// Build an HSI colour command: hue in degrees, saturation and brightness
+/// 0-100. The hue goes little-endian.
+pub fn hsi_command(hue: u16, saturation: u8, brightness: u8) -> Vec<u8> {
+    let hue = hue % 360;
+    frame(
+        TAG_HSI,
+        &[
+            (hue & 0xFF) as u8,
+            (hue >> 8) as u8,
+            saturation.min(100),
+            brightness.min(100),
+        ],
+    )
+}
+
+/// Build a power command.
+pub fn power_command(on: bool) -> Vec<u8> {
+    frame(TAG_POWER, &[if on { POWER_ON } else { POWER_OFF }])
+}
+
+/// Build a query for the power state, the only state these lights report.
+pub fn power_query() -> Vec<u8> {
+    frame(TAG_POWER_QUERY, &[])
+}
+
+/// Whether `packet` is a CCT command.
+pub fn is_cct_command(packet: &[u8]) -> bool {
+    packet.len() == 6 && packet[..3] == [HEADER, TAG_CCT, 0x02]
+}
+
+/// The status `frame` reports, if it's a known status frame. CCT commands
+/// count as the state they set, since the lights don't report it.
+pub fn status(frame: &Frame) -> Option<StatusFrame> {
+    let status = match (frame.tag, frame.payload.as_slice()) {
+        (TAG_CCT, [bri, temp]) => StatusFrame::Cct {
+            brightness: *bri,
+            temp_byte: *temp,
+            gm: None,
+        },
+        (TAG_POWER_STATUS, [state, ..]) => StatusFrame::Power {
+            on: *state == POWER_ON,
+        },
+        _ => return None,
+    };
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Format, FrameParser};
+
+    const BICOLOR: KelvinRange = KelvinRange {
+        min: 3200,
+        max: 5600,
+        steps: 24,
+    };
+
+    #[test]
+    fn test_commands_match_the_decoded_format() {
+        assert_eq!(power_command(true), [0x78, 0x81, 0x01, 0x01, 0xFB]);
+        assert_eq!(power_query(), [0x78, 0x84, 0x00, 0xFC]);
+        let cct = cct_command(&BICOLOR, 50, 3200);
+        assert_eq!(cct[..5], [0x78, 0x87, 0x02, 50, 0x20]);
+        assert_eq!(cct[5], checksum(&cct[..5]));
+        assert_eq!(cct_command(&BICOLOR, 120, 9000)[3..5], [100, 0x38]);
+        assert_eq!(hsi_command(300, 80, 100)[3..7], [0x2C, 0x01, 80, 100]);
+        assert!(is_cct_command(&cct));
+        assert!(!is_cct_command(&power_command(false)));
+    }
+
+    #[test]
+    fn test_parse_legacy_frames() {
+        let mut stream = vec![0x00, 0x3A];
+        stream.extend_from_slice(&[0x78, TAG_POWER_STATUS, 0x01, 0x02, 0x7D]);
+        stream.extend_from_slice(&cct_command(&BICOLOR, 40, 4400));
+        let frames = FrameParser::for_format(Format::Legacy).push(&stream);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(status(&frames[0]), Some(StatusFrame::Power { on: false }));
+        let Some(StatusFrame::Cct { temp_byte, .. }) = status(&frames[1]) else {
+            panic!("expected a CCT status");
+        };
+        assert_eq!(kelvin(&BICOLOR, temp_byte), 4400);
+    }
+}
@@ -1,41 +1,417 @@
+mod addressing;
+mod ambient;
+mod arbitration;
+mod autoexposure;
+mod backup;
+mod batch;
+mod bundle;
+mod calendar;
+mod calibration;
+mod capture;
 mod commands;
+mod compare;
+mod config;
+#[cfg(test)]
+mod conformance;
+mod curves;
+mod devices;
+mod dither;
+mod dmx;
+mod effects;
+mod energy;
+mod errors;
+mod events;
+mod fade;
+mod focus;
+mod frontmost;
+mod groups;
+mod health;
+mod history;
+mod hud;
+mod hue;
+mod idle;
+mod latency;
+mod legacy;
+mod limits;
+mod links;
+mod lock;
+mod macros;
+mod matching;
+mod migrations;
+mod models;
+mod mqtt;
+mod nightshift;
+mod notify;
+mod packets;
+mod plugins;
+mod pomodoro;
+mod portconfig;
+mod presets;
+mod presetsync;
+mod profiles;
 mod protocol;
+mod proximity;
+mod quit;
+mod registry;
+mod rf;
+mod rfc2217;
+mod scenes;
+mod screensync;
+mod scripting;
+mod scroll;
 mod serial;
+mod sessionlog;
+mod shortcuts;
+mod snapshots;
+mod spp;
+mod steps;
+mod supervisor;
+mod sweep;
+mod thermal;
+mod timeline;
+mod toggle;
+mod transport;
+mod tray;
+mod upnp;
+mod usage;
+#[cfg(test)]
+mod vpty;
+mod webhooks;
+mod wemo;
+mod whitebalance;
 
+use addressing::AddressBook;
+use ambient::AmbientLight;
+use arbitration::Arbiter;
+use autoexposure::AutoExposure;
+use calendar::CalendarAutomation;
+use calibration::Calibration;
+use compare::AbCompare;
+use config::SettingsManager;
+use curves::CurveManager;
+use devices::{BluetoothAliases, DeviceNames, PreferredDevice};
+use dither::Ditherer;
+use dmx::DmxOutput;
+use effects::EffectEngine;
+use energy::EnergyMeter;
+use errors::ErrorReporter;
+use events::EventBus;
+use fade::FadeEngine;
+use frontmost::AppRules;
+use groups::GroupManager;
+use health::HealthMonitor;
+use history::History;
+use hud::Hud;
+use hue::HueBridge;
+use idle::IdleDimmer;
+use limits::BrightnessLimits;
+use links::LinkManager;
+use lock::ControlLock;
+use macros::MacroRecorder;
+use matching::ColorMatching;
+use models::DeviceModels;
+use mqtt::MqttBridge;
+use nightshift::NightShiftFollow;
+use packets::PacketCapture;
+use plugins::PluginHost;
+use pomodoro::Pomodoro;
+use portconfig::PortConfig;
+use presetsync::PresetSync;
+use profiles::ProfileManager;
+use proximity::ProximityPresence;
+use quit::QuitHandler;
+use registry::DeviceRegistry;
+use rf::RfDongle;
+use scenes::SceneManager;
+use screensync::ScreenSync;
+use scripting::ScriptHost;
+use scroll::ScrollAdjuster;
 use serial::SerialManager;
+use sessionlog::SessionLog;
+use shortcuts::ShortcutManager;
+use snapshots::Snapshots;
+use supervisor::Supervisor;
+use sweep::ExposureSweep;
+use thermal::ThermalProtection;
+use timeline::TimelineEngine;
+use toggle::PowerToggle;
+use upnp::Ssdp;
+use usage::UsageTracker;
+use webhooks::Webhooks;
+use wemo::WemoEmulation;
 use tauri::{
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     Manager,
 };
 
+/// Settings store shared with the panel.
+pub(crate) const STORE_FILE: &str = "settings.json";
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let mut app = tauri::Builder::default()
         .plugin(tauri_plugin_positioner::init())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .plugin(tauri_plugin_store::Builder::new().build())
+        .plugin(tauri_plugin_notification::init())
+        .manage(SettingsManager::new())
+        .manage(ProfileManager::new())
+        .manage(PresetSync::new())
+        .manage(Webhooks::new())
+        .manage(MqttBridge::new())
+        .manage(RfDongle::new())
+        .manage(DmxOutput::new())
+        .manage(Ssdp::new())
+        .manage(HueBridge::new())
+        .manage(WemoEmulation::new())
+        .manage(CurveManager::new())
+        .manage(AddressBook::new())
+        .manage(BrightnessLimits::new())
+        .manage(ColorMatching::new())
+        .manage(Ditherer::new())
+        .manage(Calibration::new())
+        .manage(AutoExposure::new())
+        .manage(AmbientLight::new())
+        .manage(ScreenSync::new())
+        .manage(CalendarAutomation::new())
+        .manage(NightShiftFollow::new())
+        .manage(IdleDimmer::new())
+        .manage(ThermalProtection::new())
+        .manage(ProximityPresence::new())
+        .manage(AppRules::new())
+        .manage(Hud::new())
+        .manage(DeviceNames::new())
+        .manage(DeviceRegistry::new())
+        .manage(DeviceModels::new())
+        .manage(PreferredDevice::new())
+        .manage(BluetoothAliases::new())
+        .manage(PortConfig::new())
         .manage(SerialManager::new())
+        .manage(GroupManager::new())
+        .manage(SceneManager::new())
+        .manage(Snapshots::new())
+        .manage(QuitHandler::new())
+        .manage(LinkManager::new())
+        .manage(FadeEngine::new())
+        .manage(ExposureSweep::new())
+        .manage(PowerToggle::new())
+        .manage(EffectEngine::new())
+        .manage(TimelineEngine::new())
+        .manage(ScriptHost::new())
+        .manage(PluginHost::new())
+        .manage(MacroRecorder::new())
+        .manage(Pomodoro::new())
+        .manage(History::new())
+        .manage(EventBus::new())
+        .manage(ErrorReporter::new())
+        .manage(HealthMonitor::new())
+        .manage(SessionLog::new())
+        .manage(PacketCapture::new())
+        .manage(ControlLock::new())
+        .manage(Arbiter::new())
+        .manage(UsageTracker::new())
+        .manage(EnergyMeter::new())
+        .manage(AbCompare::new())
+        .manage(ScrollAdjuster::new())
+        .manage(ShortcutManager::new())
+        .manage(Supervisor::new())
         .invoke_handler(tauri::generate_handler![
             commands::list_ports,
+            commands::list_devices,
+            commands::list_known_devices,
+            commands::forget_device,
+            commands::rename_device,
+            commands::get_capabilities,
+            commands::set_device_model,
+            commands::set_device_kelvin_range,
+            commands::preferred_port,
+            commands::list_bluetooth_aliases,
+            commands::set_bluetooth_alias,
+            commands::pin_device,
+            commands::unpin_device,
+            commands::list_serial_params,
+            commands::set_serial_params,
             commands::connect,
+            commands::connect_all,
             commands::disconnect,
+            commands::query_status,
+            commands::write_stats,
+            commands::health,
+            commands::measure_latency,
             commands::is_connected,
             commands::set_light,
+            commands::set_power,
+            commands::set_color,
+            commands::set_lights,
+            commands::toggle,
+            commands::brightness_up,
+            commands::brightness_down,
+            commands::kelvin_up,
+            commands::kelvin_down,
+            commands::apply_preset,
+            commands::list_profiles,
+            commands::save_profile,
+            commands::delete_profile,
+            commands::switch_profile,
+            commands::export_config,
+            commands::import_config,
+            commands::backup_all,
+            commands::restore_all,
+            commands::get_preset_sync,
+            commands::set_preset_sync,
+            commands::set_light_lux,
+            commands::get_calibration,
+            commands::set_calibration,
+            commands::get_auto_exposure,
+            commands::set_auto_exposure,
+            commands::get_ambient_light,
+            commands::set_ambient_light,
+            commands::get_screen_sync,
+            commands::set_screen_sync,
+            commands::get_calendar_automation,
+            commands::set_calendar_automation,
+            commands::get_night_shift,
+            commands::set_night_shift,
+            commands::get_idle_dim,
+            commands::set_idle_dim,
+            commands::get_thermal_protection,
+            commands::set_thermal_protection,
+            commands::snooze_thermal_protection,
+            commands::start_exposure_sweep,
+            commands::stop_exposure_sweep,
+            commands::save_exposure_sweep,
+            commands::get_proximity,
+            commands::set_proximity,
+            commands::nearby_bluetooth_devices,
+            commands::get_app_rules,
+            commands::set_app_rules,
+            commands::list_webhooks,
+            commands::set_webhooks,
+            commands::test_webhook,
+            commands::usage_stats,
+            commands::reset_usage,
+            commands::get_energy_config,
+            commands::set_energy_config,
+            commands::energy_stats,
+            commands::reset_energy,
+            commands::get_mqtt,
+            commands::set_mqtt,
+            commands::get_hue,
+            commands::set_hue,
+            commands::pair_hue,
+            commands::unpair_hue,
+            commands::get_wemo,
+            commands::set_wemo,
+            commands::get_dmx,
+            commands::set_dmx,
+            commands::get_rf_dongle,
+            commands::set_rf_dongle,
+            commands::rf_set_cct,
+            commands::set_target_channel,
+            commands::broadcast_channel,
+            commands::list_light_addresses,
+            commands::set_light_address,
+            commands::list_white_balance,
+            commands::apply_white_balance,
+            commands::crossfade,
+            commands::stop_fade,
+            commands::start_effect,
+            commands::start_strobe,
+            commands::stop_effect,
+            commands::effect_status,
+            commands::get_pomodoro,
+            commands::set_pomodoro,
+            commands::start_pomodoro,
+            commands::stop_pomodoro,
+            commands::pomodoro_status,
+            commands::list_timelines,
+            commands::save_timeline,
+            commands::delete_timeline,
+            commands::play_timeline,
+            commands::pause_timeline,
+            commands::resume_timeline,
+            commands::stop_timeline,
+            commands::timeline_status,
+            commands::list_scripts,
+            commands::save_script,
+            commands::delete_script,
+            commands::list_plugins,
+            commands::invoke_plugin_action,
+            commands::list_macros,
+            commands::start_macro_recording,
+            commands::stop_macro_recording,
+            commands::cancel_macro_recording,
+            commands::play_macro,
+            commands::stop_macro,
+            commands::delete_macro,
+            commands::undo,
+            commands::redo,
+            commands::history_status,
+            commands::export_history,
+            commands::unknown_packets,
+            commands::send_packet,
+            commands::clear_unknown_packets,
+            commands::lock_status,
+            commands::lock_controls,
+            commands::unlock_controls,
+            commands::get_arbitration,
+            commands::set_arbitration,
+            commands::hold_status,
+            commands::clear_hold,
+            commands::mark_ab,
+            commands::toggle_ab,
+            commands::clear_ab,
+            commands::ab_status,
+            commands::list_dimming_curves,
+            commands::set_dimming_curve,
+            commands::list_brightness_caps,
+            commands::set_brightness_cap,
+            commands::list_color_offsets,
+            commands::set_color_offset,
+            commands::list_groups,
+            commands::save_group,
+            commands::delete_group,
+            commands::list_scenes,
+            commands::save_scene,
+            commands::delete_scene,
+            commands::apply_scene,
+            commands::list_snapshots,
+            commands::snapshot,
+            commands::restore_snapshot,
+            commands::delete_snapshot,
+            commands::list_links,
+            commands::link_device,
+            commands::unlink_device,
+            commands::scroll_light,
+            commands::list_shortcuts,
+            commands::bind_shortcut,
+            commands::unbind_shortcut,
+            commands::get_settings,
+            commands::update_settings,
             commands::quit_app,
         ])
         .setup(|app| {
-            // Build tray icon — click toggles the panel window
-            let tray_icon = {
-                let bytes = include_bytes!("../icons/tray-icon.png");
-                tauri::image::Image::from_bytes(bytes).expect("invalid tray icon")
-            };
-            TrayIconBuilder::new()
+            // Build tray icon — click toggles the panel window. The icon is
+            // re-rendered by `tray::refresh` as the light's state changes.
+            let (tray_icon, template) = tray::render(false, None);
+            TrayIconBuilder::with_id(tray::TRAY_ID)
                 .icon(tray_icon)
-                .icon_as_template(true)
+                .icon_as_template(template)
                 .tooltip("Neewer USB Control")
                 .on_tray_icon_event(|tray, event| {
                     tauri_plugin_positioner::on_tray_event(tray.app_handle(), &event);
 
+                    // Scrolling over the icon adjusts the lights; see `scroll`
+                    match event {
+                        TrayIconEvent::Enter { .. } => {
+                            tray.app_handle().state::<ScrollAdjuster>().set_over_tray(true)
+                        }
+                        TrayIconEvent::Leave { .. } => {
+                            tray.app_handle().state::<ScrollAdjuster>().set_over_tray(false)
+                        }
+                        _ => {}
+                    }
+
                     if let TrayIconEvent::Click {
                         button: MouseButton::Left,
                         button_state: MouseButtonState::Up,
@@ -57,13 +433,60 @@ pub fn run() {
                     }
                 })
                 .build(app)?;
+            scroll::watch_tray(app.handle());
+            app.state::<Hud>().init(app.handle())?;
+
+            events::init(app.handle());
+            migrations::run(app.handle());
+            app.state::<SettingsManager>().load(app.handle());
+            app.state::<ProfileManager>().load(app.handle());
+            app.state::<Webhooks>().load(app.handle());
+            app.state::<DeviceNames>().load(app.handle());
+            app.state::<DeviceRegistry>().load(app.handle());
+            app.state::<DeviceModels>().load(app.handle());
+            app.state::<PreferredDevice>().load(app.handle());
+            app.state::<BluetoothAliases>().load(app.handle());
+            app.state::<PortConfig>().load(app.handle());
+            app.state::<UsageTracker>().load(app.handle());
+            app.state::<EnergyMeter>().load(app.handle());
+            app.state::<CurveManager>().load(app.handle());
+            app.state::<BrightnessLimits>().load(app.handle());
+            app.state::<ColorMatching>().load(app.handle());
+            app.state::<ControlLock>().load(app.handle());
+            app.state::<Arbiter>().load(app.handle());
+            app.state::<Calibration>().load(app.handle());
+            app.state::<GroupManager>().load(app.handle());
+            app.state::<SceneManager>().load(app.handle());
+            app.state::<Snapshots>().load(app.handle());
+            app.state::<AddressBook>().load(app.handle());
+            app.state::<LinkManager>().load(app.handle());
+            app.state::<TimelineEngine>().load(app.handle());
+            app.state::<MacroRecorder>().load(app.handle());
+            app.state::<Pomodoro>().load(app.handle());
+            app.state::<ShortcutManager>().init(app.handle());
+
+            // Auto-connect and keep the lights connected, off the main
+            // thread so a slow port doesn't hold up startup
+            app.state::<Supervisor>().start(app.handle());
 
-            // Auto-connect to serial port on launch
-            let handle = app.handle().clone();
-            let serial = app.state::<SerialManager>();
-            if let Some(port) = SerialManager::find_port() {
-                let _ = serial.connect(&port, handle);
-            }
+            app.state::<AutoExposure>().load(app.handle());
+            app.state::<AmbientLight>().load(app.handle());
+            app.state::<ScreenSync>().load(app.handle());
+            app.state::<CalendarAutomation>().load(app.handle());
+            focus::init(app.handle());
+            app.state::<NightShiftFollow>().load(app.handle());
+            app.state::<IdleDimmer>().load(app.handle());
+            app.state::<ThermalProtection>().load(app.handle());
+            app.state::<ProximityPresence>().load(app.handle());
+            app.state::<AppRules>().load(app.handle());
+            app.state::<MqttBridge>().load(app.handle());
+            app.state::<RfDongle>().load(app.handle());
+            app.state::<DmxOutput>().load(app.handle());
+            app.state::<HueBridge>().load(app.handle());
+            app.state::<WemoEmulation>().load(app.handle());
+            app.state::<PluginHost>().load(app.handle());
+            app.state::<PresetSync>().load(app.handle());
+            app.state::<ScriptHost>().init(app.handle());
 
             Ok(())
         })
@@ -73,5 +496,16 @@ pub fn run() {
     #[cfg(target_os = "macos")]
     app.set_activation_policy(tauri::ActivationPolicy::Accessory);
 
-    app.run(|_app_handle, _event| {});
+    app.run(|app_handle, event| match event {
+        tauri::RunEvent::ExitRequested { code, api, .. } => {
+            app_handle
+                .state::<QuitHandler>()
+                .on_exit_requested(app_handle, code, &api);
+        }
+        // Lights still connected never disconnect, so save their last states
+        tauri::RunEvent::Exit => {
+            let _ = app_handle.state::<DeviceRegistry>().save(app_handle);
+        }
+        _ => {}
+    });
 }
@@ -0,0 +1,114 @@
+/// Per-device brightness caps.
+///
+/// A light can be given a ceiling on its hardware brightness, enforced in the
+/// serial write path so no command, preset or automation can drive it higher,
+/// for shared setups and heat-sensitive environments. A write that gets
+/// clamped is published on the event bus as [`Event::BrightnessClamped`],
+/// which the frontend gets as "brightness-clamped". Caps are persisted under
+/// `brightness_caps` in the settings store, keyed by device id.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::events::{Event, EventBus};
+use crate::STORE_FILE;
+
+const CAPS_KEY: &str = "brightness_caps";
+
+/// Reported as "brightness-clamped" when a write exceeds a light's cap.
+#[derive(Debug, Clone, Serialize)]
+pub struct Clamped {
+    pub device: String,
+    /// Requested hardware brightness.
+    pub requested: u8,
+    /// Hardware brightness actually sent.
+    pub applied: u8,
+}
+
+pub struct BrightnessLimits {
+    caps: Mutex<BTreeMap<String, u8>>,
+}
+
+impl BrightnessLimits {
+    pub fn new() -> Self {
+        Self {
+            caps: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, u8> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(CAPS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.caps.lock().unwrap() = saved;
+    }
+
+    /// Configured caps by device id. Devices not listed are uncapped.
+    pub fn list(&self) -> BTreeMap<String, u8> {
+        self.caps.lock().unwrap().clone()
+    }
+
+    pub fn cap(&self, id: &str) -> Option<u8> {
+        self.caps.lock().unwrap().get(id).copied()
+    }
+
+    /// Set a device's cap (hardware brightness 1-100); `None` removes it.
+    pub fn set(&self, app: &AppHandle, id: &str, cap: Option<u8>) -> Result<(), String> {
+        let mut caps = self.caps.lock().unwrap();
+        match cap {
+            Some(cap) if !(1..=100).contains(&cap) => {
+                return Err("Brightness cap must be 1-100".into());
+            }
+            Some(cap) => {
+                caps.insert(id.to_string(), cap);
+            }
+            None => {
+                caps.remove(id);
+            }
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            CAPS_KEY,
+            serde_json::to_value(&*caps).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())
+    }
+
+    /// Limit a hardware brightness to the device's cap, reporting any clamp.
+    pub fn clamp(&self, app: &AppHandle, id: &str, brightness: u8) -> u8 {
+        let applied = clamp_to(self.cap(id), brightness);
+        if applied != brightness {
+            app.state::<EventBus>()
+                .publish(Event::BrightnessClamped(Clamped {
+                    device: id.to_string(),
+                    requested: brightness,
+                    applied,
+                }));
+        }
+        applied
+    }
+}
+
+fn clamp_to(cap: Option<u8>, brightness: u8) -> u8 {
+    cap.map_or(brightness, |cap| brightness.min(cap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamp_to() {
+        assert_eq!(clamp_to(None, 100), 100);
+        assert_eq!(clamp_to(Some(60), 100), 60);
+        assert_eq!(clamp_to(Some(60), 40), 40);
+        // Off stays off
+        assert_eq!(clamp_to(Some(60), 0), 0);
+    }
+}
@@ -0,0 +1,147 @@
+/// Master/follower linking.
+///
+/// A follower mirrors its master's state with fixed offsets (e.g. a fill light
+/// at key −30 brightness, +300K). The brightness offset is in slider levels,
+/// mapped through the follower's own dimming curve. Whenever a master reports
+/// a new status, its followers are updated. Links are persisted under `links`
+/// in the settings store, keyed by follower id; chains are allowed, cycles are
+/// rejected.
+/// Followers are written as the source of the master's last write (see
+/// `arbitration`), so they follow a manual change that holds them too.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::arbitration::Arbiter;
+use crate::curves::CurveManager;
+use crate::protocol::{self, KelvinRange};
+use crate::serial::{LightStatus, SerialManager};
+use crate::sessionlog::{self, Source};
+use crate::{errors, models, STORE_FILE};
+
+const LINKS_KEY: &str = "links";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Link {
+    pub follower: String,
+    pub master: String,
+    /// Added to the master's slider level, in percentage points.
+    pub brightness_offset: i32,
+    /// Added to the master's temperature, in Kelvin.
+    pub kelvin_offset: i32,
+}
+
+impl Link {
+    /// Follower (level, kelvin) for a given master slider level and
+    /// temperature, within the follower's temperature `range`.
+    pub fn apply(&self, level: u8, kelvin: u32, range: &KelvinRange) -> (u8, u32) {
+        let bri = (level as i32 + self.brightness_offset).clamp(0, 100) as u8;
+        let k = (kelvin as i64 + self.kelvin_offset as i64)
+            .clamp(range.min as i64, range.max as i64) as u32;
+        // An off master turns followers off regardless of offset
+        (if level == 0 { 0 } else { bri }, k)
+    }
+}
+
+pub struct LinkManager {
+    links: Mutex<BTreeMap<String, Link>>,
+}
+
+impl LinkManager {
+    pub fn new() -> Self {
+        Self {
+            links: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, Link> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(LINKS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.links.lock().unwrap() = saved;
+    }
+
+    pub fn list(&self) -> Vec<Link> {
+        self.links.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Link `follower` to `master`, replacing any existing link for it.
+    pub fn link(&self, app: &AppHandle, link: Link) -> Result<(), String> {
+        if link.follower == link.master {
+            return Err("A light can't follow itself".into());
+        }
+        let mut links = self.links.lock().unwrap();
+        // Walk up from the new master; reaching the follower means a cycle
+        let mut cursor = Some(link.master.as_str());
+        while let Some(id) = cursor {
+            if id == link.follower {
+                return Err(format!(
+                    "{} already follows {} (directly or through a chain)",
+                    link.master, link.follower
+                ));
+            }
+            cursor = links.get(id).map(|l| l.master.as_str());
+        }
+        links.insert(link.follower.clone(), link);
+        persist(app, &links)
+    }
+
+    pub fn unlink(&self, app: &AppHandle, follower: &str) -> Result<(), String> {
+        let mut links = self.links.lock().unwrap();
+        if links.remove(follower).is_none() {
+            return Err(format!("{follower} is not linked"));
+        }
+        persist(app, &links)
+    }
+
+    /// Push a master's new status to its followers.
+    pub fn on_status(&self, app: &AppHandle, status: &LightStatus) {
+        let followers: Vec<Link> = self
+            .links
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|l| l.master == status.device)
+            .cloned()
+            .collect();
+
+        let source = app
+            .state::<Arbiter>()
+            .last_source(&status.device)
+            .unwrap_or(Source::Automation);
+        let serial = app.state::<SerialManager>();
+        let curves = app.state::<CurveManager>();
+        for link in followers {
+            let range = models::range(app, &link.follower);
+            let (level, k) = link.apply(status.level, status.kelvin, &range);
+            let bri = curves.to_hw(&link.follower, level);
+            let current = serial.status_of(&link.follower);
+            // Skip followers already at the target state
+            if current.is_some_and(|s| {
+                s.brightness == bri
+                    && protocol::kelvin_to_byte(&range, s.kelvin)
+                        == protocol::kelvin_to_byte(&range, k)
+            }) {
+                continue;
+            }
+            let result =
+                sessionlog::with_source(source, || serial.set_cct_to(&link.follower, bri, k));
+            errors::check(app, "link", Some(&link.follower), result);
+        }
+    }
+}
+
+fn persist(app: &AppHandle, links: &BTreeMap<String, Link>) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        LINKS_KEY,
+        serde_json::to_value(links).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
@@ -0,0 +1,169 @@
+/// Kiosk / control lock.
+///
+/// While locked, changes from the panel, tray and shortcuts (`Manual` and
+/// `Preset` sources, see `sessionlog`) are rejected in the write path, and
+/// commands that start effects, timelines or macros refuse to run.
+/// Only the sources allowed when locking (automations and external APIs by
+/// default) can change the lights, for installations and live events. A lock
+/// can carry a PIN that's then needed to unlock. The PIN is a guard against
+/// accidental changes, not a credential, and is stored as entered. The lock is
+/// persisted under `control_lock` in the settings store, so it survives a
+/// restart; changes emit "lock-changed".
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_store::StoreExt;
+
+use crate::errors::{ErrorCode, LightError};
+use crate::sessionlog::Source;
+use crate::STORE_FILE;
+
+const LOCK_KEY: &str = "control_lock";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct LockState {
+    locked: bool,
+    pin: Option<String>,
+    /// Sources that may still change the lights while locked.
+    allowed: Vec<Source>,
+}
+
+impl Default for LockState {
+    fn default() -> Self {
+        Self {
+            locked: false,
+            pin: None,
+            allowed: vec![Source::Automation, Source::External],
+        }
+    }
+}
+
+impl LockState {
+    fn permits(&self, source: Source) -> bool {
+        !self.locked || self.allowed.contains(&source)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LockStatus {
+    pub locked: bool,
+    pub pin_protected: bool,
+    pub allowed: Vec<Source>,
+}
+
+impl LockStatus {
+    fn of(state: &LockState) -> Self {
+        Self {
+            locked: state.locked,
+            pin_protected: state.pin.is_some(),
+            allowed: state.allowed.clone(),
+        }
+    }
+}
+
+pub struct ControlLock {
+    state: Mutex<LockState>,
+}
+
+impl ControlLock {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(LockState::default()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: LockState = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(LOCK_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.state.lock().unwrap() = saved;
+    }
+
+    pub fn status(&self) -> LockStatus {
+        LockStatus::of(&self.state.lock().unwrap())
+    }
+
+    /// Fail if a change from `source` isn't allowed right now.
+    pub fn check(&self, source: Source) -> Result<(), LightError> {
+        if self.state.lock().unwrap().permits(source) {
+            Ok(())
+        } else {
+            Err(LightError::new(ErrorCode::Refused, "Controls are locked"))
+        }
+    }
+
+    /// Lock the controls, optionally behind a PIN. `allowed` defaults to
+    /// automations and external APIs.
+    pub fn lock(
+        &self,
+        app: &AppHandle,
+        pin: Option<String>,
+        allowed: Option<Vec<Source>>,
+    ) -> Result<LockStatus, String> {
+        let pin = pin.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+        if pin.as_ref().is_some_and(|p| p.len() > 32) {
+            return Err("PIN must be at most 32 characters".into());
+        }
+        let next = {
+            let state = self.state.lock().unwrap();
+            if state.locked {
+                return Err("Controls are already locked".into());
+            }
+            LockState {
+                locked: true,
+                pin,
+                allowed: allowed.unwrap_or_else(|| LockState::default().allowed),
+            }
+        };
+        self.replace(app, next)
+    }
+
+    pub fn unlock(&self, app: &AppHandle, pin: Option<String>) -> Result<LockStatus, String> {
+        {
+            let state = self.state.lock().unwrap();
+            if !state.locked {
+                return Ok(LockStatus::of(&state));
+            }
+            if let Some(expected) = &state.pin {
+                if pin.as_deref().map(str::trim) != Some(expected.as_str()) {
+                    return Err("Wrong PIN".into());
+                }
+            }
+        }
+        self.replace(app, LockState::default())
+    }
+
+    fn replace(&self, app: &AppHandle, next: LockState) -> Result<LockStatus, String> {
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            LOCK_KEY,
+            serde_json::to_value(&next).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        let status = LockStatus::of(&next);
+        *self.state.lock().unwrap() = next;
+        let _ = app.emit("lock-changed", &status);
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permits_only_allowed_sources_while_locked() {
+        let mut state = LockState::default();
+        assert!(state.permits(Source::Manual));
+        state.locked = true;
+        assert!(!state.permits(Source::Manual));
+        assert!(!state.permits(Source::Preset));
+        assert!(state.permits(Source::Automation));
+        assert!(state.permits(Source::External));
+    }
+}
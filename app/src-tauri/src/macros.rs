@@ -0,0 +1,250 @@
+/// Macro recorder.
+///
+/// While recording, every change a light reports is captured with its offset
+/// from the start of the recording. Saved macros replay those changes with the
+/// same timing on the same devices — repeatable lighting moves for product
+/// shots. Macros are persisted under `macros` in the settings store.
+use std::collections::BTreeMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::serial::{LightStatus, SerialManager};
+use crate::sessionlog::{self, Source};
+use crate::{errors, STORE_FILE};
+
+const MACROS_KEY: &str = "macros";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroStep {
+    /// Offset from the start of the macro, in ms.
+    pub at_ms: u64,
+    pub device: String,
+    pub brightness: u8,
+    pub kelvin: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+}
+
+impl Macro {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Macro name cannot be empty".into());
+        }
+        if self.steps.windows(2).any(|w| w[1].at_ms < w[0].at_ms) {
+            return Err(format!("{}: steps must be in time order", self.name));
+        }
+        if self.steps.iter().any(|s| s.brightness > 100) {
+            return Err(format!("{}: brightness must be 0-100", self.name));
+        }
+        Ok(())
+    }
+}
+
+struct Recording {
+    started: Instant,
+    steps: Vec<MacroStep>,
+}
+
+impl Recording {
+    /// Whether `status` differs from the last step for its device. Reported
+    /// temperatures are always whole hardware steps, so they compare exactly.
+    fn is_change(&self, status: &LightStatus) -> bool {
+        self.steps
+            .iter()
+            .rev()
+            .find(|s| s.device == status.device)
+            .is_none_or(|s| s.brightness != status.brightness || s.kelvin != status.kelvin)
+    }
+}
+
+pub struct MacroRecorder {
+    macros: Mutex<BTreeMap<String, Macro>>,
+    recording: Mutex<Option<Recording>>,
+    /// Bumped by every play/stop; a playback thread exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl MacroRecorder {
+    pub fn new() -> Self {
+        Self {
+            macros: Mutex::new(BTreeMap::new()),
+            recording: Mutex::new(None),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, Macro> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(MACROS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.macros.lock().unwrap() = saved;
+    }
+
+    pub fn list(&self) -> Vec<Macro> {
+        self.macros.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Start capturing light changes. The current state of each light is
+    /// recorded as the first step so playback starts from the same setup.
+    pub fn start_recording(&self, app: &AppHandle) -> Result<(), String> {
+        let mut recording = self.recording.lock().unwrap();
+        if recording.is_some() {
+            return Err("Already recording".into());
+        }
+        let serial = app.state::<SerialManager>();
+        let steps = serial
+            .ids()
+            .iter()
+            .filter_map(|id| serial.status_of(id))
+            .map(|s| MacroStep {
+                at_ms: 0,
+                device: s.device,
+                brightness: s.brightness,
+                kelvin: s.kelvin,
+            })
+            .collect();
+        *recording = Some(Recording {
+            started: Instant::now(),
+            steps,
+        });
+        let _ = app.emit("macro-recording", true);
+        Ok(())
+    }
+
+    /// Stop recording and save the capture as `name`, replacing any macro
+    /// with that name.
+    pub fn stop_recording(&self, app: &AppHandle, name: &str) -> Result<Macro, String> {
+        if name.trim().is_empty() {
+            return Err("Macro name cannot be empty".into());
+        }
+        let recording = self
+            .recording
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or("Not recording")?;
+        let _ = app.emit("macro-recording", false);
+        if recording.steps.is_empty() {
+            return Err("No light changes were recorded".into());
+        }
+
+        let mac = Macro {
+            name: name.to_string(),
+            steps: recording.steps,
+        };
+        let mut macros = self.macros.lock().unwrap();
+        macros.insert(mac.name.clone(), mac.clone());
+        persist(app, &macros)?;
+        Ok(mac)
+    }
+
+    /// Create or replace a macro, e.g. one imported from another machine.
+    pub fn save(&self, app: &AppHandle, mac: Macro) -> Result<(), String> {
+        mac.validate()?;
+        let mut macros = self.macros.lock().unwrap();
+        macros.insert(mac.name.clone(), mac);
+        persist(app, &macros)
+    }
+
+    /// Discard the current recording.
+    pub fn cancel_recording(&self, app: &AppHandle) {
+        if self.recording.lock().unwrap().take().is_some() {
+            let _ = app.emit("macro-recording", false);
+        }
+    }
+
+    /// Capture a reported status while recording.
+    pub fn on_status(&self, status: &LightStatus) {
+        let mut recording = self.recording.lock().unwrap();
+        let Some(rec) = recording.as_mut() else {
+            return;
+        };
+        if rec.is_change(status) {
+            rec.steps.push(MacroStep {
+                at_ms: rec.started.elapsed().as_millis() as u64,
+                device: status.device.clone(),
+                brightness: status.brightness,
+                kelvin: status.kelvin,
+            });
+        }
+    }
+
+    pub fn delete(&self, app: &AppHandle, name: &str) -> Result<(), String> {
+        let mut macros = self.macros.lock().unwrap();
+        if macros.remove(name).is_none() {
+            return Err(format!("No macro named {name}"));
+        }
+        persist(app, &macros)
+    }
+
+    /// Replay a macro, replacing any macro already playing. Steps for lights
+    /// that are no longer connected are skipped.
+    pub fn play(&self, app: &AppHandle, name: &str) -> Result<(), String> {
+        let mac = self
+            .macros
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("No macro named {name}"))?;
+
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || {
+            sessionlog::set_source(Source::Automation);
+            let started = Instant::now();
+            for step in &mac.steps {
+                let at = Duration::from_millis(step.at_ms);
+                while started.elapsed() < at {
+                    if current.load(Ordering::SeqCst) != gen {
+                        return;
+                    }
+                    let left = at.saturating_sub(started.elapsed());
+                    std::thread::sleep(left.min(Duration::from_millis(50)));
+                }
+                if current.load(Ordering::SeqCst) != gen {
+                    return;
+                }
+                let result = app.state::<SerialManager>().set_cct_to(
+                    &step.device,
+                    step.brightness,
+                    step.kelvin,
+                );
+                errors::check(&app, "macro", Some(&step.device), result);
+            }
+            if current.load(Ordering::SeqCst) == gen {
+                let _ = app.emit("macro-finished", &mac.name);
+            }
+        });
+        Ok(())
+    }
+
+    /// Stop a playing macro, leaving the lights where they are.
+    pub fn stop(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn persist(app: &AppHandle, macros: &BTreeMap<String, Macro>) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        MACROS_KEY,
+        serde_json::to_value(macros).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
@@ -0,0 +1,259 @@
+/// Cross-light colour matching.
+///
+/// Two lights set to the same temperature and brightness rarely look the
+/// same. Each light can be given a matching offset, a temperature shift in
+/// Kelvin and a brightness trim in percent of its output, found once by
+/// eye. Offsets are applied in the serial write path to every CCT command,
+/// however it was sent: the request is first brought within the light's
+/// temperature range and brightness cap, then offset, then kept within them
+/// again. The request is remembered per light, so while the light reports
+/// what was sent for it, its status shows the request rather than the
+/// offset value; a report of anything else (the light's own controls) has
+/// the offset taken out instead. The panel, presets and automations so keep
+/// working in the values asked for and a group of matched lights can be set
+/// together. Offsets are persisted under `color_offsets` in the settings
+/// store, keyed by device id; requests aren't.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::limits::BrightnessLimits;
+use crate::models;
+use crate::protocol::KelvinRange;
+use crate::serial::SerialManager;
+use crate::STORE_FILE;
+
+const OFFSETS_KEY: &str = "color_offsets";
+/// Largest temperature shift accepted, either way.
+const MAX_KELVIN_SHIFT: i32 = 1500;
+/// Largest brightness trim accepted, either way, in percent.
+const MAX_TRIM: i8 = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Offset {
+    /// Added to every temperature sent.
+    pub kelvin: i32,
+    /// Percent added to (or taken from) every brightness sent.
+    pub brightness: i8,
+}
+
+impl Offset {
+    fn validate(&self) -> Result<(), String> {
+        if self.kelvin.abs() > MAX_KELVIN_SHIFT {
+            return Err(format!(
+                "Temperature offset must be within ±{MAX_KELVIN_SHIFT}K"
+            ));
+        }
+        if self.brightness.abs() > MAX_TRIM {
+            return Err(format!("Brightness trim must be within ±{MAX_TRIM}%"));
+        }
+        Ok(())
+    }
+
+    /// The (brightness, kelvin) to send for a requested one already within
+    /// `range` and `cap`, kept within them. A light that's off stays off and
+    /// one that's on stays on.
+    fn apply(self, (brightness, kelvin): (u8, u32), range: &KelvinRange, cap: u8) -> (u8, u32) {
+        let scale = (100 + self.brightness as i32) as f64 / 100.0;
+        (
+            trim(brightness, scale).min(cap.max(1)),
+            range.clamp(kelvin.saturating_add_signed(self.kelvin)),
+        )
+    }
+
+    /// The request a reported (brightness, kelvin) most likely came from.
+    fn remove(self, (brightness, kelvin): (u8, u32), range: &KelvinRange) -> (u8, u32) {
+        let scale = 100.0 / (100 + self.brightness as i32) as f64;
+        (
+            trim(brightness, scale),
+            range.clamp(kelvin.saturating_add_signed(-self.kelvin)),
+        )
+    }
+}
+
+fn trim(brightness: u8, scale: f64) -> u8 {
+    if brightness == 0 {
+        return 0;
+    }
+    (brightness as f64 * scale).round().clamp(1.0, 100.0) as u8
+}
+
+/// A request to one light and what was sent for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sent {
+    requested: (u8, u32),
+    sent: (u8, u32),
+}
+
+impl Sent {
+    /// Whether `reported` is what the light shows for `self.sent`: the same
+    /// brightness, and a temperature within half a hardware step.
+    fn matches(&self, reported: (u8, u32), range: &KelvinRange) -> bool {
+        let half_step = (range.max - range.min) / range.steps.max(1) / 2 + 1;
+        reported.0 == self.sent.0 && reported.1.abs_diff(self.sent.1) <= half_step
+    }
+}
+
+/// The requested (brightness, kelvin) for one a light reports.
+fn requested(
+    offset: Offset,
+    sent: Option<&Sent>,
+    reported: (u8, u32),
+    range: &KelvinRange,
+) -> (u8, u32) {
+    match sent {
+        Some(sent) if sent.matches(reported, range) => sent.requested,
+        _ => offset.remove(reported, range),
+    }
+}
+
+pub struct ColorMatching {
+    offsets: Mutex<BTreeMap<String, Offset>>,
+    /// The last request to each light with an offset.
+    sent: Mutex<HashMap<String, Sent>>,
+}
+
+impl ColorMatching {
+    pub fn new() -> Self {
+        Self {
+            offsets: Mutex::new(BTreeMap::new()),
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, Offset> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(OFFSETS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.offsets.lock().unwrap() = saved;
+    }
+
+    /// Configured offsets by device id. Devices not listed have none.
+    pub fn list(&self) -> BTreeMap<String, Offset> {
+        self.offsets.lock().unwrap().clone()
+    }
+
+    fn offset(&self, id: &str) -> Option<Offset> {
+        self.offsets.lock().unwrap().get(id).copied()
+    }
+
+    /// The (brightness, kelvin) to send light `id` for a request already
+    /// within its temperature range and brightness cap.
+    pub fn apply(&self, app: &AppHandle, id: &str, brightness: u8, kelvin: u32) -> (u8, u32) {
+        let Some(offset) = self.offset(id) else {
+            return (brightness, kelvin);
+        };
+        let range = models::range(app, id);
+        let cap = app.state::<BrightnessLimits>().cap(id).unwrap_or(100);
+        let sent = offset.apply((brightness, kelvin), &range, cap);
+        self.sent.lock().unwrap().insert(
+            id.to_string(),
+            Sent {
+                requested: (brightness, kelvin),
+                sent,
+            },
+        );
+        sent
+    }
+
+    /// The requested (brightness, kelvin) for one light `id` reports.
+    pub fn requested(&self, app: &AppHandle, id: &str, brightness: u8, kelvin: u32) -> (u8, u32) {
+        let Some(offset) = self.offset(id) else {
+            return (brightness, kelvin);
+        };
+        let sent = self.sent.lock().unwrap().get(id).copied();
+        requested(
+            offset,
+            sent.as_ref(),
+            (brightness, kelvin),
+            &models::range(app, id),
+        )
+    }
+
+    /// Set a device's offset; `None` removes it. A connected light is sent
+    /// its current state again so the change shows at once.
+    pub fn set(&self, app: &AppHandle, id: &str, offset: Option<Offset>) -> Result<(), String> {
+        {
+            let mut offsets = self.offsets.lock().unwrap();
+            match offset {
+                Some(offset) if offset != Offset::default() => {
+                    offset.validate()?;
+                    offsets.insert(id.to_string(), offset);
+                }
+                _ => {
+                    offsets.remove(id);
+                }
+            }
+            let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+            store.set(
+                OFFSETS_KEY,
+                serde_json::to_value(&*offsets).map_err(|e| e.to_string())?,
+            );
+            store.save().map_err(|e| e.to_string())?;
+        }
+        self.sent.lock().unwrap().remove(id);
+        let serial = app.state::<SerialManager>();
+        if let Some(status) = serial.status_of(id) {
+            serial.set_cct_to(id, status.brightness, status.kelvin)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BICOLOR: KelvinRange = KelvinRange {
+        min: 3200,
+        max: 5600,
+        steps: 24,
+    };
+
+    #[test]
+    fn test_offset_applies_within_range_and_cap() {
+        let offset = Offset {
+            kelvin: -200,
+            brightness: -10,
+        };
+        assert_eq!(offset.apply((50, 5600), &BICOLOR, 100), (45, 5400));
+        // Off stays off and on stays on
+        assert_eq!(offset.apply((0, 5600), &BICOLOR, 100).0, 0);
+        assert_eq!(offset.apply((1, 5600), &BICOLOR, 100).0, 1);
+        let brighter = Offset {
+            kelvin: 300,
+            brightness: 10,
+        };
+        assert_eq!(brighter.apply((100, 5600), &BICOLOR, 100), (100, 5600));
+        assert_eq!(brighter.apply((60, 4000), &BICOLOR, 60), (60, 4300));
+    }
+
+    #[test]
+    fn test_reports_at_the_range_edge_show_the_request() {
+        let offset = Offset {
+            kelvin: 300,
+            brightness: 10,
+        };
+        let sent = Sent {
+            requested: (100, 5600),
+            sent: offset.apply((100, 5600), &BICOLOR, 100),
+        };
+        assert_eq!(
+            requested(offset, Some(&sent), (100, 5600), &BICOLOR),
+            (100, 5600)
+        );
+        // A change on the light itself has the offset taken out
+        assert_eq!(
+            requested(offset, Some(&sent), (55, 4300), &BICOLOR),
+            (50, 4000)
+        );
+        assert_eq!(requested(offset, None, (55, 4300), &BICOLOR), (50, 4000));
+    }
+}
@@ -0,0 +1,130 @@
+/// Settings store schema versions and migrations.
+///
+/// The settings store records the version of its layout under
+/// `schema_version`; stores from before versioning count as version 0. At
+/// launch, before anything reads the store, `run` brings an older store up
+/// to [`CURRENT`] by applying each pending migration in turn to the stored
+/// values, after copying them to `settings.v{version}.json` in the app data
+/// directory so nothing is lost if a migration goes wrong. A store saved by
+/// a newer version is left as it is and the user told, rather than being
+/// rewritten in a layout that would drop what this version doesn't know.
+///
+/// A change to a stored layout (a renamed key, a field that changes type, a
+/// setting split per device) comes with a migration appended to
+/// `MIGRATIONS`, so the loaders only ever see the current layout.
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::{notify, STORE_FILE};
+
+pub const VERSION_KEY: &str = "schema_version";
+
+pub type Values = BTreeMap<String, Value>;
+
+/// `MIGRATIONS[n]` takes the stored values from version n to n + 1.
+const MIGRATIONS: &[fn(&mut Values)] = &[auto_connect_mode];
+
+/// Version of the store layout this build reads and writes.
+pub const CURRENT: u32 = MIGRATIONS.len() as u32;
+
+/// Version 1: `config.auto_connect` went from on/off to a mode.
+fn auto_connect_mode(values: &mut Values) {
+    let Some(config) = values.get_mut("config").and_then(Value::as_object_mut) else {
+        return;
+    };
+    if let Some(on) = config.get("auto_connect").and_then(Value::as_bool) {
+        let mode = if on { "first" } else { "off" };
+        config.insert("auto_connect".into(), json!(mode));
+    }
+}
+
+/// Apply the migrations after `version` to `values`.
+pub fn migrate(values: &mut Values, version: u32) {
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(values);
+    }
+}
+
+/// Bring the settings store up to the current layout. Called before any
+/// manager loads; a failure is notified and leaves the store as it was.
+pub fn run(app: &AppHandle) {
+    if let Err(e) = upgrade(app) {
+        notify::error(app, "Settings couldn't be upgraded", &e);
+    }
+}
+
+fn upgrade(app: &AppHandle) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    let version = store.get(VERSION_KEY).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > CURRENT {
+        notify::error(
+            app,
+            "Settings are from a newer version",
+            &format!("Saved in format {version}, this app reads up to {CURRENT}"),
+        );
+        return Ok(());
+    }
+    if version == CURRENT {
+        return Ok(());
+    }
+
+    let before: Values = store.entries().into_iter().collect();
+    if !before.is_empty() {
+        backup(app, version, &before)?;
+    }
+    let mut values = before.clone();
+    migrate(&mut values, version);
+    for key in before.keys().filter(|key| !values.contains_key(*key)) {
+        store.delete(key);
+    }
+    for (key, value) in values {
+        store.set(key, value);
+    }
+    store.set(VERSION_KEY, json!(CURRENT));
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Copy the stored values of a version-`version` store to a file of their
+/// own.
+fn backup(app: &AppHandle, version: u32, values: &Values) -> Result<(), String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(values).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join(format!("settings.v{version}.json")), json).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_from_unversioned() {
+        let mut values = Values::from([
+            (
+                "config".to_string(),
+                json!({ "auto_connect": true, "notifications": false }),
+            ),
+            ("presets".to_string(), json!([])),
+        ]);
+        migrate(&mut values, 0);
+        assert_eq!(
+            values["config"],
+            json!({ "auto_connect": "first", "notifications": false })
+        );
+        assert_eq!(values["presets"], json!([]));
+
+        let mut values = Values::from([("config".to_string(), json!({ "auto_connect": false }))]);
+        migrate(&mut values, 0);
+        assert_eq!(values["config"]["auto_connect"], "off");
+    }
+
+    #[test]
+    fn test_current_store_is_unchanged() {
+        let mut values = Values::from([("config".to_string(), json!({ "auto_connect": "all" }))]);
+        let before = values.clone();
+        migrate(&mut values, CURRENT);
+        assert_eq!(values, before);
+    }
+}
@@ -0,0 +1,405 @@
+/// Light models and what each can do.
+///
+/// Nothing a light sends over serial says which model it is, so every device
+/// has a model assigned: the PL81-Pro, the only model confirmed over USB,
+/// unless the user picks another. A model's profile lists the command format
+/// it speaks, its temperature range and which optional features it has
+/// (green/magenta shift, HSI colour, built-in scenes, a dedicated power
+/// command); `capabilities` reports these so the frontend and external APIs
+/// can adapt rather than guess.
+///
+/// Writes are held to the profile too: temperatures are brought within the
+/// model's range, power goes through the power command only on models that
+/// have one, and a command for a feature the model lacks is refused with an
+/// `Unsupported` error starting [`UNSUPPORTED`] rather than sent for the
+/// firmware to ignore or misread.
+///
+/// A light whose temperature range differs from its model's (some units of
+/// the same model run 3200-5600K) can have its own range and step count set
+/// instead; every temperature written to or read from that light is then
+/// converted with it. Likewise the format a light answered in when it was
+/// last connected (see `serial`) takes precedence over its model's, so
+/// users needn't know which firmware generation they have. Assignments are
+/// persisted under `device_models`, range overrides under
+/// `device_kelvin_ranges` and detected formats under `device_formats` in the
+/// settings store.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::errors::{ErrorCode, LightError};
+use crate::protocol::{self, Format, KelvinRange};
+use crate::STORE_FILE;
+
+const MODELS_KEY: &str = "device_models";
+const RANGES_KEY: &str = "device_kelvin_ranges";
+const FORMATS_KEY: &str = "device_formats";
+
+/// Start of the message for a command a light's model can't carry out:
+/// "unsupported: ...".
+pub const UNSUPPORTED: &str = "unsupported";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Model {
+    #[default]
+    Pl81Pro,
+    /// Bi-color lights using the BLE-era (legacy) command set.
+    Bicolor,
+    /// RGB lights using the BLE-era (legacy) command set.
+    Rgb,
+}
+
+/// What a model supports.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Profile {
+    pub name: &'static str,
+    pub format: Format,
+    pub kelvin_min: u32,
+    pub kelvin_max: u32,
+    /// Hardware temperature steps from `kelvin_min` to `kelvin_max`.
+    pub kelvin_steps: u32,
+    /// Green/magenta shift.
+    pub gm: bool,
+    /// Hue/saturation colour.
+    pub hsi: bool,
+    /// Built-in effect scenes.
+    pub scenes: bool,
+    /// A power command; without one, off is brightness 0.
+    pub power: bool,
+}
+
+const PL81_PRO: Profile = Profile {
+    name: "Neewer PL81-Pro",
+    format: Format::Standard,
+    kelvin_min: protocol::TEMP_MIN_K,
+    kelvin_max: protocol::TEMP_MAX_K,
+    kelvin_steps: protocol::TEMP_STEPS,
+    gm: false,
+    hsi: false,
+    scenes: false,
+    power: false,
+};
+
+/// Temperature bytes 0x20-0x38 cover 3200-5600K (see RESEARCH.md).
+const BICOLOR: Profile = Profile {
+    name: "Bi-color light",
+    format: Format::Legacy,
+    kelvin_min: 3200,
+    kelvin_max: 5600,
+    kelvin_steps: 0x38 - 0x20,
+    gm: false,
+    hsi: false,
+    scenes: false,
+    power: true,
+};
+
+const RGB: Profile = Profile {
+    name: "RGB light",
+    gm: true,
+    hsi: true,
+    scenes: true,
+    ..BICOLOR
+};
+
+/// An optional feature of a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Gm,
+    Hsi,
+    Scenes,
+    Power,
+}
+
+impl Feature {
+    fn describe(self) -> &'static str {
+        match self {
+            Feature::Gm => "green/magenta shift",
+            Feature::Hsi => "HSI colour",
+            Feature::Scenes => "built-in scenes",
+            Feature::Power => "power command",
+        }
+    }
+}
+
+impl Profile {
+    pub fn supports(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::Gm => self.gm,
+            Feature::Hsi => self.hsi,
+            Feature::Scenes => self.scenes,
+            Feature::Power => self.power,
+        }
+    }
+
+    pub fn range(&self) -> KelvinRange {
+        KelvinRange {
+            min: self.kelvin_min,
+            max: self.kelvin_max,
+            steps: self.kelvin_steps,
+        }
+    }
+}
+
+/// A temperature range for one light, replacing its model's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangeOverride {
+    pub kelvin_min: u32,
+    pub kelvin_max: u32,
+    /// Hardware temperature steps from `kelvin_min` to `kelvin_max`.
+    pub kelvin_steps: u32,
+}
+
+impl RangeOverride {
+    fn validate(&self) -> Result<(), String> {
+        if !(1000..=20000).contains(&self.kelvin_min) || !(1000..=20000).contains(&self.kelvin_max)
+        {
+            return Err("Temperatures must be 1000-20000K".into());
+        }
+        if self.kelvin_min >= self.kelvin_max {
+            return Err("The minimum temperature must be below the maximum".into());
+        }
+        if !(1..=u8::MAX as u32).contains(&self.kelvin_steps) {
+            return Err(format!("Steps must be 1-{}", u8::MAX));
+        }
+        Ok(())
+    }
+}
+
+impl Model {
+    pub fn profile(self) -> &'static Profile {
+        match self {
+            Model::Pl81Pro => &PL81_PRO,
+            Model::Bicolor => &BICOLOR,
+            Model::Rgb => &RGB,
+        }
+    }
+}
+
+/// A device's model and what it supports.
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub device: String,
+    pub model: Model,
+    /// With the temperature range in use, the override's if any.
+    #[serde(flatten)]
+    pub profile: Profile,
+    /// Whether the temperature range is the user's rather than the model's.
+    pub custom_range: bool,
+}
+
+pub struct DeviceModels {
+    models: Mutex<BTreeMap<String, Model>>,
+    ranges: Mutex<BTreeMap<String, RangeOverride>>,
+    /// Formats detected on connect.
+    formats: Mutex<BTreeMap<String, Format>>,
+}
+
+impl DeviceModels {
+    pub fn new() -> Self {
+        Self {
+            models: Mutex::new(BTreeMap::new()),
+            ranges: Mutex::new(BTreeMap::new()),
+            formats: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let store = app.store(STORE_FILE).ok();
+        let saved: BTreeMap<String, Model> = store
+            .as_ref()
+            .and_then(|store| store.get(MODELS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.models.lock().unwrap() = saved;
+        let ranges: BTreeMap<String, RangeOverride> = store
+            .as_ref()
+            .and_then(|store| store.get(RANGES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.ranges.lock().unwrap() = ranges;
+        let formats: BTreeMap<String, Format> = store
+            .and_then(|store| store.get(FORMATS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.formats.lock().unwrap() = formats;
+    }
+
+    /// The model assigned to `id`, or the default.
+    pub fn model(&self, id: &str) -> Model {
+        self.models
+            .lock()
+            .unwrap()
+            .get(id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn capabilities(&self, id: &str) -> Capabilities {
+        let model = self.model(id);
+        let custom = self.ranges.lock().unwrap().get(id).copied();
+        let mut profile = *model.profile();
+        profile.format = self.format(id);
+        if let Some(custom) = custom {
+            profile.kelvin_min = custom.kelvin_min;
+            profile.kelvin_max = custom.kelvin_max;
+            profile.kelvin_steps = custom.kelvin_steps;
+        }
+        Capabilities {
+            device: id.to_string(),
+            model,
+            profile,
+            custom_range: custom.is_some(),
+        }
+    }
+
+    /// The command format `id` speaks: as detected, or its model's.
+    pub fn format(&self, id: &str) -> Format {
+        self.detected(id)
+            .unwrap_or_else(|| self.model(id).profile().format)
+    }
+
+    /// The format `id` answered in when last connected, if it has been.
+    pub fn detected(&self, id: &str) -> Option<Format> {
+        self.formats.lock().unwrap().get(id).copied()
+    }
+
+    /// Record the format `id` answered in.
+    pub fn detect(&self, app: &AppHandle, id: &str, format: Format) -> Result<(), String> {
+        let mut formats = self.formats.lock().unwrap();
+        if formats.insert(id.to_string(), format) == Some(format) {
+            return Ok(());
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            FORMATS_KEY,
+            serde_json::to_value(&*formats).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())
+    }
+
+    /// The temperature range of `id`: its override, or its model's.
+    pub fn range(&self, id: &str) -> KelvinRange {
+        self.capabilities(id).profile.range()
+    }
+
+    /// Ok if `id`'s model has `feature`, otherwise an [`UNSUPPORTED`] error.
+    pub fn require(&self, id: &str, feature: Feature) -> Result<(), LightError> {
+        check(id, self.model(id).profile(), feature)
+    }
+
+    /// Assign a model to a device.
+    pub fn set(&self, app: &AppHandle, id: &str, model: Model) -> Result<Capabilities, String> {
+        if id.trim().is_empty() {
+            return Err("Device id is required".into());
+        }
+        let mut models = self.models.lock().unwrap();
+        models.insert(id.to_string(), model);
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            MODELS_KEY,
+            serde_json::to_value(&*models).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        drop(models);
+        Ok(self.capabilities(id))
+    }
+
+    /// Give a device its own temperature range, or with `None` go back to
+    /// its model's.
+    pub fn set_range(
+        &self,
+        app: &AppHandle,
+        id: &str,
+        range: Option<RangeOverride>,
+    ) -> Result<Capabilities, String> {
+        if id.trim().is_empty() {
+            return Err("Device id is required".into());
+        }
+        if let Some(range) = &range {
+            range.validate()?;
+        }
+        let mut ranges = self.ranges.lock().unwrap();
+        match range {
+            Some(range) => ranges.insert(id.to_string(), range),
+            None => ranges.remove(id),
+        };
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            RANGES_KEY,
+            serde_json::to_value(&*ranges).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        drop(ranges);
+        Ok(self.capabilities(id))
+    }
+}
+
+/// The temperature range of light `id`.
+pub fn range(app: &AppHandle, id: &str) -> KelvinRange {
+    app.state::<DeviceModels>().range(id)
+}
+
+/// The command format light `id` speaks.
+pub fn format(app: &AppHandle, id: &str) -> Format {
+    app.state::<DeviceModels>().format(id)
+}
+
+fn check(id: &str, profile: &Profile, feature: Feature) -> Result<(), LightError> {
+    if profile.supports(feature) {
+        return Ok(());
+    }
+    Err(LightError::new(
+        ErrorCode::Unsupported,
+        format!(
+            "{UNSUPPORTED}: {id} is a {}, which has no {}",
+            profile.name,
+            feature.describe()
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_are_consistent() {
+        for model in [Model::Pl81Pro, Model::Bicolor, Model::Rgb] {
+            let p = model.profile();
+            assert!(p.kelvin_min < p.kelvin_max, "{}", p.name);
+            assert!(p.kelvin_steps > 0, "{}", p.name);
+        }
+        // The default is what the protocol module speaks
+        let p = Model::default().profile();
+        assert_eq!(p.range(), protocol::DEFAULT_RANGE);
+        assert_eq!(p.format, Format::Standard);
+        assert!(!p.hsi);
+    }
+
+    #[test]
+    fn test_unsupported_features_are_refused() {
+        let err = check("key", Model::Bicolor.profile(), Feature::Hsi).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Unsupported);
+        assert!(err.message.starts_with(UNSUPPORTED), "{err}");
+        assert!(check("key", Model::Rgb.profile(), Feature::Hsi).is_ok());
+        assert_eq!(Model::Bicolor.profile().range().clamp(7000), 5600);
+    }
+
+    #[test]
+    fn test_range_overrides_are_validated() {
+        let range = |kelvin_min, kelvin_max, kelvin_steps| RangeOverride {
+            kelvin_min,
+            kelvin_max,
+            kelvin_steps,
+        };
+        assert!(range(3200, 5600, 24).validate().is_ok());
+        assert!(range(5600, 3200, 24).validate().is_err());
+        assert!(range(3200, 5600, 0).validate().is_err());
+        assert!(range(3200, 5600, 300).validate().is_err());
+        assert!(range(100, 5600, 24).validate().is_err());
+    }
+}
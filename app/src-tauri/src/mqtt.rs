@@ -0,0 +1,326 @@
+/// Plain MQTT client mode.
+///
+/// When enabled, the app connects to a broker and, under a configurable base
+/// topic, publishes each light's status as JSON and accepts JSON commands,
+/// without any Home Assistant conventions, for Node-RED and custom setups:
+///
+/// - `<base>/availability`: "online", or "offline" (retained, also the will)
+/// - `<base>/<device>/state`: the light's status, as in "light-status"
+/// - `<base>/<device>/set`, `<base>/set` (all lights): a `SetCommand`
+/// - `<base>/error`: why a command failed
+///
+/// Device ids are used as topic levels with `/`, `+` and `#` replaced by `_`.
+/// Configuration is persisted under `mqtt` in the settings store.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::fade::{self, MAX_TRANSITION_MS};
+use crate::groups::{self, Target};
+use crate::serial::{LightStatus, SerialManager};
+use crate::sessionlog::{self, Source};
+use crate::{notify, presets, STORE_FILE};
+
+const MQTT_KEY: &str = "mqtt";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub base_topic: String,
+    /// Publish state messages retained.
+    pub retain: bool,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "localhost".into(),
+            port: 1883,
+            client_id: "neewer-usb-control".into(),
+            username: None,
+            password: None,
+            base_topic: "neewer".into(),
+            retain: true,
+        }
+    }
+}
+
+impl MqttConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.host.trim().is_empty() || self.client_id.trim().is_empty() {
+            return Err("MQTT host and client id are required".into());
+        }
+        let base = self.base_topic.trim_matches('/');
+        if base.is_empty() || base.contains(['+', '#']) {
+            return Err("MQTT base topic must be non-empty, without wildcards".into());
+        }
+        Ok(())
+    }
+
+    fn topic(&self, suffix: &str) -> String {
+        format!("{}/{suffix}", self.base_topic.trim_matches('/'))
+    }
+}
+
+/// Payload of a `set` message. Unset fields keep the light's current value;
+/// `preset` takes precedence over the others.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SetCommand {
+    pub on: Option<bool>,
+    /// Slider level 0-100.
+    pub level: Option<u8>,
+    pub kelvin: Option<u32>,
+    /// Index of a saved preset.
+    pub preset: Option<usize>,
+    /// Fade to the new level and temperature over this long, in ms.
+    pub transition_ms: Option<u64>,
+}
+
+pub struct MqttBridge {
+    config: Mutex<MqttConfig>,
+    /// Connected client and its availability topic.
+    client: Mutex<Option<(Client, String)>>,
+    /// Bumped on every reconfigure; the event loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl MqttBridge {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(MqttConfig::default()),
+            client: Mutex::new(None),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and connect if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: MqttConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(MQTT_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> MqttConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: MqttConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            MQTT_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    /// Publish a light's new status, if connected.
+    pub fn on_status(&self, status: &LightStatus) {
+        let Some((client, _)) = self.client.lock().unwrap().clone() else {
+            return;
+        };
+        let config = self.get();
+        let Ok(payload) = serde_json::to_vec(status) else {
+            return;
+        };
+        let topic = config.topic(&format!("{}/state", topic_id(&status.device)));
+        // Never block the other network outputs on a slow broker
+        let _ = client.try_publish(topic, QoS::AtMostOnce, config.retain, payload);
+    }
+
+    fn restart(&self, app: &AppHandle, config: MqttConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some((old, availability)) = self.client.lock().unwrap().take() {
+            let _ = old.try_publish(availability, QoS::AtLeastOnce, true, "offline");
+            let _ = old.try_disconnect();
+        }
+        if !config.enabled {
+            return;
+        }
+
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(user) = &config.username {
+            options.set_credentials(user, config.password.clone().unwrap_or_default());
+        }
+        options.set_last_will(LastWill::new(
+            config.topic("availability"),
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        let (client, connection) = Client::new(options, 64);
+        *self.client.lock().unwrap() = Some((client.clone(), config.topic("availability")));
+
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, client, connection, current, gen));
+    }
+}
+
+fn run(
+    app: AppHandle,
+    config: MqttConfig,
+    client: Client,
+    mut connection: rumqttc::Connection,
+    current: Arc<AtomicU64>,
+    gen: u64,
+) {
+    sessionlog::set_source(Source::External);
+    let mut failing = false;
+    for event in connection.iter() {
+        if current.load(Ordering::SeqCst) != gen {
+            return;
+        }
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                failing = false;
+                // Subscriptions don't survive a reconnect with a clean session
+                let _ = client.try_subscribe(config.topic("set"), QoS::AtLeastOnce);
+                let _ = client.try_subscribe(config.topic("+/set"), QoS::AtLeastOnce);
+                let _ = client.try_publish(
+                    config.topic("availability"),
+                    QoS::AtLeastOnce,
+                    true,
+                    "online",
+                );
+                let serial = app.state::<SerialManager>();
+                for status in serial.ids().iter().filter_map(|id| serial.status_of(id)) {
+                    app.state::<MqttBridge>().on_status(&status);
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let result = target_for(&app, &config, &publish.topic)
+                    .ok_or_else(|| format!("Unknown device in {}", publish.topic))
+                    .and_then(|target| {
+                        let command: SetCommand = serde_json::from_slice(&publish.payload)
+                            .map_err(|e| format!("Bad command on {}: {e}", publish.topic))?;
+                        execute(&app, &target, &command)
+                    });
+                if let Err(e) = result {
+                    let _ = client.try_publish(config.topic("error"), QoS::AtMostOnce, false, e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "MQTT connection failed", &e.to_string());
+                }
+                std::thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// A device id as a single topic level.
+fn topic_id(id: &str) -> String {
+    id.replace(['/', '+', '#'], "_")
+}
+
+/// Which lights a `set` topic addresses, if any.
+fn target_for(app: &AppHandle, config: &MqttConfig, topic: &str) -> Option<Target> {
+    let ids = app.state::<SerialManager>().ids();
+    parse_set_topic(config.base_topic.trim_matches('/'), topic, &ids)
+}
+
+fn parse_set_topic(base: &str, topic: &str, ids: &[String]) -> Option<Target> {
+    let rest = topic.strip_prefix(base)?.strip_prefix('/')?;
+    if rest == "set" {
+        return Some(Target::All);
+    }
+    let level = rest.strip_suffix("/set")?;
+    ids.iter()
+        .find(|id| topic_id(id) == level)
+        .map(|id| Target::Device(id.clone()))
+}
+
+/// Apply a `SetCommand` to the lights in `target`.
+pub fn execute(app: &AppHandle, target: &Target, command: &SetCommand) -> Result<(), String> {
+    let dither = app.state::<Ditherer>();
+    if let Some(index) = command.preset {
+        let preset = presets::get(app, index)?;
+        groups::fan_out(app, Some(target), |_, id| {
+            dither.set_level(app, id, preset.brightness, preset.kelvin)
+        })?;
+        return Ok(());
+    }
+    if command.on == Some(false) {
+        groups::fan_out(app, Some(target), |serial, id| {
+            serial.set_power_to(id, false)
+        })?;
+        return Ok(());
+    }
+    if command.level.is_none() && command.kelvin.is_none() {
+        groups::fan_out(app, Some(target), |serial, id| {
+            serial.set_power_to(id, true)
+        })?;
+        return Ok(());
+    }
+    let transition = command.transition_ms.unwrap_or(0).min(MAX_TRANSITION_MS);
+    fade::transition(
+        app,
+        Some(target),
+        command.level,
+        command.kelvin,
+        Duration::from_millis(transition),
+    )
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_set_topic() {
+        let ids = vec!["AB12".to_string(), "/dev/cu.usbserial-1".to_string()];
+        assert_eq!(
+            parse_set_topic("neewer", "neewer/set", &ids),
+            Some(Target::All)
+        );
+        assert_eq!(
+            parse_set_topic("neewer", "neewer/_dev_cu.usbserial-1/set", &ids),
+            Some(Target::Device("/dev/cu.usbserial-1".into()))
+        );
+        assert_eq!(parse_set_topic("neewer", "neewer/other/set", &ids), None);
+        assert_eq!(parse_set_topic("neewer", "neewerx/set", &ids), None);
+    }
+
+    #[test]
+    fn test_set_command_defaults() {
+        let command: SetCommand = serde_json::from_str(r#"{"kelvin": 3200}"#).unwrap();
+        assert_eq!(
+            command,
+            SetCommand {
+                kelvin: Some(3200),
+                ..SetCommand::default()
+            }
+        );
+    }
+}
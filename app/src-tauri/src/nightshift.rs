@@ -0,0 +1,331 @@
+/// Follow macOS Night Shift.
+///
+/// When enabled, a loop reads Night Shift's state and warms the lights in
+/// lockstep with the display, so the key light doesn't look blue against a
+/// warmed screen in the evening. The lights' daytime temperature is shifted
+/// toward the warmest they can go by Night Shift's strength, in mireds, and
+/// returns to daytime when Night Shift ends. Night Shift has no public API; its
+/// state and schedule come from the private CoreBrightness framework through
+/// the Objective-C runtime. Configuration is persisted under `night_shift` in
+/// the settings store.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::groups::{self, Target};
+use crate::sessionlog::{self, Source};
+use crate::{notify, protocol, STORE_FILE};
+
+const NIGHT_SHIFT_KEY: &str = "night_shift";
+const POLL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NightShiftConfig {
+    pub enabled: bool,
+    /// Temperature while Night Shift is off.
+    pub day_kelvin: u32,
+    /// Temperature at Night Shift's warmest setting.
+    pub night_kelvin: u32,
+    /// Lights to drive; all connected lights by default.
+    pub target: Option<Target>,
+}
+
+impl Default for NightShiftConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            day_kelvin: 5600,
+            night_kelvin: protocol::TEMP_MIN_K,
+            target: None,
+        }
+    }
+}
+
+impl NightShiftConfig {
+    fn validate(&self) -> Result<(), String> {
+        let range = protocol::TEMP_MIN_K..=protocol::TEMP_MAX_K;
+        if !range.contains(&self.day_kelvin) || !range.contains(&self.night_kelvin) {
+            return Err(format!(
+                "Night Shift temperatures must be {}-{}K",
+                protocol::TEMP_MIN_K,
+                protocol::TEMP_MAX_K
+            ));
+        }
+        Ok(())
+    }
+
+    /// Light temperature for a Night Shift state, interpolated in mireds.
+    fn kelvin_for(&self, state: &NightShiftState) -> u32 {
+        if !state.active {
+            return self.day_kelvin;
+        }
+        let day = 1e6 / self.day_kelvin as f64;
+        let night = 1e6 / self.night_kelvin as f64;
+        let mired = day + (night - day) * state.strength.clamp(0.0, 1.0) as f64;
+        (1e6 / mired).round() as u32
+    }
+}
+
+/// Night Shift's state, reported as "night-shift" after every poll along with
+/// the temperature sent to the lights.
+#[derive(Debug, Clone, Serialize)]
+pub struct NightShiftState {
+    pub active: bool,
+    pub enabled: bool,
+    /// Warmth slider, 0.0-1.0.
+    pub strength: f32,
+    /// 0 = off, 1 = sunset to sunrise, 2 = custom schedule.
+    pub mode: i32,
+    /// Custom schedule as (hour, minute) pairs.
+    pub from: (i32, i32),
+    pub to: (i32, i32),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NightShiftReading {
+    #[serde(flatten)]
+    pub state: NightShiftState,
+    pub kelvin: u32,
+}
+
+pub struct NightShiftFollow {
+    config: Mutex<NightShiftConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl NightShiftFollow {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(NightShiftConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: NightShiftConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(NIGHT_SHIFT_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> NightShiftConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: NightShiftConfig) -> Result<(), String> {
+        config.validate()?;
+        if config.enabled {
+            read_state()?;
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            NIGHT_SHIFT_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: NightShiftConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn run(app: AppHandle, config: NightShiftConfig, current: Arc<AtomicU64>, gen: u64) {
+    sessionlog::set_source(Source::Automation);
+    // Only write when the temperature changes, so manual tweaks stick until
+    // Night Shift moves on
+    let mut applied: Option<u8> = None;
+    let mut failing = false;
+    while current.load(Ordering::SeqCst) == gen {
+        match read_state() {
+            Ok(state) => {
+                failing = false;
+                let kelvin = config.kelvin_for(&state);
+                let wire = protocol::kelvin_to_byte(&protocol::DEFAULT_RANGE, kelvin);
+                if applied != Some(wire) {
+                    let dither = app.state::<Ditherer>();
+                    let result = groups::fan_out(&app, config.target.as_ref(), |serial, id| {
+                        let status = serial
+                            .status_of(id)
+                            .ok_or("No status received from light yet")?;
+                        dither.set_level(&app, id, status.level, kelvin)
+                    });
+                    if result.is_ok() {
+                        applied = Some(wire);
+                    }
+                }
+                let _ = app.emit("night-shift", NightShiftReading { state, kelvin });
+            }
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Can't read Night Shift", &e);
+                }
+            }
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn read_state() -> Result<NightShiftState, String> {
+    corebrightness::read()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn read_state() -> Result<NightShiftState, String> {
+    Err("Night Shift is only available on macOS".into())
+}
+
+#[cfg(target_os = "macos")]
+mod corebrightness {
+    use std::ffi::{c_char, c_void};
+    use std::sync::OnceLock;
+
+    use libloading::Library;
+
+    use super::NightShiftState;
+
+    const FRAMEWORK: &str =
+        "/System/Library/PrivateFrameworks/CoreBrightness.framework/CoreBrightness";
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Time {
+        hour: i32,
+        minute: i32,
+    }
+
+    /// `StatusData` as filled in by `-[CBBlueLightClient getBlueLightStatus:]`.
+    #[repr(C)]
+    #[derive(Default)]
+    struct Status {
+        active: u8,
+        enabled: u8,
+        _sun_schedule_permitted: u8,
+        mode: i32,
+        from: Time,
+        to: Time,
+        _disable_flags: u64,
+        available: u8,
+    }
+
+    type Id = *mut c_void;
+    type Sel = *mut c_void;
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    static FRAMEWORK_LIB: OnceLock<Result<Library, String>> = OnceLock::new();
+
+    pub fn read() -> Result<NightShiftState, String> {
+        FRAMEWORK_LIB
+            .get_or_init(|| {
+                // SAFETY: loading a system framework runs only its own
+                // initializers; it stays loaded for the process lifetime.
+                unsafe { Library::new(FRAMEWORK) }.map_err(|e| e.to_string())
+            })
+            .as_ref()
+            .map_err(|e| format!("CoreBrightness unavailable: {e}"))?;
+
+        // SAFETY: each objc_msgSend is cast to the exact signature of the
+        // method it calls; the client is released before returning.
+        unsafe {
+            let send_id: unsafe extern "C" fn(Id, Sel) -> Id =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let send_ptr: unsafe extern "C" fn(Id, Sel, *mut c_void) -> u8 =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let sel = |name: &[u8]| sel_registerName(name.as_ptr() as *const c_char);
+
+            let class = objc_getClass(b"CBBlueLightClient\0".as_ptr() as *const c_char);
+            if class.is_null() {
+                return Err("Night Shift is not supported on this Mac".into());
+            }
+            let client = send_id(send_id(class, sel(b"alloc\0")), sel(b"init\0"));
+            if client.is_null() {
+                return Err("Failed to create a Night Shift client".into());
+            }
+            let mut status = Status::default();
+            let mut strength: f32 = 0.0;
+            let ok = send_ptr(
+                client,
+                sel(b"getBlueLightStatus:\0"),
+                &mut status as *mut Status as *mut c_void,
+            ) != 0
+                && send_ptr(
+                    client,
+                    sel(b"getStrength:\0"),
+                    &mut strength as *mut f32 as *mut c_void,
+                ) != 0;
+            send_id(client, sel(b"release\0"));
+            if !ok {
+                return Err("Failed to read the Night Shift status".into());
+            }
+            if status.available == 0 {
+                return Err("Night Shift is not available on this display".into());
+            }
+            Ok(NightShiftState {
+                active: status.active != 0,
+                enabled: status.enabled != 0,
+                strength,
+                mode: status.mode,
+                from: (status.from.hour, status.from.minute),
+                to: (status.to.hour, status.to.minute),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(active: bool, strength: f32) -> NightShiftState {
+        NightShiftState {
+            active,
+            enabled: true,
+            strength,
+            mode: 1,
+            from: (22, 0),
+            to: (7, 0),
+        }
+    }
+
+    #[test]
+    fn test_kelvin_follows_strength() {
+        let config = NightShiftConfig::default();
+        assert_eq!(config.kelvin_for(&state(false, 1.0)), 5600);
+        assert_eq!(config.kelvin_for(&state(true, 0.0)), 5600);
+        assert_eq!(config.kelvin_for(&state(true, 1.0)), protocol::TEMP_MIN_K);
+        // Halfway in mireds is warmer than halfway in kelvin
+        let half = config.kelvin_for(&state(true, 0.5));
+        assert!(half < (5600 + protocol::TEMP_MIN_K) / 2, "{half}");
+    }
+}
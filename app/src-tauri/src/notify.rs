@@ -0,0 +1,21 @@
+/// Native notifications for failures that would otherwise go unseen.
+///
+/// Errors in background work (disconnects, failing writes, shortcut actions)
+/// have no caller to report to when the panel is closed, so they surface as
+/// system notifications unless the user has turned them off. They are also
+/// sent to webhooks subscribed to errors.
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::config::SettingsManager;
+use crate::webhooks::{self, WebhookEvent};
+
+/// Show an error notification, if enabled.
+pub fn error(app: &AppHandle, title: &str, body: &str) {
+    webhooks::dispatch(app, WebhookEvent::Error, json!({ "title": title, "body": body }));
+    if !app.state::<SettingsManager>().get().notifications {
+        return;
+    }
+    let _ = app.notification().builder().title(title).body(body).show();
+}
@@ -0,0 +1,94 @@
+/// Capture of unrecognized packets.
+///
+/// Frames that pass their checksum but aren't a known status frame are kept
+/// in a ring buffer of the most recent [`MAX_FRAMES`], so users with other
+/// Neewer models can share what their lights send and help map the protocol.
+/// The capture is in memory only and cleared on request.
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::protocol::Frame;
+
+pub const MAX_FRAMES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CapturedFrame {
+    /// Local time, RFC 3339 with milliseconds.
+    pub time: String,
+    pub device: String,
+    pub tag: u8,
+    /// Payload bytes as space-separated hex, e.g. "01 64 09".
+    pub payload: String,
+}
+
+pub struct PacketCapture {
+    frames: Mutex<VecDeque<CapturedFrame>>,
+}
+
+impl PacketCapture {
+    pub fn new() -> Self {
+        Self {
+            frames: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn record(&self, device: &str, frame: &Frame) {
+        let mut frames = self.frames.lock().unwrap();
+        frames.push_back(CapturedFrame {
+            time: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            device: device.to_string(),
+            tag: frame.tag,
+            payload: hex(&frame.payload),
+        });
+        if frames.len() > MAX_FRAMES {
+            frames.pop_front();
+        }
+    }
+
+    /// Captured frames, oldest first.
+    pub fn list(&self) -> Vec<CapturedFrame> {
+        self.frames.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.frames.lock().unwrap().clear();
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex(&[0x01, 0x64, 0x0A]), "01 64 0a");
+        assert_eq!(hex(&[]), "");
+    }
+
+    #[test]
+    fn test_keeps_most_recent_frames() {
+        let capture = PacketCapture::new();
+        for tag in 0..=MAX_FRAMES as u8 {
+            capture.record(
+                "A",
+                &Frame {
+                    tag,
+                    payload: vec![],
+                },
+            );
+        }
+        let frames = capture.list();
+        assert_eq!(frames.len(), MAX_FRAMES);
+        assert_eq!(frames[0].tag, 1);
+    }
+}
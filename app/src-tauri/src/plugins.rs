@@ -0,0 +1,239 @@
+/// Native plugins for third-party integrations.
+///
+/// A plugin is a dynamic library in the app data `plugins` directory exporting
+/// a small C ABI that exchanges JSON strings:
+///
+/// - `neewer_plugin_abi() -> u32` — must return [`ABI_VERSION`]
+/// - `neewer_plugin_manifest() -> *const c_char` — static JSON
+///   `{"name", "version", "triggers": [..], "actions": [..]}`
+/// - `neewer_plugin_start(emit, ctx)` — called once after loading; the plugin
+///   keeps `emit` and calls `emit(ctx, trigger, payload_json)` from any thread
+///   to fire one of its triggers
+/// - `neewer_plugin_invoke(action, args_json) -> *mut c_char` — run an action,
+///   returning `{"ok": value}` or `{"error": message}`
+/// - `neewer_plugin_free(ptr)` — release a string returned by `invoke`
+///
+/// Triggers are emitted as "plugin-trigger" and reach scripts subscribed with
+/// `on_trigger("<plugin>.<trigger>", fn_name)`; actions are callable from
+/// scripts as `plugin(name, action, args)`. Plugins stay loaded until exit.
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::Path;
+use std::sync::Mutex;
+
+use libloading::Library;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::notify;
+use crate::scripting::ScriptHost;
+
+pub const ABI_VERSION: u32 = 1;
+const PLUGIN_DIR: &str = "plugins";
+
+type EmitFn = extern "C" fn(ctx: *mut c_void, trigger: *const c_char, payload: *const c_char);
+type AbiFn = unsafe extern "C" fn() -> u32;
+type ManifestFn = unsafe extern "C" fn() -> *const c_char;
+type StartFn = unsafe extern "C" fn(emit: EmitFn, ctx: *mut c_void);
+type InvokeFn = unsafe extern "C" fn(action: *const c_char, args: *const c_char) -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(ptr: *mut c_char);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub triggers: Vec<String>,
+    #[serde(default)]
+    pub actions: Vec<String>,
+}
+
+/// Trigger fired by a plugin, emitted as "plugin-trigger".
+#[derive(Debug, Clone, Serialize)]
+pub struct TriggerEvent {
+    pub plugin: String,
+    pub trigger: String,
+    pub payload: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum InvokeResult {
+    Ok(Value),
+    Error(String),
+}
+
+struct Plugin {
+    manifest: Manifest,
+    invoke: InvokeFn,
+    free: FreeFn,
+    // Keeps the function pointers above valid
+    _lib: Library,
+}
+
+/// Context handed to a plugin's `emit` callback. Leaked, since plugins are
+/// never unloaded.
+struct EmitContext {
+    app: AppHandle,
+    plugin: String,
+}
+
+pub struct PluginHost {
+    plugins: Mutex<BTreeMap<String, Plugin>>,
+}
+
+impl PluginHost {
+    pub fn new() -> Self {
+        Self {
+            plugins: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Load every plugin in the plugins directory. Failures are reported and
+    /// skipped.
+    pub fn load(&self, app: &AppHandle) {
+        let Ok(dir) = app.path().app_data_dir().map(|d| d.join(PLUGIN_DIR)) else {
+            return;
+        };
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            let is_lib = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e == std::env::consts::DLL_EXTENSION);
+            if !is_lib {
+                continue;
+            }
+            if let Err(e) = self.load_one(app, &path) {
+                notify::error(
+                    app,
+                    "Plugin failed to load",
+                    &format!("{}: {e}", path.display()),
+                );
+            }
+        }
+    }
+
+    fn load_one(&self, app: &AppHandle, path: &Path) -> Result<(), String> {
+        // SAFETY: plugins are trusted native code placed in the app's data dir
+        // by the user; the exported symbols are checked against the ABI below.
+        unsafe {
+            let lib = Library::new(path).map_err(|e| e.to_string())?;
+            let abi: AbiFn = *lib.get(b"neewer_plugin_abi\0").map_err(|e| e.to_string())?;
+            if abi() != ABI_VERSION {
+                return Err(format!("unsupported plugin ABI {}", abi()));
+            }
+            let manifest_fn: ManifestFn = *lib
+                .get(b"neewer_plugin_manifest\0")
+                .map_err(|e| e.to_string())?;
+            let start: StartFn = *lib
+                .get(b"neewer_plugin_start\0")
+                .map_err(|e| e.to_string())?;
+            let invoke: InvokeFn = *lib
+                .get(b"neewer_plugin_invoke\0")
+                .map_err(|e| e.to_string())?;
+            let free: FreeFn = *lib
+                .get(b"neewer_plugin_free\0")
+                .map_err(|e| e.to_string())?;
+
+            let raw = manifest_fn();
+            if raw.is_null() {
+                return Err("empty manifest".into());
+            }
+            let manifest: Manifest = serde_json::from_slice(CStr::from_ptr(raw).to_bytes())
+                .map_err(|e| format!("invalid manifest: {e}"))?;
+            if self.plugins.lock().unwrap().contains_key(&manifest.name) {
+                return Err(format!(
+                    "a plugin named {} is already loaded",
+                    manifest.name
+                ));
+            }
+
+            let ctx = Box::into_raw(Box::new(EmitContext {
+                app: app.clone(),
+                plugin: manifest.name.clone(),
+            }));
+            self.plugins.lock().unwrap().insert(
+                manifest.name.clone(),
+                Plugin {
+                    manifest,
+                    invoke,
+                    free,
+                    _lib: lib,
+                },
+            );
+            start(host_emit, ctx.cast());
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<Manifest> {
+        self.plugins
+            .lock()
+            .unwrap()
+            .values()
+            .map(|p| p.manifest.clone())
+            .collect()
+    }
+
+    /// Run a plugin action with JSON arguments.
+    pub fn invoke(&self, plugin: &str, action: &str, args: &Value) -> Result<Value, String> {
+        let (invoke, free) = {
+            let plugins = self.plugins.lock().unwrap();
+            let p = plugins
+                .get(plugin)
+                .ok_or_else(|| format!("No plugin named {plugin}"))?;
+            if !p.manifest.actions.iter().any(|a| a == action) {
+                return Err(format!("{plugin} has no action {action}"));
+            }
+            (p.invoke, p.free)
+        };
+        let action = CString::new(action).map_err(|e| e.to_string())?;
+        let args = CString::new(args.to_string()).map_err(|e| e.to_string())?;
+
+        // SAFETY: pointers come from a loaded plugin that is never unloaded;
+        // the returned string is released with the plugin's own `free`.
+        let reply = unsafe {
+            let raw = invoke(action.as_ptr(), args.as_ptr());
+            if raw.is_null() {
+                return Err(format!("{plugin} returned no result"));
+            }
+            let bytes = CStr::from_ptr(raw).to_bytes().to_vec();
+            free(raw);
+            bytes
+        };
+        match serde_json::from_slice(&reply).map_err(|e| format!("{plugin}: bad reply: {e}"))? {
+            InvokeResult::Ok(value) => Ok(value),
+            InvokeResult::Error(e) => Err(format!("{plugin}: {e}")),
+        }
+    }
+}
+
+extern "C" fn host_emit(ctx: *mut c_void, trigger: *const c_char, payload: *const c_char) {
+    if ctx.is_null() || trigger.is_null() {
+        return;
+    }
+    // SAFETY: `ctx` is the leaked EmitContext passed to `start`; strings are
+    // NUL-terminated and only borrowed for the duration of this call.
+    let (ctx, trigger, payload) = unsafe {
+        let ctx = &*(ctx as *const EmitContext);
+        let trigger = CStr::from_ptr(trigger).to_string_lossy().into_owned();
+        let payload = if payload.is_null() {
+            Value::Null
+        } else {
+            serde_json::from_slice(CStr::from_ptr(payload).to_bytes()).unwrap_or(Value::Null)
+        };
+        (ctx, trigger, payload)
+    };
+
+    let event = TriggerEvent {
+        plugin: ctx.plugin.clone(),
+        trigger,
+        payload,
+    };
+    let _ = ctx.app.emit("plugin-trigger", &event);
+    ctx.app.state::<ScriptHost>().on_trigger(&event);
+}
@@ -0,0 +1,358 @@
+/// Pomodoro / interval timer with light signals.
+///
+/// The timer alternates work and break phases, with a long break after every
+/// few work phases, and marks each phase change by briefly shifting the lights
+/// (a warm pulse at break time, a cool one back at work by default) before
+/// restoring them. It runs on a backend thread so it keeps going with the
+/// panel closed. Configuration is persisted under `pomodoro` in the settings
+/// store; changes apply from the next start.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::curves::CurveManager;
+use crate::groups::{self, Target};
+use crate::sessionlog::{self, Source};
+use crate::{errors, history, STORE_FILE};
+
+const POMODORO_KEY: &str = "pomodoro";
+/// How often the timer thread checks for a stop.
+const POLL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+/// How a phase change is shown on the lights.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Pattern {
+    /// Blink to the signal state `count` times.
+    Pulse { count: u8, on_ms: u64, off_ms: u64 },
+    /// Hold the signal state for a while.
+    Hold { seconds: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signal {
+    pub pattern: Pattern,
+    pub level: u8,
+    pub kelvin: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    pub work_minutes: u32,
+    pub short_break_minutes: u32,
+    pub long_break_minutes: u32,
+    /// A long break replaces the short one after this many work phases.
+    pub long_break_every: u32,
+    /// Shown when a work phase starts; `None` for no signal.
+    pub work_signal: Option<Signal>,
+    /// Shown when a break starts.
+    pub break_signal: Option<Signal>,
+    /// Lights to signal on; all connected lights by default.
+    pub target: Option<Target>,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work_minutes: 25,
+            short_break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+            work_signal: Some(Signal {
+                pattern: Pattern::Pulse {
+                    count: 2,
+                    on_ms: 400,
+                    off_ms: 300,
+                },
+                level: 80,
+                kelvin: 6500,
+            }),
+            break_signal: Some(Signal {
+                pattern: Pattern::Pulse {
+                    count: 2,
+                    on_ms: 600,
+                    off_ms: 400,
+                },
+                level: 60,
+                kelvin: 2900,
+            }),
+            target: None,
+        }
+    }
+}
+
+impl PomodoroConfig {
+    fn validate(&self) -> Result<(), String> {
+        let minutes = [
+            self.work_minutes,
+            self.short_break_minutes,
+            self.long_break_minutes,
+        ];
+        if minutes.iter().any(|m| !(1..=240).contains(m)) {
+            return Err("Pomodoro phases must be 1-240 minutes".into());
+        }
+        if self.long_break_every == 0 {
+            return Err("Long break interval must be at least 1".into());
+        }
+        for signal in [&self.work_signal, &self.break_signal]
+            .into_iter()
+            .flatten()
+        {
+            if signal.level > 100 {
+                return Err("Signal level must be 0-100".into());
+            }
+            let too_long = match signal.pattern {
+                Pattern::Pulse {
+                    count,
+                    on_ms,
+                    off_ms,
+                } => count == 0 || count > 10 || on_ms + off_ms > 10_000,
+                Pattern::Hold { seconds } => seconds == 0 || seconds > 60,
+            };
+            if too_long {
+                return Err("Signal must be 1-10 short pulses or a 1-60 s hold".into());
+            }
+        }
+        Ok(())
+    }
+
+    fn duration(&self, phase: Phase) -> Duration {
+        let minutes = match phase {
+            Phase::Work => self.work_minutes,
+            Phase::ShortBreak => self.short_break_minutes,
+            Phase::LongBreak => self.long_break_minutes,
+        };
+        Duration::from_secs(minutes as u64 * 60)
+    }
+
+    /// The phase after `phase`, given how many work phases are done.
+    fn next(&self, phase: Phase, completed: u32) -> Phase {
+        match phase {
+            Phase::Work if completed % self.long_break_every == 0 => Phase::LongBreak,
+            Phase::Work => Phase::ShortBreak,
+            Phase::ShortBreak | Phase::LongBreak => Phase::Work,
+        }
+    }
+}
+
+/// Reported as "pomodoro" on every start, stop and phase change.
+#[derive(Debug, Clone, Serialize)]
+pub struct PomodoroStatus {
+    pub running: bool,
+    pub phase: Option<Phase>,
+    /// Work phases completed this session.
+    pub completed: u32,
+    pub remaining_secs: u64,
+}
+
+struct Session {
+    phase: Phase,
+    completed: u32,
+    ends_at: Instant,
+}
+
+pub struct Pomodoro {
+    config: Mutex<PomodoroConfig>,
+    session: Arc<Mutex<Option<Session>>>,
+    /// Bumped by every start/stop; the timer thread exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl Pomodoro {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(PomodoroConfig::default()),
+            session: Arc::new(Mutex::new(None)),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: PomodoroConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(POMODORO_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved;
+    }
+
+    pub fn get(&self) -> PomodoroConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: PomodoroConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            POMODORO_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// Start a session with a work phase, restarting any running one.
+    pub fn start(&self, app: &AppHandle) -> PomodoroStatus {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let config = self.get();
+        *self.session.lock().unwrap() = Some(Session {
+            phase: Phase::Work,
+            completed: 0,
+            ends_at: Instant::now() + config.duration(Phase::Work),
+        });
+        let status = self.status();
+        let _ = app.emit("pomodoro", &status);
+
+        let (current, session, app) = (self.generation.clone(), self.session.clone(), app.clone());
+        std::thread::spawn(move || run(app, config, session, current, gen));
+        status
+    }
+
+    pub fn stop(&self, app: &AppHandle) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if self.session.lock().unwrap().take().is_some() {
+            let _ = app.emit("pomodoro", self.status());
+        }
+    }
+
+    pub fn status(&self) -> PomodoroStatus {
+        status_of(self.session.lock().unwrap().as_ref())
+    }
+}
+
+fn status_of(session: Option<&Session>) -> PomodoroStatus {
+    PomodoroStatus {
+        running: session.is_some(),
+        phase: session.map(|s| s.phase),
+        completed: session.map_or(0, |s| s.completed),
+        remaining_secs: session.map_or(0, |s| {
+            s.ends_at
+                .saturating_duration_since(Instant::now())
+                .as_secs()
+        }),
+    }
+}
+
+fn run(
+    app: AppHandle,
+    config: PomodoroConfig,
+    session: Arc<Mutex<Option<Session>>>,
+    current: Arc<AtomicU64>,
+    gen: u64,
+) {
+    sessionlog::set_source(Source::Automation);
+    while current.load(Ordering::SeqCst) == gen {
+        std::thread::sleep(POLL);
+        let next = {
+            let mut guard = session.lock().unwrap();
+            let Some(s) = guard.as_mut().filter(|s| Instant::now() >= s.ends_at) else {
+                continue;
+            };
+            if s.phase == Phase::Work {
+                s.completed += 1;
+            }
+            s.phase = config.next(s.phase, s.completed);
+            s.ends_at = Instant::now() + config.duration(s.phase);
+            let _ = app.emit("pomodoro", status_of(Some(s)));
+            s.phase
+        };
+        let signal = match next {
+            Phase::Work => &config.work_signal,
+            Phase::ShortBreak | Phase::LongBreak => &config.break_signal,
+        };
+        if let Some(signal) = signal {
+            show(&app, signal, config.target.as_ref());
+        }
+    }
+}
+
+/// Play a signal on the lights, then put them back how they were.
+fn show(app: &AppHandle, signal: &Signal, target: Option<&Target>) {
+    let saved = history::snapshot(app);
+    let curves = app.state::<CurveManager>();
+    let set = || {
+        let result = groups::fan_out(app, target, |serial, id| {
+            serial.set_cct_to(id, curves.to_hw(id, signal.level), signal.kelvin)
+        });
+        errors::check_fan_out(app, "pomodoro", result);
+    };
+    match signal.pattern {
+        Pattern::Pulse {
+            count,
+            on_ms,
+            off_ms,
+        } => {
+            for _ in 0..count {
+                set();
+                std::thread::sleep(Duration::from_millis(on_ms));
+                history::restore(app, &saved);
+                std::thread::sleep(Duration::from_millis(off_ms));
+            }
+        }
+        Pattern::Hold { seconds } => {
+            set();
+            std::thread::sleep(Duration::from_secs(seconds));
+            history::restore(app, &saved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_break_every_nth_work_phase() {
+        let config = PomodoroConfig::default();
+        let mut phase = Phase::Work;
+        let mut completed = 0;
+        let mut breaks = Vec::new();
+        for _ in 0..8 {
+            if phase == Phase::Work {
+                completed += 1;
+            }
+            phase = config.next(phase, completed);
+            if phase != Phase::Work {
+                breaks.push(phase);
+            }
+        }
+        assert_eq!(
+            breaks,
+            [
+                Phase::ShortBreak,
+                Phase::ShortBreak,
+                Phase::ShortBreak,
+                Phase::LongBreak
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_long_signals() {
+        let mut config = PomodoroConfig::default();
+        assert!(config.validate().is_ok());
+        config.break_signal = Some(Signal {
+            pattern: Pattern::Hold { seconds: 600 },
+            level: 50,
+            kelvin: 2900,
+        });
+        assert!(config.validate().is_err());
+    }
+}
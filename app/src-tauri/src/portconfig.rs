@@ -0,0 +1,155 @@
+/// Serial parameters per device.
+///
+/// The PL81-Pro runs 115200-8N1 without flow control, but clone adapters and
+/// other Neewer models don't all do, so baud rate, read timeout and flow
+/// control can be set per device and are used when connecting. Changes take
+/// effect on the next connect. Parameters are persisted under
+/// `serial_params` in the settings store, keyed by device id.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::STORE_FILE;
+
+const PARAMS_KEY: &str = "serial_params";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowControl {
+    None,
+    /// XON/XOFF.
+    Software,
+    /// RTS/CTS.
+    Hardware,
+}
+
+impl From<FlowControl> for serialport::FlowControl {
+    fn from(flow: FlowControl) -> Self {
+        match flow {
+            FlowControl::None => serialport::FlowControl::None,
+            FlowControl::Software => serialport::FlowControl::Software,
+            FlowControl::Hardware => serialport::FlowControl::Hardware,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialParams {
+    pub baud_rate: u32,
+    /// Read timeout, in ms; also the longest a command to the light waits
+    /// for the read in progress, so kept short enough for dithering.
+    pub timeout_ms: u64,
+    pub flow_control: FlowControl,
+}
+
+impl Default for SerialParams {
+    fn default() -> Self {
+        Self {
+            baud_rate: 115200,
+            timeout_ms: 10,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+impl SerialParams {
+    fn validate(&self) -> Result<(), String> {
+        if !(1200..=921_600).contains(&self.baud_rate) {
+            return Err("Baud rate must be between 1200 and 921600".into());
+        }
+        if !(10..=5000).contains(&self.timeout_ms) {
+            return Err("Serial timeout must be between 10 and 5000 ms".into());
+        }
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+pub struct PortConfig {
+    params: Mutex<BTreeMap<String, SerialParams>>,
+}
+
+impl PortConfig {
+    pub fn new() -> Self {
+        Self {
+            params: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, SerialParams> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(PARAMS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.params.lock().unwrap() = saved;
+    }
+
+    /// Configured parameters by device id. Devices not listed use 115200-8N1.
+    pub fn list(&self) -> BTreeMap<String, SerialParams> {
+        self.params.lock().unwrap().clone()
+    }
+
+    pub fn params(&self, id: &str) -> SerialParams {
+        self.params
+            .lock()
+            .unwrap()
+            .get(id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set a device's parameters; `None` resets them to the default.
+    pub fn set(
+        &self,
+        app: &AppHandle,
+        id: &str,
+        params: Option<SerialParams>,
+    ) -> Result<(), String> {
+        let mut all = self.params.lock().unwrap();
+        match params {
+            Some(params) => {
+                params.validate()?;
+                all.insert(id.to_string(), params);
+            }
+            None => {
+                all.remove(id);
+            }
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            PARAMS_KEY,
+            serde_json::to_value(&*all).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(SerialParams::default().validate().is_ok());
+        let slow = SerialParams {
+            baud_rate: 300,
+            ..SerialParams::default()
+        };
+        assert!(slow.validate().is_err());
+        let instant = SerialParams {
+            timeout_ms: 0,
+            ..SerialParams::default()
+        };
+        assert!(instant.validate().is_err());
+    }
+}
@@ -0,0 +1,80 @@
+/// Presets saved from the panel.
+///
+/// The panel stores presets under the `presets` key of the settings store with
+/// brightness as a slider level; each light receives that level mapped through
+/// its dimming curve (see `curves`).
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::groups::{self, FanOutReport, Target};
+use crate::history::History;
+use crate::sessionlog::{self, Source};
+use crate::webhooks::{self, WebhookEvent};
+use crate::STORE_FILE;
+
+pub const PRESETS_KEY: &str = "presets";
+
+/// Default gamma between the panel's slider level and hardware brightness.
+pub const BRI_GAMMA: f64 = 2.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    /// Slider level 0-100 (perceptual, before the dimming curve).
+    pub brightness: u8,
+    pub kelvin: u32,
+}
+
+impl Preset {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Preset name cannot be empty".into());
+        }
+        if self.brightness > 100 {
+            return Err(format!("{}: brightness must be 0-100", self.name));
+        }
+        Ok(())
+    }
+}
+
+/// Load the saved presets, in panel order.
+pub fn load(app: &AppHandle) -> Vec<Preset> {
+    app.store(STORE_FILE)
+        .ok()
+        .and_then(|store| store.get(PRESETS_KEY))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// The saved preset at `index` (panel order).
+pub fn get(app: &AppHandle, index: usize) -> Result<Preset, String> {
+    load(app)
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| format!("No preset {}", index + 1))
+}
+
+/// Apply the saved preset at `index` to `target` (all lights if `None`), as
+/// a preset change that can be undone, and tell the webhooks.
+pub fn apply(
+    app: &AppHandle,
+    index: usize,
+    target: Option<&Target>,
+) -> Result<FanOutReport, String> {
+    let preset = get(app, index)?;
+    app.state::<History>().checkpoint(app);
+    let dither = app.state::<Ditherer>();
+    let report = sessionlog::with_source(Source::Preset, || {
+        groups::fan_out(app, target, |_, id| {
+            dither.set_level(app, id, preset.brightness, preset.kelvin)
+        })
+    })?;
+    webhooks::dispatch(
+        app,
+        WebhookEvent::PresetApplied,
+        serde_json::json!({ "index": index, "preset": preset, "devices": report.succeeded }),
+    );
+    Ok(report)
+}
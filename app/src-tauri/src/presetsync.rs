@@ -0,0 +1,241 @@
+/// Preset sync through a shared folder.
+///
+/// Presets can be mirrored to a file in a user-chosen folder, e.g. inside
+/// iCloud Drive or Dropbox, so several machines share them without the app
+/// talking to a cloud service. A loop polls the file and the saved presets:
+/// a change to the file is loaded into the store and announced as
+/// "presets-changed" so the panel reloads; a local change is written out to
+/// the file. If both changed since the last sync, the file wins. Configuration
+/// is persisted under `preset_sync` in the settings store.
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_store::StoreExt;
+
+use crate::presets::{self, Preset};
+use crate::{notify, STORE_FILE};
+
+const SYNC_KEY: &str = "preset_sync";
+/// Name of the synced file inside the chosen folder.
+const SYNC_FILE: &str = "neewer-presets.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    pub enabled: bool,
+    /// Folder holding the synced presets file.
+    pub folder: String,
+    pub interval_ms: u64,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            folder: String::new(),
+            interval_ms: 2000,
+        }
+    }
+}
+
+impl SyncConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.interval_ms < 500 {
+            return Err("Sync interval must be at least 500 ms".into());
+        }
+        if self.enabled && !Path::new(&self.folder).is_dir() {
+            return Err(format!("{} is not a folder", self.folder));
+        }
+        Ok(())
+    }
+
+    fn file(&self) -> PathBuf {
+        Path::new(&self.folder).join(SYNC_FILE)
+    }
+}
+
+/// What a sync pass should do.
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// Nothing changed.
+    Idle,
+    /// Replace the saved presets with the file's.
+    Pull(Vec<Preset>),
+    /// Write the saved presets to the file.
+    Push,
+}
+
+/// Decide a sync pass from the presets as of the last sync, the saved ones,
+/// and the file's contents if it changed since.
+fn step(last: &[Preset], local: &[Preset], remote: Option<Vec<Preset>>) -> Step {
+    match remote {
+        Some(remote) if remote != last => Step::Pull(remote),
+        _ if local != last => Step::Push,
+        _ => Step::Idle,
+    }
+}
+
+pub struct PresetSync {
+    config: Mutex<SyncConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl PresetSync {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(SyncConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: SyncConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(SYNC_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> SyncConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: SyncConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            SYNC_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: SyncConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn run(app: AppHandle, config: SyncConfig, current: Arc<AtomicU64>, gen: u64) {
+    let path = config.file();
+    // Starting from nothing, an existing file is adopted as the shared copy
+    // and otherwise this machine's presets are published
+    let mut last: Vec<Preset> = Vec::new();
+    let mut seen: Option<SystemTime> = None;
+    let mut failing = false;
+    while current.load(Ordering::SeqCst) == gen {
+        match sync_once(&app, &path, &mut last, &mut seen) {
+            Ok(()) => failing = false,
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Preset sync failed", &e);
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(config.interval_ms));
+    }
+}
+
+fn sync_once(
+    app: &AppHandle,
+    path: &Path,
+    last: &mut Vec<Preset>,
+    seen: &mut Option<SystemTime>,
+) -> Result<(), String> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let remote = if modified.is_some() && modified != *seen {
+        Some(read(path)?)
+    } else {
+        None
+    };
+    let local = presets::load(app);
+    match step(last, &local, remote) {
+        Step::Idle => {}
+        Step::Pull(remote) => {
+            let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+            store.set(
+                presets::PRESETS_KEY,
+                serde_json::to_value(&remote).map_err(|e| e.to_string())?,
+            );
+            store.save().map_err(|e| e.to_string())?;
+            let _ = app.emit("presets-changed", ());
+            *last = remote;
+        }
+        Step::Push => {
+            write(path, &local)?;
+            *last = local;
+        }
+    }
+    *seen = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Vec<Preset>, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let presets: Vec<Preset> = serde_json::from_str(&json)
+        .map_err(|e| format!("{} is not a presets file: {e}", path.display()))?;
+    presets.iter().try_for_each(Preset::validate)?;
+    Ok(presets)
+}
+
+/// Write via a temporary file so other machines never see a partial file.
+fn write(path: &Path, presets: &[Preset]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(presets).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str, brightness: u8) -> Preset {
+        Preset {
+            name: name.into(),
+            brightness,
+            kelvin: 5600,
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let last = vec![preset("Key", 50)];
+        assert_eq!(step(&last, &last, None), Step::Idle);
+        // Touched but unchanged file
+        assert_eq!(step(&last, &last, Some(last.clone())), Step::Idle);
+        assert_eq!(step(&last, &[preset("Key", 60)], None), Step::Push);
+        let remote = vec![preset("Key", 70)];
+        assert_eq!(
+            step(&last, &last, Some(remote.clone())),
+            Step::Pull(remote.clone())
+        );
+        // Both changed: the shared copy wins
+        assert_eq!(
+            step(&last, &[preset("Key", 60)], Some(remote.clone())),
+            Step::Pull(remote)
+        );
+    }
+}
@@ -0,0 +1,150 @@
+/// Preset profiles.
+///
+/// A profile is a named set of presets plus backend settings (e.g.
+/// "Streaming", "Photography", "Video calls"). Switching profiles stores the
+/// current presets and settings back into the active profile, then swaps in the
+/// new profile's, validating its settings before anything is changed. The
+/// panel reloads its presets on "profile-changed". Profiles are persisted
+/// under `profiles` in the settings store.
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::config::{Settings, SettingsManager};
+use crate::presets::{self, Preset};
+use crate::STORE_FILE;
+
+const PROFILES_KEY: &str = "profiles";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub presets: Vec<Preset>,
+    pub settings: Settings,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profiles {
+    /// Name of the profile the current presets and settings belong to.
+    pub active: Option<String>,
+    pub profiles: Vec<Profile>,
+}
+
+impl Profiles {
+    fn find(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Insert or replace a profile by name.
+    fn put(&mut self, profile: Profile) {
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+}
+
+pub struct ProfileManager {
+    profiles: Mutex<Profiles>,
+}
+
+impl ProfileManager {
+    pub fn new() -> Self {
+        Self {
+            profiles: Mutex::new(Profiles::default()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: Profiles = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(PROFILES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.profiles.lock().unwrap() = saved;
+    }
+
+    pub fn list(&self) -> Profiles {
+        self.profiles.lock().unwrap().clone()
+    }
+
+    /// Save the current presets and settings as a profile, creating it or
+    /// overwriting one with the same name, and make it active.
+    pub fn save(&self, app: &AppHandle, name: &str) -> Result<Profiles, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Profile name is required".into());
+        }
+        let mut profiles = self.profiles.lock().unwrap();
+        profiles.put(current(app, name));
+        profiles.active = Some(name.to_string());
+        persist(app, &profiles)?;
+        Ok(profiles.clone())
+    }
+
+    pub fn delete(&self, app: &AppHandle, name: &str) -> Result<Profiles, String> {
+        let mut profiles = self.profiles.lock().unwrap();
+        let before = profiles.profiles.len();
+        profiles.profiles.retain(|p| p.name != name);
+        if profiles.profiles.len() == before {
+            return Err(format!("No profile named {name}"));
+        }
+        if profiles.active.as_deref() == Some(name) {
+            profiles.active = None;
+        }
+        persist(app, &profiles)?;
+        Ok(profiles.clone())
+    }
+
+    /// Make `name` the active profile, swapping in its presets and settings.
+    pub fn switch(&self, app: &AppHandle, name: &str) -> Result<Profiles, String> {
+        let mut profiles = self.profiles.lock().unwrap();
+        let target = profiles
+            .find(name)
+            .cloned()
+            .ok_or_else(|| format!("No profile named {name}"))?;
+        // Keep edits made since the last switch
+        if let Some(active) = profiles.active.clone().filter(|a| a != name) {
+            profiles.put(current(app, &active));
+        }
+
+        // Settings are validated here; nothing has been written yet if this
+        // fails
+        app.state::<SettingsManager>()
+            .update(app, target.settings.clone())?;
+        profiles.active = Some(target.name.clone());
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            presets::PRESETS_KEY,
+            serde_json::to_value(&target.presets).map_err(|e| e.to_string())?,
+        );
+        store.set(
+            PROFILES_KEY,
+            serde_json::to_value(&*profiles).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        let _ = app.emit("profile-changed", &profiles.active);
+        Ok(profiles.clone())
+    }
+}
+
+fn current(app: &AppHandle, name: &str) -> Profile {
+    Profile {
+        name: name.to_string(),
+        presets: presets::load(app),
+        settings: app.state::<SettingsManager>().get(),
+    }
+}
+
+fn persist(app: &AppHandle, profiles: &Profiles) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        PROFILES_KEY,
+        serde_json::to_value(profiles).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
@@ -2,10 +2,69 @@
 ///
 /// Command format: [0x3A] [tag] [payload_len] [payload...] [cs_hi] [cs_lo]
 /// Checksum: 16-bit big-endian sum of all preceding bytes.
+///
+/// Outgoing packets are built with `Packet`, which frames and checksums any
+/// tag and payload. Incoming bytes are framed by `FrameParser`, which follows
+/// the length byte so frames of any tag and payload size are read whole.
+///
+/// Older firmware speaks another format (see `legacy`); [`Format`] builds
+/// commands and reads status in whichever format a light speaks.
+use serde::{Deserialize, Serialize};
+
+use crate::legacy;
 
 pub const TEMP_MIN_K: u32 = 2900;
 pub const TEMP_MAX_K: u32 = 7000;
 pub const TEMP_STEPS: u32 = 18; // 0x00 = 2900K, 0x12 = 7000K
+pub const DEFAULT_TEMP_K: u32 = 4950; // midpoint
+
+/// A light's temperature range: `steps` hardware steps from `min` (byte
+/// 0x00) to `max`. The PL81-Pro's is [`DEFAULT_RANGE`]; a light's own comes
+/// from its model or the user's override (see `models`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KelvinRange {
+    pub min: u32,
+    pub max: u32,
+    pub steps: u32,
+}
+
+pub const DEFAULT_RANGE: KelvinRange = KelvinRange {
+    min: TEMP_MIN_K,
+    max: TEMP_MAX_K,
+    steps: TEMP_STEPS,
+};
+
+impl KelvinRange {
+    /// `kelvin` within the range.
+    pub fn clamp(&self, kelvin: u32) -> u32 {
+        kelvin.clamp(self.min, self.max)
+    }
+}
+
+/// First byte of every frame.
+pub const HEADER: u8 = 0x3A;
+/// Tag of CCT commands and their status echoes.
+pub const TAG_CCT: u8 = 0x02;
+/// Tags of the extra status frames some firmware sends.
+pub const TAG_POWER: u8 = 0x01;
+pub const TAG_SCENE: u8 = 0x03;
+pub const TAG_FAN: u8 = 0x04;
+/// Tags of the HSI colour and power commands, decoded from the official app
+/// (see RESEARCH.md). Neither has any effect on the PL81-Pro; HSI shares its
+/// number with the fan status frames.
+pub const TAG_HSI: u8 = 0x04;
+pub const TAG_POWER_COMMAND: u8 = 0x06;
+/// Tag of commands the 2.4GHz remote dongle transmits over RF, with the RF
+/// channel and group ahead of the command payload.
+pub const TAG_RF: u8 = 0x08;
+pub const RF_CHANNELS: u8 = 16;
+pub const RF_GROUPS: u8 = 4;
+/// Group byte addressing every group on a channel.
+pub const RF_ALL_GROUPS: u8 = 0x00;
+/// Offset of the green/magenta shift byte (0x00 = -50, 0x64 = +50).
+const GM_OFFSET: i16 = 50;
+/// Longest payload accepted; a longer length byte means misframing.
+const MAX_PAYLOAD: u8 = 32;
 
 /// 16-bit big-endian checksum of all bytes.
 fn checksum(data: &[u8]) -> [u8; 2] {
@@ -13,44 +72,414 @@ fn checksum(data: &[u8]) -> [u8; 2] {
     [(s >> 8) as u8, (s & 0xFF) as u8]
 }
 
-/// Build a complete command packet with checksum.
-fn build_packet(payload: &[u8]) -> Vec<u8> {
-    let cs = checksum(payload);
-    let mut pkt = payload.to_vec();
-    pkt.extend_from_slice(&cs);
-    pkt
+/// A packet for a light, framed and checksummed when built:
+/// `Packet::new(TAG_CCT).payload(&[0x01, 50, 9]).build()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    tag: u8,
+    payload: Vec<u8>,
+}
+
+impl Packet {
+    pub fn new(tag: u8) -> Self {
+        Self {
+            tag,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Append `bytes` to the payload.
+    pub fn payload(mut self, bytes: &[u8]) -> Self {
+        self.payload.extend_from_slice(bytes);
+        self
+    }
+
+    /// The packet as sent: header, tag, payload length, payload and
+    /// checksum. Fails if the payload is longer than lights accept.
+    pub fn build(&self) -> Result<Vec<u8>, String> {
+        if self.payload.len() > MAX_PAYLOAD as usize {
+            return Err(format!(
+                "Payload is {} bytes; at most {MAX_PAYLOAD} fit in a packet",
+                self.payload.len()
+            ));
+        }
+        Ok(self.frame())
+    }
+
+    /// Frame a payload known to fit.
+    fn frame(&self) -> Vec<u8> {
+        let mut pkt = vec![HEADER, self.tag, self.payload.len() as u8];
+        pkt.extend_from_slice(&self.payload);
+        let cs = checksum(&pkt);
+        pkt.extend_from_slice(&cs);
+        pkt
+    }
+}
+
+/// Which command format a light speaks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// `0x3A` frames with a 16-bit checksum, as the PL81-Pro speaks.
+    #[default]
+    Standard,
+    /// `0x78` frames with an 8-bit checksum, as older firmware speaks.
+    Legacy,
 }
 
-/// Build a CCT command: brightness 0-100, temperature in Kelvin.
-pub fn cct_command(brightness: u8, kelvin: u32) -> Vec<u8> {
+impl Format {
+    pub const ALL: [Format; 2] = [Format::Standard, Format::Legacy];
+
+    fn header(self) -> u8 {
+        match self {
+            Format::Standard => HEADER,
+            Format::Legacy => legacy::HEADER,
+        }
+    }
+
+    fn checksum_len(self) -> u8 {
+        match self {
+            Format::Standard => 2,
+            Format::Legacy => 1,
+        }
+    }
+
+    /// Whether `cs` is the checksum of `body` in this format.
+    fn checksum_matches(self, body: &[u8], cs: &[u8]) -> bool {
+        match self {
+            Format::Standard => checksum(body) == cs,
+            Format::Legacy => [legacy::checksum(body)] == cs,
+        }
+    }
+
+    /// Kelvin a reported temperature byte stands for, within `range`.
+    pub fn kelvin(self, range: &KelvinRange, temp_byte: u8) -> u32 {
+        match self {
+            Format::Standard => byte_to_kelvin(range, temp_byte),
+            Format::Legacy => legacy::kelvin(range, temp_byte),
+        }
+    }
+
+    pub fn cct_command(self, range: &KelvinRange, brightness: u8, kelvin: u32) -> Vec<u8> {
+        match self {
+            Format::Standard => cct_command(range, brightness, kelvin),
+            Format::Legacy => legacy::cct_command(range, brightness, kelvin),
+        }
+    }
+
+    pub fn hsi_command(self, hue: u16, saturation: u8, brightness: u8) -> Vec<u8> {
+        match self {
+            Format::Standard => hsi_command(hue, saturation, brightness),
+            Format::Legacy => legacy::hsi_command(hue, saturation, brightness),
+        }
+    }
+
+    pub fn power_command(self, on: bool) -> Vec<u8> {
+        match self {
+            Format::Standard => power_command(on),
+            Format::Legacy => legacy::power_command(on),
+        }
+    }
+
+    /// A query the light answers with its state: CCT for the standard
+    /// format, power for the legacy one.
+    pub fn status_query(self) -> Vec<u8> {
+        match self {
+            Format::Standard => status_query(),
+            Format::Legacy => legacy::power_query(),
+        }
+    }
+
+    /// The status `frame` reports, if it's a known status frame, from a
+    /// light with HSI colour if `hsi` is set (see `Frame::status`).
+    pub fn status(self, frame: &Frame, hsi: bool) -> Option<StatusFrame> {
+        match self {
+            Format::Standard => frame.status(hsi),
+            Format::Legacy => legacy::status(frame),
+        }
+    }
+}
+
+/// Build a CCT command for a light with temperature range `range`:
+/// brightness 0-100, temperature in Kelvin.
+pub fn cct_command(range: &KelvinRange, brightness: u8, kelvin: u32) -> Vec<u8> {
     let bri = brightness.min(100);
-    let temp = kelvin_to_byte(kelvin);
-    build_packet(&[0x3A, 0x02, 0x03, 0x01, bri, temp])
+    let temp = kelvin_to_byte(range, kelvin);
+    Packet::new(TAG_CCT).payload(&[0x01, bri, temp]).frame()
+}
+
+/// Build a CCT command for the 2.4GHz dongle to transmit on RF `channel`
+/// (1-16) to `group` (1-4, or `RF_ALL_GROUPS`). RF lights aren't tracked
+/// individually, so the default temperature range applies.
+pub fn rf_cct_command(channel: u8, group: u8, brightness: u8, kelvin: u32) -> Vec<u8> {
+    let bri = brightness.min(100);
+    let temp = kelvin_to_byte(&DEFAULT_RANGE, kelvin);
+    Packet::new(TAG_RF)
+        .payload(&[channel, group, 0x01, bri, temp])
+        .frame()
+}
+
+/// Build an HSI colour command: hue in degrees, saturation and brightness
+/// 0-100. The hue goes little-endian.
+pub fn hsi_command(hue: u16, saturation: u8, brightness: u8) -> Vec<u8> {
+    let hue = hue % 360;
+    Packet::new(TAG_HSI)
+        .payload(&[
+            (hue & 0xFF) as u8,
+            (hue >> 8) as u8,
+            saturation.min(100),
+            brightness.min(100),
+        ])
+        .frame()
+}
+
+/// Build a power command.
+pub fn power_command(on: bool) -> Vec<u8> {
+    Packet::new(TAG_POWER_COMMAND)
+        .payload(&[if on { 0x01 } else { 0x02 }])
+        .frame()
+}
+
+/// Whether `packet` is a CCT command, in either format, which fully
+/// replaces the light's state set by an earlier one.
+pub fn is_cct_command(packet: &[u8]) -> bool {
+    (packet.len() == 8 && packet[..3] == [HEADER, TAG_CCT, 0x03]) || legacy::is_cct_command(packet)
+}
+
+/// Build a query for the state reported under `tag`: an empty frame of that
+/// tag. Only the CCT query is known to be answered by the PL81-Pro; the
+/// others are for firmware that reports power, scene or fan status.
+pub fn query(tag: u8) -> Vec<u8> {
+    Packet::new(tag).frame()
+}
+
+/// Build a status query, which a light answers with its current CCT status.
+pub fn status_query() -> Vec<u8> {
+    query(TAG_CCT)
+}
+
+/// Build a query for the power state.
+pub fn power_query() -> Vec<u8> {
+    query(TAG_POWER)
+}
+
+/// Build a query for the running scene.
+pub fn scene_query() -> Vec<u8> {
+    query(TAG_SCENE)
+}
+
+/// Build a query for the fan mode.
+pub fn fan_query() -> Vec<u8> {
+    query(TAG_FAN)
+}
+
+/// Convert Kelvin to protocol byte within `range` (0x00 at `range.min`, e.g.
+/// 2900-7000K to 0x00-0x12 for the default).
+pub fn kelvin_to_byte(range: &KelvinRange, kelvin: u32) -> u8 {
+    let k = range.clamp(kelvin);
+    let step = ((k - range.min) as f64 * range.steps as f64 / (range.max - range.min) as f64)
+        .round() as u32;
+    step.min(range.steps) as u8
+}
+
+/// Convert protocol byte within `range` to Kelvin.
+pub fn byte_to_kelvin(range: &KelvinRange, b: u8) -> u32 {
+    let b = (b as u32).min(range.steps);
+    range.min + (b * (range.max - range.min) + range.steps / 2) / range.steps
 }
 
-/// Convert Kelvin (2900-7000) to protocol byte (0x00-0x12).
-pub fn kelvin_to_byte(kelvin: u32) -> u8 {
-    let k = kelvin.clamp(TEMP_MIN_K, TEMP_MAX_K);
-    let step = ((k - TEMP_MIN_K) as f64 * TEMP_STEPS as f64 / (TEMP_MAX_K - TEMP_MIN_K) as f64)
-        .round() as u8;
-    step.min(TEMP_STEPS as u8)
+/// Move `kelvin` by a number of hardware temperature steps, clamped to
+/// `range`.
+pub fn step_kelvin(range: &KelvinRange, kelvin: u32, steps: i32) -> u32 {
+    let step = kelvin_to_byte(range, kelvin) as i32 + steps;
+    byte_to_kelvin(range, step.clamp(0, range.steps as i32) as u8)
 }
 
-/// Convert protocol byte (0x00-0x12) to Kelvin.
-pub fn byte_to_kelvin(b: u8) -> u32 {
-    let b = (b as u32).min(TEMP_STEPS);
-    TEMP_MIN_K + (b * (TEMP_MAX_K - TEMP_MIN_K) + TEMP_STEPS / 2) / TEMP_STEPS
+/// Convert Kelvin to mireds (micro reciprocal degrees), as used by HomeKit,
+/// Hue and Home Assistant.
+pub fn kelvin_to_mired(kelvin: u32) -> u32 {
+    let k = kelvin.max(1);
+    (1_000_000 + k / 2) / k
 }
 
-/// Parse an 8-byte status/echo packet. Returns (brightness, temp_byte) or None.
-pub fn parse_status(data: &[u8]) -> Option<(u8, u8)> {
-    if data.len() >= 8 && data[0] == 0x3A && data[1] == 0x02 {
-        let expected = checksum(&data[..6]);
-        if data[6] == expected[0] && data[7] == expected[1] {
-            return Some((data[4], data[5]));
+/// Convert mireds to Kelvin.
+pub fn mired_to_kelvin(mired: u32) -> u32 {
+    let m = mired.max(1);
+    (1_000_000 + m / 2) / m
+}
+
+/// A complete frame with a valid checksum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub tag: u8,
+    pub payload: Vec<u8>,
+}
+
+/// A status frame reported by a light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusFrame {
+    /// `[0x01, bri, temp]`, with a trailing green/magenta byte on firmware
+    /// that supports it.
+    Cct {
+        brightness: u8,
+        temp_byte: u8,
+        /// Green/magenta shift, -50 (green) to +50 (magenta).
+        gm: Option<i8>,
+    },
+    /// `[on]`: 0x00 off, anything else on.
+    Power { on: bool },
+    /// `[scene]`: the built-in scene the light is running.
+    Scene { scene: u8 },
+    /// `[mode]`: fan mode, 0x00 for automatic.
+    Fan { mode: u8 },
+}
+
+impl Frame {
+    /// The status this frame reports, if it's a known status frame, from a
+    /// light with HSI colour if `hsi` is set. HSI commands share their tag
+    /// with fan status and such lights echo them, so from those lights tag
+    /// 0x04 frames aren't read as fan status.
+    pub fn status(&self, hsi: bool) -> Option<StatusFrame> {
+        let status = match (self.tag, self.payload.as_slice()) {
+            (TAG_CCT, [_, bri, temp, rest @ ..]) => StatusFrame::Cct {
+                brightness: *bri,
+                temp_byte: *temp,
+                gm: rest.first().map(|&g| (g.min(100) as i16 - GM_OFFSET) as i8),
+            },
+            (TAG_POWER, [on, ..]) => StatusFrame::Power { on: *on != 0 },
+            (TAG_SCENE, [scene, ..]) => StatusFrame::Scene { scene: *scene },
+            (TAG_FAN, [mode, ..]) if !hsi => StatusFrame::Fan { mode: *mode },
+            _ => return None,
+        };
+        Some(status)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ParseState {
+    /// Waiting for a header byte; anything else is skipped.
+    Header,
+    Tag,
+    Len,
+    /// Payload bytes still to come.
+    Payload(u8),
+    /// Checksum bytes still to come.
+    Checksum(u8),
+}
+
+/// Streaming frame parser. Bytes can arrive split across reads or with
+/// several frames per read; each complete, checksum-valid frame is returned
+/// as soon as its last byte is fed.
+///
+/// A header byte can also appear inside a payload, so a frame that fails its
+/// checksum or declares an implausible length is treated as misframed: its
+/// header is dropped and the bytes after it are searched again for the next
+/// frame, rather than discarding them all.
+pub struct FrameParser {
+    format: Format,
+    state: ParseState,
+    /// Bytes of the frame in progress, header included.
+    frame: Vec<u8>,
+    /// Misframed or corrupt frames seen so far.
+    errors: u64,
+}
+
+impl FrameParser {
+    pub fn new() -> Self {
+        Self::for_format(Format::Standard)
+    }
+
+    /// A parser for frames in `format`.
+    pub fn for_format(format: Format) -> Self {
+        Self {
+            format,
+            state: ParseState::Header,
+            frame: Vec::new(),
+            errors: 0,
+        }
+    }
+
+    /// Number of frames dropped as misframed or corrupt.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Feed received bytes, returning the frames they complete.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for &b in data {
+            self.feed(b, &mut frames);
+        }
+        frames
+    }
+
+    fn feed(&mut self, byte: u8, frames: &mut Vec<Frame>) {
+        self.state = match self.state {
+            ParseState::Header if byte == self.format.header() => {
+                self.frame.clear();
+                self.frame.push(byte);
+                ParseState::Tag
+            }
+            ParseState::Header => return,
+            ParseState::Tag => {
+                self.frame.push(byte);
+                ParseState::Len
+            }
+            ParseState::Len => {
+                self.frame.push(byte);
+                match byte {
+                    0 => ParseState::Checksum(self.format.checksum_len()),
+                    n if n > MAX_PAYLOAD => return self.resync(frames),
+                    n => ParseState::Payload(n),
+                }
+            }
+            ParseState::Payload(remaining) => {
+                self.frame.push(byte);
+                match remaining {
+                    1 => ParseState::Checksum(self.format.checksum_len()),
+                    n => ParseState::Payload(n - 1),
+                }
+            }
+            ParseState::Checksum(remaining) if remaining > 1 => {
+                self.frame.push(byte);
+                ParseState::Checksum(remaining - 1)
+            }
+            ParseState::Checksum(_) => {
+                self.frame.push(byte);
+                self.state = ParseState::Header;
+                match self.finish() {
+                    Some(frame) => frames.push(frame),
+                    None => self.resync(frames),
+                }
+                return;
+            }
+        };
+    }
+
+    /// The buffered frame, if its checksum matches.
+    fn finish(&self) -> Option<Frame> {
+        let body = self.frame.len() - self.format.checksum_len() as usize;
+        if !self
+            .format
+            .checksum_matches(&self.frame[..body], &self.frame[body..])
+        {
+            return None;
+        }
+        Some(Frame {
+            tag: self.frame[1],
+            payload: self.frame[3..body].to_vec(),
+        })
+    }
+
+    /// Drop a misframed frame's header and rescan the bytes after it.
+    fn resync(&mut self, frames: &mut Vec<Frame>) {
+        self.errors += 1;
+        self.state = ParseState::Header;
+        let rest = std::mem::take(&mut self.frame).split_off(1);
+        for b in rest {
+            self.feed(b, frames);
         }
     }
-    None
 }
 
 #[cfg(test)]
@@ -64,9 +493,93 @@ mod tests {
         assert_eq!(cs, [0x00, 0xAD]);
     }
 
+    #[test]
+    fn test_packet_builder() {
+        let built = Packet::new(TAG_CCT)
+            .payload(&[0x01])
+            .payload(&[50, 9])
+            .build()
+            .unwrap();
+        assert_eq!(built, cct_command(&DEFAULT_RANGE, 50, 4950));
+        assert_eq!(Packet::new(TAG_CCT).build().unwrap(), status_query());
+        let full = Packet::new(0x05).payload(&[0; MAX_PAYLOAD as usize]);
+        assert_eq!(full.build().unwrap().len(), MAX_PAYLOAD as usize + 5);
+        assert!(full.payload(&[0]).build().is_err());
+    }
+
+    #[test]
+    fn test_hsi_and_power_commands() {
+        let hsi = hsi_command(300, 80, 120);
+        assert_eq!(hsi[..7], [HEADER, TAG_HSI, 4, 0x2C, 0x01, 80, 100]);
+        assert_eq!(hsi[7..], checksum(&hsi[..7]));
+        assert_eq!(hsi_command(420, 80, 100), hsi_command(60, 80, 100));
+        assert_eq!(
+            power_command(false),
+            [HEADER, TAG_POWER_COMMAND, 1, 0x02, 0x00, 0x43]
+        );
+    }
+
+    #[test]
+    fn test_rf_cct_command() {
+        let frames = FrameParser::new().push(&rf_cct_command(3, RF_ALL_GROUPS, 100, 7000));
+        assert_eq!(
+            frames,
+            [Frame {
+                tag: TAG_RF,
+                payload: vec![3, RF_ALL_GROUPS, 0x01, 0x64, 0x12],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_is_cct_command() {
+        assert!(is_cct_command(&cct_command(&DEFAULT_RANGE, 50, 5600)));
+        assert!(!is_cct_command(&status_query()));
+        assert!(!is_cct_command(&rf_cct_command(1, 1, 50, 5600)));
+    }
+
+    #[test]
+    fn test_status_query() {
+        let frames = FrameParser::new().push(&status_query());
+        assert_eq!(
+            frames,
+            [Frame {
+                tag: TAG_CCT,
+                payload: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        for (packet, tag) in [
+            (power_query(), TAG_POWER),
+            (scene_query(), TAG_SCENE),
+            (fan_query(), TAG_FAN),
+        ] {
+            assert_eq!(packet, Packet::new(tag).build().unwrap());
+            let frames = FrameParser::new().push(&packet);
+            assert_eq!(frames[0].tag, tag);
+            assert!(frames[0].payload.is_empty());
+            assert_eq!(frames[0].status(false), None);
+        }
+        assert_eq!(query(TAG_CCT), status_query());
+    }
+
+    #[test]
+    fn test_mired_conversion() {
+        assert_eq!(kelvin_to_mired(2900), 345);
+        assert_eq!(kelvin_to_mired(7000), 143);
+        assert_eq!(mired_to_kelvin(kelvin_to_mired(5600)), 5587);
+        assert_eq!(
+            kelvin_to_byte(&DEFAULT_RANGE, mired_to_kelvin(153)),
+            kelvin_to_byte(&DEFAULT_RANGE, 6536)
+        );
+    }
+
     #[test]
     fn test_cct_command() {
-        let cmd = cct_command(100, 7000);
+        let cmd = cct_command(&DEFAULT_RANGE, 100, 7000);
         // brightness=100=0x64, temp=0x12 for 7000K
         assert_eq!(&cmd[..6], &[0x3A, 0x02, 0x03, 0x01, 0x64, 0x12]);
         assert_eq!(cmd.len(), 8);
@@ -74,19 +587,164 @@ mod tests {
 
     #[test]
     fn test_kelvin_roundtrip() {
-        assert_eq!(kelvin_to_byte(2900), 0);
-        assert_eq!(kelvin_to_byte(7000), 18);
-        assert_eq!(byte_to_kelvin(0), 2900);
-        assert_eq!(byte_to_kelvin(18), 7000);
+        assert_eq!(kelvin_to_byte(&DEFAULT_RANGE, 2900), 0);
+        assert_eq!(kelvin_to_byte(&DEFAULT_RANGE, 7000), 18);
+        assert_eq!(byte_to_kelvin(&DEFAULT_RANGE, 0), 2900);
+        assert_eq!(byte_to_kelvin(&DEFAULT_RANGE, 18), 7000);
         // midpoint
-        assert_eq!(kelvin_to_byte(4950), 9);
+        assert_eq!(kelvin_to_byte(&DEFAULT_RANGE, 4950), 9);
+    }
+
+    #[test]
+    fn test_step_kelvin() {
+        assert_eq!(
+            step_kelvin(&DEFAULT_RANGE, 4950, 1),
+            byte_to_kelvin(&DEFAULT_RANGE, 10)
+        );
+        assert_eq!(
+            step_kelvin(&DEFAULT_RANGE, 4950, -1),
+            byte_to_kelvin(&DEFAULT_RANGE, 8)
+        );
+        assert_eq!(step_kelvin(&DEFAULT_RANGE, 7000, 3), 7000);
+        assert_eq!(step_kelvin(&DEFAULT_RANGE, 2900, -3), 2900);
+    }
+
+    #[test]
+    fn test_custom_kelvin_range() {
+        let range = KelvinRange {
+            min: 3200,
+            max: 5600,
+            steps: 24,
+        };
+        assert_eq!(kelvin_to_byte(&range, 3200), 0);
+        assert_eq!(kelvin_to_byte(&range, 4400), 12);
+        assert_eq!(kelvin_to_byte(&range, 7000), 24);
+        assert_eq!(byte_to_kelvin(&range, 1), 3300);
+        assert_eq!(byte_to_kelvin(&range, 30), 5600);
+        assert_eq!(step_kelvin(&range, 3200, -1), 3200);
+        assert_eq!(cct_command(&range, 50, 4400)[5], 12);
     }
 
     #[test]
     fn test_parse_status() {
-        let pkt = cct_command(50, 4950);
-        let (bri, temp) = parse_status(&pkt).unwrap();
-        assert_eq!(bri, 50);
-        assert_eq!(temp, 9);
+        let pkt = cct_command(&DEFAULT_RANGE, 50, 4950);
+        let frames = FrameParser::new().push(&pkt);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            frames[0].status(false),
+            Some(StatusFrame::Cct {
+                brightness: 50,
+                temp_byte: 9,
+                gm: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parser_frames_by_length() {
+        let long = Packet::new(0x05)
+            .payload(&[0x3A, 0x01, 0x02, 0x03])
+            .build()
+            .unwrap();
+        let mut stream = vec![0x00, 0xFF];
+        stream.extend_from_slice(&long);
+        stream.extend_from_slice(&cct_command(&DEFAULT_RANGE, 20, 2900));
+        let mut parser = FrameParser::new();
+        // Split mid-frame, as reads from the port can be
+        let (a, b) = stream.split_at(5);
+        let mut frames = parser.push(a);
+        frames.extend(parser.push(b));
+        assert_eq!(
+            frames,
+            [
+                Frame {
+                    tag: 0x05,
+                    payload: vec![0x3A, 0x01, 0x02, 0x03],
+                },
+                Frame {
+                    tag: TAG_CCT,
+                    payload: vec![0x01, 20, 0],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_drops_bad_checksum() {
+        let mut bad = cct_command(&DEFAULT_RANGE, 50, 4950);
+        bad[7] ^= 0xFF;
+        let mut parser = FrameParser::new();
+        assert!(parser.push(&bad).is_empty());
+        assert_eq!(parser.push(&cct_command(&DEFAULT_RANGE, 60, 4950)).len(), 1);
+        assert_eq!(parser.errors(), 1);
+    }
+
+    #[test]
+    fn test_parser_resyncs_inside_a_bad_frame() {
+        // A truncated frame whose declared payload swallows the next frame's
+        // start; the next frame must still be found
+        let mut stream = vec![HEADER, TAG_CCT, 0x03, 0x01];
+        stream.extend_from_slice(&cct_command(&DEFAULT_RANGE, 70, 7000));
+        let mut parser = FrameParser::new();
+        let frames = parser.push(&stream);
+        assert_eq!(parser.errors(), 1);
+        assert_eq!(frames.len(), 1);
+        assert!(matches!(
+            frames[0].status(false),
+            Some(StatusFrame::Cct {
+                brightness: 70,
+                temp_byte: 18,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parser_rejects_implausible_length() {
+        let mut stream = vec![HEADER, 0x01, 0xF0];
+        stream.extend_from_slice(&cct_command(&DEFAULT_RANGE, 10, 2900));
+        let mut parser = FrameParser::new();
+        assert_eq!(parser.push(&stream).len(), 1);
+        assert_eq!(parser.errors(), 1);
+    }
+
+    #[test]
+    fn test_extended_status() {
+        let frame = |tag, payload: &[u8]| Frame {
+            tag,
+            payload: payload.to_vec(),
+        };
+        assert_eq!(
+            frame(TAG_CCT, &[0x01, 40, 3, 0x00]).status(false),
+            Some(StatusFrame::Cct {
+                brightness: 40,
+                temp_byte: 3,
+                gm: Some(-50),
+            })
+        );
+        assert_eq!(
+            frame(TAG_POWER, &[0x00]).status(false),
+            Some(StatusFrame::Power { on: false })
+        );
+        assert_eq!(
+            frame(TAG_SCENE, &[7]).status(false),
+            Some(StatusFrame::Scene { scene: 7 })
+        );
+        assert_eq!(
+            frame(TAG_FAN, &[2]).status(false),
+            Some(StatusFrame::Fan { mode: 2 })
+        );
+        assert_eq!(frame(TAG_FAN, &[]).status(false), None);
+        assert_eq!(frame(0x7F, &[1]).status(false), None);
+    }
+
+    #[test]
+    fn test_hsi_echo_is_not_fan_status() {
+        // Hue 300, saturation 100, brightness 80
+        let echo = Frame {
+            tag: TAG_HSI,
+            payload: vec![0x2C, 0x01, 100, 80],
+        };
+        assert_eq!(echo.status(true), None);
     }
 }
@@ -0,0 +1,398 @@
+/// Bluetooth proximity presence.
+///
+/// An optional loop follows the signal strength (RSSI) of one Bluetooth LE
+/// device, such as the user's phone or watch, from its advertisements. When
+/// the signal rises to `arrive_rssi` the user has arrived and the lights come
+/// back on as they were; once it has stayed below `leave_rssi`, or the device
+/// hasn't been heard, for `leave_seconds` they have left and the lights go
+/// off. The gap between the two thresholds and the smoothing of readings keep
+/// a signal hovering near one of them from flapping the lights. Presence
+/// found when the loop starts sets the state without switching anything.
+/// Configuration is persisted under `proximity` in the settings store.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use btleplug::api::{Central, CentralEvent, Manager as _, Peripheral as _, ScanFilter};
+use btleplug::platform::{Adapter, Manager};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_store::StoreExt;
+
+use crate::groups::{self, Target};
+use crate::history::{self, Snapshot};
+use crate::sessionlog::{self, Source};
+use crate::{errors, notify, STORE_FILE};
+
+const PROXIMITY_KEY: &str = "proximity";
+const POLL: Duration = Duration::from_secs(1);
+/// Weight of each new reading in the smoothed RSSI.
+const SMOOTHING: f64 = 0.3;
+/// How long `nearby` listens for advertisements.
+const SCAN_TIME: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProximityConfig {
+    pub enabled: bool,
+    /// Bluetooth id or advertised name (case-insensitive) of the device.
+    pub device: String,
+    /// Smoothed RSSI, in dBm, at or above which the user has arrived.
+    pub arrive_rssi: i16,
+    /// Smoothed RSSI, in dBm, below which the user may have left.
+    pub leave_rssi: i16,
+    /// How long the signal must stay weak, or missing, to count as leaving.
+    pub leave_seconds: u32,
+    /// Lights to switch; all connected lights by default.
+    pub target: Option<Target>,
+}
+
+impl Default for ProximityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            device: String::new(),
+            arrive_rssi: -65,
+            leave_rssi: -80,
+            leave_seconds: 60,
+            target: None,
+        }
+    }
+}
+
+impl ProximityConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.enabled && self.device.trim().is_empty() {
+            return Err("Choose a Bluetooth device to follow".into());
+        }
+        for rssi in [self.arrive_rssi, self.leave_rssi] {
+            if !(-100..=0).contains(&rssi) {
+                return Err("Signal thresholds must be between -100 and 0 dBm".into());
+            }
+        }
+        if self.arrive_rssi <= self.leave_rssi {
+            return Err("The arrive threshold must be above the leave threshold".into());
+        }
+        if !(5..=3600).contains(&self.leave_seconds) {
+            return Err("Leave time must be 5-3600 seconds".into());
+        }
+        Ok(())
+    }
+}
+
+/// A Bluetooth LE device heard while scanning.
+#[derive(Debug, Clone, Serialize)]
+pub struct BleDevice {
+    pub id: String,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+}
+
+/// Reported as "proximity" when the user arrives or leaves.
+#[derive(Debug, Clone, Serialize)]
+pub struct PresenceState {
+    pub present: bool,
+}
+
+pub struct ProximityPresence {
+    config: Mutex<ProximityConfig>,
+    /// Bumped on every reconfigure; the loop and scan exit when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl ProximityPresence {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(ProximityConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: ProximityConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(PROXIMITY_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> ProximityConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: ProximityConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            PROXIMITY_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: ProximityConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        // Latest reading of the device, from the scan
+        let latest = Arc::new(Mutex::new(None));
+        let scan_app = app.clone();
+        let device = config.device.clone();
+        let (current, scan_latest) = (self.generation.clone(), latest.clone());
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = follow(&device, &scan_latest, &current, gen).await {
+                notify::error(&scan_app, "Bluetooth proximity stopped", &e);
+            }
+        });
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, latest, current, gen));
+    }
+}
+
+fn run(
+    app: AppHandle,
+    config: ProximityConfig,
+    latest: Arc<Mutex<Option<(i16, Instant)>>>,
+    current: Arc<AtomicU64>,
+    gen: u64,
+) {
+    sessionlog::set_source(Source::Automation);
+    let mut tracker = Tracker::default();
+    // State to restore on arrival, saved on leaving
+    let mut saved: Option<Snapshot> = None;
+
+    while current.load(Ordering::SeqCst) == gen {
+        std::thread::sleep(POLL);
+        if let Some((rssi, at)) = latest.lock().unwrap().take() {
+            tracker.reading(rssi, at);
+        }
+        let Some(present) = tracker.update(&config, Instant::now()) else {
+            continue;
+        };
+        let target = config.target.as_ref();
+        if present {
+            match saved.take() {
+                Some(saved) => history::restore(&app, &saved),
+                None => {
+                    let result =
+                        groups::fan_out(&app, target, |serial, id| serial.set_power_to(id, true));
+                    errors::check_fan_out(&app, "proximity", result);
+                }
+            }
+        } else {
+            saved = Some(history::snapshot(&app));
+            let result = groups::fan_out(&app, target, |serial, id| serial.set_power_to(id, false));
+            errors::check_fan_out(&app, "proximity", result);
+        }
+        let _ = app.emit("proximity", PresenceState { present });
+    }
+}
+
+/// Presence from RSSI readings, with hysteresis.
+#[derive(Debug, Default)]
+struct Tracker {
+    smoothed: Option<f64>,
+    last_seen: Option<Instant>,
+    /// Since when the signal has been weak or missing.
+    weak_since: Option<Instant>,
+    /// Unknown until the first decision.
+    present: Option<bool>,
+}
+
+impl Tracker {
+    fn reading(&mut self, rssi: i16, at: Instant) {
+        let rssi = rssi as f64;
+        self.smoothed = Some(self.smoothed.map_or(rssi, |s| s + (rssi - s) * SMOOTHING));
+        self.last_seen = Some(at);
+    }
+
+    /// The new presence when it changes; the first decision is kept silently.
+    fn update(&mut self, config: &ProximityConfig, now: Instant) -> Option<bool> {
+        let window = Duration::from_secs(config.leave_seconds as u64);
+        if let Some(seen) = self.last_seen {
+            if now.duration_since(seen) >= window {
+                // Gone quiet: weak since it was last heard
+                self.smoothed = None;
+                self.last_seen = None;
+                self.weak_since.get_or_insert(seen);
+            }
+        }
+        let near = self
+            .smoothed
+            .is_some_and(|s| s >= config.arrive_rssi as f64);
+        let weak = self.smoothed.map_or(true, |s| s < config.leave_rssi as f64);
+        if !weak {
+            self.weak_since = None;
+        }
+        let present = if near {
+            true
+        } else if weak && now.duration_since(*self.weak_since.get_or_insert(now)) >= window {
+            false
+        } else {
+            return None;
+        };
+        let was = self.present.replace(present);
+        (was.is_some() && was != Some(present)).then_some(present)
+    }
+}
+
+/// Whether `wanted` names a device by id or advertised name.
+fn is_device(wanted: &str, id: &str, name: Option<&str>) -> bool {
+    let wanted = wanted.trim();
+    id.eq_ignore_ascii_case(wanted) || name.is_some_and(|n| n.trim().eq_ignore_ascii_case(wanted))
+}
+
+async fn adapter() -> Result<Adapter, String> {
+    let manager = Manager::new().await.map_err(|e| e.to_string())?;
+    manager
+        .adapters()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No Bluetooth adapter found".into())
+}
+
+/// Record the RSSI of each advertisement from `device` in `latest` until the
+/// generation changes.
+async fn follow(
+    device: &str,
+    latest: &Mutex<Option<(i16, Instant)>>,
+    current: &AtomicU64,
+    gen: u64,
+) -> Result<(), String> {
+    let central = adapter().await?;
+    let mut events = central.events().await.map_err(|e| e.to_string())?;
+    central
+        .start_scan(ScanFilter::default())
+        .await
+        .map_err(|e| e.to_string())?;
+    while let Some(event) = events.next().await {
+        if current.load(Ordering::SeqCst) != gen {
+            break;
+        }
+        let (CentralEvent::DeviceDiscovered(id) | CentralEvent::DeviceUpdated(id)) = event else {
+            continue;
+        };
+        let Ok(peripheral) = central.peripheral(&id).await else {
+            continue;
+        };
+        let Ok(Some(props)) = peripheral.properties().await else {
+            continue;
+        };
+        if is_device(device, &id.to_string(), props.local_name.as_deref()) {
+            if let Some(rssi) = props.rssi {
+                *latest.lock().unwrap() = Some((rssi, Instant::now()));
+            }
+        }
+    }
+    let _ = central.stop_scan().await;
+    Ok(())
+}
+
+/// Bluetooth LE devices advertising nearby, strongest first, to choose one
+/// to follow.
+pub async fn nearby() -> Result<Vec<BleDevice>, String> {
+    let central = adapter().await?;
+    central
+        .start_scan(ScanFilter::default())
+        .await
+        .map_err(|e| e.to_string())?;
+    let _ = tauri::async_runtime::spawn_blocking(|| std::thread::sleep(SCAN_TIME)).await;
+    let peripherals = central.peripherals().await.map_err(|e| e.to_string())?;
+    let _ = central.stop_scan().await;
+
+    let mut devices = Vec::new();
+    for peripheral in peripherals {
+        let props = peripheral.properties().await.ok().flatten();
+        devices.push(BleDevice {
+            id: peripheral.id().to_string(),
+            name: props.as_ref().and_then(|p| p.local_name.clone()),
+            rssi: props.and_then(|p| p.rssi),
+        });
+    }
+    devices.sort_by_key(|d| std::cmp::Reverse(d.rssi.unwrap_or(i16::MIN)));
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_hysteresis() {
+        let config = ProximityConfig {
+            leave_seconds: 10,
+            ..ProximityConfig::default()
+        };
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let mut tracker = Tracker::default();
+
+        // Already at the desk on start: decided without acting
+        tracker.reading(-50, at(0));
+        assert_eq!(tracker.update(&config, at(0)), None);
+        assert_eq!(tracker.present, Some(true));
+
+        // Between the thresholds never leaves
+        for s in 1..30 {
+            tracker.reading(-72, at(s));
+            assert_eq!(tracker.update(&config, at(s)), None);
+        }
+        // Weak, but not for long enough yet
+        for s in 30..45 {
+            tracker.reading(-95, at(s));
+            let left = tracker.update(&config, at(s));
+            if left.is_some() {
+                assert_eq!(left, Some(false));
+                assert!(s >= 40, "left after {s}s");
+                break;
+            }
+        }
+        assert_eq!(tracker.present, Some(false));
+
+        // Coming back
+        tracker.reading(-50, at(50));
+        tracker.reading(-50, at(51));
+        let mut arrived = None;
+        for s in 51..60 {
+            tracker.reading(-50, at(s));
+            arrived = arrived.or(tracker.update(&config, at(s)));
+        }
+        assert_eq!(arrived, Some(true));
+    }
+
+    #[test]
+    fn test_tracker_leaves_when_unheard() {
+        let config = ProximityConfig {
+            leave_seconds: 10,
+            ..ProximityConfig::default()
+        };
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let mut tracker = Tracker::default();
+        tracker.reading(-50, at(0));
+        tracker.update(&config, at(0));
+        assert_eq!(tracker.update(&config, at(9)), None);
+        assert_eq!(tracker.update(&config, at(10)), Some(false));
+    }
+
+    #[test]
+    fn test_is_device() {
+        assert!(is_device("Alex's iPhone", "1234", Some("alex's iphone")));
+        assert!(is_device(" ab:cd ", "AB:CD", None));
+        assert!(!is_device("watch", "1234", Some("iPhone")));
+    }
+}
@@ -0,0 +1,84 @@
+/// What happens to the lights when the app quits.
+///
+/// `run()`'s event handler passes every exit request here. Unless the lights
+/// are to be left as they are, the exit is held off while any running effect
+/// or timeline stops and the lights are switched, or faded, off on a thread of
+/// its own; the app then exits again, and that request goes through. Writes
+/// are queued to each light's actor, so the exit also waits, briefly, for
+/// those queues to empty.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, ExitRequestApi, Manager};
+
+use crate::config::{QuitBehavior, SettingsManager};
+use crate::effects::EffectEngine;
+use crate::fade;
+use crate::serial::SerialManager;
+use crate::timeline::TimelineEngine;
+
+/// Longest wait for queued writes to reach the lights.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+const DRAIN_POLL: Duration = Duration::from_millis(20);
+
+pub struct QuitHandler {
+    /// The lights are being switched off; further requests wait for it.
+    started: AtomicBool,
+    /// The lights are off; the next request exits.
+    done: AtomicBool,
+}
+
+impl QuitHandler {
+    pub fn new() -> Self {
+        Self {
+            started: AtomicBool::new(false),
+            done: AtomicBool::new(false),
+        }
+    }
+
+    /// Handle an exit request, holding it off if the lights need switching
+    /// off first. Exits with `code` once they are.
+    pub fn on_exit_requested(&self, app: &AppHandle, code: Option<i32>, api: &ExitRequestApi) {
+        if self.done.load(Ordering::SeqCst) {
+            return;
+        }
+        if self.started.load(Ordering::SeqCst) {
+            api.prevent_exit();
+            return;
+        }
+        let settings = app.state::<SettingsManager>().get();
+        let fade = match settings.quit {
+            QuitBehavior::LeaveAsIs => return,
+            QuitBehavior::Off => Duration::ZERO,
+            QuitBehavior::FadeOff => Duration::from_secs(settings.quit_fade_secs as u64),
+        };
+        if app.state::<SerialManager>().ids().is_empty() {
+            return;
+        }
+        api.prevent_exit();
+        self.started.store(true, Ordering::SeqCst);
+
+        let app = app.clone();
+        std::thread::spawn(move || {
+            switch_off(&app, fade);
+            app.state::<QuitHandler>()
+                .done
+                .store(true, Ordering::SeqCst);
+            app.exit(code.unwrap_or(0));
+        });
+    }
+}
+
+/// Switch every light off over `fade`, blocking until the writes are out.
+fn switch_off(app: &AppHandle, fade: Duration) {
+    app.state::<EffectEngine>().stop(app);
+    app.state::<TimelineEngine>().stop(app);
+    if fade::transition(app, None, Some(0), None, fade).is_ok() {
+        std::thread::sleep(fade);
+    }
+    let serial = app.state::<SerialManager>();
+    let until = Instant::now() + DRAIN_TIMEOUT;
+    while Instant::now() < until && serial.write_stats().values().any(|s| s.queued > 0) {
+        std::thread::sleep(DRAIN_POLL);
+    }
+}
@@ -0,0 +1,139 @@
+/// Registry of every light that has been connected.
+///
+/// Discovery only sees lights whose adapter is attached, so a light that's
+/// unplugged would otherwise drop out of the device list, and groups and
+/// automations naming it would point at nothing. Each light is recorded on
+/// connect with the ports it was reached on, and its last reported state and
+/// when it was last seen are kept, so it's still listed (as absent) after a
+/// restart. Its name, model and range override stay with `devices` and
+/// `models`, keyed by the same identifier, and apply again when it's plugged
+/// back in. The registry is persisted under `known_devices` in the settings
+/// store; states are written when a light disconnects and when the app
+/// exits, for lights still connected then, rather than on every status.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::batch::LightState;
+use crate::serial::LightStatus;
+use crate::STORE_FILE;
+
+const KNOWN_KEY: &str = "known_devices";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KnownDevice {
+    /// Ports the light has been reached on, most recent first.
+    pub ports: Vec<String>,
+    /// When it was last connected or disconnected, in Unix seconds.
+    pub last_seen: u64,
+    /// The state it last reported.
+    pub last_state: Option<LightState>,
+}
+
+impl KnownDevice {
+    fn seen_on(&mut self, port: &str, now: u64) {
+        self.ports.retain(|p| p != port);
+        self.ports.insert(0, port.to_string());
+        self.last_seen = now;
+    }
+}
+
+pub struct DeviceRegistry {
+    known: Mutex<BTreeMap<String, KnownDevice>>,
+}
+
+impl DeviceRegistry {
+    pub fn new() -> Self {
+        Self {
+            known: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, KnownDevice> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(KNOWN_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.known.lock().unwrap() = saved;
+    }
+
+    /// Every light connected so far, by device id.
+    pub fn list(&self) -> BTreeMap<String, KnownDevice> {
+        self.known.lock().unwrap().clone()
+    }
+
+    /// Record a connection to light `id` on `port`.
+    pub fn on_connect(&self, app: &AppHandle, id: &str, port: &str) -> Result<(), String> {
+        self.known
+            .lock()
+            .unwrap()
+            .entry(id.to_string())
+            .or_default()
+            .seen_on(port, now());
+        self.save(app)
+    }
+
+    /// Keep the state a light reported.
+    pub fn on_status(&self, status: &LightStatus) {
+        let state = LightState {
+            brightness: status.level,
+            kelvin: status.kelvin,
+        };
+        let mut known = self.known.lock().unwrap();
+        known.entry(status.device.clone()).or_default().last_state = Some(state);
+    }
+
+    /// Note a light going away, saving its last state.
+    pub fn on_disconnect(&self, app: &AppHandle, id: &str) -> Result<(), String> {
+        if let Some(known) = self.known.lock().unwrap().get_mut(id) {
+            known.last_seen = now();
+        }
+        self.save(app)
+    }
+
+    /// Drop a light from the registry. Its name and model are kept, and it's
+    /// recorded again the next time it connects.
+    pub fn forget(&self, app: &AppHandle, id: &str) -> Result<(), String> {
+        if self.known.lock().unwrap().remove(id).is_none() {
+            return Err(format!("Unknown device: {id}"));
+        }
+        self.save(app)
+    }
+
+    /// Write the registry, with every light's last state, to the store.
+    pub fn save(&self, app: &AppHandle) -> Result<(), String> {
+        let value =
+            serde_json::to_value(&*self.known.lock().unwrap()).map_err(|e| e.to_string())?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(KNOWN_KEY, value);
+        store.save().map_err(|e| e.to_string())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen_on_moves_port_to_front() {
+        let mut known = KnownDevice::default();
+        known.seen_on("/dev/a", 10);
+        known.seen_on("bt://b", 20);
+        known.seen_on("/dev/a", 30);
+        assert_eq!(known.ports, ["/dev/a", "bt://b"]);
+        assert_eq!(known.last_seen, 30);
+    }
+}
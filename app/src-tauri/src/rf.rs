@@ -0,0 +1,183 @@
+/// Neewer 2.4GHz USB remote dongle.
+///
+/// The dongle is a USB-serial transmitter that relays commands to lights over
+/// RF, like the hardware remote, so lights without a cable can be controlled
+/// too. Lights listen on one of 16 RF channels and can be put in one of four
+/// groups; commands go out on the dongle's selected channel, to one group or
+/// broadcast to every group on it. The dongle doesn't report the lights'
+/// state, so nothing is read back. Its port and channel are persisted under
+/// `rf_dongle` in the settings store, and it's opened again on launch.
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::lock::ControlLock;
+use crate::portconfig::SerialParams;
+use crate::protocol::{self, RF_ALL_GROUPS, RF_CHANNELS, RF_GROUPS};
+use crate::sessionlog;
+use crate::transport::{self, Link};
+use crate::{notify, STORE_FILE};
+
+const RF_KEY: &str = "rf_dongle";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RfConfig {
+    /// Port of the dongle, or `None` when not in use.
+    pub port: Option<String>,
+    /// RF channel, 1-16.
+    pub channel: u8,
+}
+
+impl Default for RfConfig {
+    fn default() -> Self {
+        Self {
+            port: None,
+            channel: 1,
+        }
+    }
+}
+
+impl RfConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=RF_CHANNELS).contains(&self.channel) {
+            return Err(format!("RF channel must be between 1 and {RF_CHANNELS}"));
+        }
+        Ok(())
+    }
+}
+
+/// The group byte for `group`, 1-4, or every group for `None`.
+fn group_byte(group: Option<u8>) -> Result<u8, String> {
+    match group {
+        None => Ok(RF_ALL_GROUPS),
+        Some(g) if (1..=RF_GROUPS).contains(&g) => Ok(g),
+        Some(_) => Err(format!("RF group must be between 1 and {RF_GROUPS}")),
+    }
+}
+
+pub struct RfDongle {
+    config: Mutex<RfConfig>,
+    port: Mutex<Option<Box<dyn Link>>>,
+}
+
+impl RfDongle {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(RfConfig::default()),
+            port: Mutex::new(None),
+        }
+    }
+
+    /// Load the saved configuration and open the dongle, if one is set.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: RfConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(RF_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        match open(&saved) {
+            Ok(port) => *self.port.lock().unwrap() = port,
+            Err(e) => notify::error(app, "2.4GHz dongle unavailable", &e),
+        }
+        *self.config.lock().unwrap() = saved;
+    }
+
+    pub fn get(&self) -> RfConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.port.lock().unwrap().is_some()
+    }
+
+    /// Save the configuration, reopening the dongle if its port changed.
+    pub fn set(&self, app: &AppHandle, config: RfConfig) -> Result<(), String> {
+        config.validate()?;
+        let reopen = config.port != self.get().port || !self.is_connected();
+        if reopen {
+            // Close first so the same port can be opened again
+            *self.port.lock().unwrap() = None;
+            *self.port.lock().unwrap() = open(&config)?;
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            RF_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// Select the channel commands go out on.
+    pub fn set_channel(&self, app: &AppHandle, channel: u8) -> Result<(), String> {
+        let config = RfConfig {
+            channel,
+            ..self.get()
+        };
+        self.set(app, config)
+    }
+
+    /// Transmit a CCT command on the selected channel to `group` (1-4), or to
+    /// every group for `None`.
+    pub fn set_cct(
+        &self,
+        app: &AppHandle,
+        group: Option<u8>,
+        brightness: u8,
+        kelvin: u32,
+    ) -> Result<(), String> {
+        let channel = self.config.lock().unwrap().channel;
+        self.send_cct(app, channel, group, brightness, kelvin)
+    }
+
+    /// Transmit a CCT command on `channel`, unless the controls are locked
+    /// against the current source.
+    pub fn send_cct(
+        &self,
+        app: &AppHandle,
+        channel: u8,
+        group: Option<u8>,
+        brightness: u8,
+        kelvin: u32,
+    ) -> Result<(), String> {
+        app.state::<ControlLock>()
+            .check(sessionlog::current_source())?;
+        if !(1..=RF_CHANNELS).contains(&channel) {
+            return Err(format!("RF channel must be between 1 and {RF_CHANNELS}"));
+        }
+        let group = group_byte(group)?;
+        let mut port = self.port.lock().unwrap();
+        let port = port.as_mut().ok_or("The 2.4GHz dongle is not connected")?;
+        port.write_all(&protocol::rf_cct_command(
+            channel, group, brightness, kelvin,
+        ))
+        .and_then(|_| port.flush())
+        .map_err(|e| format!("Write failed: {e}"))
+    }
+}
+
+fn open(config: &RfConfig) -> Result<Option<Box<dyn Link>>, String> {
+    config
+        .port
+        .as_deref()
+        .map(|path| transport::open(path, &SerialParams::default()))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_byte() {
+        assert_eq!(group_byte(None), Ok(RF_ALL_GROUPS));
+        assert_eq!(group_byte(Some(4)), Ok(4));
+        assert!(group_byte(Some(0)).is_err());
+        assert!(group_byte(Some(5)).is_err());
+    }
+}
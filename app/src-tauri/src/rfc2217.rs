@@ -0,0 +1,265 @@
+/// RFC 2217 (Telnet Com Port Control) client.
+///
+/// Serial-over-network servers that speak RFC 2217 let the client set the
+/// remote port's line settings. On connect the link agrees binary mode and
+/// the com port option, then sends the device's baud rate and flow control
+/// with 8N1 framing. Afterwards data is exchanged as Telnet: 0xFF bytes are
+/// doubled on the way out, and Telnet commands and the server's notifications
+/// are stripped on the way in, answering option requests it doesn't support.
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+use crate::portconfig::{FlowControl, SerialParams};
+use crate::transport::{self, Link};
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const OPT_BINARY: u8 = 0;
+const OPT_SGA: u8 = 3;
+const OPT_COM_PORT: u8 = 44;
+
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const PARITY_NONE: u8 = 1;
+const STOPSIZE_ONE: u8 = 1;
+
+/// Options requested when connecting, as (verb, option).
+const OFFERED: [(u8, u8); 5] = [
+    (WILL, OPT_BINARY),
+    (DO, OPT_BINARY),
+    (WILL, OPT_SGA),
+    (DO, OPT_SGA),
+    (WILL, OPT_COM_PORT),
+];
+
+/// Telnet negotiation and com port settings sent on connect.
+fn handshake(params: &SerialParams) -> Vec<u8> {
+    let mut out: Vec<u8> = OFFERED
+        .iter()
+        .flat_map(|&(verb, opt)| [IAC, verb, opt])
+        .collect();
+    let control = match params.flow_control {
+        FlowControl::None => 1,
+        FlowControl::Software => 2,
+        FlowControl::Hardware => 3,
+    };
+    let settings: [(u8, &[u8]); 5] = [
+        (SET_BAUDRATE, &params.baud_rate.to_be_bytes()),
+        (SET_DATASIZE, &[8]),
+        (SET_PARITY, &[PARITY_NONE]),
+        (SET_STOPSIZE, &[STOPSIZE_ONE]),
+        (SET_CONTROL, &[control]),
+    ];
+    for (command, value) in settings {
+        out.extend_from_slice(&[IAC, SB, OPT_COM_PORT, command]);
+        out.extend_from_slice(&escape(value));
+        out.extend_from_slice(&[IAC, SE]);
+    }
+    out
+}
+
+/// Double every IAC byte so it's sent as data.
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for &b in data {
+        out.push(b);
+        if b == IAC {
+            out.push(IAC);
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TelnetState {
+    Data,
+    Iac,
+    /// After IAC and a negotiation verb.
+    Option(u8),
+    /// Inside a subnegotiation, which is skipped.
+    Sub,
+    SubIac,
+}
+
+/// Strips Telnet commands from received bytes.
+#[derive(Debug, Clone)]
+struct Decoder {
+    state: TelnetState,
+    /// Negotiation replies already sent, as (verb, option), so each is sent
+    /// once.
+    sent: Vec<(u8, u8)>,
+}
+
+impl Decoder {
+    fn new() -> Self {
+        Self {
+            state: TelnetState::Data,
+            sent: OFFERED.to_vec(),
+        }
+    }
+
+    /// Append the data bytes in `input` to `data`, returning any replies to
+    /// send back.
+    fn decode(&mut self, input: &[u8], data: &mut Vec<u8>) -> Vec<u8> {
+        let mut replies = Vec::new();
+        for &b in input {
+            self.state = match (self.state, b) {
+                (TelnetState::Data, IAC) => TelnetState::Iac,
+                (TelnetState::Data, _) => {
+                    data.push(b);
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, IAC) => {
+                    data.push(IAC);
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, WILL | WONT | DO | DONT) => TelnetState::Option(b),
+                (TelnetState::Iac, SB) => TelnetState::Sub,
+                // NOP, GA and the like carry nothing
+                (TelnetState::Iac, _) => TelnetState::Data,
+                (TelnetState::Option(verb), opt) => {
+                    if let Some(answer) = self.answer(verb, opt) {
+                        replies.extend_from_slice(&[IAC, answer, opt]);
+                    }
+                    TelnetState::Data
+                }
+                (TelnetState::Sub, IAC) => TelnetState::SubIac,
+                (TelnetState::Sub, _) => TelnetState::Sub,
+                (TelnetState::SubIac, SE) => TelnetState::Data,
+                (TelnetState::SubIac, _) => TelnetState::Sub,
+            };
+        }
+        replies
+    }
+
+    /// Reply to a negotiation request, unless it was already given.
+    fn answer(&mut self, verb: u8, opt: u8) -> Option<u8> {
+        let answer = match verb {
+            DO if matches!(opt, OPT_BINARY | OPT_SGA | OPT_COM_PORT) => WILL,
+            DO | DONT => WONT,
+            WILL if matches!(opt, OPT_BINARY | OPT_SGA) => DO,
+            _ => DONT,
+        };
+        if self.sent.contains(&(answer, opt)) {
+            return None;
+        }
+        self.sent.push((answer, opt));
+        Some(answer)
+    }
+}
+
+/// A connection to an RFC 2217 server.
+struct Rfc2217Link {
+    stream: TcpStream,
+    decoder: Decoder,
+    /// Decoded data not yet returned by `read`.
+    pending: Vec<u8>,
+}
+
+impl Read for Rfc2217Link {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Reads that only carried Telnet commands yield no data; keep going
+        // until data arrives or the read times out
+        let mut raw = [0u8; 256];
+        while self.pending.is_empty() {
+            let n = transport::read_socket(&mut self.stream, &mut raw)?;
+            let replies = self.decoder.decode(&raw[..n], &mut self.pending);
+            if !replies.is_empty() {
+                self.stream.write_all(&replies)?;
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for Rfc2217Link {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write_all(&escape(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Link for Rfc2217Link {}
+
+/// Negotiate com port control on a connected stream.
+pub fn open(mut stream: TcpStream, params: &SerialParams) -> Result<Box<dyn Link>, String> {
+    stream
+        .write_all(&handshake(params))
+        .map_err(|e| format!("RFC 2217 negotiation failed: {e}"))?;
+    Ok(Box::new(Rfc2217Link {
+        stream,
+        decoder: Decoder::new(),
+        pending: Vec::new(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(&[0x3A, 0xFF, 0x01]), [0x3A, 0xFF, 0xFF, 0x01]);
+    }
+
+    #[test]
+    fn test_handshake_sets_baud_rate() {
+        let out = handshake(&SerialParams::default());
+        let baud = [
+            &[IAC, SB, OPT_COM_PORT, SET_BAUDRATE][..],
+            &115_200u32.to_be_bytes(),
+            &[IAC, SE],
+        ]
+        .concat();
+        assert!(out.windows(baud.len()).any(|w| w == baud.as_slice()));
+    }
+
+    #[test]
+    fn test_decoder_strips_commands() {
+        let mut decoder = Decoder::new();
+        let mut data = Vec::new();
+        let input = [
+            // Data with an escaped IAC
+            &[0x3A, IAC, IAC][..],
+            // Already offered
+            &[IAC, DO, OPT_COM_PORT],
+            // Server notification
+            &[IAC, SB, OPT_COM_PORT, 101, 0x00, IAC, SE],
+            // Terminal type, which is refused
+            &[IAC, DO, 24],
+            &[0x02],
+        ]
+        .concat();
+        let replies = decoder.decode(&input, &mut data);
+        assert_eq!(data, [0x3A, IAC, 0x02]);
+        assert_eq!(replies, [IAC, WONT, 24]);
+        // Refused once only
+        assert!(decoder.decode(&[IAC, DO, 24], &mut data).is_empty());
+    }
+
+    #[test]
+    fn test_decoder_handles_split_commands() {
+        let mut decoder = Decoder::new();
+        let mut data = Vec::new();
+        decoder.decode(&[0x01, IAC], &mut data);
+        decoder.decode(&[SB, OPT_COM_PORT, 0x65], &mut data);
+        decoder.decode(&[IAC, SE, 0x02], &mut data);
+        assert_eq!(data, [0x01, 0x02]);
+    }
+}
@@ -0,0 +1,192 @@
+/// Scenes: a state for each of several lights, applied together.
+///
+/// A preset is one state for every light; a key/fill/back setup needs each
+/// light at its own. A scene lists targets (a device, a group, a channel or
+/// all lights, see `groups`) with the state for each. Entries apply in order,
+/// so a later entry overrides an earlier one for the lights they share ("all
+/// at 30%, the key at 80%"). Targets are resolved when the scene is applied,
+/// so a group's current members are used, and the lights are then set
+/// together (see `batch`). Saving a scene without entries captures every
+/// connected light as it is. Scenes are persisted under `scenes` in the
+/// settings store.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::batch::{self, BatchEntry, LightState};
+use crate::groups::{FanOutReport, GroupManager, Target};
+use crate::STORE_FILE;
+
+const SCENES_KEY: &str = "scenes";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneEntry {
+    pub target: Target,
+    pub state: LightState,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Scene {
+    pub name: String,
+    pub entries: Vec<SceneEntry>,
+}
+
+pub struct SceneManager {
+    scenes: Mutex<BTreeMap<String, Vec<SceneEntry>>>,
+}
+
+impl SceneManager {
+    pub fn new() -> Self {
+        Self {
+            scenes: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, Vec<SceneEntry>> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(SCENES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.scenes.lock().unwrap() = saved;
+    }
+
+    pub fn list(&self) -> Vec<Scene> {
+        self.scenes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, entries)| Scene {
+                name: name.clone(),
+                entries: entries.clone(),
+            })
+            .collect()
+    }
+
+    /// Create a scene, or replace an existing one. Without `entries`, every
+    /// connected light is captured as it is now.
+    pub fn save(
+        &self,
+        app: &AppHandle,
+        name: &str,
+        entries: Option<Vec<SceneEntry>>,
+    ) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Scene name cannot be empty".into());
+        }
+        let entries = match entries {
+            Some(entries) => entries,
+            None => batch::capture(app)
+                .into_iter()
+                .map(|e| SceneEntry {
+                    target: Target::Device(e.device),
+                    state: e.state,
+                })
+                .collect(),
+        };
+        if entries.is_empty() {
+            return Err("A scene needs at least one light".into());
+        }
+        if let Some(entry) = entries.iter().find(|e| e.state.brightness > 100) {
+            return Err(format!("{:?}: brightness must be 0-100", entry.target));
+        }
+        let mut scenes = self.scenes.lock().unwrap();
+        scenes.insert(name.to_string(), entries);
+        persist(app, &scenes)
+    }
+
+    pub fn delete(&self, app: &AppHandle, name: &str) -> Result<(), String> {
+        let mut scenes = self.scenes.lock().unwrap();
+        if scenes.remove(name).is_none() {
+            return Err(format!("No scene named {name}"));
+        }
+        persist(app, &scenes)
+    }
+
+    /// Set every light in the scene to its state, together.
+    pub fn apply(&self, app: &AppHandle, name: &str) -> Result<FanOutReport, String> {
+        let entries = self
+            .scenes
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("No scene named {name}"))?;
+        let groups = app.state::<GroupManager>();
+        let batch = resolve(&entries, |target| groups.resolve(app, target))?;
+        batch::apply(app, &batch)
+    }
+}
+
+/// One batch entry per light, later scene entries overriding earlier ones.
+fn resolve(
+    entries: &[SceneEntry],
+    targets: impl Fn(&Target) -> Result<Vec<String>, String>,
+) -> Result<Vec<BatchEntry>, String> {
+    let mut states = BTreeMap::new();
+    for entry in entries {
+        for device in targets(&entry.target)? {
+            states.insert(device, entry.state);
+        }
+    }
+    Ok(states
+        .into_iter()
+        .map(|(device, state)| BatchEntry { device, state })
+        .collect())
+}
+
+fn persist(app: &AppHandle, scenes: &BTreeMap<String, Vec<SceneEntry>>) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        SCENES_KEY,
+        serde_json::to_value(scenes).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_later_entries_override() {
+        let state = |brightness| LightState {
+            brightness,
+            kelvin: 5600,
+        };
+        let entries = [
+            SceneEntry {
+                target: Target::Group("all".into()),
+                state: state(30),
+            },
+            SceneEntry {
+                target: Target::Device("key".into()),
+                state: state(80),
+            },
+        ];
+        let batch = resolve(&entries, |target| match target {
+            Target::Group(_) => Ok(vec!["fill".into(), "key".into()]),
+            Target::Device(id) => Ok(vec![id.clone()]),
+            _ => Err("unexpected".into()),
+        })
+        .unwrap();
+        assert_eq!(
+            batch,
+            [
+                BatchEntry {
+                    device: "fill".into(),
+                    state: state(30),
+                },
+                BatchEntry {
+                    device: "key".into(),
+                    state: state(80),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,257 @@
+/// Screen-content ambient sync ("bias light" mode).
+///
+/// An optional loop samples the screen at a low rate, takes its average color,
+/// and pushes a matching temperature and brightness to the lights, much like an
+/// Ambilight. The average is converted to a correlated color temperature with
+/// McCamy's approximation and to relative luminance, then averaged over a
+/// sliding window so cuts and flashes don't make the light jump. Screens are
+/// grabbed as a tiny frame (see `capture`). Configuration is persisted under
+/// `screen_sync` in the settings store.
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::groups::{self, Target};
+use crate::sessionlog::{self, Source};
+use crate::{capture, notify, protocol, STORE_FILE};
+
+const SCREEN_SYNC_KEY: &str = "screen_sync";
+const FRAME_W: usize = 32;
+const FRAME_H: usize = 18;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScreenSyncConfig {
+    pub enabled: bool,
+    /// Screen index, or an X11 display on Linux.
+    pub screen: String,
+    pub interval_ms: u64,
+    /// Samples within this window are averaged.
+    pub smoothing_ms: u64,
+    /// Level range that screen luminance 0..1 maps onto.
+    pub min_level: u8,
+    pub max_level: u8,
+    /// Lights to drive; all connected lights by default.
+    pub target: Option<Target>,
+}
+
+impl Default for ScreenSyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            screen: "0".into(),
+            interval_ms: 500,
+            smoothing_ms: 3000,
+            min_level: 5,
+            max_level: 60,
+            target: None,
+        }
+    }
+}
+
+impl ScreenSyncConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.interval_ms < 200 {
+            return Err("Screen sync interval must be at least 200 ms".into());
+        }
+        if self.smoothing_ms > 60_000 {
+            return Err("Screen sync smoothing window must be at most 60 s".into());
+        }
+        if self.min_level > self.max_level || self.max_level > 100 {
+            return Err("Screen sync level range must be within 0-100".into());
+        }
+        Ok(())
+    }
+}
+
+/// Reported after every sample, as "screen-sync".
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenSample {
+    pub kelvin: u32,
+    pub luminance: f64,
+    pub level: u8,
+}
+
+pub struct ScreenSync {
+    config: Mutex<ScreenSyncConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl ScreenSync {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(ScreenSyncConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: ScreenSyncConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(SCREEN_SYNC_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> ScreenSyncConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: ScreenSyncConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            SCREEN_SYNC_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: ScreenSyncConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn run(app: AppHandle, config: ScreenSyncConfig, current: Arc<AtomicU64>, gen: u64) {
+    sessionlog::set_source(Source::Automation);
+    let window = Duration::from_millis(config.smoothing_ms);
+    // (taken at, mired, luminance)
+    let mut samples: VecDeque<(Instant, f64, f64)> = VecDeque::new();
+    let mut last: Option<(u8, u8)> = None;
+    let mut failing = false;
+
+    while current.load(Ordering::SeqCst) == gen {
+        let source = capture::Source::Screen(&config.screen);
+        match capture::grab(&source, FRAME_W, FRAME_H, capture::PixelFormat::Rgb24) {
+            Ok(frame) => {
+                failing = false;
+                let (kelvin, luminance) = measure(&frame);
+                let now = Instant::now();
+                samples.push_back((now, protocol::kelvin_to_mired(kelvin) as f64, luminance));
+                while samples
+                    .front()
+                    .is_some_and(|(t, _, _)| now.duration_since(*t) > window)
+                    && samples.len() > 1
+                {
+                    samples.pop_front();
+                }
+
+                // Average temperature in mireds, where steps are perceptually even
+                let n = samples.len() as f64;
+                let mired = samples.iter().map(|s| s.1).sum::<f64>() / n;
+                let luminance = samples.iter().map(|s| s.2).sum::<f64>() / n;
+                let kelvin = protocol::mired_to_kelvin(mired.round() as u32)
+                    .clamp(protocol::TEMP_MIN_K, protocol::TEMP_MAX_K);
+                let (lo, hi) = (config.min_level as f64, config.max_level as f64);
+                let level = (lo + (hi - lo) * luminance).round() as u8;
+
+                let wire = (
+                    level,
+                    protocol::kelvin_to_byte(&protocol::DEFAULT_RANGE, kelvin),
+                );
+                if last != Some(wire) {
+                    let dither = app.state::<Ditherer>();
+                    let result = groups::fan_out(&app, config.target.as_ref(), |_, id| {
+                        dither.set_level(&app, id, level, kelvin)
+                    });
+                    if result.is_ok() {
+                        last = Some(wire);
+                    }
+                }
+                let _ = app.emit(
+                    "screen-sync",
+                    ScreenSample {
+                        kelvin,
+                        luminance,
+                        level,
+                    },
+                );
+            }
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    notify::error(&app, "Screen sync can't capture the screen", &e);
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(config.interval_ms));
+    }
+}
+
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// (correlated color temperature, relative luminance 0..1) of a frame's
+/// average RGB24 color.
+fn measure(frame: &[u8]) -> (u32, f64) {
+    let pixels = (frame.len() / 3).max(1) as f64;
+    let mut sum = [0.0; 3];
+    for px in frame.chunks_exact(3) {
+        for (s, &c) in sum.iter_mut().zip(px) {
+            *s += srgb_to_linear(c as f64 / 255.0);
+        }
+    }
+    let [r, g, b] = sum.map(|s| s / pixels);
+
+    // Linear sRGB (D65) to CIE XYZ
+    let x = 0.4124 * r + 0.3576 * g + 0.1805 * b;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = 0.0193 * r + 0.1192 * g + 0.9505 * b;
+    let total = x + y + z;
+    if total <= f64::EPSILON {
+        // Black frame: keep a neutral temperature
+        return (protocol::DEFAULT_TEMP_K, 0.0);
+    }
+    let (cx, cy) = (x / total, y / total);
+
+    // McCamy's approximation
+    let n = (cx - 0.3320) / (0.1858 - cy);
+    let cct = 449.0 * n.powi(3) + 3525.0 * n.powi(2) + 6823.3 * n + 5520.33;
+    (cct.clamp(1000.0, 20000.0).round() as u32, y.clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_white_is_d65() {
+        let (kelvin, luminance) = measure(&[255, 255, 255, 255, 255, 255]);
+        assert!((6400..=6600).contains(&kelvin), "{kelvin}");
+        assert!((luminance - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_measure_warm_and_black() {
+        let (kelvin, _) = measure(&[255, 170, 90]);
+        assert!(kelvin < 4000, "{kelvin}");
+        assert_eq!(measure(&[0, 0, 0]), (protocol::DEFAULT_TEMP_K, 0.0));
+    }
+}
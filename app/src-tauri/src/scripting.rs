@@ -0,0 +1,404 @@
+/// Embedded Rhai scripting for user automations.
+///
+/// Each enabled script runs on its own thread. The top level runs once and may
+/// subscribe handlers, after which the thread keeps serving them until the
+/// script is disabled or deleted:
+///
+/// ```rhai
+/// every(30 * 60 * 1000, "nudge");
+/// fn nudge() {
+///     let s = get_light();
+///     set_light(s.brightness, s.kelvin - 100);
+/// }
+/// ```
+///
+/// API: `get_light()`, `set_light(brightness, kelvin)`, `set_device(id,
+/// brightness, kelvin)`, `sleep(ms)`, `on_status(fn_name)` (called with the
+/// status map), `every(ms, fn_name)`, `on_trigger("plugin.trigger", fn_name)`
+/// (called with the trigger payload), `plugin(name, action, args)`,
+/// `white_balance(name)` (Kelvin of a named setpoint such as "Half CTO"), and
+/// `print`. Brightness is a slider level 0-100, as the panel shows it, and
+/// is written through each light's dimming curve. Scripts are persisted under
+/// `scripts` in the settings store.
+use std::collections::BTreeMap;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::plugins::{PluginHost, TriggerEvent};
+use crate::serial::{LightStatus, SerialManager};
+use crate::sessionlog::{self, Source};
+use crate::{groups, notify, whitebalance, STORE_FILE};
+
+const SCRIPTS_KEY: &str = "scripts";
+/// Shortest allowed `every` interval.
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+/// Granularity at which `sleep` and idle waits notice a stop request.
+const POLL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Script {
+    pub name: String,
+    pub source: String,
+    pub enabled: bool,
+}
+
+impl Script {
+    /// Check the name and compile the source, so syntax errors are reported
+    /// to the caller.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Script name cannot be empty".into());
+        }
+        Engine::new()
+            .compile(&self.source)
+            .map(|_| ())
+            .map_err(|e| format!("{}: {e}", self.name))
+    }
+}
+
+/// Handlers a script subscribed during its top-level run.
+#[derive(Default)]
+struct Subscriptions {
+    status: Vec<String>,
+    timers: Vec<(Duration, String)>,
+    /// (`plugin.trigger`, handler)
+    triggers: Vec<(String, String)>,
+}
+
+/// Events delivered to a running script's thread.
+enum ScriptEvent {
+    Status(LightStatus),
+    Trigger(TriggerEvent),
+}
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    events: mpsc::Sender<ScriptEvent>,
+}
+
+pub struct ScriptHost {
+    scripts: Mutex<BTreeMap<String, Script>>,
+    running: Mutex<BTreeMap<String, Running>>,
+}
+
+impl ScriptHost {
+    pub fn new() -> Self {
+        Self {
+            scripts: Mutex::new(BTreeMap::new()),
+            running: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Load persisted scripts and start the enabled ones.
+    pub fn init(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, Script> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(SCRIPTS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        for script in saved.values().filter(|s| s.enabled) {
+            self.start(app, script);
+        }
+        *self.scripts.lock().unwrap() = saved;
+    }
+
+    pub fn list(&self) -> Vec<Script> {
+        self.scripts.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Create or replace a script after validating it; restarts it if
+    /// enabled.
+    pub fn save(&self, app: &AppHandle, script: Script) -> Result<(), String> {
+        script.validate()?;
+
+        self.stop(&script.name);
+        if script.enabled {
+            self.start(app, &script);
+        }
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.insert(script.name.clone(), script);
+        persist(app, &scripts)
+    }
+
+    pub fn delete(&self, app: &AppHandle, name: &str) -> Result<(), String> {
+        self.stop(name);
+        let mut scripts = self.scripts.lock().unwrap();
+        if scripts.remove(name).is_none() {
+            return Err(format!("No script named {name}"));
+        }
+        persist(app, &scripts)
+    }
+
+    /// Forward a status update to scripts subscribed with `on_status`.
+    pub fn on_status(&self, status: &LightStatus) {
+        for running in self.running.lock().unwrap().values() {
+            let _ = running.events.send(ScriptEvent::Status(status.clone()));
+        }
+    }
+
+    /// Forward a plugin trigger to scripts subscribed with `on_trigger`.
+    pub fn on_trigger(&self, event: &TriggerEvent) {
+        for running in self.running.lock().unwrap().values() {
+            let _ = running.events.send(ScriptEvent::Trigger(event.clone()));
+        }
+    }
+
+    fn start(&self, app: &AppHandle, script: &Script) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        self.running.lock().unwrap().insert(
+            script.name.clone(),
+            Running {
+                stop: stop.clone(),
+                events: tx,
+            },
+        );
+        let app = app.clone();
+        let script = script.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = run(&app, &script, stop, rx) {
+                let _ = app.emit(
+                    "script-error",
+                    serde_json::json!({ "name": script.name, "error": e }),
+                );
+                notify::error(&app, &format!("Script {} failed", script.name), &e);
+            }
+        });
+    }
+
+    fn stop(&self, name: &str) {
+        if let Some(running) = self.running.lock().unwrap().remove(name) {
+            running.stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn persist(app: &AppHandle, scripts: &BTreeMap<String, Script>) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        SCRIPTS_KEY,
+        serde_json::to_value(scripts).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
+
+fn status_map(status: &LightStatus) -> Map {
+    let mut map = Map::new();
+    map.insert("brightness".into(), Dynamic::from(status.level as i64));
+    map.insert("kelvin".into(), Dynamic::from(status.kelvin as i64));
+    map.insert("device".into(), Dynamic::from(status.device.clone()));
+    map.insert("name".into(), Dynamic::from(status.name.clone()));
+    map
+}
+
+fn light_args(brightness: i64, kelvin: i64) -> (u8, u32) {
+    (brightness.clamp(0, 100) as u8, kelvin.max(0) as u32)
+}
+
+/// Build an engine with the light API bound to `app`.
+fn engine(
+    app: &AppHandle,
+    script: &str,
+    stop: &Arc<AtomicBool>,
+    subs: &Arc<Mutex<Subscriptions>>,
+) -> Engine {
+    let mut engine = Engine::new();
+
+    // Abort long-running script code once the script is stopped
+    let flag = stop.clone();
+    engine.on_progress(move |_| flag.load(Ordering::Relaxed).then_some(Dynamic::UNIT));
+
+    let (handle, name) = (app.clone(), script.to_string());
+    engine.on_print(move |s| {
+        let _ = handle.emit(
+            "script-log",
+            serde_json::json!({ "name": name, "message": s }),
+        );
+    });
+
+    let handle = app.clone();
+    engine.register_fn("get_light", move || -> Result<Map, Box<EvalAltResult>> {
+        let status = handle
+            .state::<SerialManager>()
+            .status()
+            .ok_or("No status received from light yet")?;
+        Ok(status_map(&status))
+    });
+
+    let handle = app.clone();
+    engine.register_fn(
+        "set_light",
+        move |brightness: i64, kelvin: i64| -> Result<(), Box<EvalAltResult>> {
+            let (level, k) = light_args(brightness, kelvin);
+            let dither = handle.state::<Ditherer>();
+            groups::fan_out(&handle, None, |_, id| {
+                dither.set_level(&handle, id, level, k)
+            })
+            .map(|_| ())
+            .map_err(|e| e.message.into())
+        },
+    );
+
+    let handle = app.clone();
+    engine.register_fn(
+        "set_device",
+        move |id: &str, brightness: i64, kelvin: i64| -> Result<(), Box<EvalAltResult>> {
+            let (level, k) = light_args(brightness, kelvin);
+            handle
+                .state::<Ditherer>()
+                .set_level(&handle, id, level, k)
+                .map_err(|e| e.message.into())
+        },
+    );
+
+    let flag = stop.clone();
+    engine.register_fn("sleep", move |ms: i64| {
+        let until = Instant::now() + Duration::from_millis(ms.max(0) as u64);
+        while Instant::now() < until && !flag.load(Ordering::Relaxed) {
+            std::thread::sleep(POLL.min(until.saturating_duration_since(Instant::now())));
+        }
+    });
+
+    let s = subs.clone();
+    engine.register_fn("on_status", move |handler: &str| {
+        s.lock().unwrap().status.push(handler.to_string());
+    });
+
+    let s = subs.clone();
+    engine.register_fn("every", move |ms: i64, handler: &str| {
+        let interval = Duration::from_millis(ms.max(0) as u64).max(MIN_INTERVAL);
+        s.lock()
+            .unwrap()
+            .timers
+            .push((interval, handler.to_string()));
+    });
+
+    engine.register_fn(
+        "white_balance",
+        |name: &str| -> Result<i64, Box<EvalAltResult>> {
+            Ok(whitebalance::get(name)?.kelvin as i64)
+        },
+    );
+
+    let s = subs.clone();
+    engine.register_fn("on_trigger", move |trigger: &str, handler: &str| {
+        s.lock()
+            .unwrap()
+            .triggers
+            .push((trigger.to_string(), handler.to_string()));
+    });
+
+    let handle = app.clone();
+    engine.register_fn(
+        "plugin",
+        move |name: &str, action: &str, args: Dynamic| -> Result<Dynamic, Box<EvalAltResult>> {
+            let args: serde_json::Value = rhai::serde::from_dynamic(&args)?;
+            let result = handle.state::<PluginHost>().invoke(name, action, &args)?;
+            rhai::serde::to_dynamic(result)
+        },
+    );
+
+    engine
+}
+
+/// Run a script's top level, then serve its subscriptions until stopped.
+fn run(
+    app: &AppHandle,
+    script: &Script,
+    stop: Arc<AtomicBool>,
+    events: mpsc::Receiver<ScriptEvent>,
+) -> Result<(), String> {
+    sessionlog::set_source(Source::Automation);
+    let subs = Arc::new(Mutex::new(Subscriptions::default()));
+    let engine = engine(app, &script.name, &stop, &subs);
+    let ast: AST = engine.compile(&script.source).map_err(|e| e.to_string())?;
+    let mut scope = Scope::new();
+
+    if let Err(e) = engine.run_ast_with_scope(&mut scope, &ast) {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        return Err(e.to_string());
+    }
+
+    let (status_handlers, timers, triggers) = {
+        let subs = subs.lock().unwrap();
+        (
+            subs.status.clone(),
+            subs.timers.clone(),
+            subs.triggers.clone(),
+        )
+    };
+    if status_handlers.is_empty() && timers.is_empty() && triggers.is_empty() {
+        return Ok(());
+    }
+
+    let mut due: Vec<Instant> = timers
+        .iter()
+        .map(|(every, _)| Instant::now() + *every)
+        .collect();
+    while !stop.load(Ordering::Relaxed) {
+        let next = due.iter().min().copied();
+        let wait = next
+            .map_or(POLL, |t| t.saturating_duration_since(Instant::now()))
+            .min(POLL);
+
+        match events.recv_timeout(wait) {
+            Ok(ScriptEvent::Status(status)) => {
+                for handler in &status_handlers {
+                    call(
+                        &engine,
+                        &mut scope,
+                        &ast,
+                        handler,
+                        (status_map(&status),),
+                        &stop,
+                    )?;
+                }
+            }
+            Ok(ScriptEvent::Trigger(event)) => {
+                let key = format!("{}.{}", event.plugin, event.trigger);
+                for (_, handler) in triggers.iter().filter(|(t, _)| *t == key) {
+                    let payload = rhai::serde::to_dynamic(&event.payload).unwrap_or(Dynamic::UNIT);
+                    call(&engine, &mut scope, &ast, handler, (payload,), &stop)?;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        let now = Instant::now();
+        for (i, (every, handler)) in timers.iter().enumerate() {
+            if due[i] <= now {
+                due[i] = now + *every;
+                call(&engine, &mut scope, &ast, handler, (), &stop)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn call(
+    engine: &Engine,
+    scope: &mut Scope,
+    ast: &AST,
+    handler: &str,
+    args: impl rhai::FuncArgs,
+    stop: &AtomicBool,
+) -> Result<(), String> {
+    match engine.call_fn::<Dynamic>(scope, ast, handler, args) {
+        Ok(_) => Ok(()),
+        Err(_) if stop.load(Ordering::Relaxed) => Ok(()),
+        Err(e) => Err(format!("{handler}: {e}")),
+    }
+}
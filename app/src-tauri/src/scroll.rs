@@ -0,0 +1,262 @@
+/// Scroll-wheel light adjustment with coalesced writes.
+///
+/// Wheel and trackpad events, over the panel or the tray icon, arrive far
+/// faster than the lights can usefully accept commands, so each event only
+/// adds to a pending step and a short-lived flush thread writes it at most
+/// once per configured write interval. Steps go through `steps`, so every
+/// light moves from its own level or temperature. A step waits for the lights
+/// to report the last one (up to `ECHO_WAIT`) so it doesn't start from a
+/// stale status.
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Manager};
+
+use crate::config::SettingsManager;
+use crate::serial::SerialManager;
+use crate::{errors, steps};
+
+/// Longest a step waits for the lights to report the previous one.
+const ECHO_WAIT: Duration = Duration::from_millis(500);
+/// Slider levels per wheel notch.
+const BRIGHTNESS_PER_NOTCH: f64 = 2.0;
+/// Kelvin per wheel notch.
+const KELVIN_PER_NOTCH: f64 = 100.0;
+
+#[derive(Default)]
+struct Pending {
+    /// Whole slider levels and Kelvin not yet written.
+    brightness: i32,
+    kelvin: i32,
+    /// Fractions of a level or Kelvin not yet applied.
+    brightness_carry: f64,
+    kelvin_carry: f64,
+    flushing: bool,
+}
+
+pub struct ScrollAdjuster {
+    pending: Arc<Mutex<Pending>>,
+    /// Whether the pointer is over the tray icon.
+    over_tray: AtomicBool,
+}
+
+impl ScrollAdjuster {
+    pub fn new() -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(Pending::default())),
+            over_tray: AtomicBool::new(false),
+        }
+    }
+
+    /// Apply a scroll of `delta` notches (positive = brighter / cooler) to
+    /// every connected light. Adjusts kelvin instead of brightness when
+    /// `kelvin` is set.
+    pub fn scroll(&self, delta: f64, kelvin: bool, app: &AppHandle) -> Result<(), String> {
+        if !app.state::<SerialManager>().is_connected() {
+            return Err("Port not open".into());
+        }
+        let mut p = self.pending.lock().unwrap();
+        if kelvin {
+            p.kelvin_carry += delta * KELVIN_PER_NOTCH;
+            let whole = p.kelvin_carry.trunc();
+            p.kelvin_carry -= whole;
+            p.kelvin += whole as i32;
+        } else {
+            p.brightness_carry += delta * BRIGHTNESS_PER_NOTCH;
+            let whole = p.brightness_carry.trunc();
+            p.brightness_carry -= whole;
+            p.brightness += whole as i32;
+        }
+        if (p.brightness != 0 || p.kelvin != 0) && !p.flushing {
+            p.flushing = true;
+            let pending = self.pending.clone();
+            let app = app.clone();
+            std::thread::spawn(move || flush_loop(pending, app));
+        }
+        Ok(())
+    }
+
+    /// Track whether the pointer is over the tray icon, from its enter and
+    /// leave events.
+    pub fn set_over_tray(&self, over: bool) {
+        self.over_tray.store(over, Ordering::Relaxed);
+    }
+
+    fn over_tray(&self) -> bool {
+        self.over_tray.load(Ordering::Relaxed)
+    }
+}
+
+/// Write the pending steps every write interval until nothing is pending.
+fn flush_loop(pending: Arc<Mutex<Pending>>, app: AppHandle) {
+    let mut last_write: Option<Instant> = None;
+    loop {
+        let interval = app.state::<SettingsManager>().get().write_interval_ms;
+        std::thread::sleep(Duration::from_millis(interval));
+        if let Some(at) = last_write {
+            if !reported_since(&app, at) && at.elapsed() < ECHO_WAIT {
+                continue;
+            }
+        }
+        let (brightness, kelvin) = {
+            let mut p = pending.lock().unwrap();
+            if p.brightness == 0 && p.kelvin == 0 {
+                p.flushing = false;
+                return;
+            }
+            (
+                std::mem::take(&mut p.brightness),
+                std::mem::take(&mut p.kelvin),
+            )
+        };
+        last_write = Some(Instant::now());
+        if brightness != 0 {
+            let result = steps::brightness(&app, None, brightness);
+            errors::check_fan_out(&app, "scroll", result);
+        }
+        if kelvin != 0 {
+            let result = steps::kelvin(&app, None, kelvin).map(|r| r.report);
+            errors::check_fan_out(&app, "scroll", result);
+        }
+    }
+}
+
+/// Whether every connected light has reported its status since `at`.
+fn reported_since(app: &AppHandle, at: Instant) -> bool {
+    let serial = app.state::<SerialManager>();
+    serial
+        .ids()
+        .iter()
+        .all(|id| serial.status_age(id).is_some_and(|age| age < at.elapsed()))
+}
+
+/// Scroll the lights from wheel events over the tray icon. Tauri's tray
+/// events don't include the wheel, so this watches the app's own scroll
+/// events and takes the ones that arrive while the pointer is over the icon.
+#[cfg(target_os = "macos")]
+pub fn watch_tray(app: &AppHandle) {
+    appkit::watch_scroll(app.clone());
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn watch_tray(_app: &AppHandle) {}
+
+#[cfg(target_os = "macos")]
+mod appkit {
+    use std::ffi::{c_char, c_void};
+    use std::sync::OnceLock;
+
+    use tauri::{AppHandle, Manager};
+
+    use super::ScrollAdjuster;
+
+    type Id = *mut c_void;
+    type Sel = *mut c_void;
+
+    /// `NSEventMaskScrollWheel`.
+    const SCROLL_WHEEL_MASK: u64 = 1 << 22;
+    /// `NSEventModifierFlagOption`.
+    const OPTION_KEY: u64 = 1 << 19;
+    /// Pixels of trackpad scrolling per wheel notch, as in the panel.
+    const PIXELS_PER_NOTCH: f64 = 50.0;
+    /// `BLOCK_IS_GLOBAL`: the block captures nothing and is never copied.
+    const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+
+    /// The layout of an Objective-C block literal.
+    #[repr(C)]
+    struct Block {
+        isa: *const c_void,
+        flags: i32,
+        reserved: i32,
+        invoke: unsafe extern "C" fn(*mut Block, Id) -> Id,
+        descriptor: *const BlockDescriptor,
+    }
+
+    #[repr(C)]
+    struct BlockDescriptor {
+        reserved: usize,
+        size: usize,
+    }
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    extern "C" {
+        static _NSConcreteGlobalBlock: c_void;
+    }
+
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+
+    fn sel(name: &[u8]) -> Sel {
+        // SAFETY: `name` is a NUL-terminated selector name.
+        unsafe { sel_registerName(name.as_ptr() as *const c_char) }
+    }
+
+    pub fn watch_scroll(app: AppHandle) {
+        if APP.set(app).is_err() {
+            return;
+        }
+        let descriptor = Box::leak(Box::new(BlockDescriptor {
+            reserved: 0,
+            size: std::mem::size_of::<Block>(),
+        }));
+        // SAFETY: the block and its descriptor are leaked so they outlive the
+        // monitor, which AppKit keeps for the life of the app. The handler
+        // is cast to the exact signature of
+        // `+[NSEvent addLocalMonitorForEventsMatchingMask:handler:]`.
+        unsafe {
+            let block = Box::leak(Box::new(Block {
+                isa: &_NSConcreteGlobalBlock,
+                flags: BLOCK_IS_GLOBAL,
+                reserved: 0,
+                invoke: on_scroll,
+                descriptor,
+            }));
+            let add_monitor: unsafe extern "C" fn(Id, Sel, u64, *mut Block) -> Id =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let class = objc_getClass(b"NSEvent\0".as_ptr() as *const c_char);
+            add_monitor(
+                class,
+                sel(b"addLocalMonitorForEventsMatchingMask:handler:\0"),
+                SCROLL_WHEEL_MASK,
+                block,
+            );
+        }
+    }
+
+    /// Called by AppKit on the main thread for each scroll event; passes
+    /// every event on.
+    unsafe extern "C" fn on_scroll(_block: *mut Block, event: Id) -> Id {
+        let Some(app) = APP.get() else {
+            return event;
+        };
+        let scroll = app.state::<ScrollAdjuster>();
+        if !scroll.over_tray() {
+            return event;
+        }
+        let send_f64: unsafe extern "C" fn(Id, Sel) -> f64 =
+            std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let send_i8: unsafe extern "C" fn(Id, Sel) -> i8 =
+            std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let send_u64: unsafe extern "C" fn(Id, Sel) -> u64 =
+            std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+        let dy = send_f64(event, sel(b"scrollingDeltaY\0"));
+        let delta = if send_i8(event, sel(b"hasPreciseScrollingDeltas\0")) != 0 {
+            dy / PIXELS_PER_NOTCH
+        } else {
+            dy
+        };
+        let kelvin = send_u64(event, sel(b"modifierFlags\0")) & OPTION_KEY != 0;
+        // Not connected: nothing to adjust
+        let _ = scroll.scroll(delta, kelvin, app);
+        event
+    }
+}
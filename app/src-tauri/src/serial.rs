@@ -1,142 +1,1168 @@
-/// Serial port management for Neewer PL81-Pro.
+/// Serial port management for Neewer lights.
 ///
-/// Handles port discovery, connection, read loop, and write commands.
-/// Emits "light-status" events to the frontend when status packets arrive.
+/// Handles port discovery, connections to one or more lights (keyed by device
+/// id, see `devices`) and write commands. Each connection is an actor: a
+/// thread that alone owns the port, reads status frames from it and, between
+/// reads, carries out the commands sent to it over a channel. Writes are
+/// queued and return at once, so callers never wait on serial I/O; failures
+/// are reported by the actor. Other commands reply on a channel of their
+/// own, within the port's read timeout. The actor takes every queued command
+/// at once and drops CCT writes a later one in the batch replaces; queue
+/// depth, coalesced writes and time to the wire are kept per connection (see
+/// `WriteStats`), and "write-backlog" is reported when a light falls
+/// [`BACKLOG_WARNING`] writes behind.
+/// Publishes status reports, dropped frames and connection changes on the
+/// event bus (see `events`).
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Read;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+    mpsc, Arc, Mutex,
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use serde::Serialize;
-use tauri::{AppHandle, Emitter};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
 
-use crate::protocol;
+use crate::arbitration::Arbiter;
+use crate::calibration::Calibration;
+use crate::config::SettingsManager;
+use crate::curves::CurveManager;
+use crate::devices::{self, DeviceNames, PreferredDevice};
+use crate::dither::Ditherer;
+use crate::energy::EnergyMeter;
+use crate::errors::{ErrorCode, LightError};
+use crate::events::{Event, EventBus};
+use crate::limits::BrightnessLimits;
+use crate::lock::ControlLock;
+use crate::matching::ColorMatching;
+use crate::models::{self, DeviceModels, Feature};
+use crate::packets::PacketCapture;
+use crate::portconfig::PortConfig;
+use crate::protocol::{Format, Frame, KelvinRange, StatusFrame};
+use crate::registry::DeviceRegistry;
+use crate::sessionlog::{self, SessionLog};
+use crate::transport::{self, Link};
+use crate::usage::UsageTracker;
+use crate::webhooks::{self, WebhookEvent};
+use crate::{errors, notify, protocol, tray};
+
+/// Consecutive write failures before the user is notified.
+const WRITE_FAILURE_NOTIFY: u32 = 3;
+/// Queued writes at which a light is reported as backed up.
+pub const BACKLOG_WARNING: usize = 32;
+/// Status queries sent when connecting, and how long to wait after each.
+const IDENTIFY_ATTEMPTS: u32 = 2;
+const IDENTIFY_TIMEOUT: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone, Serialize)]
 pub struct LightStatus {
+    /// Hardware brightness byte. It and the temperature are as requested,
+    /// without any matching offset (see `matching`).
     pub brightness: u8,
+    /// Brightness as a slider level, through the device's dimming curve.
+    pub level: u8,
     pub kelvin: u32,
+    /// The same temperature in mireds.
+    pub mired: u32,
+    /// Estimated illuminance at the subject, for lux-calibrated lights.
+    pub lux: Option<f64>,
+    /// Device identifier (see `devices`) and its friendly name.
+    pub device: String,
+    pub name: String,
+    /// Seconds left of the manual hold on automations, if one is on.
+    pub hold_secs: Option<u64>,
+    #[serde(flatten)]
+    pub extended: ExtendedStatus,
+}
+
+/// Status fields only some firmware reports; `None` until a light sends them.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExtendedStatus {
+    pub on: Option<bool>,
+    /// Green/magenta shift, -50 (green) to +50 (magenta).
+    pub gm: Option<i8>,
+    pub scene: Option<u8>,
+    pub fan: Option<u8>,
+}
+
+impl ExtendedStatus {
+    fn update(&mut self, frame: StatusFrame) {
+        match frame {
+            StatusFrame::Cct { gm, .. } => self.gm = gm.or(self.gm),
+            StatusFrame::Power { on } => self.on = Some(on),
+            StatusFrame::Scene { scene } => self.scene = Some(scene),
+            StatusFrame::Fan { mode } => self.fan = Some(mode),
+        }
+    }
+}
+
+/// Reported as "parse-error" when frames from a light are dropped as
+/// misframed or corrupt.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseErrors {
+    pub device: String,
+    /// Frames dropped since the light was connected.
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    /// Connecting again after the connection was lost.
+    Reconnecting,
+    /// Closed on request.
+    Disconnected,
+    /// A connection attempt failed or the connection was lost.
+    Error,
+}
+
+/// Reported as "device-state" whenever a light's connection changes.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceStateChange {
+    pub device_id: String,
+    pub state: ConnectionState,
+    pub reason: Option<String>,
+}
+
+/// Reported as "write-backlog" when writes to a light queue up, usually
+/// because its adapter is slow or stalled.
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteBacklog {
+    pub device: String,
+    pub queued: usize,
+}
+
+/// Counters of a connection's write pipeline.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WriteStats {
+    /// Writes waiting for the actor now.
+    pub queued: usize,
+    /// Most writes ever waiting at once.
+    pub max_queued: usize,
+    pub written: u64,
+    /// CCT writes dropped because a later one replaced them in the queue.
+    pub coalesced: u64,
+    pub failed: u64,
+    /// Time from queueing a write to handing it to the port, in ms.
+    pub mean_wire_ms: f64,
+    pub max_wire_ms: f64,
+}
+
+impl WriteStats {
+    fn record_written(&mut self, wire: Duration) {
+        let ms = wire.as_secs_f64() * 1000.0;
+        self.written += 1;
+        self.mean_wire_ms += (ms - self.mean_wire_ms) / self.written as f64;
+        self.max_wire_ms = self.max_wire_ms.max(ms);
+    }
+}
+
+/// Outcome of connecting one port in `connect_all`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortResult {
+    /// Device id, when connected.
+    pub device: Option<String>,
+    pub error: Option<String>,
+}
+
+/// A light's last reported state, kept up to date by its actor.
+#[derive(Clone, Default)]
+struct DeviceState {
+    status: Option<LightStatus>,
+    /// Last (brightness, kelvin) seen while the light was on.
+    last_on: Option<(u8, u32)>,
+    extended: ExtendedStatus,
+    /// When `status` was last reported.
+    updated: Option<Instant>,
+}
+
+type Port = Box<dyn Link>;
+type Reply<T> = mpsc::SyncSender<T>;
+
+/// Requests to a connection's actor.
+enum Command {
+    /// Open the port and, unless disabled in the settings, check that a light
+    /// answers on it.
+    Connect {
+        reply: Reply<Result<(), String>>,
+    },
+    Write {
+        data: Vec<u8>,
+        queued_at: Instant,
+    },
+    /// Ask the light for its status and reply with its answer.
+    Query {
+        reply: Reply<Result<LightStatus, LightError>>,
+    },
+    /// Close the port and stop.
+    Shutdown,
+}
+
+/// Send the command `make` builds around a reply channel and wait for the
+/// reply; `None` if the actor has stopped.
+fn request<T>(
+    commands: &mpsc::Sender<Command>,
+    make: impl FnOnce(Reply<T>) -> Command,
+) -> Option<T> {
+    let (reply, answer) = mpsc::sync_channel(1);
+    commands.send(make(reply)).ok()?;
+    answer.recv().ok()
+}
+
+struct Connection {
+    commands: mpsc::Sender<Command>,
+    path: String,
+    /// Tells this connection apart from later ones to the same device.
+    generation: u64,
+    state: Arc<Mutex<DeviceState>>,
+    stats: Arc<Mutex<WriteStats>>,
 }
 
 pub struct SerialManager {
-    port: Mutex<Option<Box<dyn serialport::SerialPort>>>,
-    reading: Arc<AtomicBool>,
+    connections: Mutex<BTreeMap<String, Connection>>,
+    app: Mutex<Option<AppHandle>>,
+    /// Devices whose connection was lost and not yet re-established.
+    lost: Mutex<BTreeSet<String>>,
+    next_generation: AtomicU64,
 }
 
 impl SerialManager {
     pub fn new() -> Self {
         Self {
-            port: Mutex::new(None),
-            reading: Arc::new(AtomicBool::new(false)),
+            connections: Mutex::new(BTreeMap::new()),
+            app: Mutex::new(None),
+            lost: Mutex::new(BTreeSet::new()),
+            next_generation: AtomicU64::new(0),
         }
     }
 
-    /// Find the first matching USB serial port.
-    pub fn find_port() -> Option<String> {
-        serialport::available_ports()
-            .ok()?
+    /// Start an actor for the serial port and connect it. Reconnects if the
+    /// device is already connected. Unless disabled in the settings, the port
+    /// is only accepted once a light answers a status query, so other
+    /// USB-serial adapters matching the port filter are rejected; the query
+    /// is tried in each command format, and the one the light answers in is
+    /// recorded for it (see `models`). Returns the device id.
+    pub fn connect(&self, path: &str, app: AppHandle) -> Result<String, String> {
+        let id = devices::id_for_port(&app, path);
+        *self.app.lock().unwrap() = Some(app.clone());
+        self.disconnect_device(&id);
+
+        let reconnecting = self.lost.lock().unwrap().contains(&id);
+        let attempt = if reconnecting {
+            ConnectionState::Reconnecting
+        } else {
+            ConnectionState::Connecting
+        };
+        publish_state(&app, &id, attempt, None);
+        let state = Arc::new(Mutex::new(DeviceState::default()));
+        let stats = Arc::new(Mutex::new(WriteStats::default()));
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let (commands, inbox) = mpsc::channel();
+        let actor = Actor {
+            device: id.clone(),
+            path: path.to_string(),
+            format: models::format(&app, &id),
+            generation,
+            state: state.clone(),
+            stats: stats.clone(),
+            app: app.clone(),
+        };
+        std::thread::spawn(move || actor.run(inbox));
+        request(&commands, |reply| Command::Connect { reply })
+            .unwrap_or_else(|| Err("Connection closed".into()))
+            .inspect_err(|e| publish_state(&app, &id, ConnectionState::Error, Some(e)))?;
+        self.lost.lock().unwrap().remove(&id);
+
+        self.connections.lock().unwrap().insert(
+            id.clone(),
+            Connection {
+                commands,
+                path: path.to_string(),
+                generation,
+                state,
+                stats,
+            },
+        );
+
+        publish_state(&app, &id, ConnectionState::Connected, None);
+        let remembered = app.state::<PreferredDevice>().remember(&app, &id);
+        errors::check_save(&app, "remember_device", Some(&id), remembered);
+        let registered = app.state::<DeviceRegistry>().on_connect(&app, &id, path);
+        errors::check_save(&app, "register_device", Some(&id), registered);
+        tray::refresh(&app);
+        webhooks::dispatch(
+            &app,
+            WebhookEvent::Connected,
+            json!({ "device": id, "port": path }),
+        );
+        Ok(id)
+    }
+
+    /// Connect to every light found, over its preferred port, keyed by port
+    /// path. Lights that are already connected are left as they are.
+    pub fn connect_all(&self, app: &AppHandle) -> BTreeMap<String, PortResult> {
+        let open = self.ids();
+        devices::lights(app)
             .into_iter()
-            .find(|p| p.port_name.contains("usbserial"))
-            .map(|p| p.port_name)
+            .map(|light| {
+                let port = light.ports[0].clone();
+                let result = match open.contains(&light.id) {
+                    true => Ok(light.id),
+                    false => self.connect(&port, app.clone()),
+                };
+                let (device, error) = match result {
+                    Ok(id) => (Some(id), None),
+                    Err(e) => (None, Some(e)),
+                };
+                (port, PortResult { device, error })
+            })
+            .collect()
     }
 
-    /// Open the serial port and start the read loop.
-    pub fn connect(&self, path: &str, app: AppHandle) -> Result<(), String> {
-        // Stop any existing read loop
-        self.reading.store(false, Ordering::Relaxed);
+    /// The command channel and write counters of a light's actor.
+    fn commands(
+        &self,
+        id: &str,
+    ) -> Result<(mpsc::Sender<Command>, Arc<Mutex<WriteStats>>), LightError> {
+        let conns = self.connections.lock().unwrap();
+        let conn = conns.get(id).ok_or_else(|| LightError::not_connected(id))?;
+        Ok((conn.commands.clone(), conn.stats.clone()))
+    }
 
-        let port = serialport::new(path, 115200)
-            .data_bits(serialport::DataBits::Eight)
-            .parity(serialport::Parity::None)
-            .stop_bits(serialport::StopBits::One)
-            .timeout(Duration::from_millis(100))
-            .open()
-            .map_err(|e| format!("Failed to open {path}: {e}"))?;
+    /// Queue raw bytes for one light. Returns once queued; the light's actor
+    /// writes them and reports failures.
+    pub fn write_to(&self, id: &str, data: &[u8]) -> Result<(), LightError> {
+        let (commands, stats) = self.commands(id)?;
+        // Counted before sending, so the actor never takes it off first
+        let queued = {
+            let mut stats = stats.lock().unwrap();
+            stats.queued += 1;
+            stats.max_queued = stats.max_queued.max(stats.queued);
+            stats.queued
+        };
+        let command = Command::Write {
+            data: data.to_vec(),
+            queued_at: Instant::now(),
+        };
+        if commands.send(command).is_err() {
+            stats.lock().unwrap().queued -= 1;
+            return Err(LightError::not_connected(id));
+        }
+        if queued == BACKLOG_WARNING {
+            if let Some(app) = self.app.lock().unwrap().clone() {
+                app.state::<EventBus>()
+                    .publish(Event::WriteBacklog(WriteBacklog {
+                        device: id.to_string(),
+                        queued,
+                    }));
+            }
+        }
+        Ok(())
+    }
 
-        // Clone the port for the read thread
-        let reader = port
-            .try_clone()
-            .map_err(|e| format!("Failed to clone port: {e}"))?;
+    /// Write pipeline counters of every connected light, by device id.
+    pub fn write_stats(&self) -> BTreeMap<String, WriteStats> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, c)| (id.clone(), c.stats.lock().unwrap().clone()))
+            .collect()
+    }
 
-        *self.port.lock().unwrap() = Some(port);
+    /// Send a CCT command to one light: brightness 0-100, temperature in Kelvin.
+    /// Stops any dithering on that light.
+    pub fn set_cct_to(&self, id: &str, brightness: u8, kelvin: u32) -> Result<(), LightError> {
+        let app = self.app.lock().unwrap().clone();
+        if let Some(app) = &app {
+            app.state::<Ditherer>().stop(id);
+        }
+        self.write_cct(app.as_ref(), id, brightness, kelvin)
+    }
 
-        // Start background read loop
-        let reading = self.reading.clone();
-        reading.store(true, Ordering::Relaxed);
+    /// Send a CCT command to every connected light.
+    pub fn set_cct(&self, brightness: u8, kelvin: u32) -> Result<(), LightError> {
+        let app = self.app.lock().unwrap().clone();
+        if let Some(app) = &app {
+            app.state::<Ditherer>().stop_all();
+        }
+        let ids = self.ids();
+        if ids.is_empty() {
+            return Err(LightError::new(ErrorCode::NotConnected, "Port not open"));
+        }
+        let errors: Vec<LightError> = ids
+            .iter()
+            .filter_map(|id| self.write_cct(app.as_ref(), id, brightness, kelvin).err())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(LightError::join(errors))
+        }
+    }
 
-        std::thread::spawn(move || {
-            read_loop(reader, reading, app);
-        });
+    /// Send CCT commands to several lights together: (device id,
+    /// brightness, kelvin) each. Every light is checked and capped first, then
+    /// the commands are queued back to back so they reach the lights as close
+    /// together as their connections allow. Stops dithering on those lights.
+    /// Returns each light's result, in order.
+    pub fn set_cct_many(&self, settings: &[(String, u8, u32)]) -> Vec<Result<(), LightError>> {
+        let app = self.app.lock().unwrap().clone();
+        let admitted: Vec<Result<u8, LightError>> = settings
+            .iter()
+            .map(|(id, brightness, _)| {
+                if let Some(app) = &app {
+                    app.state::<Ditherer>().stop(id);
+                }
+                self.admit(app.as_ref(), id, *brightness)
+            })
+            .collect();
+        let results: Vec<Result<(u8, u32), LightError>> = settings
+            .iter()
+            .zip(admitted)
+            .map(|((id, _, kelvin), brightness)| {
+                let brightness = brightness?;
+                let range = range_of(app.as_ref(), id);
+                let kelvin = range.clamp(*kelvin);
+                let (brightness, kelvin) = matched(app.as_ref(), id, brightness, kelvin);
+                let cmd = format_of(app.as_ref(), id).cct_command(&range, brightness, kelvin);
+                self.write_to(id, &cmd)?;
+                Ok((brightness, kelvin))
+            })
+            .collect();
+        if let Some(app) = &app {
+            let log = app.state::<SessionLog>();
+            for ((id, _, _), result) in settings.iter().zip(&results) {
+                if let Ok((brightness, kelvin)) = result {
+                    log.record(id, *brightness, *kelvin);
+                }
+            }
+        }
+        results.into_iter().map(|r| r.map(|_| ())).collect()
+    }
 
+    /// Write a CCT command within the light's brightness cap and with its
+    /// matching offset, unless the controls are locked against the current
+    /// source or a higher-priority source holds the light, and record it in
+    /// the session log.
+    fn write_cct(
+        &self,
+        app: Option<&AppHandle>,
+        id: &str,
+        brightness: u8,
+        kelvin: u32,
+    ) -> Result<(), LightError> {
+        let brightness = self.admit(app, id, brightness)?;
+        let range = range_of(app, id);
+        let kelvin = range.clamp(kelvin);
+        let (brightness, kelvin) = matched(app, id, brightness, kelvin);
+        let cmd = format_of(app, id).cct_command(&range, brightness, kelvin);
+        self.write_to(id, &cmd)?;
+        if let Some(app) = app {
+            app.state::<SessionLog>().record(id, brightness, kelvin);
+        }
         Ok(())
     }
 
-    /// Send raw bytes to the light.
-    pub fn write(&self, data: &[u8]) -> Result<(), String> {
-        let mut lock = self.port.lock().unwrap();
-        let port = lock.as_mut().ok_or("Port not open")?;
-        port.write_all(data).map_err(|e| format!("Write failed: {e}"))?;
-        port.flush().map_err(|e| format!("Flush failed: {e}"))?;
-        Ok(())
+    /// Check a write to light `id` against the control lock and arbitration,
+    /// returning `brightness` within the light's cap.
+    fn admit(&self, app: Option<&AppHandle>, id: &str, brightness: u8) -> Result<u8, LightError> {
+        let Some(app) = app else {
+            return Ok(brightness);
+        };
+        let source = sessionlog::current_source();
+        app.state::<ControlLock>().check(source)?;
+        app.state::<Arbiter>().claim(id, source)?;
+        Ok(app.state::<BrightnessLimits>().clamp(app, id, brightness))
     }
 
-    /// Check if the port is currently open.
+    /// Ask one light for its status, returning its answer.
+    pub fn query(&self, id: &str) -> Result<LightStatus, LightError> {
+        let (commands, _) = self.commands(id)?;
+        request(&commands, |reply| Command::Query { reply })
+            .unwrap_or_else(|| Err(LightError::not_connected(id)))
+    }
+
+    /// Send an HSI colour command to one light: hue in degrees, saturation
+    /// and brightness 0-100. Refused for models without HSI colour.
+    pub fn set_hsi_to(
+        &self,
+        id: &str,
+        hue: u16,
+        saturation: u8,
+        brightness: u8,
+    ) -> Result<(), LightError> {
+        let app = self.app.lock().unwrap().clone();
+        if let Some(app) = &app {
+            app.state::<DeviceModels>().require(id, Feature::Hsi)?;
+            app.state::<Ditherer>().stop(id);
+        }
+        let brightness = self.admit(app.as_ref(), id, brightness)?;
+        let cmd = format_of(app.as_ref(), id).hsi_command(hue, saturation, brightness);
+        self.write_to(id, &cmd)
+    }
+
+    /// Turn one light off or back on, with the power command on models that
+    /// have one. Otherwise off is brightness 0, keeping the temperature, and
+    /// on is the light's last lit state.
+    pub fn set_power_to(&self, id: &str, on: bool) -> Result<(), LightError> {
+        let app = self.app.lock().unwrap().clone();
+        if let Some(app) = &app {
+            if app.state::<DeviceModels>().model(id).profile().power {
+                app.state::<Ditherer>().stop(id);
+                self.admit(Some(app), id, 0)?;
+                return self.write_to(id, &models::format(app, id).power_command(on));
+            }
+        }
+        let state = self.state_of(id)?;
+        let kelvin = state
+            .status
+            .as_ref()
+            .map_or(protocol::DEFAULT_TEMP_K, |s| s.kelvin);
+        let (bri, kelvin) = if on {
+            state.last_on.unwrap_or((100, kelvin))
+        } else {
+            (0, kelvin)
+        };
+        self.set_cct_to(id, bri, kelvin)
+    }
+
+    /// Check if any port is currently open.
     pub fn is_connected(&self) -> bool {
-        self.port.lock().unwrap().is_some()
+        !self.connections.lock().unwrap().is_empty()
+    }
+
+    /// Ids of all connected devices, in id order.
+    pub fn ids(&self) -> Vec<String> {
+        self.connections.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// (device id, port path) of every open connection.
+    pub fn devices(&self) -> Vec<(String, String)> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, c)| (id.clone(), c.path.clone()))
+            .collect()
+    }
+
+    /// (device id, port path) of the primary connection, if any. The primary
+    /// device is the one shown in the tray and used as the reference for
+    /// relative adjustments.
+    pub fn device(&self) -> Option<(String, String)> {
+        self.devices().into_iter().next()
+    }
+
+    /// Last status reported by the primary light, if any.
+    pub fn status(&self) -> Option<LightStatus> {
+        let (id, _) = self.device()?;
+        self.status_of(&id)
+    }
+
+    /// Last status reported by one light, if any.
+    pub fn status_of(&self, id: &str) -> Option<LightStatus> {
+        self.state_of(id).ok()?.status
+    }
+
+    /// Last (brightness, kelvin) one light reported while lit, if any.
+    pub fn last_on(&self, id: &str) -> Option<(u8, u32)> {
+        self.state_of(id).ok()?.last_on
+    }
+
+    /// Time since one light last reported its status, if it has.
+    pub fn status_age(&self, id: &str) -> Option<Duration> {
+        Some(self.state_of(id).ok()?.updated?.elapsed())
+    }
+
+    fn state_of(&self, id: &str) -> Result<DeviceState, LightError> {
+        let conns = self.connections.lock().unwrap();
+        let conn = conns.get(id).ok_or_else(|| LightError::not_connected(id))?;
+        let state = conn.state.lock().unwrap().clone();
+        Ok(state)
+    }
+
+    /// Disconnect one light and stop its actor.
+    pub fn disconnect_device(&self, id: &str) {
+        let conn = self.connections.lock().unwrap().remove(id);
+        if let Some(conn) = conn {
+            let _ = conn.commands.send(Command::Shutdown);
+            self.on_closed(id);
+        }
     }
 
-    /// Disconnect and stop the read loop.
+    /// Disconnect every light and stop the actors.
     pub fn disconnect(&self) {
-        self.reading.store(false, Ordering::Relaxed);
-        *self.port.lock().unwrap() = None;
+        for (id, conn) in std::mem::take(&mut *self.connections.lock().unwrap()) {
+            let _ = conn.commands.send(Command::Shutdown);
+            self.on_closed(&id);
+        }
+    }
+
+    fn on_closed(&self, id: &str) {
+        self.lost.lock().unwrap().remove(id);
+        if let Some(app) = self.app.lock().unwrap().clone() {
+            publish_state(&app, id, ConnectionState::Disconnected, None);
+        }
+    }
+
+    /// Drop a connection only if it is still generation `generation`, so a
+    /// dying actor can't remove a newer connection to the same device.
+    fn remove_if_current(&self, id: &str, generation: u64) {
+        let mut conns = self.connections.lock().unwrap();
+        if conns.get(id).is_some_and(|c| c.generation == generation) {
+            conns.remove(id);
+        }
     }
 }
 
-/// Background read loop — parses 8-byte status packets and emits events.
-fn read_loop(
-    mut port: Box<dyn serialport::SerialPort>,
-    running: Arc<AtomicBool>,
-    app: AppHandle,
-) {
+/// The temperature range of light `id`.
+fn range_of(app: Option<&AppHandle>, id: &str) -> KelvinRange {
+    match app {
+        Some(app) => models::range(app, id),
+        None => protocol::DEFAULT_RANGE,
+    }
+}
+
+/// What to send light `id` for a request within its range and cap, with
+/// its colour-matching offset.
+fn matched(app: Option<&AppHandle>, id: &str, brightness: u8, kelvin: u32) -> (u8, u32) {
+    match app {
+        Some(app) => app
+            .state::<ColorMatching>()
+            .apply(app, id, brightness, kelvin),
+        None => (brightness, kelvin),
+    }
+}
+
+/// The command format light `id` speaks.
+fn format_of(app: Option<&AppHandle>, id: &str) -> Format {
+    app.map_or(Format::Standard, |app| models::format(app, id))
+}
+
+fn publish_state(app: &AppHandle, id: &str, state: ConnectionState, reason: Option<&str>) {
+    app.state::<EventBus>()
+        .publish(Event::DeviceState(DeviceStateChange {
+            device_id: id.to_string(),
+            state,
+            reason: reason.map(str::to_string),
+        }));
+}
+
+/// Open `path` for light `id` with its serial parameters, returning the port,
+/// the format the light speaks and its answer to the identify handshake.
+/// Without the handshake the format is the one last known for `id`.
+fn open(id: &str, path: &str, app: &AppHandle) -> Result<(Port, Format, Vec<Frame>), String> {
+    let params = app.state::<PortConfig>().params(id);
+    let mut port = transport::open(path, &params)?;
+    let models = app.state::<DeviceModels>();
+    let known = models.format(id);
+    if !app.state::<SettingsManager>().get().identify_on_connect {
+        return Ok((port, known, Vec::new()));
+    }
+    let (format, answer) = detect(port.as_mut(), known).map_err(|e| format!("{path}: {e}"))?;
+    errors::check_save(
+        app,
+        "detect_format",
+        Some(id),
+        models.detect(app, id, format),
+    );
+    Ok((port, format, answer))
+}
+
+fn write(port: &mut dyn Link, data: &[u8]) -> std::io::Result<()> {
+    port.write_all(data)?;
+    port.flush()
+}
+
+/// Ask the light on `port` for its status in each known format, `first`
+/// first, returning the format it answers in and the frames it answers with.
+fn detect(port: &mut dyn Link, first: Format) -> Result<(Format, Vec<Frame>), String> {
+    let others = Format::ALL.into_iter().filter(|&f| f != first);
+    let mut error = String::new();
+    for format in std::iter::once(first).chain(others) {
+        match identify(port, format) {
+            Ok(frames) => return Ok((format, frames)),
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// Ask the light on `port` for its status in `format`, returning the frames
+/// it answers with. Fails if nothing valid arrives in time.
+fn identify(port: &mut dyn Link, format: Format) -> Result<Vec<Frame>, String> {
+    let mut parser = protocol::FrameParser::for_format(format);
     let mut buf = [0u8; 256];
-    let mut accum: Vec<u8> = Vec::new();
-
-    while running.load(Ordering::Relaxed) {
-        match port.read(&mut buf) {
-            Ok(n) if n > 0 => {
-                accum.extend_from_slice(&buf[..n]);
-                // Try to parse complete 8-byte packets
-                while accum.len() >= 8 {
-                    // Find 0x3A start byte
-                    if let Some(start) = accum.iter().position(|&b| b == 0x3A) {
-                        if start > 0 {
-                            accum.drain(..start);
+    for _ in 0..IDENTIFY_ATTEMPTS {
+        port.write_all(&format.status_query())
+            .and_then(|_| port.flush())
+            .map_err(|e| format!("Write failed: {e}"))?;
+        let deadline = Instant::now() + IDENTIFY_TIMEOUT;
+        while Instant::now() < deadline {
+            match port.read(&mut buf) {
+                Ok(n) => {
+                    let frames = parser.push(&buf[..n]);
+                    if !frames.is_empty() {
+                        return Ok(frames);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(format!("Read failed: {e}")),
+            }
+        }
+    }
+    Err("No answer from a Neewer light; is this the right port?".into())
+}
+
+/// The thread owning a light's port.
+struct Actor {
+    device: String,
+    path: String,
+    /// The command format the light speaks, as detected when connected.
+    format: Format,
+    generation: u64,
+    state: Arc<Mutex<DeviceState>>,
+    stats: Arc<Mutex<WriteStats>>,
+    app: AppHandle,
+}
+
+impl Actor {
+    fn run(mut self, inbox: mpsc::Receiver<Command>) {
+        // Until connected there's nothing to read, so just wait for commands
+        let mut port = loop {
+            match inbox.recv() {
+                Ok(Command::Connect { reply }) => match open(&self.device, &self.path, &self.app) {
+                    Ok((port, format, answer)) => {
+                        self.format = format;
+                        // The identify handshake's answer
+                        for frame in answer {
+                            self.on_frame(frame);
                         }
-                        if accum.len() < 8 {
-                            break;
+                        let _ = reply.send(Ok(()));
+                        break port;
+                    }
+                    Err(e) => {
+                        let _ = reply.send(Err(e));
+                    }
+                },
+                // Only sent once connected
+                Ok(Command::Write { .. }) => {}
+                Ok(Command::Query { reply }) => {
+                    let _ = reply.send(Err(LightError::not_connected(&self.device)));
+                }
+                Ok(Command::Shutdown) | Err(_) => return,
+            }
+        };
+        let lost = self.serve(port.as_mut(), &inbox);
+        drop(port);
+        self.app.state::<UsageTracker>().on_disconnect(&self.device);
+        let registered = self
+            .app
+            .state::<DeviceRegistry>()
+            .on_disconnect(&self.app, &self.device);
+        errors::check_save(&self.app, "register_device", Some(&self.device), registered);
+        self.app.state::<EnergyMeter>().on_disconnect(&self.device);
+        webhooks::dispatch(
+            &self.app,
+            WebhookEvent::Disconnected,
+            json!({ "device": self.device, "reason": if lost { "lost" } else { "closed" } }),
+        );
+        if lost {
+            self.fail_over();
+        }
+    }
+
+    /// Read status frames and carry out commands until shut down or the
+    /// connection is lost. Returns whether it was lost.
+    fn serve(&self, port: &mut dyn Link, inbox: &mpsc::Receiver<Command>) -> bool {
+        let mut buf = [0u8; 256];
+        let mut parser = protocol::FrameParser::for_format(self.format);
+        // Queries waiting for the light's answer, with their deadlines
+        let mut queries: Vec<(Reply<Result<LightStatus, LightError>>, Instant)> = Vec::new();
+        let mut write_failures = 0;
+        loop {
+            let mut batch = Vec::new();
+            let closed = loop {
+                match inbox.try_recv() {
+                    Ok(command) => batch.push(command),
+                    Err(mpsc::TryRecvError::Empty) => break false,
+                    Err(mpsc::TryRecvError::Disconnected) => break true,
+                }
+            };
+            // Only the last CCT write of a batch needs to reach the light
+            let last_cct = batch.iter().rposition(
+                |c| matches!(c, Command::Write { data, .. } if protocol::is_cct_command(data)),
+            );
+            for (i, command) in batch.into_iter().enumerate() {
+                match command {
+                    Command::Connect { reply } => {
+                        let _ = reply.send(Ok(()));
+                    }
+                    Command::Write { data, queued_at } => {
+                        let superseded = protocol::is_cct_command(&data) && Some(i) != last_cct;
+                        self.write(port, &data, queued_at, superseded, &mut write_failures);
+                    }
+                    Command::Query { reply } => match write(port, &self.status_queries()) {
+                        Ok(()) => queries.push((reply, Instant::now() + IDENTIFY_TIMEOUT)),
+                        Err(e) => {
+                            errors::report_io(&self.app, "query", &self.device, &e);
+                            let _ = reply.send(Err(LightError::new(
+                                ErrorCode::Io,
+                                format!("Write failed: {e}"),
+                            )));
                         }
-                        if let Some((bri, temp_byte)) = protocol::parse_status(&accum[..8]) {
-                            let status = LightStatus {
-                                brightness: bri,
-                                kelvin: protocol::byte_to_kelvin(temp_byte),
-                            };
-                            let _ = app.emit("light-status", &status);
+                    },
+                    Command::Shutdown => return false,
+                }
+            }
+            if closed {
+                return false;
+            }
+
+            match port.read(&mut buf) {
+                Ok(n) if n > 0 => {
+                    let errors = parser.errors();
+                    for frame in parser.push(&buf[..n]) {
+                        if let Some(status) = self.on_frame(frame) {
+                            for (reply, _) in queries.drain(..) {
+                                let _ = reply.send(Ok(status.clone()));
+                            }
                         }
-                        accum.drain(..8);
-                    } else {
-                        accum.clear();
-                        break;
+                    }
+                    if parser.errors() > errors {
+                        self.app
+                            .state::<EventBus>()
+                            .publish(Event::ParseErrors(ParseErrors {
+                                device: self.device.clone(),
+                                count: parser.errors(),
+                            }));
+                    }
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => {
+                    errors::report_io(&self.app, "read", &self.device, &e);
+                    self.on_lost();
+                    return true;
+                }
+                _ => {}
+            }
+
+            let now = Instant::now();
+            queries.retain(|(reply, deadline)| {
+                let waiting = now < *deadline;
+                if !waiting {
+                    let _ = reply.send(Err(LightError::new(
+                        ErrorCode::Timeout,
+                        "No answer from the light",
+                    )));
+                }
+                waiting
+            });
+        }
+    }
+
+    /// Carry out a queued write, unless `superseded`, and count it.
+    fn write(
+        &self,
+        port: &mut dyn Link,
+        data: &[u8],
+        queued_at: Instant,
+        superseded: bool,
+        failures: &mut u32,
+    ) {
+        self.stats.lock().unwrap().queued -= 1;
+        if superseded {
+            self.stats.lock().unwrap().coalesced += 1;
+            return;
+        }
+        let wire = queued_at.elapsed();
+        match write(port, data) {
+            Ok(()) => {
+                *failures = 0;
+                self.stats.lock().unwrap().record_written(wire);
+                // Legacy firmware doesn't echo commands or report its CCT
+                // state, so what was written is the best status there is
+                if self.format == Format::Legacy && protocol::is_cct_command(data) {
+                    for frame in protocol::FrameParser::for_format(self.format).push(data) {
+                        self.on_frame(frame);
                     }
                 }
             }
-            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
-            Err(_) => {
-                let _ = app.emit("serial-disconnected", ());
-                break;
+            Err(e) => {
+                *failures += 1;
+                self.stats.lock().unwrap().failed += 1;
+                errors::report_io(&self.app, "write", &self.device, &e);
+                // Notify once when the failure streak reaches the threshold
+                if *failures == WRITE_FAILURE_NOTIFY {
+                    let name = self
+                        .app
+                        .state::<DeviceNames>()
+                        .name(&self.device, &self.path);
+                    notify::error(
+                        &self.app,
+                        &format!("{name} not responding"),
+                        &format!("Write failed: {e}"),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Queries for the light's state: CCT, and power, scene and fan on
+    /// firmware that has reported them. Legacy firmware only reports power.
+    fn status_queries(&self) -> Vec<u8> {
+        let mut data = self.format.status_query();
+        if self.format == Format::Legacy {
+            return data;
+        }
+        let extended = self.state.lock().unwrap().extended.clone();
+        if extended.on.is_some() {
+            data.extend(protocol::power_query());
+        }
+        if extended.scene.is_some() {
+            data.extend(protocol::scene_query());
+        }
+        if extended.fan.is_some() {
+            data.extend(protocol::fan_query());
+        }
+        data
+    }
+
+    fn on_frame(&self, frame: Frame) -> Option<LightStatus> {
+        let hsi = self
+            .app
+            .state::<DeviceModels>()
+            .model(&self.device)
+            .profile()
+            .hsi;
+        match self.format.status(&frame, hsi) {
+            Some(status) => on_status(
+                &self.app,
+                (&self.device, &self.path),
+                self.format,
+                &self.state,
+                status,
+            ),
+            None => {
+                self.app
+                    .state::<PacketCapture>()
+                    .record(&self.device, &frame);
+                None
+            }
+        }
+    }
+
+    fn on_lost(&self) {
+        let serial = self.app.state::<SerialManager>();
+        serial.remove_if_current(&self.device, self.generation);
+        serial.lost.lock().unwrap().insert(self.device.clone());
+    }
+
+    /// After a lost connection, connect to the light over another port if it
+    /// has one attached, otherwise report it disconnected.
+    fn fail_over(&self) {
+        let (app, device) = (&self.app, &self.device);
+        let serial = app.state::<SerialManager>();
+        let others = devices::other_ports(app, device, &self.path);
+        if others
+            .iter()
+            .any(|port| serial.connect(port, app.clone()).is_ok())
+        {
+            return;
+        }
+        publish_state(
+            app,
+            device,
+            ConnectionState::Error,
+            Some("The USB connection was lost."),
+        );
+        let name = app.state::<DeviceNames>().name(device, &self.path);
+        notify::error(
+            app,
+            &format!("{name} disconnected"),
+            "The USB connection was lost.",
+        );
+        tray::refresh(app);
+    }
+}
+
+/// Update state and publish a status frame from a light, returning the
+/// light's new status.
+fn on_status(
+    app: &AppHandle,
+    (device, path): (&str, &str),
+    format: Format,
+    state: &Mutex<DeviceState>,
+    frame: StatusFrame,
+) -> Option<LightStatus> {
+    let (bri, kelvin, extended) = {
+        let mut state = state.lock().unwrap();
+        state.extended.update(frame);
+        let (bri, kelvin) = match frame {
+            StatusFrame::Cct {
+                brightness,
+                temp_byte,
+                ..
+            } => (
+                brightness,
+                format.kelvin(&models::range(app, device), temp_byte),
+            ),
+            // Other frames re-report the last brightness and temperature
+            _ => match &state.status {
+                Some(last) => (last.brightness, last.kelvin),
+                None => return None,
+            },
+        };
+        (bri, kelvin, state.extended.clone())
+    };
+    let lux = app.state::<Calibration>().estimate(device, bri);
+    let (bri, kelvin) = app
+        .state::<ColorMatching>()
+        .requested(app, device, bri, kelvin);
+    let status = LightStatus {
+        brightness: bri,
+        level: app.state::<CurveManager>().to_level(device, bri),
+        kelvin,
+        mired: protocol::kelvin_to_mired(kelvin),
+        lux,
+        device: device.to_string(),
+        name: app.state::<DeviceNames>().name(device, path),
+        hold_secs: app
+            .state::<Arbiter>()
+            .hold_remaining()
+            .map(|left| left.as_secs() + 1),
+        extended,
+    };
+    {
+        let mut state = state.lock().unwrap();
+        if status.brightness > 0 {
+            state.last_on = Some((status.brightness, status.kelvin));
+        }
+        state.status = Some(status.clone());
+        state.updated = Some(Instant::now());
+    }
+    app.state::<UsageTracker>().on_status(&status);
+    app.state::<DeviceRegistry>().on_status(&status);
+    app.state::<EnergyMeter>().on_status(&status);
+    // Echoes of dither writes alternate between two bytes; keep them out of
+    // events
+    if !app.state::<Ditherer>().is_active(device) {
+        app.state::<EventBus>()
+            .publish(Event::Status(status.clone()));
+    }
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portconfig::SerialParams;
+    use crate::vpty::FakeLight;
+
+    fn open_port(light: &FakeLight) -> Port {
+        transport::open(light.path(), &SerialParams::default()).unwrap()
+    }
+
+    #[test]
+    fn test_identify_reads_the_lights_answer() {
+        let light = FakeLight::new(40, 5600);
+        let mut port = open_port(&light);
+        let frames = identify(port.as_mut(), Format::Standard).unwrap();
+        assert_eq!(
+            frames[0].status(false),
+            Some(StatusFrame::Cct {
+                brightness: 40,
+                temp_byte: protocol::kelvin_to_byte(&protocol::DEFAULT_RANGE, 5600),
+                gm: None,
+            })
+        );
+        assert_eq!(light.received()[0].payload, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_identify_rejects_a_silent_port() {
+        let light = FakeLight::silent();
+        let mut port = open_port(&light);
+        assert!(identify(port.as_mut(), Format::Standard).is_err());
+        assert_eq!(light.received().len(), IDENTIFY_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn test_detect_finds_the_format_the_light_answers_in() {
+        let light = FakeLight::new(40, 5600);
+        let mut port = open_port(&light);
+        let (format, frames) = detect(port.as_mut(), Format::Legacy).unwrap();
+        assert_eq!(format, Format::Standard);
+        assert!(!frames.is_empty());
+    }
+
+    #[test]
+    fn test_writes_reach_the_light_and_are_echoed() {
+        let light = FakeLight::new(0, 2900);
+        let mut port = open_port(&light);
+        write(
+            port.as_mut(),
+            &protocol::cct_command(&protocol::DEFAULT_RANGE, 70, 4000),
+        )
+        .unwrap();
+        let mut parser = protocol::FrameParser::new();
+        let mut buf = [0u8; 64];
+        let deadline = Instant::now() + IDENTIFY_TIMEOUT;
+        let mut frames = Vec::new();
+        while frames.is_empty() && Instant::now() < deadline {
+            if let Ok(n) = port.read(&mut buf) {
+                frames = parser.push(&buf[..n]);
+            }
+        }
+        assert!(matches!(
+            frames[0].status(false),
+            Some(StatusFrame::Cct { brightness: 70, .. })
+        ));
+        assert_eq!(light.received()[0].payload[1], 70);
+    }
+
+    #[test]
+    fn test_garbage_and_split_frames_from_the_port() {
+        let mut light = FakeLight::silent();
+        let mut port = open_port(&light);
+        let status = protocol::cct_command(&protocol::DEFAULT_RANGE, 25, 3200);
+        light.send(&[0x00, 0x3A, 0xFF]);
+        light.send(&status[..4]);
+        std::thread::sleep(Duration::from_millis(20));
+        light.send(&status[4..]);
+        let mut parser = protocol::FrameParser::new();
+        let mut buf = [0u8; 64];
+        let deadline = Instant::now() + IDENTIFY_TIMEOUT;
+        let mut frames = Vec::new();
+        while frames.is_empty() && Instant::now() < deadline {
+            if let Ok(n) = port.read(&mut buf) {
+                frames = parser.push(&buf[..n]);
             }
-            _ => continue,
         }
+        assert_eq!(frames.len(), 1);
+        assert_eq!(parser.errors(), 1);
+    }
+
+    #[test]
+    fn test_unplugged_light_fails_reads() {
+        let light = FakeLight::new(50, 5000);
+        let mut port = open_port(&light);
+        light.unplug();
+        let mut buf = [0u8; 64];
+        let deadline = Instant::now() + IDENTIFY_TIMEOUT;
+        let err = loop {
+            match port.read(&mut buf) {
+                Err(e) if e.kind() != std::io::ErrorKind::TimedOut => break Some(e),
+                _ if Instant::now() >= deadline => break None,
+                _ => {}
+            }
+        };
+        // What the actor takes as a lost connection
+        assert!(err.is_some());
     }
 }
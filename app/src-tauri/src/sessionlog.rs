@@ -0,0 +1,176 @@
+/// Session log of light changes.
+///
+/// Every CCT write to a light is recorded with a timestamp and the source that
+/// caused it, and the log can be exported as CSV or JSON for production
+/// records. The source is tracked per thread: the panel, tray and shortcuts
+/// run as `Manual`, automation loops mark their threads `Automation` when they
+/// start, network integrations mark theirs `External`, and preset applies are
+/// wrapped in `Preset`. Consecutive identical writes to a light are recorded
+/// once, dithering writes are not recorded, and the log keeps the most recent
+/// [`MAX_ENTRIES`] entries for the current session only.
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+pub const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Manual,
+    Preset,
+    Automation,
+    External,
+}
+
+impl Source {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::Manual => "manual",
+            Source::Preset => "preset",
+            Source::Automation => "automation",
+            Source::External => "external",
+        }
+    }
+}
+
+thread_local! {
+    static SOURCE: Cell<Source> = const { Cell::new(Source::Manual) };
+}
+
+/// Source of changes made from the current thread.
+pub fn current_source() -> Source {
+    SOURCE.with(Cell::get)
+}
+
+/// Attribute every later change from the current thread to `source`.
+pub fn set_source(source: Source) {
+    SOURCE.with(|s| s.set(source));
+}
+
+/// Run `f` with its changes attributed to `source`.
+pub fn with_source<T>(source: Source, f: impl FnOnce() -> T) -> T {
+    let previous = SOURCE.with(|s| s.replace(source));
+    let result = f();
+    set_source(previous);
+    result
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogEntry {
+    /// Local time, RFC 3339 with milliseconds.
+    pub time: String,
+    pub device: String,
+    pub brightness: u8,
+    pub kelvin: u32,
+    pub source: Source,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Default)]
+struct Log {
+    entries: VecDeque<LogEntry>,
+    /// Last (brightness, kelvin) recorded per device.
+    last: BTreeMap<String, (u8, u32)>,
+}
+
+pub struct SessionLog {
+    log: Mutex<Log>,
+}
+
+impl SessionLog {
+    pub fn new() -> Self {
+        Self {
+            log: Mutex::new(Log::default()),
+        }
+    }
+
+    /// Record a write to a light, attributed to the current thread's source.
+    pub fn record(&self, device: &str, brightness: u8, kelvin: u32) {
+        let mut log = self.log.lock().unwrap();
+        if log.last.insert(device.to_string(), (brightness, kelvin)) == Some((brightness, kelvin)) {
+            return;
+        }
+        log.entries.push_back(LogEntry {
+            time: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            device: device.to_string(),
+            brightness,
+            kelvin,
+            source: current_source(),
+        });
+        if log.entries.len() > MAX_ENTRIES {
+            log.entries.pop_front();
+        }
+    }
+
+    pub fn export(&self, format: ExportFormat) -> Result<String, String> {
+        let log = self.log.lock().unwrap();
+        match format {
+            ExportFormat::Json => {
+                serde_json::to_string_pretty(&log.entries).map_err(|e| e.to_string())
+            }
+            ExportFormat::Csv => Ok(to_csv(log.entries.iter())),
+        }
+    }
+}
+
+fn to_csv<'a>(entries: impl Iterator<Item = &'a LogEntry>) -> String {
+    let mut out = String::from("time,device,brightness,kelvin,source\n");
+    for e in entries {
+        out += &format!(
+            "{},{},{},{},{}\n",
+            e.time,
+            csv_field(&e.device),
+            e.brightness,
+            e.kelvin,
+            e.source.as_str()
+        );
+    }
+    out
+}
+
+/// Quote a field if it contains a separator, quote or line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_source_restores() {
+        assert_eq!(current_source(), Source::Manual);
+        let inner = with_source(Source::Preset, current_source);
+        assert_eq!(inner, Source::Preset);
+        assert_eq!(current_source(), Source::Manual);
+    }
+
+    #[test]
+    fn test_csv_escapes_device() {
+        let entry = LogEntry {
+            time: "2026-01-01T09:00:00.000+01:00".into(),
+            device: "a,\"b\"".into(),
+            brightness: 40,
+            kelvin: 5600,
+            source: Source::Automation,
+        };
+        assert_eq!(
+            to_csv([entry].iter()),
+            "time,device,brightness,kelvin,source\n\
+             2026-01-01T09:00:00.000+01:00,\"a,\"\"b\"\"\",40,5600,automation\n"
+        );
+    }
+}
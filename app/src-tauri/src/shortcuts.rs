@@ -0,0 +1,209 @@
+/// User-configurable global shortcuts.
+///
+/// Bindings map an accelerator string (e.g. "CommandOrControl+Alt+1") to a
+/// light action. They are persisted under `shortcuts` in the settings store and
+/// registered with the global-shortcut plugin on launch. Brightness and
+/// temperature steps are shown in the HUD (see `hud`).
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tauri_plugin_store::StoreExt;
+
+use crate::config::SettingsManager;
+use crate::effects::EffectEngine;
+use crate::history::History;
+use crate::hud::Hud;
+use crate::serial::SerialManager;
+use crate::toggle::PowerToggle;
+use crate::{models, notify, presets, steps, STORE_FILE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShortcutAction {
+    TogglePower,
+    BrightnessUp,
+    BrightnessDown,
+    KelvinUp,
+    KelvinDown,
+    ApplyPreset { index: usize },
+    /// Stop any running effect or strobe and restore the lights.
+    StopEffects,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Binding {
+    pub accelerator: String,
+    pub action: ShortcutAction,
+}
+
+pub struct ShortcutManager {
+    bindings: Mutex<Vec<Binding>>,
+}
+
+impl ShortcutManager {
+    pub fn new() -> Self {
+        Self {
+            bindings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Load persisted bindings and register them if shortcuts are enabled.
+    pub fn init(&self, app: &AppHandle) {
+        let saved: Vec<Binding> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get("shortcuts"))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.bindings.lock().unwrap() = saved;
+        if app.state::<SettingsManager>().get().shortcuts {
+            self.set_active(app, true);
+        }
+    }
+
+    /// Register or unregister every binding. Bindings that fail to register
+    /// (e.g. taken by another app since the last run) stay listed but inactive.
+    pub fn set_active(&self, app: &AppHandle, active: bool) {
+        for binding in self.bindings.lock().unwrap().iter() {
+            let Ok(shortcut) = binding.accelerator.parse::<Shortcut>() else {
+                continue;
+            };
+            if active {
+                let _ = register(app, shortcut, binding.action);
+            } else {
+                let _ = app.global_shortcut().unregister(shortcut);
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<Binding> {
+        self.bindings.lock().unwrap().clone()
+    }
+
+    /// Bind an accelerator to an action. Fails if the accelerator is invalid,
+    /// already bound here, or held by another application. While shortcuts are
+    /// disabled the binding is only saved.
+    pub fn bind(
+        &self,
+        app: &AppHandle,
+        accelerator: &str,
+        action: ShortcutAction,
+    ) -> Result<(), String> {
+        let shortcut = parse(accelerator)?;
+        let mut bindings = self.bindings.lock().unwrap();
+        if let Some(existing) = bindings
+            .iter()
+            .find(|b| b.accelerator.parse::<Shortcut>().ok() == Some(shortcut))
+        {
+            return Err(format!(
+                "{accelerator} is already bound to {:?}",
+                existing.action
+            ));
+        }
+        if app.state::<SettingsManager>().get().shortcuts {
+            register(app, shortcut, action).map_err(|e| {
+                format!("{accelerator} is unavailable (in use by another app?): {e}")
+            })?;
+        }
+        bindings.push(Binding {
+            accelerator: accelerator.to_string(),
+            action,
+        });
+        save(app, &bindings)
+    }
+
+    /// Remove the binding for an accelerator.
+    pub fn unbind(&self, app: &AppHandle, accelerator: &str) -> Result<(), String> {
+        let shortcut = parse(accelerator)?;
+        let mut bindings = self.bindings.lock().unwrap();
+        let before = bindings.len();
+        bindings.retain(|b| b.accelerator.parse::<Shortcut>().ok() != Some(shortcut));
+        if bindings.len() == before {
+            return Err(format!("{accelerator} is not bound"));
+        }
+        let _ = app.global_shortcut().unregister(shortcut);
+        save(app, &bindings)
+    }
+
+    /// Run an action against the connected lights. Steps move each light
+    /// from where it is (see `steps`); the HUD shows where the primary light
+    /// ended up.
+    pub fn run(&self, app: &AppHandle, action: ShortcutAction) -> Result<(), String> {
+        let serial = app.state::<SerialManager>();
+        let primary = serial.status();
+        let hud = app.state::<Hud>();
+
+        match action {
+            ShortcutAction::StopEffects => {
+                app.state::<EffectEngine>().stop(app);
+            }
+            ShortcutAction::ApplyPreset { index } => {
+                presets::apply(app, index, None)?;
+            }
+            ShortcutAction::TogglePower => {
+                app.state::<History>().checkpoint(app);
+                app.state::<PowerToggle>().toggle(app, None, Duration::ZERO)?;
+            }
+            ShortcutAction::BrightnessUp | ShortcutAction::BrightnessDown => {
+                let step = steps::DEFAULT_BRIGHTNESS_STEP as i32;
+                let delta = if action == ShortcutAction::BrightnessUp {
+                    step
+                } else {
+                    -step
+                };
+                let report = steps::brightness(app, None, delta)?;
+                if let Some(status) = primary.filter(|s| report.succeeded.contains(&s.device)) {
+                    hud.brightness(app, steps::step_level(status.level, delta));
+                }
+            }
+            ShortcutAction::KelvinUp | ShortcutAction::KelvinDown => {
+                let step = steps::DEFAULT_KELVIN_STEP as i32;
+                let delta = if action == ShortcutAction::KelvinUp {
+                    step
+                } else {
+                    -step
+                };
+                let report = steps::kelvin(app, None, delta)?;
+                if let Some(status) = primary {
+                    if let Some(step) = report.steps.get(&status.device) {
+                        hud.kelvin(app, step.kelvin, &models::range(app, &status.device));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse(accelerator: &str) -> Result<Shortcut, String> {
+    accelerator
+        .parse::<Shortcut>()
+        .map_err(|e| format!("Invalid shortcut {accelerator}: {e}"))
+}
+
+fn register(
+    app: &AppHandle,
+    shortcut: Shortcut,
+    action: ShortcutAction,
+) -> Result<(), tauri_plugin_global_shortcut::Error> {
+    app.global_shortcut()
+        .on_shortcut(shortcut, move |app, _shortcut, event| {
+            if event.state() == ShortcutState::Pressed {
+                if let Err(e) = app.state::<ShortcutManager>().run(app, action) {
+                    notify::error(app, "Shortcut failed", &e);
+                }
+            }
+        })
+}
+
+fn save(app: &AppHandle, bindings: &[Binding]) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        "shortcuts",
+        serde_json::to_value(bindings).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
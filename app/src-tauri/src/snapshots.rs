@@ -0,0 +1,119 @@
+/// Snapshots of every connected light.
+///
+/// Taking a snapshot records each connected light's level and temperature
+/// with the time, so a known-good setup can be brought back after
+/// experimenting. Restoring sets the lights in a snapshot together (see
+/// `batch`); lights not connected at the time are reported as failed. The
+/// [`MAX_SNAPSHOTS`] most recent are kept, persisted under `snapshots` in
+/// the settings store.
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::batch::{self, BatchEntry};
+use crate::groups::FanOutReport;
+use crate::STORE_FILE;
+
+const SNAPSHOTS_KEY: &str = "snapshots";
+pub const MAX_SNAPSHOTS: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: u64,
+    /// Local time, RFC 3339.
+    pub time: String,
+    pub lights: Vec<BatchEntry>,
+}
+
+pub struct Snapshots {
+    /// Oldest first.
+    snapshots: Mutex<VecDeque<Snapshot>>,
+}
+
+impl Snapshots {
+    pub fn new() -> Self {
+        Self {
+            snapshots: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: VecDeque<Snapshot> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(SNAPSHOTS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.snapshots.lock().unwrap() = saved;
+    }
+
+    /// Snapshots, newest first.
+    pub fn list(&self) -> Vec<Snapshot> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    /// Record every connected light as it is now, dropping the oldest
+    /// snapshot beyond the limit.
+    pub fn take(&self, app: &AppHandle) -> Result<Snapshot, String> {
+        let lights = batch::capture(app);
+        if lights.is_empty() {
+            return Err("No light has reported its state yet".into());
+        }
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let snapshot = Snapshot {
+            id: snapshots.back().map_or(1, |s| s.id + 1),
+            time: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            lights,
+        };
+        snapshots.push_back(snapshot.clone());
+        while snapshots.len() > MAX_SNAPSHOTS {
+            snapshots.pop_front();
+        }
+        persist(app, &snapshots)?;
+        Ok(snapshot)
+    }
+
+    /// Set the lights back to snapshot `id`.
+    pub fn restore(&self, app: &AppHandle, id: u64) -> Result<FanOutReport, String> {
+        let lights = self.get(id)?.lights;
+        batch::apply(app, &lights)
+    }
+
+    pub fn delete(&self, app: &AppHandle, id: u64) -> Result<(), String> {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let len = snapshots.len();
+        snapshots.retain(|s| s.id != id);
+        if snapshots.len() == len {
+            return Err(format!("No snapshot {id}"));
+        }
+        persist(app, &snapshots)
+    }
+
+    fn get(&self, id: u64) -> Result<Snapshot, String> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|s| s.id == id)
+            .cloned()
+            .ok_or_else(|| format!("No snapshot {id}"))
+    }
+}
+
+fn persist(app: &AppHandle, snapshots: &VecDeque<Snapshot>) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        SNAPSHOTS_KEY,
+        serde_json::to_value(snapshots).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
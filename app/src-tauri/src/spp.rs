@@ -0,0 +1,192 @@
+/// Bluetooth Classic serial (SPP) transport.
+///
+/// Some Neewer control boxes expose the serial protocol over Bluetooth. Once
+/// paired, macOS and Windows present them as serial ports named after the
+/// device, which discovery lists alongside USB adapters. On Linux, where a
+/// paired device has no port until bound with `rfcomm`, the light is connected
+/// to directly over an RFCOMM socket as `bt://AA:BB:CC:DD:EE:FF`, optionally
+/// followed by `/channel` (channel 1 by default).
+use std::time::Duration;
+
+use crate::transport::Link;
+
+pub const SCHEME: &str = "bt://";
+const DEFAULT_CHANNEL: u8 = 1;
+
+/// Whether a serial port is a paired Neewer Bluetooth device, e.g. macOS's
+/// `/dev/cu.NEEWER-CB60`.
+pub fn is_neewer_port(port_name: &str) -> bool {
+    port_name.to_ascii_lowercase().contains("neewer")
+}
+
+/// Parse `AA:BB:CC:DD:EE:FF[/channel]` into the address, in the byte order
+/// it's written, and RFCOMM channel.
+fn parse_target(target: &str) -> Result<([u8; 6], u8), String> {
+    let (addr, channel) = match target.split_once('/') {
+        Some((addr, channel)) => {
+            let channel = channel
+                .parse::<u8>()
+                .ok()
+                .filter(|c| (1..=30).contains(c))
+                .ok_or_else(|| format!("Invalid RFCOMM channel: {channel}"))?;
+            (addr, channel)
+        }
+        None => (target, DEFAULT_CHANNEL),
+    };
+    let invalid = || format!("Invalid Bluetooth address: {addr}");
+    let parts: Vec<&str> = addr.split(':').collect();
+    if parts.len() != 6 {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 6];
+    for (byte, part) in bytes.iter_mut().zip(parts) {
+        if part.len() != 2 {
+            return Err(invalid());
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    Ok((bytes, channel))
+}
+
+/// Connect to the light at `target` with `timeout` as the read timeout.
+pub fn open(target: &str, timeout: Duration) -> Result<Box<dyn Link>, String> {
+    let (addr, channel) = parse_target(target)?;
+    rfcomm::open(addr, channel, timeout).map_err(|e| format!("Failed to connect to {target}: {e}"))
+}
+
+#[cfg(target_os = "linux")]
+mod rfcomm {
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::mem::size_of;
+    use std::os::fd::{FromRawFd, RawFd};
+    use std::time::Duration;
+
+    use crate::transport::{self, Link};
+
+    const AF_BLUETOOTH: i32 = 31;
+    const SOCK_STREAM: i32 = 1;
+    const SOCK_CLOEXEC: i32 = 0o2000000;
+    const BTPROTO_RFCOMM: i32 = 3;
+    const SOL_SOCKET: i32 = 1;
+    const SO_RCVTIMEO: i32 = 20;
+
+    #[repr(C)]
+    struct SockaddrRc {
+        rc_family: u16,
+        rc_bdaddr: [u8; 6],
+        rc_channel: u8,
+    }
+
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: std::ffi::c_long,
+        tv_usec: std::ffi::c_long,
+    }
+
+    extern "C" {
+        fn socket(domain: i32, ty: i32, protocol: i32) -> RawFd;
+        fn connect(fd: RawFd, addr: *const SockaddrRc, len: u32) -> i32;
+        fn setsockopt(fd: RawFd, level: i32, name: i32, value: *const Timeval, len: u32) -> i32;
+    }
+
+    /// An RFCOMM socket. Reads and writes go through the descriptor as a
+    /// file.
+    struct RfcommLink(File);
+
+    impl Read for RfcommLink {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            transport::read_socket(&mut self.0, buf)
+        }
+    }
+
+    impl Write for RfcommLink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    impl Link for RfcommLink {}
+
+    pub fn open(addr: [u8; 6], channel: u8, timeout: Duration) -> io::Result<Box<dyn Link>> {
+        // SAFETY: plain socket creation; the result is checked before use.
+        let fd = unsafe { socket(AF_BLUETOOTH, SOCK_STREAM | SOCK_CLOEXEC, BTPROTO_RFCOMM) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a freshly created socket owned by nothing else, so
+        // the file takes ownership and closes it on every path below.
+        let file = unsafe { File::from_raw_fd(fd) };
+        let mut bdaddr = addr;
+        // bdaddr_t is stored least significant byte first
+        bdaddr.reverse();
+        let sockaddr = SockaddrRc {
+            rc_family: AF_BLUETOOTH as u16,
+            rc_bdaddr: bdaddr,
+            rc_channel: channel,
+        };
+        // SAFETY: `sockaddr` is a correctly sized sockaddr_rc owned by this
+        // frame.
+        let rc = unsafe { connect(fd, &sockaddr, size_of::<SockaddrRc>() as u32) };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let tv = Timeval {
+            tv_sec: timeout.as_secs() as std::ffi::c_long,
+            tv_usec: timeout.subsec_micros() as std::ffi::c_long,
+        };
+        // SAFETY: `tv` is a correctly sized timeval owned by this frame.
+        let rc = unsafe {
+            setsockopt(
+                fd,
+                SOL_SOCKET,
+                SO_RCVTIMEO,
+                &tv,
+                size_of::<Timeval>() as u32,
+            )
+        };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Box::new(RfcommLink(file)))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod rfcomm {
+    use std::io;
+    use std::time::Duration;
+
+    use crate::transport::Link;
+
+    pub fn open(_addr: [u8; 6], _channel: u8, _timeout: Duration) -> io::Result<Box<dyn Link>> {
+        Err(io::Error::other(
+            "direct Bluetooth connections need Linux; pair the light and use its serial port",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        let addr = [0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0x0F];
+        assert_eq!(parse_target("AA:BB:CC:DD:EE:0F"), Ok((addr, 1)));
+        assert_eq!(parse_target("aa:bb:cc:dd:ee:0f/3"), Ok((addr, 3)));
+        assert!(parse_target("AA:BB:CC:DD:EE").is_err());
+        assert!(parse_target("AA:BB:CC:DD:EE:F").is_err());
+        assert!(parse_target("AA:BB:CC:DD:EE:0F/0").is_err());
+    }
+
+    #[test]
+    fn test_is_neewer_port() {
+        assert!(is_neewer_port("/dev/cu.NEEWER-CB60"));
+        assert!(!is_neewer_port("/dev/cu.Bluetooth-Incoming-Port"));
+    }
+}
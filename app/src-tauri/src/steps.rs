@@ -0,0 +1,153 @@
+/// Relative brightness and temperature steps.
+///
+/// Hotkeys, scroll wheels and Stream Deck dials only know "up" or "down", not
+/// where a light is now. A step starts from each light's last reported level
+/// or temperature (see `serial`), moves it and clamps it, so lights at
+/// different settings each move by the same amount from where they are.
+///
+/// Lights only show a temperature in hardware steps of about 228K, so a
+/// temperature step is rounded to the nearest one, moving at least one
+/// hardware step so small steps never stall, and reports both the
+/// temperature asked for and the one each light took.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::dither::Ditherer;
+use crate::errors::LightError;
+use crate::groups::{self, FanOutReport, Target};
+use crate::models;
+use crate::protocol::{self, KelvinRange};
+
+/// Steps used when a caller doesn't give one, in slider levels and Kelvin.
+pub const DEFAULT_BRIGHTNESS_STEP: u8 = 10;
+pub const DEFAULT_KELVIN_STEP: u32 = 200;
+/// Largest temperature step accepted from callers, the width of the widest
+/// range a light can be given (see `models`).
+pub const MAX_KELVIN_STEP: u32 = 19_000;
+
+/// Where a temperature step took one light.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct KelvinStep {
+    /// The temperature asked for, within the light's range.
+    pub requested: u32,
+    /// The nearest temperature the light can show, which it was set to.
+    pub kelvin: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KelvinStepReport {
+    #[serde(flatten)]
+    pub report: FanOutReport,
+    /// By device id, for the lights that took the step.
+    pub steps: BTreeMap<String, KelvinStep>,
+}
+
+/// `level` moved by `delta`, within 0-100.
+pub fn step_level(level: u8, delta: i32) -> u8 {
+    (level as i32 + delta).clamp(0, 100) as u8
+}
+
+/// Move the brightness of every light in `target` by `delta` slider levels.
+pub fn brightness(
+    app: &AppHandle,
+    target: Option<&Target>,
+    delta: i32,
+) -> Result<FanOutReport, LightError> {
+    let dither = app.state::<Ditherer>();
+    groups::fan_out(app, target, |serial, id| {
+        let status = serial
+            .status_of(id)
+            .ok_or("No status received from light yet")?;
+        dither.set_level(app, id, step_level(status.level, delta), status.kelvin)
+    })
+}
+
+/// A caller's temperature step in Kelvin, `DEFAULT_KELVIN_STEP` if not given.
+pub fn kelvin_step(step: Option<u32>) -> Result<i32, String> {
+    let step = step.unwrap_or(DEFAULT_KELVIN_STEP);
+    if step > MAX_KELVIN_STEP {
+        return Err(format!("Kelvin step must be at most {MAX_KELVIN_STEP}K"));
+    }
+    Ok(step as i32)
+}
+
+/// `kelvin` moved by `delta` Kelvin within the light's `range`, and rounded
+/// to a hardware step other than the current one unless at the end of the
+/// range.
+fn step_kelvin(range: &KelvinRange, kelvin: u32, delta: i32) -> KelvinStep {
+    let requested = (kelvin as i64 + delta as i64).clamp(range.min as i64, range.max as i64) as u32;
+    let snap = |k| protocol::byte_to_kelvin(range, protocol::kelvin_to_byte(range, k));
+    let mut stepped = snap(requested);
+    if stepped == snap(kelvin) && delta != 0 {
+        stepped = protocol::step_kelvin(range, kelvin, delta.signum());
+    }
+    KelvinStep {
+        requested,
+        kelvin: stepped,
+    }
+}
+
+/// Move the temperature of every light in `target` by `delta` Kelvin.
+pub fn kelvin(
+    app: &AppHandle,
+    target: Option<&Target>,
+    delta: i32,
+) -> Result<KelvinStepReport, LightError> {
+    let dither = app.state::<Ditherer>();
+    let steps = RefCell::new(BTreeMap::new());
+    let report = groups::fan_out(app, target, |serial, id| -> Result<(), LightError> {
+        let status = serial
+            .status_of(id)
+            .ok_or("No status received from light yet")?;
+        let step = step_kelvin(&models::range(app, id), status.kelvin, delta);
+        dither.set_level(app, id, status.level, step.kelvin)?;
+        steps.borrow_mut().insert(id.to_string(), step);
+        Ok(())
+    })?;
+    Ok(KelvinStepReport {
+        report,
+        steps: steps.into_inner(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_level() {
+        assert_eq!(step_level(50, 10), 60);
+        assert_eq!(step_level(95, 10), 100);
+        assert_eq!(step_level(5, -10), 0);
+    }
+
+    #[test]
+    fn test_kelvin_step_is_bounded() {
+        assert_eq!(kelvin_step(None), Ok(DEFAULT_KELVIN_STEP as i32));
+        assert_eq!(kelvin_step(Some(500)), Ok(500));
+        assert!(kelvin_step(Some(MAX_KELVIN_STEP + 1)).is_err());
+    }
+
+    #[test]
+    fn test_step_kelvin() {
+        let range = &protocol::DEFAULT_RANGE;
+        let step = step_kelvin(range, 4950, 500);
+        assert_eq!(step.requested, 5450);
+        assert_eq!(step.kelvin, protocol::byte_to_kelvin(range, 11));
+        // Smaller than a hardware step still moves one
+        assert_eq!(
+            step_kelvin(range, 4950, 50).kelvin,
+            protocol::byte_to_kelvin(range, 10)
+        );
+        assert_eq!(
+            step_kelvin(range, 4950, -50).kelvin,
+            protocol::byte_to_kelvin(range, 8)
+        );
+        let end = step_kelvin(range, 6900, 500);
+        assert_eq!((end.requested, end.kelvin), (7000, 7000));
+        assert_eq!(step_kelvin(range, 2900, -200).kelvin, 2900);
+    }
+}
@@ -0,0 +1,158 @@
+/// Connection supervisor.
+///
+/// Keeps the set of lights that should be connected and reconciles it with
+/// the open connections every `TICK`: a light that should be connected but
+/// isn't is connected over its preferred attached port, waiting longer after
+/// each failed attempt (see `retry_delay`), and a connected light that hasn't
+/// reported its status for `STALE_AFTER` is asked for it and reconnected if
+/// it doesn't answer. Lights that aren't attached are left alone until they
+/// are, so an unplugged light comes back on its own when it's plugged in
+/// again.
+///
+/// At launch the set holds the lights auto-connect picks (see
+/// `devices::auto_connect_targets`), and the startup state is sent if any of
+/// them connects; after that it follows the user's connects and disconnects.
+/// It isn't persisted.
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Manager};
+
+use crate::serial::SerialManager;
+use crate::{config, devices};
+
+const TICK: Duration = Duration::from_secs(2);
+/// Status age after which a connected light is checked.
+const STALE_AFTER: Duration = Duration::from_secs(60);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Wait before the next attempt after `failures` failed attempts in a row.
+fn retry_delay(failures: u32) -> Duration {
+    TICK.saturating_mul(1 << failures.min(5))
+        .min(MAX_RETRY_DELAY)
+}
+
+struct Retry {
+    failures: u32,
+    next: Instant,
+}
+
+pub struct Supervisor {
+    desired: Mutex<BTreeSet<String>>,
+    retries: Mutex<HashMap<String, Retry>>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            desired: Mutex::new(BTreeSet::new()),
+            retries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Connect the lights auto-connect picks and keep reconciling. Blocks
+    /// while ports open, so the work happens on its own thread.
+    pub fn start(&self, app: &AppHandle) {
+        let app = app.clone();
+        std::thread::spawn(move || {
+            let supervisor = app.state::<Supervisor>();
+            supervisor
+                .desired
+                .lock()
+                .unwrap()
+                .extend(devices::auto_connect_targets(&app));
+            supervisor.reconcile(&app);
+            if app.state::<SerialManager>().is_connected() {
+                config::apply_startup(&app);
+            }
+            loop {
+                std::thread::sleep(TICK);
+                supervisor.reconcile(&app);
+            }
+        });
+    }
+
+    /// Keep light `id` connected.
+    pub fn want(&self, id: &str) {
+        self.desired.lock().unwrap().insert(id.to_string());
+        self.retries.lock().unwrap().remove(id);
+    }
+
+    /// Stop keeping light `id`, or every light with `None`, connected.
+    pub fn release(&self, id: Option<&str>) {
+        let mut desired = self.desired.lock().unwrap();
+        match id {
+            Some(id) => {
+                desired.remove(id);
+            }
+            None => desired.clear(),
+        }
+    }
+
+    /// Lights being kept connected.
+    pub fn desired(&self) -> BTreeSet<String> {
+        self.desired.lock().unwrap().clone()
+    }
+
+    fn reconcile(&self, app: &AppHandle) {
+        let serial = app.state::<SerialManager>();
+        let open = serial.ids();
+        let missing: Vec<String> = self
+            .desired()
+            .into_iter()
+            .filter(|id| !open.contains(id) && self.due(id))
+            .collect();
+        if !missing.is_empty() {
+            let found = devices::lights(app);
+            for id in missing {
+                let Some(light) = found.iter().find(|light| light.id == id) else {
+                    continue;
+                };
+                let connected = serial.connect(&light.ports[0], app.clone()).is_ok();
+                self.record(&id, connected);
+            }
+        }
+        for id in open {
+            let stale = serial.status_age(&id).is_some_and(|age| age >= STALE_AFTER);
+            if stale && serial.query(&id).is_err() {
+                serial.disconnect_device(&id);
+            }
+        }
+    }
+
+    fn due(&self, id: &str) -> bool {
+        self.retries
+            .lock()
+            .unwrap()
+            .get(id)
+            .is_none_or(|retry| Instant::now() >= retry.next)
+    }
+
+    fn record(&self, id: &str, connected: bool) {
+        let mut retries = self.retries.lock().unwrap();
+        if connected {
+            retries.remove(id);
+            return;
+        }
+        let retry = retries.entry(id.to_string()).or_insert(Retry {
+            failures: 0,
+            next: Instant::now(),
+        });
+        retry.next = Instant::now() + retry_delay(retry.failures);
+        retry.failures += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_backs_off_to_a_cap() {
+        assert_eq!(retry_delay(0), TICK);
+        assert_eq!(retry_delay(1), TICK * 2);
+        assert_eq!(retry_delay(3), TICK * 8);
+        assert_eq!(retry_delay(10), MAX_RETRY_DELAY);
+    }
+}
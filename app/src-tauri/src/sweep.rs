@@ -0,0 +1,251 @@
+/// Exposure sweep.
+///
+/// Steps the lights through a range of slider levels, pausing at each one and
+/// emitting "exposure-sweep" as it goes, so users can watch their camera's
+/// exposure and note the level that looks right. The sweep holds at the last
+/// level when it reaches the end of the range. Saving records a level (the
+/// one the sweep is at, or one noted earlier) as a preset and leaves the
+/// lights there; stopping instead puts the lights back where they were
+/// before the sweep started.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::errors::{self, LightError};
+use crate::groups::{self, FanOutReport, GroupManager, Target};
+use crate::history::{self, Snapshot};
+use crate::presets::{self, Preset};
+use crate::{protocol, STORE_FILE};
+
+/// Longest pause accepted at each step, ten minutes.
+const MAX_PAUSE_MS: u64 = 10 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepConfig {
+    /// Lights to sweep; all connected lights by default.
+    pub target: Option<Target>,
+    /// First and last slider level, 0-100, in either order.
+    pub from: u8,
+    pub to: u8,
+    /// Levels between steps.
+    pub step: u8,
+    /// Time to hold each step.
+    pub pause_ms: u64,
+    /// Temperature to sweep at; the first light's current one by default.
+    pub kelvin: Option<u32>,
+}
+
+impl SweepConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.from > 100 || self.to > 100 {
+            return Err("Sweep levels must be 0-100".into());
+        }
+        if self.step == 0 {
+            return Err("Sweep step must be at least 1".into());
+        }
+        if !(100..=MAX_PAUSE_MS).contains(&self.pause_ms) {
+            return Err("Sweep pause must be 100 ms to 10 minutes".into());
+        }
+        Ok(())
+    }
+}
+
+/// Reported as "exposure-sweep" at each step.
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepProgress {
+    /// Step the sweep is at, from 1.
+    pub step: usize,
+    pub steps: usize,
+    pub level: u8,
+    pub kelvin: u32,
+    /// Whether this is the last step.
+    pub done: bool,
+}
+
+/// Levels from `from` to `to` by `step`, ending on `to`.
+fn levels(from: u8, to: u8, step: u8) -> Vec<u8> {
+    let step = step.max(1) as usize;
+    let mut levels: Vec<u8> = if from <= to {
+        (from..=to).step_by(step).collect()
+    } else {
+        (to..=from).rev().step_by(step).collect()
+    };
+    if levels.last() != Some(&to) {
+        levels.push(to);
+    }
+    levels
+}
+
+/// A sweep in progress or holding at its last step.
+struct Sweep {
+    target: Option<Target>,
+    kelvin: u32,
+    /// Level the lights are at, once the first step is written.
+    level: Option<u8>,
+    /// The lights as they were before the sweep.
+    saved: Snapshot,
+}
+
+pub struct ExposureSweep {
+    /// Bumped by every start, stop and save; the sweep thread exits when it
+    /// changes.
+    generation: Arc<AtomicU64>,
+    sweep: Mutex<Option<Sweep>>,
+}
+
+impl ExposureSweep {
+    pub fn new() -> Self {
+        Self {
+            generation: Arc::new(AtomicU64::new(0)),
+            sweep: Mutex::new(None),
+        }
+    }
+
+    /// Start sweeping, replacing any sweep in progress. Returns immediately.
+    pub fn start(&self, app: &AppHandle, config: SweepConfig) -> Result<(), String> {
+        config.validate()?;
+        let ids = app
+            .state::<GroupManager>()
+            .resolve(app, config.target.as_ref().unwrap_or(&Target::All))?;
+        let snapshot = history::snapshot(app);
+        let kelvin = config
+            .kelvin
+            .or_else(|| ids.iter().find_map(|id| snapshot.get(id).map(|s| s.1)))
+            .unwrap_or(protocol::DEFAULT_TEMP_K);
+
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        {
+            let mut sweep = self.sweep.lock().unwrap();
+            // A sweep replacing another still restores to before the first
+            let saved = sweep.take().map_or(snapshot, |s| s.saved);
+            *sweep = Some(Sweep {
+                target: config.target.clone(),
+                kelvin,
+                level: None,
+                saved,
+            });
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, kelvin, current, gen));
+        Ok(())
+    }
+
+    /// Stop the sweep and put the lights back where they were before it.
+    pub fn stop(&self, app: &AppHandle) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if let Some(sweep) = self.sweep.lock().unwrap().take() {
+            history::restore(app, &sweep.saved);
+        }
+    }
+
+    /// End the sweep, leaving the lights at `level` (the sweep's current one
+    /// by default), and save that level at the sweep's temperature as preset
+    /// `name`, replacing any preset of that name.
+    pub fn save(&self, app: &AppHandle, name: &str, level: Option<u8>) -> Result<Preset, String> {
+        let (target, kelvin, level) = {
+            let sweep = self.sweep.lock().unwrap();
+            let sweep = sweep.as_ref().ok_or("No exposure sweep to save")?;
+            let level = level
+                .or(sweep.level)
+                .ok_or("The sweep hasn't set a level yet")?;
+            (sweep.target.clone(), sweep.kelvin, level)
+        };
+        let preset = Preset {
+            name: name.trim().to_string(),
+            brightness: level,
+            kelvin,
+        };
+        preset.validate()?;
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.sweep.lock().unwrap().take();
+        errors::check_fan_out(
+            app,
+            "exposure sweep",
+            apply(app, target.as_ref(), level, kelvin),
+        );
+
+        let mut saved = presets::load(app);
+        match saved.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset.clone(),
+            None => saved.push(preset.clone()),
+        }
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            presets::PRESETS_KEY,
+            serde_json::to_value(&saved).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        let _ = app.emit("presets-changed", ());
+        Ok(preset)
+    }
+}
+
+fn run(app: AppHandle, config: SweepConfig, kelvin: u32, current: Arc<AtomicU64>, gen: u64) {
+    let levels = levels(config.from, config.to, config.step);
+    let steps = levels.len();
+    for (i, level) in levels.into_iter().enumerate() {
+        if current.load(Ordering::SeqCst) != gen {
+            return;
+        }
+        let result = apply(&app, config.target.as_ref(), level, kelvin);
+        errors::check_fan_out(&app, "exposure sweep", result);
+        {
+            let mut sweep = app.state::<ExposureSweep>().sweep.lock().unwrap();
+            // Stopped or saved while this step was written
+            let Some(sweep) = sweep
+                .as_mut()
+                .filter(|_| current.load(Ordering::SeqCst) == gen)
+            else {
+                return;
+            };
+            sweep.level = Some(level);
+        }
+        let _ = app.emit(
+            "exposure-sweep",
+            SweepProgress {
+                step: i + 1,
+                steps,
+                level,
+                kelvin,
+                done: i + 1 == steps,
+            },
+        );
+        if i + 1 < steps {
+            std::thread::sleep(Duration::from_millis(config.pause_ms));
+        }
+    }
+}
+
+fn apply(
+    app: &AppHandle,
+    target: Option<&Target>,
+    level: u8,
+    kelvin: u32,
+) -> Result<FanOutReport, LightError> {
+    let dither = app.state::<Ditherer>();
+    groups::fan_out(app, target, |_, id| {
+        dither.set_level(app, id, level, kelvin)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_end_on_the_last_level() {
+        assert_eq!(levels(20, 50, 10), [20, 30, 40, 50]);
+        assert_eq!(levels(20, 45, 10), [20, 30, 40, 45]);
+        assert_eq!(levels(60, 30, 15), [60, 45, 30]);
+        assert_eq!(levels(40, 40, 5), [40]);
+    }
+}
@@ -0,0 +1,241 @@
+/// Thermal-protection dimming.
+///
+/// An optional loop watches the connected lights' reported levels and, when
+/// a light has run at or above a threshold (90% by default) for a
+/// configurable number of minutes, steps it down, notifies, and emits
+/// "thermal-dimmed". A light still above the threshold after a step is
+/// stepped down again after the same time, so a panel in an enclosed softbox
+/// settles below the level it overheats at. Protection can be snoozed for a
+/// while to run hot on purpose, e.g. for the rest of a shoot.
+/// Configuration is persisted under `thermal_protection` in the settings
+/// store; a snooze isn't.
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::dither::Ditherer;
+use crate::serial::{LightStatus, SerialManager};
+use crate::sessionlog::{self, Source};
+use crate::{errors, notify, STORE_FILE};
+
+const THERMAL_KEY: &str = "thermal_protection";
+const POLL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalConfig {
+    pub enabled: bool,
+    /// Level (0-100) at or above which a light counts as running hot.
+    pub threshold: u8,
+    /// Minutes a light may run hot before it's stepped down.
+    pub after_minutes: u32,
+    /// Levels to step down by each time.
+    pub step: u8,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 90,
+            after_minutes: 20,
+            step: 10,
+        }
+    }
+}
+
+impl ThermalConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=100).contains(&self.threshold) {
+            return Err("Thermal threshold must be 1-100".into());
+        }
+        if !(1..=480).contains(&self.after_minutes) {
+            return Err("Thermal time must be 1-480 minutes".into());
+        }
+        if !(1..=100).contains(&self.step) {
+            return Err("Thermal step must be 1-100".into());
+        }
+        Ok(())
+    }
+}
+
+/// Reported as "thermal-dimmed" when a light is stepped down.
+#[derive(Debug, Clone, Serialize)]
+pub struct ThermalStep {
+    pub device: String,
+    pub from: u8,
+    pub to: u8,
+}
+
+/// When each light started running hot, or was last stepped down.
+#[derive(Debug, Default)]
+struct HotSince(HashMap<String, Instant>);
+
+impl HotSince {
+    /// Note whether light `id` is running hot at `now`, returning whether it
+    /// has run hot for `after` and is due a step down.
+    fn observe(&mut self, id: &str, hot: bool, now: Instant, after: Duration) -> bool {
+        if !hot {
+            self.0.remove(id);
+            return false;
+        }
+        let since = *self.0.entry(id.to_string()).or_insert(now);
+        if now.duration_since(since) < after {
+            return false;
+        }
+        // Give the light the same time again at its new level
+        self.0.insert(id.to_string(), now);
+        true
+    }
+}
+
+pub struct ThermalProtection {
+    config: Mutex<ThermalConfig>,
+    /// Bumped on every reconfigure; the loop exits when it changes.
+    generation: Arc<AtomicU64>,
+    snoozed_until: Mutex<Option<Instant>>,
+}
+
+impl ThermalProtection {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(ThermalConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+            snoozed_until: Mutex::new(None),
+        }
+    }
+
+    /// Load the saved configuration and start the loop if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: ThermalConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(THERMAL_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> ThermalConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: ThermalConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            THERMAL_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    /// Stop stepping lights down for `minutes`; 0 ends a snooze.
+    pub fn snooze(&self, minutes: u32) {
+        *self.snoozed_until.lock().unwrap() =
+            (minutes > 0).then(|| Instant::now() + Duration::from_secs(minutes as u64 * 60));
+    }
+
+    /// Time left of the current snooze, if any.
+    pub fn snoozed_for(&self) -> Option<Duration> {
+        let until = (*self.snoozed_until.lock().unwrap())?;
+        until
+            .checked_duration_since(Instant::now())
+            .filter(|left| !left.is_zero())
+    }
+
+    fn restart(&self, app: &AppHandle, config: ThermalConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if !config.enabled {
+            return;
+        }
+        let current = self.generation.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, config, current, gen));
+    }
+}
+
+fn run(app: AppHandle, config: ThermalConfig, current: Arc<AtomicU64>, gen: u64) {
+    sessionlog::set_source(Source::Automation);
+    let after = Duration::from_secs(config.after_minutes as u64 * 60);
+    let mut hot = HotSince::default();
+
+    while current.load(Ordering::SeqCst) == gen {
+        std::thread::sleep(POLL);
+        if app.state::<ThermalProtection>().snoozed_for().is_some() {
+            // Time spent snoozed doesn't count towards a step
+            hot = HotSince::default();
+            continue;
+        }
+        let serial = app.state::<SerialManager>();
+        let now = Instant::now();
+        for id in serial.ids() {
+            let Some(status) = serial.status_of(&id) else {
+                continue;
+            };
+            let lit = status.extended.on != Some(false) && status.level >= config.threshold;
+            if hot.observe(&id, lit, now, after) {
+                step_down(&app, &config, &id, &status);
+            }
+        }
+    }
+}
+
+fn step_down(app: &AppHandle, config: &ThermalConfig, id: &str, status: &LightStatus) {
+    // Never step a light all the way off
+    let to = status.level.saturating_sub(config.step).max(1);
+    let result = app
+        .state::<Ditherer>()
+        .set_level(app, id, to, status.kelvin);
+    if result.is_ok() {
+        let _ = app.emit(
+            "thermal-dimmed",
+            ThermalStep {
+                device: id.to_string(),
+                from: status.level,
+                to,
+            },
+        );
+        notify::error(
+            app,
+            &format!("{} dimmed to protect it from heat", status.name),
+            &format!(
+                "It ran at {}% or more for {} min, so it's now at {to}%. Snooze thermal protection to run it hotter.",
+                config.threshold, config.after_minutes
+            ),
+        );
+    }
+    errors::check(app, "thermal protection", Some(id), result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hot_since_steps_after_each_period() {
+        let after = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut hot = HotSince::default();
+        assert!(!hot.observe("A", true, start, after));
+        assert!(!hot.observe("A", true, start + Duration::from_secs(30), after));
+        assert!(hot.observe("A", true, start + after, after));
+        // The next step needs another full period
+        assert!(!hot.observe("A", true, start + after + Duration::from_secs(30), after));
+        assert!(hot.observe("A", true, start + after * 2, after));
+        // Cooling down resets the timer
+        assert!(!hot.observe("A", false, start + after * 2, after));
+        assert!(!hot.observe("A", true, start + after * 3, after));
+    }
+}
@@ -0,0 +1,395 @@
+/// Keyframe timeline animation.
+///
+/// A timeline is a list of keyframes (time, brightness, kelvin, easing) that
+/// the backend plays on a target, optionally looping — repeatable lighting cues
+/// for shoots. Each keyframe's easing shapes the segment leading into it.
+/// Brightness is a slider level, mapped through each light's dimming curve on
+/// write.
+/// Timelines are persisted under `timelines` in the settings store.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::curves::CurveManager;
+use crate::groups::{self, Target};
+use crate::sessionlog::{self, Source};
+use crate::{errors, fade, models, protocol, STORE_FILE};
+
+const TIMELINES_KEY: &str = "timelines";
+const TICK: Duration = Duration::from_millis(40);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// Hold the previous keyframe, then jump.
+    Step,
+}
+
+impl Easing {
+    /// Map linear progress 0..=1 to eased progress.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Step => {
+                if t >= 1.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyframe {
+    /// Offset from the start of the timeline, in ms.
+    pub time_ms: u64,
+    /// Slider level, 0-100.
+    pub brightness: u8,
+    pub kelvin: u32,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timeline {
+    pub name: String,
+    pub keyframes: Vec<Keyframe>,
+    #[serde(default)]
+    pub looped: bool,
+}
+
+impl Timeline {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Timeline name cannot be empty".into());
+        }
+        if self.keyframes.is_empty() {
+            return Err("Timeline needs at least one keyframe".into());
+        }
+        if self
+            .keyframes
+            .windows(2)
+            .any(|w| w[1].time_ms <= w[0].time_ms)
+        {
+            return Err("Keyframe times must be strictly increasing".into());
+        }
+        if self.keyframes.iter().any(|k| k.brightness > 100) {
+            return Err("Keyframe brightness must be 0-100".into());
+        }
+        Ok(())
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.keyframes.last().map_or(0, |k| k.time_ms))
+    }
+
+    /// (brightness, kelvin) at `pos`. Holds the first/last keyframe outside
+    /// the keyframed range.
+    pub fn sample(&self, pos: Duration) -> (u8, u32) {
+        let ms = pos.as_secs_f64() * 1000.0;
+        let first = &self.keyframes[0];
+        if ms <= first.time_ms as f64 {
+            return (first.brightness, first.kelvin);
+        }
+        for w in self.keyframes.windows(2) {
+            let (a, b) = (&w[0], &w[1]);
+            if ms <= b.time_ms as f64 {
+                let t = (ms - a.time_ms as f64) / (b.time_ms - a.time_ms) as f64;
+                return fade::lerp(
+                    (a.brightness, a.kelvin),
+                    (b.brightness, b.kelvin),
+                    b.easing.apply(t),
+                );
+            }
+        }
+        let last = self.keyframes.last().unwrap();
+        (last.brightness, last.kelvin)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayState {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// Snapshot emitted as "timeline-state" and returned by `status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackStatus {
+    pub name: Option<String>,
+    pub state: PlayState,
+    pub position_ms: u64,
+}
+
+struct Playback {
+    gen: u64,
+    name: String,
+    position: Duration,
+    paused: bool,
+}
+
+pub struct TimelineEngine {
+    timelines: Mutex<BTreeMap<String, Timeline>>,
+    playback: Arc<Mutex<Option<Playback>>>,
+    generation: AtomicU64,
+}
+
+impl TimelineEngine {
+    pub fn new() -> Self {
+        Self {
+            timelines: Mutex::new(BTreeMap::new()),
+            playback: Arc::new(Mutex::new(None)),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, Timeline> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(TIMELINES_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.timelines.lock().unwrap() = saved;
+    }
+
+    pub fn list(&self) -> Vec<Timeline> {
+        self.timelines.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Create or replace a timeline.
+    pub fn save(&self, app: &AppHandle, timeline: Timeline) -> Result<(), String> {
+        timeline.validate()?;
+        let mut timelines = self.timelines.lock().unwrap();
+        timelines.insert(timeline.name.clone(), timeline);
+        persist(app, &timelines)
+    }
+
+    pub fn delete(&self, app: &AppHandle, name: &str) -> Result<(), String> {
+        let mut timelines = self.timelines.lock().unwrap();
+        if timelines.remove(name).is_none() {
+            return Err(format!("No timeline named {name}"));
+        }
+        persist(app, &timelines)
+    }
+
+    /// Start playing a timeline from the beginning, replacing any playback.
+    pub fn play(&self, app: &AppHandle, name: &str, target: Option<Target>) -> Result<(), String> {
+        let timeline = self
+            .timelines
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("No timeline named {name}"))?;
+
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        *self.playback.lock().unwrap() = Some(Playback {
+            gen,
+            name: name.to_string(),
+            position: Duration::ZERO,
+            paused: false,
+        });
+        emit(app, &self.playback);
+
+        let playback = self.playback.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(app, timeline, target, playback, gen));
+        Ok(())
+    }
+
+    pub fn pause(&self, app: &AppHandle) -> Result<(), String> {
+        self.set_paused(app, true)
+    }
+
+    pub fn resume(&self, app: &AppHandle) -> Result<(), String> {
+        self.set_paused(app, false)
+    }
+
+    fn set_paused(&self, app: &AppHandle, paused: bool) -> Result<(), String> {
+        self.playback
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or("No timeline is playing")?
+            .paused = paused;
+        emit(app, &self.playback);
+        Ok(())
+    }
+
+    /// Stop playback, leaving the light at its current state.
+    pub fn stop(&self, app: &AppHandle) {
+        *self.playback.lock().unwrap() = None;
+        emit(app, &self.playback);
+    }
+
+    pub fn status(&self) -> PlaybackStatus {
+        status_of(&self.playback.lock().unwrap())
+    }
+}
+
+fn status_of(playback: &Option<Playback>) -> PlaybackStatus {
+    match playback {
+        Some(p) => PlaybackStatus {
+            name: Some(p.name.clone()),
+            state: if p.paused {
+                PlayState::Paused
+            } else {
+                PlayState::Playing
+            },
+            position_ms: p.position.as_millis() as u64,
+        },
+        None => PlaybackStatus {
+            name: None,
+            state: PlayState::Stopped,
+            position_ms: 0,
+        },
+    }
+}
+
+fn emit(app: &AppHandle, playback: &Mutex<Option<Playback>>) {
+    let status = status_of(&playback.lock().unwrap());
+    let _ = app.emit("timeline-state", &status);
+}
+
+/// Playback thread. Exits when the playback slot no longer holds `gen`.
+fn run(
+    app: AppHandle,
+    timeline: Timeline,
+    target: Option<Target>,
+    playback: Arc<Mutex<Option<Playback>>>,
+    gen: u64,
+) {
+    sessionlog::set_source(Source::Automation);
+    let duration = timeline.duration();
+    let mut last_tick = Instant::now();
+    let last: RefCell<BTreeMap<String, (u8, u8)>> = RefCell::new(BTreeMap::new());
+
+    loop {
+        let now = Instant::now();
+        let dt = now - last_tick;
+        last_tick = now;
+
+        let (pos, finished) = {
+            let mut slot = playback.lock().unwrap();
+            let Some(p) = slot.as_mut().filter(|p| p.gen == gen) else {
+                return;
+            };
+            if !p.paused {
+                p.position += dt;
+            }
+            if p.position >= duration {
+                if timeline.looped && !duration.is_zero() {
+                    p.position =
+                        Duration::from_nanos((p.position.as_nanos() % duration.as_nanos()) as u64);
+                    (p.position, false)
+                } else {
+                    (duration, true)
+                }
+            } else {
+                (p.position, false)
+            }
+        };
+
+        let (bri, k) = timeline.sample(pos);
+        let curves = app.state::<CurveManager>();
+        let result = groups::fan_out(&app, target.as_ref(), |serial, id| {
+            // Only write when the light would actually change
+            let wire = (bri, protocol::kelvin_to_byte(&models::range(&app, id), k));
+            if last.borrow().get(id) == Some(&wire) {
+                return Ok(());
+            }
+            last.borrow_mut().insert(id.to_string(), wire);
+            serial.set_cct_to(id, curves.to_hw(id, bri), k)
+        });
+        errors::check_fan_out(&app, "timeline", result);
+
+        if finished {
+            let mut slot = playback.lock().unwrap();
+            if slot.as_ref().is_some_and(|p| p.gen == gen) {
+                *slot = None;
+            }
+            drop(slot);
+            emit(&app, &playback);
+            return;
+        }
+        std::thread::sleep(TICK);
+    }
+}
+
+fn persist(app: &AppHandle, timelines: &BTreeMap<String, Timeline>) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    store.set(
+        TIMELINES_KEY,
+        serde_json::to_value(timelines).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kf(time_ms: u64, brightness: u8, easing: Easing) -> Keyframe {
+        Keyframe {
+            time_ms,
+            brightness,
+            kelvin: 4950,
+            easing,
+        }
+    }
+
+    #[test]
+    fn test_sample_holds_outside_range() {
+        let tl = Timeline {
+            name: "t".into(),
+            keyframes: vec![kf(1000, 10, Easing::Linear), kf(2000, 90, Easing::Linear)],
+            looped: false,
+        };
+        assert_eq!(tl.sample(Duration::ZERO).0, 10);
+        assert_eq!(tl.sample(Duration::from_millis(5000)).0, 90);
+        assert_eq!(tl.sample(Duration::from_millis(1500)).0, 50);
+    }
+
+    #[test]
+    fn test_step_easing_holds_previous() {
+        let tl = Timeline {
+            name: "t".into(),
+            keyframes: vec![kf(0, 0, Easing::Linear), kf(1000, 100, Easing::Step)],
+            looped: false,
+        };
+        assert_eq!(tl.sample(Duration::from_millis(999)).0, 0);
+        assert_eq!(tl.sample(Duration::from_millis(1000)).0, 100);
+    }
+
+    #[test]
+    fn test_validate_rejects_unordered() {
+        let tl = Timeline {
+            name: "t".into(),
+            keyframes: vec![kf(500, 0, Easing::Linear), kf(500, 100, Easing::Linear)],
+            looped: false,
+        };
+        assert!(tl.validate().is_err());
+    }
+}
@@ -0,0 +1,117 @@
+/// Power toggle that remembers where each light was.
+///
+/// Toggling switches the lights off when any of them is on, remembering each
+/// lit light's level and temperature at that moment, and otherwise brings
+/// each light back to what was remembered for it. A light with nothing
+/// remembered comes back at the last state it reported while lit, as
+/// `set_power` does. Remembering at toggle time matters when fading: the
+/// fade's own steps down would otherwise become the "last lit" state. What is
+/// remembered lasts until the app quits.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::curves::CurveManager;
+use crate::dither::Ditherer;
+use crate::fade::FadeEngine;
+use crate::groups::{DeviceError, FanOutReport, GroupManager, Target};
+use crate::serial::SerialManager;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ToggleReport {
+    /// Whether the lights were switched on (rather than off).
+    pub on: bool,
+    #[serde(flatten)]
+    pub report: FanOutReport,
+}
+
+pub struct PowerToggle {
+    /// (slider level, kelvin) by device id, as switched off.
+    remembered: Mutex<BTreeMap<String, (u8, u32)>>,
+}
+
+impl PowerToggle {
+    pub fn new() -> Self {
+        Self {
+            remembered: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Switch `target` (all lights if `None`) off if any of its lights is on,
+    /// otherwise back on, fading over `fade` when it isn't zero.
+    pub fn toggle(
+        &self,
+        app: &AppHandle,
+        target: Option<&Target>,
+        fade: Duration,
+    ) -> Result<ToggleReport, String> {
+        let ids = app
+            .state::<GroupManager>()
+            .resolve(app, target.unwrap_or(&Target::All))?;
+        let serial = app.state::<SerialManager>();
+        let curves = app.state::<CurveManager>();
+        let mut report = ToggleReport::default();
+        // (slider level, kelvin) from and to, by device id
+        let mut changes = BTreeMap::new();
+        let lit: Vec<_> = ids
+            .iter()
+            .filter_map(|id| Some((id, serial.status_of(id)?)))
+            .filter(|(_, status)| status.brightness > 0)
+            .collect();
+        if lit.is_empty() {
+            report.on = true;
+            let remembered = self.remembered.lock().unwrap();
+            for id in &ids {
+                let Some(status) = serial.status_of(id) else {
+                    report.report.failed.push(DeviceError::new(
+                        id.clone(),
+                        "No status received from light yet",
+                    ));
+                    continue;
+                };
+                let to = remembered.get(id).copied().unwrap_or_else(|| {
+                    let (bri, kelvin) = serial.last_on(id).unwrap_or((100, status.kelvin));
+                    (curves.to_level(id, bri), kelvin)
+                });
+                changes.insert(id.clone(), ((0, status.kelvin), to));
+            }
+        } else {
+            let mut remembered = self.remembered.lock().unwrap();
+            for (id, status) in lit {
+                remembered.insert(id.clone(), (status.level, status.kelvin));
+                let from = (status.level, status.kelvin);
+                changes.insert(id.clone(), (from, (0, status.kelvin)));
+            }
+        }
+
+        if fade.is_zero() {
+            let dither = app.state::<Ditherer>();
+            for (id, (_, (level, kelvin))) in changes {
+                match dither.set_level(app, &id, level, kelvin) {
+                    Ok(()) => report.report.succeeded.push(id),
+                    Err(error) => report.report.failed.push(DeviceError::new(id, error)),
+                }
+            }
+        } else {
+            report.report.succeeded = changes.keys().cloned().collect();
+            app.state::<FadeEngine>().start_each(app, changes, fade);
+        }
+        if report.report.succeeded.is_empty() {
+            let errors: Vec<String> = report
+                .report
+                .failed
+                .iter()
+                .map(|f| f.error.clone())
+                .collect();
+            return Err(if errors.is_empty() {
+                "Port not open".into()
+            } else {
+                errors.join("; ")
+            });
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,131 @@
+/// Byte transports to a light.
+///
+/// A light is normally on a local serial port, but it can also be reached
+/// through a serial port exposed over TCP (ser2net, ESP-Link, USB-over-IP
+/// bridges), so it can be attached to another machine or a microcontroller
+/// across the studio. A raw byte pipe is connected to as `tcp://host:port`;
+/// the remote end owns the serial parameters, so only the timeout applies.
+/// Servers speaking RFC 2217 are connected to as `rfc2217://host:port`, and
+/// the device's serial parameters are negotiated with them (see `rfc2217`).
+/// Bluetooth serial control boxes can also be connected to directly as
+/// `bt://address` (see `spp`). Every transport reports a read timeout as
+/// `TimedOut` and a closed connection as an error, which is what a
+/// connection's actor expects from a serial port.
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::portconfig::SerialParams;
+use crate::{rfc2217, spp};
+
+pub const TCP_SCHEME: &str = "tcp://";
+pub const RFC2217_SCHEME: &str = "rfc2217://";
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A connection to a light, owned by a single thread (see `serial`).
+pub trait Link: Read + Write + Send {}
+
+impl Link for Box<dyn serialport::SerialPort> {}
+
+/// A raw TCP connection to a network serial server.
+struct TcpLink(TcpStream);
+
+impl Read for TcpLink {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        read_socket(&mut self.0, buf)
+    }
+}
+
+impl Write for TcpLink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Link for TcpLink {}
+
+/// Open a connection to the light at `path`: a serial port path,
+/// `tcp://host:port`, `rfc2217://host:port` or `bt://address[/channel]`.
+pub fn open(path: &str, params: &SerialParams) -> Result<Box<dyn Link>, String> {
+    if let Some(addr) = path.strip_prefix(TCP_SCHEME) {
+        let stream = connect_tcp(addr, params.timeout())?;
+        return Ok(Box::new(TcpLink(stream)));
+    }
+    if let Some(addr) = path.strip_prefix(RFC2217_SCHEME) {
+        let stream = connect_tcp(addr, params.timeout())?;
+        return rfc2217::open(stream, params);
+    }
+    if let Some(target) = path.strip_prefix(spp::SCHEME) {
+        return spp::open(target, params.timeout());
+    }
+    open_serial(path, params)
+}
+
+fn open_serial(path: &str, params: &SerialParams) -> Result<Box<dyn Link>, String> {
+    let port = serialport::new(path, params.baud_rate)
+        .data_bits(serialport::DataBits::Eight)
+        .parity(serialport::Parity::None)
+        .stop_bits(serialport::StopBits::One)
+        .flow_control(params.flow_control.into())
+        .timeout(params.timeout())
+        .open()
+        .map_err(|e| format!("Failed to open {path}: {e}"))?;
+    Ok(Box::new(port))
+}
+
+/// Connect to `addr` (host:port) with `timeout` as the read timeout.
+pub fn connect_tcp(addr: &str, timeout: Duration) -> Result<TcpStream, String> {
+    let resolved = addr
+        .to_socket_addrs()
+        .map_err(|e| format!("Can't resolve {addr}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("Can't resolve {addr}"))?;
+    let stream = TcpStream::connect_timeout(&resolved, TCP_CONNECT_TIMEOUT)
+        .map_err(|e| format!("Failed to connect to {addr}: {e}"))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|_| stream.set_nodelay(true))
+        .map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+/// Read from a socket, reporting an expired timeout as `TimedOut` and a closed
+/// connection as `UnexpectedEof`.
+pub fn read_socket(stream: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    match stream.read(buf) {
+        Ok(0) if !buf.is_empty() => Err(io::ErrorKind::UnexpectedEof.into()),
+        // Unix reports an expired read timeout as WouldBlock
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(io::ErrorKind::TimedOut.into()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_tcp_link_reports_timeout_and_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let stream = connect_tcp(&addr, Duration::from_millis(20)).unwrap();
+        let mut link = TcpLink(stream);
+        let (mut server, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 8];
+
+        let err = link.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        server.write_all(&[0x3A]).unwrap();
+        assert_eq!(link.read(&mut buf).unwrap(), 1);
+
+        drop(server);
+        let err = link.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
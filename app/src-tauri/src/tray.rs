@@ -0,0 +1,117 @@
+/// Tray icon rendering.
+///
+/// The icon is drawn at runtime so it can reflect the connection state and the
+/// light's current brightness/CCT: grey when disconnected, a template outline
+/// when the light is off, and a bulb filled with the light's warmth when on.
+use tauri::{image::Image, AppHandle, Manager};
+
+use crate::devices::DeviceNames;
+use crate::protocol;
+use crate::serial::{LightStatus, SerialManager};
+
+pub const TRAY_ID: &str = "main";
+
+/// Icon edge in pixels (22pt menu bar icon at 2x).
+const SIZE: u32 = 44;
+/// Supersampling grid per pixel axis, for anti-aliased edges.
+const SAMPLES: u32 = 4;
+const OUTLINE: f32 = 2.5;
+
+const GREY: [u8; 3] = [128, 128, 128];
+const BLACK: [u8; 3] = [0, 0, 0];
+
+/// Re-render the tray icon from the current serial state.
+pub fn refresh(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let serial = app.state::<SerialManager>();
+    let (icon, template) = render(serial.is_connected(), serial.status().as_ref());
+    let _ = tray.set_icon(Some(icon));
+    let _ = tray.set_icon_as_template(template);
+
+    let tooltip = match serial.device() {
+        Some((id, port)) => {
+            let name = app.state::<DeviceNames>().name(&id, &port);
+            format!("Neewer USB Control — {name}")
+        }
+        None => "Neewer USB Control — Disconnected".to_string(),
+    };
+    let _ = tray.set_tooltip(Some(tooltip));
+}
+
+/// Render the icon for the given state. Returns the image and whether it
+/// should be drawn as a macOS template image.
+pub fn render(connected: bool, status: Option<&LightStatus>) -> (Image<'static>, bool) {
+    let lit = status.filter(|s| connected && s.brightness > 0);
+    let (outline, fill, template) = match lit {
+        Some(s) => {
+            let tint = kelvin_to_rgb(s.kelvin);
+            let alpha = 0.35 + 0.65 * (s.brightness.min(100) as f32 / 100.0);
+            (tint, Some((tint, alpha)), false)
+        }
+        None if connected => (BLACK, None, true),
+        None => (GREY, None, false),
+    };
+
+    let mut rgba = vec![0u8; (SIZE * SIZE * 4) as usize];
+    let total = (SAMPLES * SAMPLES) as f32;
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let (mut edge, mut inner) = (0u32, 0u32);
+            for sy in 0..SAMPLES {
+                for sx in 0..SAMPLES {
+                    let px = x as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+                    let py = y as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+                    if in_glass(px, py, OUTLINE) {
+                        inner += 1;
+                    } else if in_glass(px, py, 0.0) || in_base(px, py) {
+                        edge += 1;
+                    }
+                }
+            }
+            let edge_a = edge as f32 / total;
+            let fill_a = fill.map_or(0.0, |(_, a)| a * inner as f32 / total);
+            let fill_rgb = fill.map_or(outline, |(c, _)| c);
+
+            // Outline and fill never overlap within a sample, so coverages add.
+            let a = edge_a + fill_a;
+            if a <= 0.0 {
+                continue;
+            }
+            let i = ((y * SIZE + x) * 4) as usize;
+            for c in 0..3 {
+                let v = (outline[c] as f32 * edge_a + fill_rgb[c] as f32 * fill_a) / a;
+                rgba[i + c] = v.round() as u8;
+            }
+            rgba[i + 3] = (a.min(1.0) * 255.0).round() as u8;
+        }
+    }
+
+    (Image::new_owned(rgba, SIZE, SIZE), template)
+}
+
+/// Bulb glass: a round head over a short neck, shrunk by `inset`.
+fn in_glass(x: f32, y: f32, inset: f32) -> bool {
+    let (cx, cy, r) = (22.0, 17.0, 13.0 - inset);
+    let head = (x - cx).powi(2) + (y - cy).powi(2) <= r * r;
+    let neck = x >= 16.0 + inset && x <= 28.0 - inset && y >= cy && y <= 31.0 - inset;
+    head || neck
+}
+
+/// Two horizontal bands forming the screw base.
+fn in_base(x: f32, y: f32) -> bool {
+    (17.0..=27.0).contains(&x) && ((33.0..=35.5).contains(&y) || (37.5..=40.0).contains(&y))
+}
+
+/// Approximate display color for a CCT, matching the panel's preview.
+fn kelvin_to_rgb(kelvin: u32) -> [u8; 3] {
+    let k = kelvin.clamp(protocol::TEMP_MIN_K, protocol::TEMP_MAX_K);
+    let t =
+        (k - protocol::TEMP_MIN_K) as f32 / (protocol::TEMP_MAX_K - protocol::TEMP_MIN_K) as f32;
+    [
+        (255.0 - t * 15.0).round() as u8,
+        (210.0 + t * 40.0).round() as u8,
+        (140.0 + t * 115.0).round() as u8,
+    ]
+}
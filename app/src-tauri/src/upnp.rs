@@ -0,0 +1,253 @@
+/// Shared plumbing of the emulated network devices (see `hue` and `wemo`).
+///
+/// Both answer SSDP searches on 239.255.255.250:1900. That port can only be
+/// bound once, so one responder thread answers for every registered service
+/// and runs while any are registered. Each service also serves plain
+/// HTTP/1.1, one request per connection, from threads that exit when the
+/// service's generation counter moves on.
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream, UdpSocket};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use tauri::AppHandle;
+
+use crate::notify;
+use crate::sessionlog::{self, Source};
+
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+/// How often server threads check whether they should exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Maps a search target and this machine's address to the responses a
+/// service sends.
+pub type Responder = Box<dyn Fn(&str, IpAddr) -> Vec<String> + Send + Sync>;
+
+type Services = Arc<Mutex<BTreeMap<&'static str, Responder>>>;
+
+pub struct Ssdp {
+    services: Services,
+    running: Arc<AtomicBool>,
+}
+
+impl Ssdp {
+    pub fn new() -> Self {
+        Self {
+            services: Arc::new(Mutex::new(BTreeMap::new())),
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Answer searches for service `name` with `responder`, replacing any
+    /// earlier one.
+    pub fn register(&self, app: &AppHandle, name: &'static str, responder: Responder) {
+        self.services.lock().unwrap().insert(name, responder);
+        if !self.running.swap(true, Ordering::SeqCst) {
+            let services = self.services.clone();
+            let running = self.running.clone();
+            let app = app.clone();
+            std::thread::spawn(move || respond_to_searches(app, services, running));
+        }
+    }
+
+    pub fn unregister(&self, name: &str) {
+        self.services.lock().unwrap().remove(name);
+    }
+}
+
+fn respond_to_searches(app: AppHandle, services: Services, running: Arc<AtomicBool>) {
+    let socket = bind_with_retry(|| {
+        let socket = UdpSocket::bind(("0.0.0.0", SSDP_PORT))?;
+        socket.join_multicast_v4(&SSDP_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        Ok(socket)
+    });
+    let socket = match socket {
+        Ok(socket) => socket,
+        Err(e) => {
+            running.store(false, Ordering::SeqCst);
+            notify::error(
+                &app,
+                "Network discovery unavailable",
+                &format!("Add the device by this computer's address instead ({e})"),
+            );
+            return;
+        }
+    };
+    let mut buf = [0u8; 1024];
+    loop {
+        let received = socket.recv_from(&mut buf);
+        // Checked under the lock, so a service registered meanwhile either
+        // sees this thread running or starts a new one
+        let services = services.lock().unwrap();
+        if services.is_empty() {
+            running.store(false, Ordering::SeqCst);
+            return;
+        }
+        let Ok((n, from)) = received else {
+            continue;
+        };
+        let request = String::from_utf8_lossy(&buf[..n]);
+        let (Some(st), Some(ip)) = (search_target(&request), local_ip()) else {
+            continue;
+        };
+        for response in services.values().flat_map(|responder| responder(st, ip)) {
+            let _ = socket.send_to(response.as_bytes(), from);
+        }
+    }
+}
+
+/// The search target of an SSDP M-SEARCH request.
+fn search_target(request: &str) -> Option<&str> {
+    if !request.starts_with("M-SEARCH") {
+        return None;
+    }
+    request.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("st").then(|| value.trim())
+    })
+}
+
+/// The address other devices on the LAN reach this machine at.
+pub fn local_ip() -> Option<IpAddr> {
+    // Connecting a UDP socket sends nothing; it only picks the route
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:80").ok()?;
+    socket.local_addr().ok().map(|a| a.ip())
+}
+
+/// Bind a socket, retrying while a previous server thread releases it.
+fn bind_with_retry<T>(bind: impl Fn() -> io::Result<T>) -> io::Result<T> {
+    let mut result = bind();
+    for _ in 0..5 {
+        if result.is_ok() {
+            break;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+        result = bind();
+    }
+    result
+}
+
+/// Listen for HTTP on `port`, on every interface.
+pub fn listen(port: u16) -> io::Result<TcpListener> {
+    bind_with_retry(|| {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    })
+}
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of header `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+    let length = match request.header("content-length") {
+        Some(length) => length.parse().ok()?,
+        None => 0,
+    };
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).ok()?;
+    Some(request)
+}
+
+/// Answer HTTP requests on `listener` (from `listen`) with `handler`, which
+/// returns the content type and body, until `current` moves on from `gen`.
+pub fn serve(
+    listener: TcpListener,
+    current: Arc<AtomicU64>,
+    gen: u64,
+    handler: impl Fn(&Request) -> (&'static str, String),
+) {
+    sessionlog::set_source(Source::External);
+    while current.load(Ordering::SeqCst) == gen {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+        let Some(request) = read_request(&stream) else {
+            continue;
+        };
+        let (content_type, body) = handler(&request);
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_target() {
+        let search = "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nST: ssdp:all\r\n";
+        assert_eq!(search_target(search), Some("ssdp:all"));
+        assert_eq!(search_target("NOTIFY * HTTP/1.1\r\nST: ssdp:all\r\n"), None);
+    }
+
+    #[test]
+    fn test_read_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .write_all(
+                b"PUT /api/a/lights/1/state HTTP/1.1\r\nContent-Length: 11\r\n\r\n{\"on\":true}",
+            )
+            .unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let request = read_request(&server).unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/api/a/lights/1/state");
+        assert_eq!(request.header("Content-length"), Some("11"));
+        assert_eq!(request.body, b"{\"on\":true}");
+    }
+}
@@ -0,0 +1,184 @@
+/// Lamp-hours usage tracking.
+///
+/// Cumulative on-time is accumulated per device from its status reports, split
+/// into hardware brightness bands, for LED aging awareness and for checking
+/// how much a rented light has been used. A light counts as on from a status
+/// with non-zero brightness until its next status or until it disconnects.
+/// Running time is folded in once a minute, so at most a minute is lost if the
+/// app exits. Totals are persisted under `usage` in the settings store, keyed
+/// by device id.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::serial::LightStatus;
+use crate::{errors, STORE_FILE};
+
+const USAGE_KEY: &str = "usage";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+/// Upper bounds (inclusive) of the hardware brightness bands.
+const BANDS: [u8; 4] = [25, 50, 75, 100];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceUsage {
+    /// Total on-time in seconds.
+    pub on_seconds: f64,
+    /// On-time in seconds per brightness band: 1-25, 26-50, 51-75, 76-100.
+    pub band_seconds: [f64; BANDS.len()],
+}
+
+impl DeviceUsage {
+    fn add(&mut self, brightness: u8, elapsed: Duration) {
+        let Some(band) = band_of(brightness) else {
+            return;
+        };
+        let secs = elapsed.as_secs_f64();
+        self.on_seconds += secs;
+        self.band_seconds[band] += secs;
+    }
+}
+
+fn band_of(brightness: u8) -> Option<usize> {
+    if brightness == 0 {
+        return None;
+    }
+    Some(
+        BANDS
+            .iter()
+            .position(|&max| brightness <= max)
+            .unwrap_or(BANDS.len() - 1),
+    )
+}
+
+pub struct UsageTracker {
+    totals: Mutex<BTreeMap<String, DeviceUsage>>,
+    /// Hardware brightness of each connected light and when it was last
+    /// accounted for.
+    live: Mutex<HashMap<String, (u8, Instant)>>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self {
+            totals: Mutex::new(BTreeMap::new()),
+            live: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Load the saved totals and start folding in running time.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: BTreeMap<String, DeviceUsage> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(USAGE_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.totals.lock().unwrap() = saved;
+
+        let app = app.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(FLUSH_INTERVAL);
+            let tracker = app.state::<UsageTracker>();
+            tracker.flush();
+            errors::check_save(&app, "save_usage", None, tracker.save(&app));
+        });
+    }
+
+    /// Usage of every light seen so far, up to now.
+    pub fn stats(&self) -> BTreeMap<String, DeviceUsage> {
+        self.flush();
+        self.totals.lock().unwrap().clone()
+    }
+
+    /// Clear the totals of one light, or of all lights.
+    pub fn reset(&self, app: &AppHandle, device: Option<&str>) -> Result<(), String> {
+        self.flush();
+        match device {
+            Some(id) => {
+                self.totals.lock().unwrap().remove(id);
+            }
+            None => self.totals.lock().unwrap().clear(),
+        }
+        self.save(app)
+    }
+
+    /// Account for the time at the previous brightness and start timing the
+    /// new one.
+    pub fn on_status(&self, status: &LightStatus) {
+        self.advance(&status.device, Some(status.brightness));
+    }
+
+    /// Stop timing a light that went away.
+    pub fn on_disconnect(&self, device: &str) {
+        self.advance(device, None);
+    }
+
+    fn advance(&self, device: &str, brightness: Option<u8>) {
+        let now = Instant::now();
+        let mut live = self.live.lock().unwrap();
+        let previous = match brightness {
+            Some(b) => live.insert(device.to_string(), (b, now)),
+            None => live.remove(device),
+        };
+        if let Some((b, since)) = previous {
+            self.totals
+                .lock()
+                .unwrap()
+                .entry(device.to_string())
+                .or_default()
+                .add(b, now - since);
+        }
+    }
+
+    /// Fold the running time of every lit light into the totals.
+    fn flush(&self) {
+        let now = Instant::now();
+        let mut live = self.live.lock().unwrap();
+        let mut totals = self.totals.lock().unwrap();
+        for (device, (b, since)) in live.iter_mut() {
+            totals
+                .entry(device.clone())
+                .or_default()
+                .add(*b, now - *since);
+            *since = now;
+        }
+    }
+
+    fn save(&self, app: &AppHandle) -> Result<(), String> {
+        let value =
+            serde_json::to_value(&*self.totals.lock().unwrap()).map_err(|e| e.to_string())?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(USAGE_KEY, value);
+        store.save().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_band_of() {
+        assert_eq!(band_of(0), None);
+        assert_eq!(band_of(1), Some(0));
+        assert_eq!(band_of(25), Some(0));
+        assert_eq!(band_of(26), Some(1));
+        assert_eq!(band_of(100), Some(3));
+        assert_eq!(band_of(255), Some(3));
+    }
+
+    #[test]
+    fn test_add_skips_off_time() {
+        let mut usage = DeviceUsage::default();
+        usage.add(80, Duration::from_secs(90));
+        usage.add(0, Duration::from_secs(600));
+        usage.add(10, Duration::from_secs(30));
+        assert_eq!(usage.on_seconds, 120.0);
+        assert_eq!(usage.band_seconds, [30.0, 0.0, 0.0, 90.0]);
+    }
+}
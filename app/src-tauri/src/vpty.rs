@@ -0,0 +1,150 @@
+/// A scripted fake light on a pseudo-terminal, for tests.
+///
+/// `FakeLight` opens a PTY pair and plays a PL81-Pro on the master side: it
+/// parses what is written to it, answers status queries with its CCT state,
+/// and applies and echoes CCT commands, as the real firmware does. The slave
+/// side is an ordinary serial port path, so tests open it through
+/// `transport::open` exactly as a connection does. A light can also be made
+/// silent, fed raw bytes, or unplugged, which closes the master so reads on
+/// the port fail the way they do when a USB adapter is pulled.
+///
+/// Connection actors need a running Tauri app, so tests drive the port-level
+/// steps (the identify handshake, framing, loss detection) directly.
+use std::io::{Read, Write};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serialport::{SerialPort, TTYPort};
+
+use crate::protocol::{self, Frame, FrameParser, StatusFrame};
+
+pub struct FakeLight {
+    path: String,
+    /// Writes raw bytes to the port.
+    master: TTYPort,
+    /// Kept open so the port stays usable between connections.
+    _slave: TTYPort,
+    /// Frames received from the port, in order.
+    received: Arc<Mutex<Vec<Frame>>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FakeLight {
+    /// A light at `brightness` and `kelvin` that answers queries.
+    pub fn new(brightness: u8, kelvin: u32) -> Self {
+        Self::start(Some((brightness, kelvin)))
+    }
+
+    /// A device that takes bytes but never answers, like another USB-serial
+    /// adapter.
+    pub fn silent() -> Self {
+        Self::start(None)
+    }
+
+    fn start(state: Option<(u8, u32)>) -> Self {
+        let (mut master, mut slave) = TTYPort::pair().expect("PTY pair");
+        slave.set_exclusive(false).unwrap();
+        master.set_timeout(Duration::from_millis(10)).unwrap();
+        let path = slave.name().expect("PTY name");
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let running = Arc::new(AtomicBool::new(true));
+        let device = master.try_clone_native().unwrap();
+        let thread = {
+            let (received, running) = (received.clone(), running.clone());
+            std::thread::spawn(move || play(device, state, &received, &running))
+        };
+        Self {
+            path,
+            master,
+            _slave: slave,
+            received,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    /// Path of the port to connect to.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Frames written to the light so far.
+    pub fn received(&self) -> Vec<Frame> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Send raw bytes to whoever has the port open.
+    pub fn send(&mut self, data: &[u8]) {
+        self.master.write_all(data).unwrap();
+    }
+
+    /// Close the master, as if the light's USB adapter was pulled.
+    pub fn unplug(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for FakeLight {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The light's side of the port: record every frame, and answer if `state`
+/// is set.
+fn play(
+    mut port: TTYPort,
+    mut state: Option<(u8, u32)>,
+    received: &Mutex<Vec<Frame>>,
+    running: &AtomicBool,
+) {
+    let mut parser = FrameParser::new();
+    let mut buf = [0u8; 256];
+    while running.load(Ordering::Relaxed) {
+        // Times out while nobody writes; fails while the slave is closed
+        let n = match port.read(&mut buf) {
+            Ok(n) => n,
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+        };
+        for frame in parser.push(&buf[..n]) {
+            received.lock().unwrap().push(frame.clone());
+            let Some((brightness, kelvin)) = state.as_mut() else {
+                continue;
+            };
+            if let Some(StatusFrame::Cct {
+                brightness: b,
+                temp_byte,
+                ..
+            }) = frame.status()
+            {
+                (*brightness, *kelvin) = (
+                    b,
+                    protocol::byte_to_kelvin(&protocol::DEFAULT_RANGE, temp_byte),
+                );
+            } else if frame.tag != protocol::TAG_CCT {
+                continue;
+            }
+            // Queries and CCT commands are both answered with the state
+            let _ = port.write_all(&protocol::cct_command(
+                &protocol::DEFAULT_RANGE,
+                *brightness,
+                *kelvin,
+            ));
+        }
+    }
+}
@@ -0,0 +1,202 @@
+/// Outbound webhooks.
+///
+/// Configured URLs receive a JSON POST when chosen events happen (a light
+/// connects or disconnects, a preset is applied, a background error occurs),
+/// so the lights can feed existing automation stacks. Deliveries run on their
+/// own threads and retry with exponential backoff on network errors, 429s and
+/// 5xx responses. Webhooks are persisted under `webhooks` in the settings
+/// store.
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::STORE_FILE;
+
+const WEBHOOKS_KEY: &str = "webhooks";
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 4;
+/// Delay before the first retry; doubled for each one after.
+const BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Connected,
+    Disconnected,
+    PresetApplied,
+    Error,
+    /// Sent only by `test_webhook`.
+    Test,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Webhook {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.url.starts_with("http://") || self.url.starts_with("https://")) {
+            return Err(format!("Webhook URL must be http(s): {}", self.url));
+        }
+        Ok(())
+    }
+}
+
+pub struct Webhooks {
+    hooks: Mutex<Vec<Webhook>>,
+}
+
+impl Webhooks {
+    pub fn new() -> Self {
+        Self {
+            hooks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn load(&self, app: &AppHandle) {
+        let saved: Vec<Webhook> = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(WEBHOOKS_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.hooks.lock().unwrap() = saved;
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        self.hooks.lock().unwrap().clone()
+    }
+
+    /// Replace the configured webhooks.
+    pub fn set(&self, app: &AppHandle, hooks: Vec<Webhook>) -> Result<(), String> {
+        hooks.iter().try_for_each(Webhook::validate)?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            WEBHOOKS_KEY,
+            serde_json::to_value(&hooks).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.hooks.lock().unwrap() = hooks;
+        Ok(())
+    }
+}
+
+/// Deliver `event` to every enabled webhook subscribed to it, in the
+/// background.
+pub fn dispatch(app: &AppHandle, event: WebhookEvent, data: Value) {
+    let urls: Vec<String> = app
+        .state::<Webhooks>()
+        .list()
+        .into_iter()
+        .filter(|h| h.enabled && h.events.contains(&event))
+        .map(|h| h.url)
+        .collect();
+    if urls.is_empty() {
+        return;
+    }
+    let body = payload(event, data);
+    for url in urls {
+        let body = body.clone();
+        std::thread::spawn(move || {
+            let mut delay = BACKOFF;
+            for attempt in 1..=MAX_ATTEMPTS {
+                match post(&url, &body) {
+                    Ok(()) | Err(Failure::Permanent(_)) => return,
+                    Err(Failure::Retry(_)) if attempt < MAX_ATTEMPTS => {
+                        std::thread::sleep(delay);
+                        delay *= 2;
+                    }
+                    // Not notified: an error notification would itself be
+                    // dispatched as a webhook event
+                    Err(Failure::Retry(_)) => return,
+                }
+            }
+        });
+    }
+}
+
+/// Send one test event to `url` and wait for the result, without retries.
+pub fn test(url: &str) -> Result<(), String> {
+    Webhook {
+        url: url.to_string(),
+        events: Vec::new(),
+        enabled: true,
+    }
+    .validate()?;
+    let body = payload(
+        WebhookEvent::Test,
+        json!({ "message": "Test from Neewer USB Control" }),
+    );
+    post(url, &body).map_err(|f| match f {
+        Failure::Retry(e) | Failure::Permanent(e) => e,
+    })
+}
+
+fn payload(event: WebhookEvent, data: Value) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    json!({ "event": event, "timestamp": timestamp, "data": data }).to_string()
+}
+
+enum Failure {
+    Retry(String),
+    Permanent(String),
+}
+
+fn post(url: &str, body: &str) -> Result<(), Failure> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    match agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(body)
+    {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, _)) if retryable(code) => {
+            Err(Failure::Retry(format!("{url} returned {code}")))
+        }
+        Err(ureq::Error::Status(code, _)) => {
+            Err(Failure::Permanent(format!("{url} returned {code}")))
+        }
+        Err(e) => Err(Failure::Retry(format!("{url}: {e}"))),
+    }
+}
+
+fn retryable(status: u16) -> bool {
+    status == 429 || status >= 500
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_shape() {
+        let body: Value =
+            serde_json::from_str(&payload(WebhookEvent::PresetApplied, json!({"index": 2})))
+                .unwrap();
+        assert_eq!(body["event"], "preset_applied");
+        assert_eq!(body["data"]["index"], 2);
+        assert!(body["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(retryable(429));
+        assert!(retryable(503));
+        assert!(!retryable(404));
+        assert!(!retryable(400));
+    }
+}
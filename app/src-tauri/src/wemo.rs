@@ -0,0 +1,297 @@
+/// Local voice control through Belkin Wemo emulation.
+///
+/// Alexa (and other assistants that discover Wemo devices on the LAN) finds
+/// Wemo plugs over SSDP and switches them with simple SOAP calls, without any
+/// cloud account. Each chosen light is presented as its own Wemo device under
+/// its friendly name, so "Alexa, turn on the key light" works locally. Devices
+/// listen on consecutive HTTP ports from `base_port` and answer
+/// `GetBinaryState` and `SetBinaryState`; a `brightness` (0-100) sent with
+/// `SetBinaryState`, as Wemo dimmer clients do, sets the slider level.
+/// Configuration is persisted under `wemo` in the settings store.
+use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::devices::DeviceNames;
+use crate::groups::Target;
+use crate::mqtt::{self, SetCommand};
+use crate::serial::SerialManager;
+use crate::upnp::{self, Request, Ssdp};
+use crate::{errors, notify, STORE_FILE};
+
+const WEMO_KEY: &str = "wemo";
+const SSDP_SERVICE: &str = "wemo";
+const SEARCH_TARGET: &str = "urn:Belkin:device:**";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WemoConfig {
+    pub enabled: bool,
+    /// Ids of the lights to present, each as a Wemo device.
+    pub devices: Vec<String>,
+    /// HTTP port of the first device; the others use the ports after it.
+    pub base_port: u16,
+}
+
+impl Default for WemoConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            devices: Vec::new(),
+            base_port: 49153,
+        }
+    }
+}
+
+impl WemoConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.base_port < 1024 || self.base_port as usize + self.devices.len() > 65535 {
+            return Err("Wemo ports must be between 1024 and 65535".into());
+        }
+        Ok(())
+    }
+
+    fn port(&self, index: usize) -> u16 {
+        self.base_port + index as u16
+    }
+}
+
+/// A stable serial number for a device id (64-bit FNV-1a).
+fn serial(id: &str) -> String {
+    let hash = id.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// The text of the first `<tag>` element in `xml`.
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    Some(xml[start..end].trim())
+}
+
+fn soap_response(action: &str, on: bool) -> String {
+    format!(
+        r#"<?xml version="1.0"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><u:{action}Response xmlns:u="urn:Belkin:service:basicevent:1"><BinaryState>{}</BinaryState></u:{action}Response></s:Body></s:Envelope>"#,
+        on as u8
+    )
+}
+
+pub struct WemoEmulation {
+    config: Mutex<WemoConfig>,
+    /// Bumped on every reconfigure; the server threads exit when it changes.
+    generation: Arc<AtomicU64>,
+}
+
+impl WemoEmulation {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(WemoConfig::default()),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Load the saved configuration and start answering if enabled.
+    pub fn load(&self, app: &AppHandle) {
+        let saved: WemoConfig = app
+            .store(STORE_FILE)
+            .ok()
+            .and_then(|store| store.get(WEMO_KEY))
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        *self.config.lock().unwrap() = saved.clone();
+        self.restart(app, saved);
+    }
+
+    pub fn get(&self) -> WemoConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, app: &AppHandle, config: WemoConfig) -> Result<(), String> {
+        config.validate()?;
+        let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+        store.set(
+            WEMO_KEY,
+            serde_json::to_value(&config).map_err(|e| e.to_string())?,
+        );
+        store.save().map_err(|e| e.to_string())?;
+        *self.config.lock().unwrap() = config.clone();
+        self.restart(app, config);
+        Ok(())
+    }
+
+    fn restart(&self, app: &AppHandle, config: WemoConfig) {
+        let gen = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let ssdp = app.state::<Ssdp>();
+        ssdp.unregister(SSDP_SERVICE);
+        if !config.enabled || config.devices.is_empty() {
+            return;
+        }
+        for (index, id) in config.devices.iter().enumerate() {
+            let port = config.port(index);
+            let listener = match upnp::listen(port) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    notify::error(
+                        app,
+                        "Wemo emulation unavailable",
+                        &format!("Can't listen on port {port}: {e}"),
+                    );
+                    continue;
+                }
+            };
+            let current = self.generation.clone();
+            let handle = app.clone();
+            let id = id.clone();
+            std::thread::spawn(move || {
+                upnp::serve(listener, current, gen, |request| {
+                    handle_request(&handle, &id, request)
+                })
+            });
+        }
+        ssdp.register(
+            app,
+            SSDP_SERVICE,
+            Box::new(move |st, ip| search_responses(&config, st, ip)),
+        );
+    }
+}
+
+/// One response per device when searched for Wemo devices.
+fn search_responses(config: &WemoConfig, st: &str, ip: IpAddr) -> Vec<String> {
+    if !matches!(st, SEARCH_TARGET | "ssdp:all" | "upnp:rootdevice") {
+        return Vec::new();
+    }
+    config
+        .devices
+        .iter()
+        .enumerate()
+        .map(|(index, id)| {
+            format!(
+                "HTTP/1.1 200 OK\r\n\
+                 CACHE-CONTROL: max-age=86400\r\n\
+                 EXT:\r\n\
+                 LOCATION: http://{ip}:{}/setup.xml\r\n\
+                 OPT: \"http://schemas.upnp.org/upnp/1/0/\"; ns=01\r\n\
+                 SERVER: Unspecified, UPnP/1.0, Unspecified\r\n\
+                 ST: {SEARCH_TARGET}\r\n\
+                 USN: uuid:Socket-1_0-{}::{SEARCH_TARGET}\r\n\r\n",
+                config.port(index),
+                serial(id)
+            )
+        })
+        .collect()
+}
+
+fn handle_request(app: &AppHandle, id: &str, request: &Request) -> (&'static str, String) {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/setup.xml") => ("text/xml", setup(app, id)),
+        ("GET", "/eventservice.xml") => ("text/xml", EVENT_SERVICE.to_string()),
+        ("POST", "/upnp/control/basicevent1") => {
+            let action = request.header("soapaction").unwrap_or_default();
+            let name = if action.contains("SetBinaryState") {
+                set_state(app, id, &String::from_utf8_lossy(&request.body));
+                "SetBinaryState"
+            } else {
+                "GetBinaryState"
+            };
+            ("text/xml", soap_response(name, is_on(app, id)))
+        }
+        _ => ("text/plain", String::new()),
+    }
+}
+
+fn is_on(app: &AppHandle, id: &str) -> bool {
+    app.state::<SerialManager>()
+        .status_of(id)
+        .is_some_and(|s| s.brightness > 0)
+}
+
+fn set_state(app: &AppHandle, id: &str, body: &str) {
+    let command = SetCommand {
+        on: xml_value(body, "BinaryState").map(|s| s != "0"),
+        level: xml_value(body, "brightness")
+            .and_then(|b| b.parse::<u8>().ok())
+            .map(|b| b.min(100)),
+        ..SetCommand::default()
+    };
+    // Wemo clients only take a state back, so failures are only reported
+    let result = mqtt::execute(app, &Target::Device(id.to_string()), &command);
+    errors::check(app, "wemo", Some(id), result);
+}
+
+fn setup(app: &AppHandle, id: &str) -> String {
+    let port = app
+        .state::<SerialManager>()
+        .devices()
+        .into_iter()
+        .find(|(d, _)| d == id)
+        .map_or(id.to_string(), |(_, port)| port);
+    let name = app.state::<DeviceNames>().name(id, &port);
+    let serial = serial(id);
+    format!(
+        r#"<?xml version="1.0"?>
+<root xmlns="urn:Belkin:device-1-0">
+<device>
+<deviceType>urn:Belkin:device:controllee:1</deviceType>
+<friendlyName>{name}</friendlyName>
+<manufacturer>Belkin International Inc.</manufacturer>
+<modelName>Socket</modelName>
+<modelNumber>3.1415</modelNumber>
+<UDN>uuid:Socket-1_0-{serial}</UDN>
+<serialNumber>{serial}</serialNumber>
+<binaryState>{}</binaryState>
+<serviceList>
+<service>
+<serviceType>urn:Belkin:service:basicevent:1</serviceType>
+<serviceId>urn:Belkin:serviceId:basicevent1</serviceId>
+<controlURL>/upnp/control/basicevent1</controlURL>
+<eventSubURL>/upnp/event/basicevent1</eventSubURL>
+<SCPDURL>/eventservice.xml</SCPDURL>
+</service>
+</serviceList>
+</device>
+</root>
+"#,
+        is_on(app, id) as u8
+    )
+}
+
+const EVENT_SERVICE: &str = r#"<?xml version="1.0"?>
+<scpd xmlns="urn:Belkin:service-1-0">
+<actionList>
+<action><name>SetBinaryState</name><argumentList><argument><retval/><name>BinaryState</name><relatedStateVariable>BinaryState</relatedStateVariable><direction>in</direction></argument></argumentList></action>
+<action><name>GetBinaryState</name><argumentList><argument><retval/><name>BinaryState</name><relatedStateVariable>BinaryState</relatedStateVariable><direction>out</direction></argument></argumentList></action>
+</actionList>
+<serviceStateTable>
+<stateVariable sendEvents="yes"><name>BinaryState</name><dataType>Boolean</dataType><defaultValue>0</defaultValue></stateVariable>
+</serviceStateTable>
+</scpd>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xml_value() {
+        let body = "<u:SetBinaryState><BinaryState>1</BinaryState><brightness>40</brightness></u:SetBinaryState>";
+        assert_eq!(xml_value(body, "BinaryState"), Some("1"));
+        assert_eq!(xml_value(body, "brightness"), Some("40"));
+        assert_eq!(xml_value(body, "Duration"), None);
+    }
+
+    #[test]
+    fn test_serial_is_stable() {
+        assert_eq!(serial("AB12"), serial("AB12"));
+        assert_ne!(serial("AB12"), serial("AB13"));
+        assert_eq!(serial("").len(), 16);
+    }
+}
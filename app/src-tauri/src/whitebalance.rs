@@ -0,0 +1,121 @@
+/// Built-in white balance and gel setpoints.
+///
+/// Standard camera white balance temperatures plus the CCT equivalents of
+/// common correction gels, so the panel and automations can refer to
+/// "Tungsten" or "Half CTO" instead of raw Kelvin. Gels are expressed as their
+/// mired shift applied to the usual base: CTO warms daylight (5600K), CTB cools
+/// tungsten (3200K). Everything is clamped to the light's range.
+use serde::Serialize;
+
+use crate::protocol;
+
+const DAYLIGHT_K: u32 = 5600;
+const TUNGSTEN_K: u32 = 3200;
+
+enum Source {
+    Kelvin(u32),
+    /// Mired shift applied to a base temperature.
+    Gel {
+        base: u32,
+        shift: i32,
+    },
+}
+
+struct Setpoint {
+    name: &'static str,
+    source: Source,
+    description: &'static str,
+}
+
+const fn fixed(name: &'static str, kelvin: u32, description: &'static str) -> Setpoint {
+    Setpoint {
+        name,
+        source: Source::Kelvin(kelvin),
+        description,
+    }
+}
+
+const fn gel(name: &'static str, base: u32, shift: i32, description: &'static str) -> Setpoint {
+    Setpoint {
+        name,
+        source: Source::Gel { base, shift },
+        description,
+    }
+}
+
+const SETPOINTS: &[Setpoint] = &[
+    fixed("Household", 2900, "Warm household incandescent"),
+    fixed("Tungsten", TUNGSTEN_K, "Studio tungsten"),
+    fixed("Fluorescent", 4000, "Cool white fluorescent"),
+    fixed("Daylight", DAYLIGHT_K, "Midday sun"),
+    fixed("Overcast", 6500, "Cloudy sky"),
+    fixed("Shade", 7000, "Open shade"),
+    gel("Full CTO", DAYLIGHT_K, 159, "Daylight to tungsten"),
+    gel("Half CTO", DAYLIGHT_K, 81, "Half warming of daylight"),
+    gel("Quarter CTO", DAYLIGHT_K, 42, "Quarter warming of daylight"),
+    gel("Eighth CTO", DAYLIGHT_K, 20, "Eighth warming of daylight"),
+    gel("Eighth CTB", TUNGSTEN_K, -12, "Eighth cooling of tungsten"),
+    gel(
+        "Quarter CTB",
+        TUNGSTEN_K,
+        -30,
+        "Quarter cooling of tungsten",
+    ),
+    gel("Half CTB", TUNGSTEN_K, -68, "Half cooling of tungsten"),
+    gel("Full CTB", TUNGSTEN_K, -131, "Tungsten to daylight"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WhiteBalance {
+    pub name: &'static str,
+    pub kelvin: u32,
+    pub mired: u32,
+    pub description: &'static str,
+}
+
+fn resolve(source: &Source) -> u32 {
+    let kelvin = match *source {
+        Source::Kelvin(k) => k,
+        Source::Gel { base, shift } => {
+            let mired = protocol::kelvin_to_mired(base) as i32 + shift;
+            protocol::mired_to_kelvin(mired.max(1) as u32)
+        }
+    };
+    kelvin.clamp(protocol::TEMP_MIN_K, protocol::TEMP_MAX_K)
+}
+
+/// All setpoints, warmest first within each family.
+pub fn list() -> Vec<WhiteBalance> {
+    SETPOINTS
+        .iter()
+        .map(|sp| {
+            let kelvin = resolve(&sp.source);
+            WhiteBalance {
+                name: sp.name,
+                kelvin,
+                mired: protocol::kelvin_to_mired(kelvin),
+                description: sp.description,
+            }
+        })
+        .collect()
+}
+
+/// Look up a setpoint by name, ignoring case.
+pub fn get(name: &str) -> Result<WhiteBalance, String> {
+    list()
+        .into_iter()
+        .find(|wb| wb.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("No white balance preset named {name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gels_land_near_their_targets() {
+        assert_eq!(get("full cto").unwrap().kelvin, 2959);
+        assert_eq!(get("Full CTB").unwrap().kelvin, 5495);
+        assert!(get("Mars").is_err());
+    }
+}